@@ -1,13 +1,24 @@
 //! Utilities for displaying data via the CLI.
 
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use comfy_table::{presets, Attribute, Cell, CellAlignment, Color, ContentArrangement, Table};
-use crossterm::style::Stylize;
+use comfy_table::{presets, Attribute, Cell, CellAlignment, Color, ContentArrangement, Row, Table};
+use crossterm::tty::IsTty;
 use loiter::{
-    cmd::{ListLogs, ListProjects, ListTasks, LogStatus},
-    Duration, Log, Project, ProjectId, Task, TaskId, TaskState, MAX_TASK_PRIORITY,
+    cmd::{
+        CheckReport, ExportSummary, FillDays, ImportSummary, InvoiceSummary, ListLogs,
+        ListProjects, ListTasks, LogStats, LogStatus, PatchOutcome, RenameTag, Report,
+        ReportPeriod, VerifyReport,
+    },
+    AuditEntry, Config, Duration, DurationFormat, Log, LogId, LogTotals, LogView, Project,
+    ProjectId, ProjectView, Task, TaskId, TaskState, TaskStateConfig, TaskView, Timestamp,
+    MAX_TASK_PRIORITY,
 };
+use serde::Serialize;
 
 pub const COLOR_STATES: Color = Color::DarkCyan;
 pub const COLOR_PROJECT: Color = Color::Blue;
@@ -18,9 +29,139 @@ pub const COLOR_PRIORITY_HIGH: Color = Color::Red;
 pub const COLOR_PRIORITY_MEDIUM: Color = Color::Yellow;
 pub const COLOR_PRIORITY_LOW: Color = Color::Green;
 
+/// The format in which lists of projects, tasks and work logs are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    /// A human-readable table.
+    #[default]
+    Table,
+    /// Tab-separated values, without quoting. Literal tabs and newlines within
+    /// fields are escaped as `\t` and `\n`.
+    Tsv,
+    /// A JSON array of the underlying objects, including their IDs.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "tsv" => Ok(Self::Tsv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unrecognized output format: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Table => write!(f, "table"),
+            Self::Tsv => write!(f, "tsv"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    /// Only color output written to a terminal, and only if the `NO_COLOR`
+    /// environment variable isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!("unrecognized color choice: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Always => write!(f, "always"),
+            Self::Never => write!(f, "never"),
+        }
+    }
+}
+
+thread_local! {
+    // Whether output is styled at all (see `set_color`).
+    static COLOR_ENABLED: std::cell::Cell<bool> = const { std::cell::Cell::new(true) };
+}
+
+/// Resolve whether to color output and apply that to everything displayed
+/// from here on.
+pub fn set_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            !no_color && std::io::stdout().is_tty()
+        }
+    };
+    COLOR_ENABLED.with(|color| color.set(enabled));
+}
+
+fn color_enabled() -> bool {
+    COLOR_ENABLED.with(|color| color.get())
+}
+
+// Stands in for crossterm's `Stylize` (which always styles its content),
+// leaving content unstyled if color is disabled.
+trait Paint: crossterm::style::Stylize + Sized {
+    fn with(self, color: Color) -> Self::Styled {
+        if color_enabled() {
+            crossterm::style::Stylize::with(self, color)
+        } else {
+            crossterm::style::Stylize::stylize(self)
+        }
+    }
+
+    fn attribute(self, attribute: Attribute) -> Self::Styled {
+        if color_enabled() {
+            crossterm::style::Stylize::attribute(self, attribute)
+        } else {
+            crossterm::style::Stylize::stylize(self)
+        }
+    }
+
+    fn red(self) -> Self::Styled {
+        Paint::with(self, Color::Red)
+    }
+}
+
+impl<T: crossterm::style::Stylize> Paint for T {}
+
+// Tables only style their cells if color is enabled.
+fn new_table() -> Table {
+    let mut table = Table::new();
+    if color_enabled() {
+        table.enforce_styling();
+    } else {
+        table.force_no_tty();
+    }
+    table
+}
+
 /// List the given task states.
 pub fn task_states(states: Vec<TaskState>) {
-    let mut table = Table::new();
+    let mut table = new_table();
     table.load_preset(presets::NOTHING);
     for state in states.iter() {
         table.add_row(vec![Cell::new(state).fg(COLOR_STATES)]);
@@ -28,9 +169,176 @@ pub fn task_states(states: Vec<TaskState>) {
     println!("{}", table);
 }
 
+/// List the states tasks are in along with the number of tasks in each,
+/// flagging any states that aren't among the configured ones.
+pub fn states_in_use(
+    state_counts: BTreeMap<TaskState, usize>,
+    configured: &[TaskState],
+    format: OutputFormat,
+) {
+    if format == OutputFormat::Json {
+        #[derive(Serialize)]
+        struct StateCount<'a> {
+            state: &'a str,
+            count: usize,
+            configured: bool,
+        }
+        return print_json(
+            &state_counts
+                .iter()
+                .map(|(state, count)| StateCount {
+                    state,
+                    count: *count,
+                    configured: configured.contains(state),
+                })
+                .collect::<Vec<StateCount>>(),
+        );
+    }
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(vec!["State", "Tasks", ""]));
+    for (state, count) in state_counts {
+        let not_configured = if configured.contains(&state) {
+            ""
+        } else {
+            "(not configured)"
+        };
+        table.add_row(vec![
+            Cell::new(state).fg(COLOR_STATES),
+            Cell::new(count).set_alignment(CellAlignment::Right),
+            Cell::new(not_configured).fg(Color::Yellow),
+        ]);
+    }
+    print_table(table, format);
+}
+
+/// List the given tags along with the number of objects using each.
+pub fn tags(tag_counts: BTreeMap<String, usize>, format: OutputFormat) {
+    if format == OutputFormat::Json {
+        #[derive(Serialize)]
+        struct TagCount<'a> {
+            tag: &'a str,
+            count: usize,
+        }
+        return print_json(
+            &tag_counts
+                .iter()
+                .map(|(tag, count)| TagCount { tag, count: *count })
+                .collect::<Vec<TagCount>>(),
+        );
+    }
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(vec!["Tag", "Count"]));
+    for (tag, count) in tag_counts {
+        table.add_row(vec![
+            Cell::new(tag).fg(COLOR_TAGS),
+            Cell::new(count).set_alignment(CellAlignment::Right),
+        ]);
+    }
+    print_table(table, format);
+}
+
+pub fn tag_renamed(params: &RenameTag, changed: usize) {
+    println!(
+        "Renamed tag {} to {} on {} object(s)",
+        params.old.as_str().with(COLOR_TAGS),
+        params.new.as_str().with(COLOR_TAGS),
+        changed
+    );
+}
+
+/// Summarize any files skipped because they could not be parsed.
+pub fn skipped_files(skipped: &[(PathBuf, String)]) {
+    if skipped.is_empty() {
+        return;
+    }
+    println!();
+    println!(
+        "{} {} skipped because {} could not be parsed:",
+        skipped.len(),
+        if skipped.len() == 1 { "file" } else { "files" },
+        if skipped.len() == 1 { "it" } else { "they" },
+    );
+    for (path, _) in skipped {
+        println!("  {}", path.display().to_string().with(Color::Red));
+    }
+}
+
+pub fn break_tags(config: &Config) {
+    let mut tags = config.break_tags().collect::<Vec<&str>>();
+    tags.sort_unstable();
+    if tags.is_empty() {
+        println!("No logs are treated as breaks");
+    } else {
+        println!("Break tags set to {}", tags.join(", ").with(COLOR_TAGS));
+    }
+}
+
+pub fn rounding(config: &Config) {
+    match config.maybe_rounding() {
+        Some(rounding) => println!(
+            "Log durations rounded up to the nearest {}",
+            rounding.to_string().with(COLOR_TIME)
+        ),
+        None => println!("Log durations are not rounded"),
+    }
+}
+
+pub fn week_start(config: &Config) {
+    println!(
+        "Weeks start on {}",
+        config.week_start().to_string().with(COLOR_TIME)
+    );
+}
+
+pub fn duration_format(config: &Config) {
+    println!(
+        "Durations in JSON output are rendered as {}",
+        match config.duration_format() {
+            DurationFormat::Seconds => "whole seconds",
+            DurationFormat::Iso8601 => "whole seconds and ISO 8601 durations",
+        }
+        .with(COLOR_TIME)
+    );
+}
+
+pub fn stop_tolerance(config: &Config) {
+    println!(
+        "Stop times may be up to {} in the future",
+        config.stop_tolerance().to_string().with(COLOR_TIME)
+    );
+}
+
+pub fn task_state_config(config: &TaskStateConfig) {
+    println!(
+        "Task states set to {} (initial: {}, in progress: {}, done: {})",
+        config
+            .states()
+            .collect::<Vec<&str>>()
+            .join(", ")
+            .with(COLOR_STATES),
+        config.initial().with(COLOR_STATES),
+        config.in_progress().with(COLOR_STATES),
+        config.done().with(COLOR_STATES),
+    );
+    let terminal = config.terminal_states().collect::<Vec<&str>>();
+    if !terminal.is_empty() {
+        println!(
+            "Other terminal states: {}",
+            terminal.join(", ").with(COLOR_STATES)
+        );
+    }
+}
+
 /// Render the given list of projects with the specified parameters.
-pub fn projects(projects: Vec<Project>, params: &ListProjects) {
-    let mut table = Table::new();
+pub fn projects(projects: Vec<Project>, params: &ListProjects, format: OutputFormat) {
+    if format == OutputFormat::Json {
+        return print_json(&projects.iter().map(ProjectView::from).collect::<Vec<_>>());
+    }
+    let mut table = new_table();
     table.load_preset(presets::NOTHING);
     if params.detailed {
         table.set_header(header_cells(vec![
@@ -42,21 +350,114 @@ pub fn projects(projects: Vec<Project>, params: &ListProjects) {
             "GitHub",
         ]));
     }
-    for project in projects {
+    let project_row = |label: String, project: &Project| {
         if params.detailed {
-            table.add_row(vec![
-                Cell::new(project.id()).fg(COLOR_PROJECT),
+            vec![
+                Cell::new(label).fg(COLOR_PROJECT),
                 Cell::new(project.name()),
                 Cell::new(display_optional(project.description())),
                 Cell::new(display_optional(project.deadline())).fg(COLOR_DEADLINE),
                 Cell::new(join(project.tags(), ",")).fg(COLOR_TAGS),
                 Cell::new(display_optional(project.github_url().transpose().unwrap())),
-            ]);
+            ]
         } else {
-            table.add_row(vec![Cell::new(project.id()).fg(Color::Blue)]);
+            vec![Cell::new(label).fg(Color::Blue)]
+        }
+    };
+    if params.tree {
+        let ids = projects.iter().map(Project::id).collect::<Vec<&str>>();
+        for row in project_tree(&ids) {
+            let label = format!("{}{}", "  ".repeat(row.depth), row.label);
+            match row.maybe_project {
+                Some(idx) => table.add_row(project_row(label, &projects[idx])),
+                None => table.add_row(vec![Cell::new(label).add_attribute(Attribute::Bold)]),
+            };
+        }
+    } else {
+        for project in projects.iter() {
+            table.add_row(project_row(project.id().to_string(), project));
         }
     }
-    println!("{}", table);
+    print_table(table, format);
+}
+
+// A row of a project tree: either a group header for an ID prefix shared by
+// several projects (which may itself be a project's ID), or a project.
+#[derive(Debug, PartialEq)]
+struct ProjectTreeRow {
+    depth: usize,
+    label: String,
+    // The index of the project in the list from which the tree was built.
+    maybe_project: Option<usize>,
+}
+
+// Arranges the given project IDs into a tree by splitting them into segments
+// on "-". Projects whose IDs share leading segments are nested under a group
+// header for the longest prefix they all share, while a project sharing no
+// leading segment with any other is shown as-is. Groups and projects keep the
+// order in which they first appear in the given IDs.
+fn project_tree(ids: &[&str]) -> Vec<ProjectTreeRow> {
+    let members = ids
+        .iter()
+        .enumerate()
+        .map(|(idx, id)| (idx, id.split('-').collect::<Vec<&str>>()))
+        .collect::<Vec<(usize, Vec<&str>)>>();
+    let mut rows = Vec::new();
+    build_project_tree(&members, 0, 0, &mut rows);
+    rows
+}
+
+// Groups the given members by their segment at the given offset, all of them
+// being assumed to share the segments before it.
+fn build_project_tree(
+    members: &[(usize, Vec<&str>)],
+    offset: usize,
+    depth: usize,
+    rows: &mut Vec<ProjectTreeRow>,
+) {
+    let mut groups: Vec<Vec<&(usize, Vec<&str>)>> = Vec::new();
+    for member in members {
+        match groups
+            .iter_mut()
+            .find(|group| group[0].1[offset] == member.1[offset])
+        {
+            Some(group) => group.push(member),
+            None => groups.push(vec![member]),
+        }
+    }
+    for group in groups {
+        if let [(idx, segments)] = group.as_slice() {
+            rows.push(ProjectTreeRow {
+                depth,
+                label: segments.join("-"),
+                maybe_project: Some(*idx),
+            });
+            continue;
+        }
+        // Extend the group's prefix for as long as all of its members share
+        // the next segment.
+        let mut end = offset + 1;
+        while group
+            .iter()
+            .all(|(_, segments)| segments.len() > end && segments[end] == group[0].1[end])
+        {
+            end += 1;
+        }
+        rows.push(ProjectTreeRow {
+            depth,
+            label: group[0].1[..end].join("-"),
+            maybe_project: group
+                .iter()
+                .find(|(_, segments)| segments.len() == end)
+                .map(|(idx, _)| *idx),
+        });
+        let children = group
+            .into_iter()
+            .filter(|(_, segments)| segments.len() > end)
+            .cloned()
+            .collect::<Vec<(usize, Vec<&str>)>>();
+        build_project_tree(&children, end, depth + 1, rows);
+    }
 }
 
 pub fn project_added(project: &Project) {
@@ -67,8 +468,47 @@ pub fn project_removed<S: AsRef<str>>(id: S) {
     println!("Removed project {}", id.as_ref().with(COLOR_PROJECT));
 }
 
-pub fn tasks(tasks: Vec<Task>, maybe_active_task: Option<(ProjectId, TaskId)>, params: &ListTasks) {
-    let mut table = Table::new();
+pub fn tasks_removed<S: AsRef<str>>(project_id: S, task_ids: &[TaskId]) {
+    println!(
+        "Removed {} {} from project {}",
+        if task_ids.len() == 1 { "task" } else { "tasks" },
+        join(task_ids.iter(), ", "),
+        project_id.as_ref().with(COLOR_PROJECT)
+    );
+}
+
+pub fn project_renamed<S: AsRef<str>>(old_id: S, project: &Project) {
+    println!(
+        "Renamed project {} to {} ({})",
+        old_id.as_ref().with(COLOR_PROJECT),
+        project.id().with(COLOR_PROJECT),
+        project.name()
+    );
+}
+
+pub fn project_updated(project: &Project) {
+    println!(
+        "Project {} updated{}",
+        project.id().with(COLOR_PROJECT),
+        if project.is_archived() {
+            " (archived)"
+        } else {
+            ""
+        }
+    );
+}
+
+pub fn tasks(
+    tasks: Vec<Task>,
+    active_tasks: Vec<(ProjectId, TaskId)>,
+    task_state_configs: &BTreeMap<ProjectId, TaskStateConfig>,
+    params: &ListTasks,
+    format: OutputFormat,
+) {
+    if format == OutputFormat::Json {
+        return print_json(&tasks.iter().map(TaskView::from).collect::<Vec<_>>());
+    }
+    let mut table = new_table();
     let mut cells = vec![
         "Project",
         "ID",
@@ -80,18 +520,20 @@ pub fn tasks(tasks: Vec<Task>, maybe_active_task: Option<(ProjectId, TaskId)>, p
         "Logged",
     ];
     if params.detailed {
+        cells.push("Estimate");
+        cells.push("Actual");
+        cells.push("Blocked By");
         cells.push("GitHub");
     }
     table
         .load_preset(presets::NOTHING)
         .set_header(header_cells(cells))
         .set_content_arrangement(ContentArrangement::Dynamic);
+    let now = Timestamp::now().unwrap();
     for task in tasks {
-        let is_active = if let Some((project_id, task_id)) = &maybe_active_task {
+        let is_active = active_tasks.iter().any(|(project_id, task_id)| {
             task.project_id().unwrap() == project_id && task.id().unwrap() == *task_id
-        } else {
-            false
-        };
+        });
 
         let priority = task.priority();
         let mut cells = vec![
@@ -102,10 +544,14 @@ pub fn tasks(tasks: Vec<Task>, maybe_active_task: Option<(ProjectId, TaskId)>, p
             Cell::new(format!(
                 "{}{}",
                 display_optional(task.state()),
-                if is_active { " ⏲️" } else { "" }
+                if is_active && format == OutputFormat::Table {
+                    " ⏲️"
+                } else {
+                    ""
+                }
             ))
             .fg(COLOR_STATES),
-            Cell::new(display_optional(task.deadline())).fg(COLOR_DEADLINE),
+            deadline_cell(&task, now, task_state_configs, format),
             Cell::new(join(task.tags(), ",")).fg(COLOR_TAGS),
             Cell::new(display_optional(
                 task.stats()
@@ -115,6 +561,30 @@ pub fn tasks(tasks: Vec<Task>, maybe_active_task: Option<(ProjectId, TaskId)>, p
             .set_alignment(CellAlignment::Right),
         ];
         if params.detailed {
+            let actual = task.stats().map(|stats| stats.time_logged);
+            let over_estimate = match (actual, task.estimate()) {
+                (Some(actual), Some(estimate)) => actual > estimate,
+                _ => false,
+            };
+            cells.push(
+                Cell::new(display_optional(
+                    task.estimate().map(|estimate| estimate.to_aligned_string()),
+                ))
+                .fg(COLOR_TIME)
+                .set_alignment(CellAlignment::Right),
+            );
+            cells.push(
+                Cell::new(display_optional(
+                    actual.map(|actual| actual.to_aligned_string()),
+                ))
+                .fg(if over_estimate {
+                    Color::Red
+                } else {
+                    COLOR_TIME
+                })
+                .set_alignment(CellAlignment::Right),
+            );
+            cells.push(Cell::new(join(task.blocked_by(), ",")));
             if let Some(project) = task.project() {
                 let maybe_github_url = if let Some(github_pr) = task.github_pr() {
                     project.github_pr_url(github_pr).transpose().unwrap()
@@ -137,7 +607,7 @@ pub fn tasks(tasks: Vec<Task>, maybe_active_task: Option<(ProjectId, TaskId)>, p
 
         table.add_row(cells);
     }
-    println!("{}", table);
+    print_table(table, format);
 }
 
 pub fn task_added(task: &Task) {
@@ -171,80 +641,500 @@ pub fn tasks_updated(tasks: Vec<Task>) {
     );
 }
 
-pub fn logs(logs: Vec<Log>, params: &ListLogs) {
-    let mut table = Table::new();
-    table.load_preset(presets::NOTHING);
+pub fn logs(logs: Vec<Log>, params: &ListLogs, config: &Config, format: OutputFormat) {
+    if format == OutputFormat::Json {
+        return print_json(
+            &logs
+                .iter()
+                .map(|log| LogView::new(log, config.duration_format()))
+                .collect::<Vec<_>>(),
+        );
+    }
+    let mut header = vec!["Project", "Task", "ID", "Start", "Duration"];
+    if params.show_stop {
+        header.push("Stop");
+    }
+    if params.show_gaps {
+        header.push("Gap");
+    }
     if params.detailed {
-        table
-            .set_header(header_cells(vec![
-                "Project",
-                "Task",
-                "ID",
-                "Start",
-                "Duration",
-                "Comment",
-                "Tags",
-                "Task Description",
-                "Task Tags",
-            ]))
-            .set_content_arrangement(ContentArrangement::Dynamic);
-    } else {
-        table.set_header(header_cells(vec![
-            "Project", "Task", "ID", "Start", "Duration", "Tags",
-        ]));
+        header.push("Comment");
+    }
+    header.push("Tags");
+    if params.detailed {
+        header.extend(["Task Description", "Task Tags"]);
+    }
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(header));
+    if params.detailed {
+        table.set_content_arrangement(ContentArrangement::Dynamic);
     }
-    let mut total_duration = Duration::zero();
+    let now = Timestamp::now().unwrap();
+    let totals = LogTotals::new(&logs, config, now);
+    let gaps = log_gaps(&logs);
     let log_count = logs.len();
-    for log in logs {
-        if params.detailed {
-            table.add_row(vec![
-                Cell::new(log.project_id().unwrap()).fg(COLOR_PROJECT),
-                Cell::new(display_optional(log.task_id())),
-                Cell::new(log.id().unwrap()),
-                Cell::new(display_optional(log.start())).fg(COLOR_TIME),
+    for (log, maybe_gap) in logs.iter().zip(gaps) {
+        let mut row = vec![
+            Cell::new(log.project_id().unwrap()).fg(COLOR_PROJECT),
+            Cell::new(display_optional(log.task_id())),
+            Cell::new(log.id().unwrap()),
+            Cell::new(display_optional(log.start())).fg(COLOR_TIME),
+            Cell::new(match log.duration() {
+                Some(duration) => duration.to_aligned_string(),
+                None if params.show_stop => "(running)".to_string(),
+                None => "".to_string(),
+            })
+            .fg(COLOR_TIME)
+            .set_alignment(CellAlignment::Right),
+        ];
+        if params.show_stop {
+            row.push(Cell::new(display_optional(log.stop())).fg(COLOR_TIME));
+        }
+        if params.show_gaps {
+            row.push(
                 Cell::new(display_optional(
-                    log.duration().map(|d| d.to_aligned_string()),
+                    maybe_gap.map(|gap| gap.to_aligned_string()),
                 ))
-                .fg(COLOR_TIME)
                 .set_alignment(CellAlignment::Right),
-                Cell::new(display_optional(log.comment())),
-                Cell::new(join(log.tags(), ",")).fg(COLOR_TAGS),
+            );
+        }
+        if params.detailed {
+            row.push(Cell::new(display_optional(log.comment())));
+        }
+        row.push(Cell::new(join(log.tags(), ",")).fg(COLOR_TAGS));
+        if params.detailed {
+            row.extend([
                 Cell::new(display_optional(log.task().map(|task| task.description()))),
                 Cell::new(display_optional(
                     log.task().map(|task| join(task.tags(), ",")),
                 ))
                 .fg(COLOR_TAGS),
             ]);
-        } else {
-            table.add_row(vec![
+        }
+        table.add_row(row);
+    }
+    if format != OutputFormat::Table {
+        return print_table(table, format);
+    }
+    println!("{}", table);
+    println!();
+    print_log_totals(log_count, &totals);
+    if params.maybe_round.is_some() && totals.gross > Duration::zero() {
+        println!(
+            "Billed: {} (+{})",
+            totals.rounded_net().to_string().with(COLOR_TIME),
+            (totals.rounded_net() - totals.net())
+                .to_string()
+                .with(COLOR_TIME)
+        );
+    }
+}
+
+// Computes the gap between each of the given logs and the log started before
+// it on the same day, regardless of the order in which the logs are given.
+// The first log of each day has no gap.
+fn log_gaps(logs: &[Log]) -> Vec<Option<Duration>> {
+    let mut by_start = logs
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, log)| log.start().map(|start| (start, idx)))
+        .collect::<Vec<(Timestamp, usize)>>();
+    by_start.sort();
+    let mut gaps = vec![None; logs.len()];
+    for pair in by_start.windows(2) {
+        let ((prev_start, prev_idx), (start, idx)) = (pair[0], pair[1]);
+        if prev_start.today() == start.today() {
+            gaps[idx] = logs[idx].gap_since(&logs[prev_idx]);
+        }
+    }
+    gaps
+}
+
+/// Render each of the given pairs of overlapping logs as a row. Running logs
+/// have no stop time.
+pub fn overlapping_logs(pairs: Vec<(Log, Log)>, config: &Config, format: OutputFormat) {
+    if format == OutputFormat::Json {
+        let duration_format = config.duration_format();
+        return print_json(
+            &pairs
+                .iter()
+                .map(|(log, other)| {
+                    (
+                        LogView::new(log, duration_format),
+                        LogView::new(other, duration_format),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
+    if pairs.is_empty() && format == OutputFormat::Table {
+        println!("No overlapping logs found");
+        return;
+    }
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(vec![
+            "Project", "Task", "ID", "Start", "Stop", "Project", "Task", "ID", "Start", "Stop",
+        ]));
+    for (log, other) in pairs.iter() {
+        let mut row = Vec::new();
+        for log in [log, other] {
+            row.extend([
                 Cell::new(log.project_id().unwrap()).fg(COLOR_PROJECT),
                 Cell::new(display_optional(log.task_id())),
                 Cell::new(log.id().unwrap()),
                 Cell::new(display_optional(log.start())).fg(COLOR_TIME),
-                Cell::new(display_optional(
-                    log.duration().map(|d| d.to_aligned_string()),
-                ))
-                .fg(COLOR_TIME)
-                .set_alignment(CellAlignment::Right),
-                Cell::new(join(log.tags(), ",")).fg(COLOR_TAGS),
+                Cell::new(display_optional(log.stop())).fg(COLOR_TIME),
             ]);
         }
-        total_duration += log.duration().unwrap_or_else(Duration::zero);
+        table.add_row(row);
+    }
+    if format != OutputFormat::Table {
+        return print_table(table, format);
+    }
+    println!("{}", table);
+    println!();
+    println!(
+        "{} overlapping {}",
+        pairs.len(),
+        if pairs.len() == 1 { "pair" } else { "pairs" }
+    );
+}
+
+/// Render the given logs, assumed to be sorted by start time, as a timeline
+/// for a single day.
+pub fn day(logs: Vec<Log>, config: &Config) {
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(vec![
+            "Start", "Stop", "Duration", "Gap", "Project", "Task", "Comment",
+        ]))
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    let now = Timestamp::now().unwrap();
+    let totals = LogTotals::new(&logs, config, now);
+    let mut maybe_prev_log: Option<&Log> = None;
+    for log in logs.iter() {
+        let maybe_gap = maybe_prev_log.and_then(|prev_log| log.gap_since(prev_log));
+        table.add_row(vec![
+            Cell::new(display_optional(
+                log.start().map(|start| start.format_time()),
+            ))
+            .fg(COLOR_TIME),
+            Cell::new(display_optional(log.stop().map(|stop| stop.format_time()))).fg(COLOR_TIME),
+            Cell::new(
+                log.duration()
+                    .map(|d| d.to_aligned_string())
+                    .unwrap_or_else(|| "(running)".to_string()),
+            )
+            .fg(COLOR_TIME)
+            .set_alignment(CellAlignment::Right),
+            Cell::new(display_optional(
+                maybe_gap.map(|gap| gap.to_aligned_string()),
+            ))
+            .set_alignment(CellAlignment::Right),
+            Cell::new(log.project_id().unwrap()).fg(COLOR_PROJECT),
+            Cell::new(display_optional(log.task_id())),
+            Cell::new(display_optional(log.comment())),
+        ]);
+        maybe_prev_log = Some(log);
     }
     println!("{}", table);
     println!();
+    print_log_totals(logs.len(), &totals);
+}
+
+// Prints the number of logs along with their net time, as well as their gross
+// time and breaks if any breaks were taken.
+fn print_log_totals(log_count: usize, totals: &LogTotals) {
     print!(
         "{} {}",
         log_count,
         if log_count == 1 { "log" } else { "logs" }
     );
-    if total_duration > Duration::zero() {
-        println!(", {}", total_duration.to_string().with(COLOR_TIME));
+    if totals.breaks > Duration::zero() {
+        println!(
+            ", {} net ({} gross, {} breaks)",
+            totals.net().to_string().with(COLOR_TIME),
+            totals.gross.to_string().with(COLOR_TIME),
+            totals.breaks.to_string().with(COLOR_TIME),
+        );
+    } else if totals.gross > Duration::zero() {
+        println!(", {}", totals.gross.to_string().with(COLOR_TIME));
     } else {
         println!();
     }
 }
 
+pub fn report(periods: Vec<ReportPeriod>, params: &Report) {
+    if params.maybe_period.is_none() {
+        if let Some(period) = periods.first() {
+            return project_report(period, params.with_rounding());
+        }
+    }
+    let project_ids = periods
+        .iter()
+        .flat_map(|period| period.by_project.keys())
+        .collect::<BTreeSet<&ProjectId>>();
+    let with_breaks = periods
+        .iter()
+        .any(|period| period.breaks > Duration::zero());
+    let mut header = vec!["From", "To", "Total"];
+    if with_breaks {
+        header.extend(["Gross", "Breaks"]);
+    }
+    if params.with_rounding() {
+        header.extend(["Billed", "Diff"]);
+    }
+    header.extend(project_ids.iter().map(|project_id| project_id.as_str()));
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(header))
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    let mut grand_total = Duration::zero();
+    let mut grand_rounded = Duration::zero();
+    for period in periods.iter() {
+        let mut row = vec![
+            Cell::new(period.start.format_date()),
+            Cell::new(period.end.format_date()),
+            Cell::new(period.total.to_aligned_string())
+                .fg(COLOR_TIME)
+                .set_alignment(CellAlignment::Right),
+        ];
+        if with_breaks {
+            row.extend([
+                Cell::new(period.gross.to_aligned_string()).set_alignment(CellAlignment::Right),
+                Cell::new(period.breaks.to_aligned_string()).set_alignment(CellAlignment::Right),
+            ]);
+        }
+        if params.with_rounding() {
+            row.extend([
+                Cell::new(period.rounded.to_aligned_string()).set_alignment(CellAlignment::Right),
+                Cell::new(period.rounding_diff().to_aligned_string())
+                    .set_alignment(CellAlignment::Right),
+            ]);
+        }
+        row.extend(project_ids.iter().map(|project_id| {
+            Cell::new(
+                period
+                    .by_project
+                    .get(*project_id)
+                    .copied()
+                    .unwrap_or_else(Duration::zero)
+                    .to_aligned_string(),
+            )
+            .set_alignment(CellAlignment::Right)
+        }));
+        table.add_row(row);
+        grand_total += period.total;
+        grand_rounded += period.rounded;
+    }
+    println!("{}", table);
+    if periods.len() > 1 {
+        println!();
+        println!("Total: {}", grand_total.to_string().with(COLOR_TIME));
+        if params.with_rounding() {
+            println!(
+                "Billed: {} (+{})",
+                grand_rounded.to_string().with(COLOR_TIME),
+                (grand_rounded - grand_total).to_string().with(COLOR_TIME)
+            );
+        }
+    }
+}
+
+// Renders the total time logged per project in the given period, along with
+// each project's share of the total.
+fn project_report(period: &ReportPeriod, with_rounding: bool) {
+    println!(
+        "From {} to {}",
+        period.start.format_date().with(COLOR_TIME),
+        period.end.format_date().with(COLOR_TIME)
+    );
+    println!();
+    let mut header = vec!["Project", "Total", "%"];
+    if with_rounding {
+        header.extend(["Billed", "Diff"]);
+    }
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(header));
+    for (project_id, total) in period.by_project.iter() {
+        let mut row = vec![
+            Cell::new(project_id).fg(COLOR_PROJECT),
+            Cell::new(total.to_aligned_string())
+                .fg(COLOR_TIME)
+                .set_alignment(CellAlignment::Right),
+            Cell::new(display_optional(
+                period
+                    .project_percentage(project_id)
+                    .map(|percentage| format!("{:.1}", percentage)),
+            ))
+            .set_alignment(CellAlignment::Right),
+        ];
+        if with_rounding {
+            row.extend([
+                Cell::new(display_optional(
+                    period
+                        .rounded_by_project
+                        .get(project_id)
+                        .map(Duration::to_aligned_string),
+                ))
+                .set_alignment(CellAlignment::Right),
+                Cell::new(display_optional(
+                    period
+                        .project_rounding_diff(project_id)
+                        .map(|diff| diff.to_aligned_string()),
+                ))
+                .set_alignment(CellAlignment::Right),
+            ]);
+        }
+        table.add_row(row);
+    }
+    let mut total_row = vec![
+        header_cell("Total"),
+        header_cell(period.total.to_aligned_string())
+            .fg(COLOR_TIME)
+            .set_alignment(CellAlignment::Right),
+        header_cell(if period.total > Duration::zero() {
+            "100.0"
+        } else {
+            ""
+        })
+        .set_alignment(CellAlignment::Right),
+    ];
+    if with_rounding {
+        total_row.extend([
+            header_cell(period.rounded.to_aligned_string()).set_alignment(CellAlignment::Right),
+            header_cell(period.rounding_diff().to_aligned_string())
+                .set_alignment(CellAlignment::Right),
+        ]);
+    }
+    table.add_row(total_row);
+    println!("{}", table);
+    if period.breaks > Duration::zero() {
+        println!();
+        println!(
+            "{} gross, {} breaks",
+            period.gross.to_string().with(COLOR_TIME),
+            period.breaks.to_string().with(COLOR_TIME)
+        );
+    }
+}
+
+// Renders the total time logged per tag, most-logged tag first.
+pub fn tag_report(totals: &[(String, Duration)]) {
+    println!("Logs with more than one tag count towards each of their tags' totals");
+    println!();
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(["Tag", "Total"]));
+    for (tag, total) in totals {
+        table.add_row(vec![
+            Cell::new(tag).fg(COLOR_TAGS),
+            Cell::new(total.to_aligned_string())
+                .fg(COLOR_TIME)
+                .set_alignment(CellAlignment::Right),
+        ]);
+    }
+    println!("{}", table);
+}
+
+pub fn invoice(invoice: &InvoiceSummary) {
+    println!(
+        "Invoice for project {} at {} per hour",
+        invoice.project_id.as_str().with(COLOR_PROJECT),
+        format_amount(invoice.rate, invoice.currency.as_deref())
+    );
+    println!();
+    let mut table = new_table();
+    table
+        .load_preset(presets::NOTHING)
+        .set_header(header_cells(["Date", "Task", "Comment", "Time", "Amount"]))
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    for item in invoice.items.iter() {
+        table.add_row(vec![
+            Cell::new(display_optional(
+                item.log.start().map(|start| start.format_date()),
+            )),
+            Cell::new(display_optional(item.log.task_id())),
+            Cell::new(display_optional(item.log.comment())),
+            Cell::new(display_optional(
+                item.log
+                    .duration()
+                    .map(|duration| duration.to_aligned_string()),
+            ))
+            .fg(COLOR_TIME)
+            .set_alignment(CellAlignment::Right),
+            Cell::new(format!("{:.2}", item.amount)).set_alignment(CellAlignment::Right),
+        ]);
+    }
+    table.add_row(vec![
+        header_cell("Total"),
+        Cell::new(""),
+        Cell::new(""),
+        header_cell(invoice.duration.to_aligned_string())
+            .fg(COLOR_TIME)
+            .set_alignment(CellAlignment::Right),
+        header_cell(format!("{:.2}", invoice.total)).set_alignment(CellAlignment::Right),
+    ]);
+    println!("{}", table);
+    println!();
+    println!(
+        "Total due: {}",
+        format_amount(invoice.total, invoice.currency.as_deref()).attribute(Attribute::Bold)
+    );
+}
+
+fn format_amount(amount: f64, maybe_currency: Option<&str>) -> String {
+    match maybe_currency {
+        Some(currency) => format!("{:.2} {}", amount, currency),
+        None => format!("{:.2}", amount),
+    }
+}
+
+pub fn stats(stats: &LogStats) {
+    println!(
+        "{} {}, {}",
+        stats.log_count,
+        if stats.log_count == 1 { "log" } else { "logs" },
+        stats.total.to_string().with(COLOR_TIME)
+    );
+    if let Some(punchcard) = &stats.maybe_punchcard {
+        println!();
+        let mut header = vec!["".to_string()];
+        header.extend((0..24).map(|hour| format!("{:02}", hour)));
+        let mut table = new_table();
+        table
+            .load_preset(presets::NOTHING)
+            .set_header(header_cells(header));
+        let weekdays = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+        for (weekday, hours) in weekdays.iter().zip(punchcard.iter()) {
+            let mut row = vec![header_cell(weekday)];
+            row.extend(hours.iter().map(|duration| {
+                let mins = time::Duration::from(*duration).whole_minutes();
+                Cell::new(if mins > 0 {
+                    mins.to_string()
+                } else {
+                    "".to_string()
+                })
+                .fg(COLOR_TIME)
+                .set_alignment(CellAlignment::Right)
+            }));
+            table.add_row(row);
+        }
+        println!("{}", table);
+        println!();
+        println!("(minutes logged per hour of each day of the week)");
+    }
+}
+
 pub fn log_added(log: &Log) {
     println!(
         "Log {} added for {}{}",
@@ -254,6 +1144,18 @@ pub fn log_added(log: &Log) {
     );
 }
 
+/// Warn about having logged time against the given closed task, if any.
+pub fn closed_task_warning(maybe_task: Option<&Task>) {
+    if let Some(task) = maybe_task {
+        println!(
+            "{} task {} is {}; use --reopen to move it back to in progress",
+            "Warning:".with(Color::Yellow),
+            task.id().unwrap(),
+            display_optional(task.state()).with(COLOR_STATES),
+        );
+    }
+}
+
 pub fn log_started(log: &Log) {
     println!(
         "Log {} for {}{} started at {}",
@@ -264,6 +1166,20 @@ pub fn log_started(log: &Log) {
     );
 }
 
+pub fn log_continued(log: &Log) {
+    println!(
+        "Log {} for {}{} started at {}{}",
+        log.id().unwrap(),
+        log.project_id().unwrap().with(COLOR_PROJECT),
+        display_optional(log.task_id().map(|task_id| format!(", task {}", task_id))),
+        log.start().unwrap().to_string().with(COLOR_TIME),
+        display_optional(
+            log.comment()
+                .map(|comment| format!(", continuing \"{}\"", comment))
+        ),
+    );
+}
+
 pub fn log_stopped(log: &Log) {
     println!(
         "Log {} for {}{} stopped at {} ({})",
@@ -275,6 +1191,44 @@ pub fn log_stopped(log: &Log) {
     );
 }
 
+pub fn log_linked(old_id: LogId, log: &Log) {
+    println!(
+        "Log {} for {} linked to task {} (now log {})",
+        old_id,
+        log.project_id().unwrap().with(COLOR_PROJECT),
+        log.task_id().unwrap(),
+        log.id().unwrap(),
+    );
+}
+
+pub fn log_updated(log: &Log) {
+    println!(
+        "Log {} for {}{} updated ({}{})",
+        log.id().unwrap(),
+        log.project_id().unwrap().with(COLOR_PROJECT),
+        display_optional(log.task_id().map(|task_id| format!(", task {}", task_id))),
+        display_optional(log.start()).with(COLOR_TIME),
+        display_optional(log.duration().map(|duration| format!(", {}", duration))),
+    );
+}
+
+pub fn days_filled(logs: &[Log], params: &FillDays) {
+    if logs.is_empty() {
+        println!("No empty days to fill");
+        return;
+    }
+    for log in logs {
+        println!(
+            "{} {} for {}{} ({})",
+            if params.dry_run { "Would add" } else { "Added" },
+            log.start().unwrap().format_date().with(COLOR_TIME),
+            log.project_id().unwrap().with(COLOR_PROJECT),
+            display_optional(log.task_id().map(|task_id| format!(", task {}", task_id))),
+            display_optional(log.duration()),
+        );
+    }
+}
+
 pub fn log_cancelled(maybe_log: Option<&Log>) {
     match maybe_log {
         Some(log) => {
@@ -289,33 +1243,140 @@ pub fn log_cancelled(maybe_log: Option<&Log>) {
     }
 }
 
-pub fn log_status(maybe_log_status: Option<LogStatus>) {
-    match maybe_log_status {
-        Some(status) => {
+pub fn log_status(statuses: Vec<LogStatus>) {
+    if statuses.is_empty() {
+        return println!("No active log");
+    }
+    for (i, status) in statuses.into_iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!(
+            "Log {} for {}{}{} active since {} ({})",
+            status.log.id().unwrap(),
+            status.log.project_id().unwrap().with(COLOR_PROJECT),
+            display_optional(
+                status
+                    .log
+                    .task_id()
+                    .map(|task_id| format!(", task {}", task_id))
+            ),
+            display_optional(
+                status
+                    .maybe_task_description
+                    .map(|desc| format!(" \"{}\"", desc))
+            )
+            .attribute(Attribute::Bold),
+            status.log.start().unwrap().to_string().with(COLOR_TIME),
+            status.active_for.to_string().with(COLOR_TIME),
+        );
+        let mut tags = status.log.tags().collect::<Vec<&str>>();
+        if !tags.is_empty() {
+            tags.sort_unstable();
+            println!("Tags: {}", tags.join(", ").with(COLOR_TAGS));
+        }
+        if let Some(progress) = status.maybe_estimate_progress {
             println!(
-                "Log {} for {}{}{} active since {} ({})",
-                status.log.id().unwrap(),
-                status.log.project_id().unwrap().with(COLOR_PROJECT),
-                display_optional(
-                    status
-                        .log
-                        .task_id()
-                        .map(|task_id| format!(", task {}", task_id))
-                ),
-                display_optional(
-                    status
-                        .maybe_task_description
-                        .map(|desc| format!(" \"{}\"", desc))
-                )
-                .attribute(Attribute::Bold),
-                status.log.start().unwrap().to_string().with(COLOR_TIME),
-                status.active_for.to_string().with(COLOR_TIME),
+                "Logged {} of {} estimated for this task ({})",
+                progress.logged.to_string().with(COLOR_TIME),
+                progress.estimate.to_string().with(COLOR_TIME),
+                match progress.overrun() {
+                    Some(overrun) => format!("{} over", overrun).with(Color::Red),
+                    None => format!("{} remaining", progress.remaining()).with(COLOR_TIME),
+                }
             );
         }
-        None => println!("No active log"),
     }
 }
 
+pub fn audit_entries(entries: Vec<AuditEntry>, format: OutputFormat) {
+    if format == OutputFormat::Json {
+        return print_json(&entries);
+    }
+    let mut table = new_table();
+    table.load_preset(presets::NOTHING);
+    table.set_header(header_cells(vec![
+        "Timestamp",
+        "Operation",
+        "Project",
+        "Task",
+        "Log",
+    ]));
+    for entry in entries {
+        table.add_row(vec![
+            Cell::new(entry.timestamp()).fg(COLOR_TIME),
+            Cell::new(entry.operation()),
+            Cell::new(display_optional(entry.project_id())).fg(COLOR_PROJECT),
+            Cell::new(display_optional(entry.task_id())),
+            Cell::new(display_optional(entry.log_id())),
+        ]);
+    }
+    print_table(table, format);
+}
+
+pub fn exported(path: &Path, summary: &ExportSummary) {
+    println!(
+        "Exported {} project(s), {} task(s) and {} work log(s) to {}",
+        summary.projects,
+        summary.tasks,
+        summary.logs,
+        path.display()
+    );
+}
+
+pub fn imported(path: &Path, summary: &ImportSummary) {
+    println!(
+        "Imported {} project(s), {} task(s) and {} work log(s) from {}",
+        summary.projects,
+        summary.tasks,
+        summary.logs,
+        path.display()
+    );
+    if !summary.conflicts.is_empty() {
+        println!(
+            "{} {}:",
+            summary.conflicts.len(),
+            if summary.conflicts.len() == 1 {
+                "conflict"
+            } else {
+                "conflicts"
+            }
+        );
+        for conflict in summary.conflicts.iter() {
+            println!("  {}", conflict);
+        }
+    }
+}
+
+/// Show which page of the matching logs was listed.
+pub fn logs_page(params: &ListLogs, listed: usize, total: usize, format: OutputFormat) {
+    if format == OutputFormat::Json {
+        return;
+    }
+    if listed == 0 {
+        println!("No logs listed ({} matching)", total);
+    } else {
+        println!(
+            "Listed logs {}-{} of {}",
+            params.offset + 1,
+            params.offset + listed,
+            total
+        );
+    }
+}
+
+pub fn logs_exported(path: &Path, count: usize) {
+    println!("Exported {} work log(s) to {}", count, path.display());
+}
+
+pub fn backed_up(path: &Path, files: &[PathBuf]) {
+    println!("Backed up {} file(s) to {}", files.len(), path.display());
+}
+
+pub fn restored(backup_path: &Path, path: &Path) {
+    println!("Restored {} to {}", backup_path.display(), path.display());
+}
+
 pub fn remote_initialized(path: &Path) {
     println!("{} initialized as a Git repository", path.display());
 }
@@ -328,6 +1389,103 @@ pub fn remote_pulled(path: &Path) {
     println!("{} pulled from remote", path.display());
 }
 
+pub fn patch_outcomes(outcomes: Vec<PatchOutcome>) {
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.maybe_error.is_some())
+        .count();
+    for outcome in outcomes.iter() {
+        match &outcome.maybe_error {
+            Some(e) => println!("Failed to patch {}: {}", outcome.target, e.as_str().red()),
+            None => println!("Patched {}", outcome.target),
+        }
+    }
+    println!(
+        "{} of {} {} applied",
+        outcomes.len() - failed,
+        outcomes.len(),
+        if outcomes.len() == 1 {
+            "patch"
+        } else {
+            "patches"
+        }
+    );
+}
+
+pub fn check_report(report: &CheckReport) {
+    if report.orphaned_logs.is_empty() && report.id_collisions.is_empty() {
+        println!("No problems found");
+        return;
+    }
+    if !report.orphaned_logs.is_empty() {
+        println!(
+            "{} orphaned work {} (no associated project):",
+            report.orphaned_logs.len(),
+            if report.orphaned_logs.len() == 1 {
+                "log"
+            } else {
+                "logs"
+            }
+        );
+        for path in report.orphaned_logs.iter() {
+            println!("  {}", path.display());
+        }
+    }
+    if !report.id_collisions.is_empty() {
+        println!(
+            "{} ID {} (only one of each group is visible):",
+            report.id_collisions.len(),
+            if report.id_collisions.len() == 1 {
+                "collision"
+            } else {
+                "collisions"
+            }
+        );
+        for paths in report.id_collisions.iter() {
+            println!("  {}", join(paths.iter().map(|path| path.display()), ", "));
+        }
+    }
+    for project_id in report.recovered_projects.iter() {
+        println!(
+            "Created placeholder project {}",
+            project_id.as_str().with(COLOR_PROJECT)
+        );
+    }
+}
+
+pub fn verify_report(report: &VerifyReport) {
+    if !report.repairs.is_empty() {
+        println!(
+            "Made {} {}:",
+            report.repairs.len(),
+            if report.repairs.len() == 1 {
+                "repair"
+            } else {
+                "repairs"
+            }
+        );
+        for repair in report.repairs.iter() {
+            println!("  {}", repair);
+        }
+    }
+    if report.inconsistencies.is_empty() {
+        println!("No inconsistencies found");
+        return;
+    }
+    println!(
+        "Found {} {}:",
+        report.inconsistencies.len(),
+        if report.inconsistencies.len() == 1 {
+            "inconsistency"
+        } else {
+            "inconsistencies"
+        }
+    );
+    for inconsistency in report.inconsistencies.iter() {
+        println!("  {}", inconsistency);
+    }
+}
+
 fn display_optional<D: std::fmt::Display>(v: Option<D>) -> String {
     v.map(|inner| inner.to_string())
         .unwrap_or_else(|| "".to_string())
@@ -344,6 +1502,45 @@ where
         .join(sep)
 }
 
+fn print_table(mut table: Table, format: OutputFormat) {
+    match format {
+        OutputFormat::Table => println!("{}", table),
+        OutputFormat::Tsv => print!("{}", tsv(&mut table)),
+        // Callers serialize the underlying objects instead of the table
+        OutputFormat::Json => unreachable!(),
+    }
+}
+
+fn print_json<T: Serialize>(items: &[T]) {
+    println!("{}", serde_json::to_string_pretty(items).unwrap());
+}
+
+/// Render the header (if any) and rows of the given table as tab-separated
+/// values, one line per row.
+fn tsv(table: &mut Table) -> String {
+    let header = table.get_header().map(tsv_row);
+    header
+        .into_iter()
+        .chain(table.row_iter().map(tsv_row))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn tsv_row(row: &Row) -> String {
+    row.cell_iter()
+        .map(|cell| escape_tsv_field(cell.get_content().trim()))
+        .collect::<Vec<String>>()
+        .join("\t")
+}
+
+fn escape_tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
 fn header_cell<S: ToString>(s: S) -> Cell {
     Cell::new(s).add_attribute(Attribute::Bold)
 }
@@ -352,6 +1549,28 @@ fn header_cells<S: ToString, I: IntoIterator<Item = S>>(headings: I) -> Vec<Cell
     headings.into_iter().map(header_cell).collect()
 }
 
+// Overdue deadlines are marked with a "!" when rendered as a table.
+fn deadline_cell(
+    task: &Task,
+    now: Timestamp,
+    task_state_configs: &BTreeMap<ProjectId, TaskStateConfig>,
+    format: OutputFormat,
+) -> Cell {
+    let deadline = display_optional(task.deadline());
+    let is_overdue = task
+        .project_id()
+        .and_then(|project_id| task_state_configs.get(project_id))
+        .map(|task_state_config| task.is_overdue(now, task_state_config))
+        .unwrap_or(false);
+    if format == OutputFormat::Table && is_overdue {
+        Cell::new(format!("{} !", deadline))
+            .fg(COLOR_DEADLINE)
+            .add_attribute(Attribute::Bold)
+    } else {
+        Cell::new(deadline).fg(COLOR_DEADLINE)
+    }
+}
+
 fn priority_color(priority: u8) -> Color {
     let prio_thresh = MAX_TASK_PRIORITY / 3;
     if priority <= prio_thresh {
@@ -362,3 +1581,110 @@ fn priority_color(priority: u8) -> Color {
         COLOR_PRIORITY_LOW
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tsv_escapes_tabs_and_newlines() {
+        let mut table = Table::new();
+        table.set_header(header_cells(vec!["ID", "Comment", "Tags"]));
+        table.add_row(vec![
+            Cell::new(1),
+            Cell::new("Did\tthings"),
+            Cell::new("dev"),
+        ]);
+        table.add_row(vec![
+            Cell::new(2),
+            Cell::new("Line 1\nLine 2"),
+            Cell::new(""),
+        ]);
+
+        let rendered = tsv(&mut table);
+        let lines = rendered.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 3);
+        for line in lines.iter() {
+            assert_eq!(line.split('\t').count(), 3);
+        }
+        assert_eq!(lines[0], "ID\tComment\tTags");
+        assert_eq!(lines[1], "1\tDid\\tthings\tdev");
+        assert_eq!(lines[2], "2\tLine 1\\nLine 2\t");
+    }
+
+    #[test]
+    fn no_ansi_escapes_with_color_disabled() {
+        let render = || {
+            let mut table = new_table();
+            table.add_row(vec![
+                Cell::new("project-1").fg(COLOR_PROJECT),
+                Cell::new("Overdue").add_attribute(Attribute::Bold),
+            ]);
+            format!(
+                "{}\n{} {} {}",
+                table,
+                "project-1".with(COLOR_PROJECT),
+                "Total".attribute(Attribute::Bold),
+                "Failed".red()
+            )
+        };
+
+        set_color(ColorChoice::Always);
+        assert!(render().contains('\x1b'));
+        set_color(ColorChoice::Never);
+        let rendered = render();
+        assert!(!rendered.contains('\x1b'), "{:?}", rendered);
+        assert!(rendered.contains("project-1 Total Failed"));
+    }
+
+    #[test]
+    fn gaps_between_logs_on_the_same_day() {
+        let log = |id, start: &str, duration: &str| {
+            Log::new("project-1")
+                .with_id(id)
+                .with_start(Timestamp::from_str(start).unwrap())
+                .with_duration(Duration::from_str(duration).unwrap())
+        };
+        // Listed out of order, as if sorted by something other than start time
+        let logs = vec![
+            log(3, "2021-11-29 13:00", "1h"),
+            log(1, "2021-11-29 09:00", "1h"),
+            log(2, "2021-11-29 10:15", "2h"),
+            log(4, "2021-11-29 13:30", "1h"),
+            log(5, "2021-11-30 09:00", "1h"),
+        ];
+        let minutes = |mins| Some(Duration::from(time::Duration::minutes(mins)));
+        assert_eq!(
+            log_gaps(&logs),
+            vec![minutes(45), None, minutes(15), minutes(0), None]
+        );
+    }
+
+    #[test]
+    fn project_tree_groups_by_shared_prefix() {
+        let rows = project_tree(&["client-a-web", "client-a-api", "client-b", "internal"]);
+        let row = |depth, label: &str, maybe_project| ProjectTreeRow {
+            depth,
+            label: label.to_string(),
+            maybe_project,
+        };
+        assert_eq!(
+            rows,
+            vec![
+                row(0, "client", None),
+                row(1, "client-a", None),
+                row(2, "client-a-web", Some(0)),
+                row(2, "client-a-api", Some(1)),
+                row(1, "client-b", Some(2)),
+                row(0, "internal", Some(3)),
+            ]
+        );
+
+        // A project whose ID is itself a shared prefix heads its group.
+        let rows = project_tree(&["client-a", "client-a-web"]);
+        assert_eq!(
+            rows,
+            vec![row(0, "client-a", Some(0)), row(1, "client-a-web", Some(1))]
+        );
+    }
+}
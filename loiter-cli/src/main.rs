@@ -3,17 +3,22 @@ mod display;
 use std::error::Error;
 use std::{convert::Infallible, path::PathBuf, str::FromStr};
 
+use crossterm::tty::IsTty;
 use log::{error, Level};
-use loiter::{cmd, ProjectId, Store};
+use loiter::{cmd, Store, STORE_DIR_NAME};
 use structopt::StructOpt;
 
-// Defaults to ~/.loiter
 #[derive(Debug, Clone)]
 struct StorePath(PathBuf);
 
-impl Default for StorePath {
-    fn default() -> Self {
-        Self(home::home_dir().unwrap().join(".loiter"))
+impl StorePath {
+    // Searches the current directory and its parents for a `.loiter` store,
+    // falling back to ~/.loiter
+    fn discover() -> Self {
+        let maybe_discovered = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| Store::discover(&cwd));
+        Self(maybe_discovered.unwrap_or_else(|| home::home_dir().unwrap().join(STORE_DIR_NAME)))
     }
 }
 
@@ -25,21 +30,38 @@ impl FromStr for StorePath {
     }
 }
 
-impl std::fmt::Display for StorePath {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.display())
-    }
-}
-
 #[derive(Debug, StructOpt)]
 #[structopt(name = "loiter")]
 struct Opt {
-    /// Where to find your local Loiter data store. Defaults to `~/.loiter`.
-    #[structopt(short, long, default_value)]
-    path: StorePath,
+    /// Where to find your local Loiter data store. If not given, the current
+    /// directory and its parents are searched for a `.loiter` directory,
+    /// falling back to `~/.loiter`.
+    #[structopt(name = "path", short, long, env = "LOITER_PATH")]
+    maybe_path: Option<StorePath>,
     /// Increase output logging verbosity to DEBUG level.
     #[structopt(short, long)]
     verbose: bool,
+    /// Skip (with a warning) any project, task or work log files that cannot
+    /// be parsed when listing them, instead of failing.
+    #[structopt(long)]
+    skip_corrupt: bool,
+    /// Fail if the active work log no longer exists, instead of clearing it
+    /// (with a warning). Useful for debugging.
+    #[structopt(long)]
+    strict_state: bool,
+    /// How long (in seconds) to wait for another Loiter process to finish
+    /// changing the store before giving up.
+    #[structopt(long, default_value = "5")]
+    lock_timeout: u64,
+    /// The format in which to render lists of projects, tasks and work logs
+    /// ("table", "tsv" or "json"). JSON output is never colored.
+    #[structopt(long, alias = "output", default_value)]
+    format: display::OutputFormat,
+    /// When to color output ("auto", "always" or "never"). By default, output
+    /// is only colored when written to a terminal, unless the NO_COLOR
+    /// environment variable is set.
+    #[structopt(long, default_value)]
+    color: display::ColorChoice,
     #[structopt(subcommand)]
     command: Command,
 }
@@ -52,26 +74,84 @@ enum Command {
     Remove(RemoveCommand),
     /// Alias for "remove".
     Rm(RemoveCommand),
+    /// Rename a project.
+    Rename(RenameCommand),
     /// Update a project, task or work log.
     Update(UpdateCommand),
+    /// Create a work log from a line of text like "project-1 14:00-15:30 fixed
+    /// the parser #bug" (or start one from a single time like "14:00").
+    Log(cmd::LogFromLine),
     /// Start a work log.
     Start(cmd::StartLog),
-    /// Stop the currently active work log (or another specified one).
+    /// Stop the currently active work log (or another specified one, e.g. if
+    /// more than one is active).
     Stop(cmd::StopLog),
-    /// Cancel the currently active work log (or another specified one).
+    /// Start a new work log continuing the most recently stopped one, with the
+    /// same project, task, comment and tags.
+    Continue(cmd::ContinueLog),
+    /// Cancel the currently active work log (or another specified one, e.g. if
+    /// more than one is active).
     Cancel(cmd::CancelLog),
-    /// Show the status of the currently active work log (if any).
+    /// Link a work log that was created without a task to one of its
+    /// project's tasks.
+    Link(cmd::LinkLog),
+    /// Show the status of all currently active work logs (if any).
     Status,
+    /// Show a timeline of all work logs for a particular day.
+    Day(cmd::Day),
+    /// Add placeholder work logs to all workdays in a date range on which
+    /// nothing was logged.
+    Fill(cmd::FillDays),
+    /// Report on the total time logged per project (this week, by default), or
+    /// optionally as a weekly/daily/monthly time series.
+    Report(cmd::Report),
+    /// Bill the time logged for a project (this month, by default) at the
+    /// project's hourly rate.
+    Invoice(cmd::Invoice),
+    /// Show statistics about logged time, such as a punchcard of when time is
+    /// logged during the week.
+    Stats(cmd::Stats),
+    /// List all tags used by projects, tasks and work logs, along with how
+    /// many of them use each tag.
+    Tags,
     /// Show a list of valid task states.
     States(cmd::TaskStates),
+    /// Change Loiter's configuration.
+    Config(ConfigCommand),
     /// List projects, tasks or work logs.
     List(ListCommand),
     /// Alias for "list".
     Ls(ListCommand),
     /// Working with remote storage.
     Remote(RemoteCommand),
-    /// Shortcut to update one or more tasks as done.
-    Done(DoneCommand),
+    /// Show the audit trail of changes made to the store.
+    Audit(cmd::Audit),
+    /// Back up the entire store to a gzipped tarball.
+    Backup(cmd::Backup),
+    /// Restore a backup created by "backup" into an empty directory.
+    Restore(cmd::Restore),
+    /// Export the store's contents to another format for ad-hoc querying.
+    Export(ExportCommand),
+    /// Import projects, tasks and work logs from a JSON document created by
+    /// "export json".
+    Import(cmd::Import),
+    /// Check the store for problems (e.g. orphaned work logs).
+    Check(cmd::Check),
+    /// Verify the integrity of the whole store (e.g. after manual edits or a
+    /// bad merge), reporting any inconsistencies found and optionally
+    /// repairing them.
+    #[structopt(alias = "doctor")]
+    Verify(cmd::Verify),
+    /// Apply a batch of changes to projects, tasks and/or work logs, read from
+    /// standard input as a JSON array of patches.
+    Patch(cmd::Patch),
+    /// Shortcut to mark one or more tasks as done, stopping any active work
+    /// logs belonging to them.
+    Done(cmd::MarkTasks),
+    /// Shortcut to mark one or more tasks as in progress.
+    Doing(cmd::MarkTasks),
+    /// Alias for "doing".
+    StartTask(cmd::MarkTasks),
 }
 
 #[derive(Debug, StructOpt)]
@@ -84,14 +164,27 @@ enum AddCommand {
 #[derive(Debug, StructOpt)]
 enum RemoveCommand {
     Project(cmd::RemoveProject),
+    Task(cmd::RemoveTask),
+}
+
+#[derive(Debug, StructOpt)]
+enum RenameCommand {
+    Project(cmd::RenameProject),
 }
 
 #[derive(Debug, StructOpt)]
 enum UpdateCommand {
+    /// Archive or unarchive a project.
+    Project(cmd::UpdateProject),
     /// Alias for "tasks".
     Task(cmd::UpdateTask),
     /// Update one or more tasks' properties.
     Tasks(cmd::UpdateTask),
+    /// Edit a work log's start time, duration, comment and/or tags, and/or
+    /// move it to a different project and/or task.
+    Log(cmd::UpdateLog),
+    /// Rename a tag on every project, task and work log that has it.
+    Tag(cmd::RenameTag),
 }
 
 #[derive(Debug, StructOpt)]
@@ -101,6 +194,49 @@ enum ListCommand {
     Logs(cmd::ListLogs),
 }
 
+#[derive(Debug, StructOpt)]
+enum ConfigCommand {
+    /// Configure the default task states, either via arguments or
+    /// interactively (with --wizard).
+    States(cmd::ConfigStates),
+    /// Configure the tags marking work logs as breaks (e.g. lunch). Breaks
+    /// are assumed to have been taken during other logged work, and are
+    /// subtracted from net time totals.
+    BreakTags(cmd::ConfigBreakTags),
+    /// Configure the increment (e.g. 15m) to which each work log's duration
+    /// is rounded up when computing billed time (see report
+    /// --round-and-report-diff).
+    Rounding(cmd::ConfigRounding),
+    /// Configure the day on which weeks start (e.g. for "this-week" filters
+    /// and weekly reports).
+    WeekStart(cmd::ConfigWeekStart),
+    /// Configure how far in the future (e.g. 5m) a work log's stop time may
+    /// be before it's rejected as a likely typo.
+    StopTolerance(cmd::ConfigStopTolerance),
+    /// Configure whether durations in JSON output and exports are also
+    /// rendered as ISO 8601 durations (e.g. PT1H30M).
+    DurationFormat(cmd::ConfigDurationFormat),
+}
+
+#[derive(Debug, StructOpt)]
+enum ExportCommand {
+    /// Export all projects, tasks and work logs to a new SQLite database.
+    Sqlite(cmd::ExportSqlite),
+    /// Export work logs matching the given filters as CSV, to standard output
+    /// or a new file.
+    Logs(Box<cmd::ExportLogs>),
+    /// Export work logs matching the given filters as a Markdown document
+    /// grouped by project (e.g. for a stand-up summary), to standard output
+    /// or a new file.
+    Markdown(Box<cmd::ExportMarkdown>),
+    /// Export work logs matching the given filters as iCalendar events (e.g.
+    /// for viewing in a calendar app), to standard output or a new file.
+    Ical(Box<cmd::ExportIcal>),
+    /// Export all projects, tasks and work logs as JSON (e.g. for importing
+    /// into another store via "import"), to standard output or a new file.
+    Json(cmd::ExportJson),
+}
+
 #[derive(Debug, StructOpt)]
 enum RemoteCommand {
     /// Initialize the remote storage.
@@ -111,39 +247,113 @@ enum RemoteCommand {
     Pull,
 }
 
-#[derive(Debug, StructOpt)]
-struct DoneCommand {
-    /// The ID of the project whose task(s) must be marked as done.
-    project_id: ProjectId,
-    /// The ID(s) of the tasks to mark as done (comma-separated).
-    task_ids: String,
-}
-
 fn execute(opt: Opt) -> Result<(), Box<dyn Error>> {
-    let store = Store::new(&opt.path.0)?;
+    let path = opt.maybe_path.unwrap_or_else(StorePath::discover);
+    let store = Store::new(&path.0)?
+        .with_skip_corrupt(opt.skip_corrupt)
+        .with_strict_state(opt.strict_state)
+        .with_lock_timeout(std::time::Duration::from_secs(opt.lock_timeout));
     match opt.command {
         Command::Add(sub_cmd) => add(&store, sub_cmd)?,
         Command::Remove(sub_cmd) | Command::Rm(sub_cmd) => remove(&store, sub_cmd)?,
+        Command::Rename(sub_cmd) => rename(&store, sub_cmd)?,
         Command::Update(sub_cmd) => update(&store, sub_cmd)?,
-        Command::Start(params) => display::log_started(&cmd::start_log(&store, &params)?),
-        Command::Stop(params) => display::log_stopped(&cmd::stop_log(&store, &params)?),
+        Command::Log(params) => {
+            let log = cmd::log_from_line(&store, &params, std::io::stdin().lock())?;
+            if log.stop().is_some() {
+                display::log_added(&log)
+            } else {
+                display::log_started(&log)
+            }
+        }
+        Command::Start(params) => {
+            let log = cmd::start_log(&store, &params)?;
+            display::log_started(&log);
+            display::closed_task_warning(cmd::closed_log_task(&store, &log)?.as_ref());
+        }
+        Command::Stop(mut params) => {
+            let has_comment =
+                params.maybe_comment.is_some() || params.maybe_comment_append.is_some();
+            if use_editor(params.editor, has_comment) {
+                params.maybe_comment = cmd::edit_comment(&cmd::ExternalEditor, None)?;
+            }
+            display::log_stopped(&cmd::stop_log(&store, &params)?)
+        }
+        Command::Continue(params) => display::log_continued(&cmd::continue_log(&store, &params)?),
         Command::Cancel(params) => {
             display::log_cancelled(cmd::cancel_log(&store, &params)?.as_ref())
         }
-        Command::Status => display::log_status(cmd::active_log_status(&store)?),
+        Command::Link(params) => display::log_linked(params.id, &cmd::link_log(&store, &params)?),
+        Command::Status => display::log_status(cmd::active_log_statuses(&store)?),
+        Command::Day(params) => display::day(cmd::day(&store, &params)?, &store.config()?),
+        Command::Fill(params) => display::days_filled(&cmd::fill_days(&store, &params)?, &params),
+        Command::Report(params) if params.by_tag => {
+            display::tag_report(&cmd::report_by_tag(&store, &params)?)
+        }
+        Command::Report(params) => display::report(cmd::report(&store, &params)?, &params),
+        Command::Invoice(params) => display::invoice(&cmd::invoice(&store, &params)?),
+        Command::Stats(params) => display::stats(&cmd::stats(&store, &params)?),
+        Command::Tags => display::tags(cmd::tags(&store)?, opt.format),
+        Command::States(params) if params.in_use => display::states_in_use(
+            cmd::states_in_use(&store, &params)?,
+            &cmd::task_states(&store, &params)?,
+            opt.format,
+        ),
         Command::States(params) => display::task_states(cmd::task_states(&store, &params)?),
-        Command::List(list_cmd) | Command::Ls(list_cmd) => list(&store, list_cmd)?,
+        Command::Config(sub_cmd) => config(&store, sub_cmd)?,
+        Command::List(list_cmd) | Command::Ls(list_cmd) => list(&store, list_cmd, opt.format)?,
         Command::Remote(sub_cmd) => remote(&store, sub_cmd)?,
-        Command::Done(done_cmd) => task_done(&store, done_cmd)?,
+        Command::Audit(params) => display::audit_entries(cmd::audit(&store, &params)?, opt.format),
+        Command::Backup(params) => {
+            display::backed_up(&params.output, &cmd::backup(&store, &params)?)
+        }
+        Command::Restore(params) => {
+            display::restored(&params.input, &cmd::restore(&store, &params)?)
+        }
+        Command::Export(sub_cmd) => export(&store, sub_cmd)?,
+        Command::Import(params) => display::imported(&params.path, &cmd::import(&store, &params)?),
+        Command::Check(params) => display::check_report(&cmd::check(&store, &params)?),
+        Command::Verify(params) => display::verify_report(&cmd::verify(&store, &params)?),
+        Command::Patch(params) => {
+            display::patch_outcomes(cmd::patch(&store, &params, std::io::stdin())?)
+        }
+        Command::Done(params) => {
+            let (tasks, stopped_logs) = cmd::mark_tasks_done(&store, &params)?;
+            for log in stopped_logs.iter() {
+                display::log_stopped(log);
+            }
+            display::tasks_updated(tasks)
+        }
+        Command::Doing(params) | Command::StartTask(params) => {
+            display::tasks_updated(cmd::mark_tasks_in_progress(&store, &params)?)
+        }
+    }
+    // Skipped files are already logged as warnings, and a summary would
+    // corrupt JSON output
+    if opt.format != display::OutputFormat::Json {
+        display::skipped_files(&store.skipped_files());
     }
     Ok(())
 }
 
+// Comments are written in an external editor if explicitly requested, or if
+// none was given and the user is at a terminal (like "git commit").
+fn use_editor(editor: bool, has_comment: bool) -> bool {
+    editor || (!has_comment && std::io::stdin().is_tty())
+}
+
 fn add(store: &Store, cmd: AddCommand) -> Result<(), Box<dyn Error>> {
     match cmd {
         AddCommand::Project(params) => display::project_added(&cmd::add_project(store, &params)?),
         AddCommand::Task(params) => display::task_added(&cmd::add_task(store, &params)?),
-        AddCommand::Log(params) => display::log_added(&cmd::add_log(store, &params)?),
+        AddCommand::Log(mut params) => {
+            if use_editor(params.editor, params.maybe_comment.is_some()) {
+                params.maybe_comment = cmd::edit_comment(&cmd::ExternalEditor, None)?;
+            }
+            let log = cmd::add_log(store, &params)?;
+            display::log_added(&log);
+            display::closed_task_warning(cmd::closed_log_task(store, &log)?.as_ref());
+        }
     }
     Ok(())
 }
@@ -153,30 +363,135 @@ fn remove(store: &Store, cmd: RemoveCommand) -> Result<(), Box<dyn Error>> {
         RemoveCommand::Project(params) => {
             display::project_removed(&cmd::remove_project(store, &params)?)
         }
+        RemoveCommand::Task(params) => {
+            display::tasks_removed(&params.project_id, &cmd::remove_task(store, &params)?)
+        }
+    }
+    Ok(())
+}
+
+fn rename(store: &Store, cmd: RenameCommand) -> Result<(), Box<dyn Error>> {
+    match cmd {
+        RenameCommand::Project(params) => {
+            display::project_renamed(&params.old_id, &cmd::rename_project(store, &params)?)
+        }
     }
     Ok(())
 }
 
 fn update(store: &Store, cmd: UpdateCommand) -> Result<(), Box<dyn Error>> {
     match cmd {
+        UpdateCommand::Project(params) => {
+            display::project_updated(&cmd::update_project(store, &params)?)
+        }
         UpdateCommand::Task(params) | UpdateCommand::Tasks(params) => {
             display::tasks_updated(cmd::update_tasks(store, &params)?)
         }
+        UpdateCommand::Log(params) => display::log_updated(&cmd::update_log(store, &params)?),
+        UpdateCommand::Tag(params) => {
+            display::tag_renamed(&params, cmd::rename_tag(store, &params)?)
+        }
     }
     Ok(())
 }
 
-fn list(store: &Store, cmd: ListCommand) -> Result<(), Box<dyn Error>> {
+fn list(
+    store: &Store,
+    cmd: ListCommand,
+    format: display::OutputFormat,
+) -> Result<(), Box<dyn Error>> {
     match cmd {
         ListCommand::Projects(params) => {
-            display::projects(cmd::list_projects(store, &params)?, &params)
+            display::projects(cmd::list_projects(store, &params)?, &params, format)
         }
         ListCommand::Tasks(params) => display::tasks(
             cmd::list_tasks(store, &params)?,
-            store.active_task()?,
+            store.active_tasks()?,
+            &store.task_state_configs()?,
             &params,
+            format,
         ),
-        ListCommand::Logs(params) => display::logs(cmd::list_logs(store, &params)?, &params),
+        ListCommand::Logs(params) if params.check_overlaps => display::overlapping_logs(
+            cmd::overlapping_logs(store, &params)?,
+            &store.config()?,
+            format,
+        ),
+        ListCommand::Logs(params) => {
+            let logs = cmd::list_logs(store, &params)?;
+            let listed = logs.len();
+            let config = cmd::rounding_config(store, params.maybe_round)?;
+            display::logs(logs, &params, &config, format);
+            if params.maybe_limit.is_some() || params.offset > 0 {
+                display::logs_page(&params, listed, cmd::count_logs(store, &params)?, format);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn config(store: &Store, cmd: ConfigCommand) -> Result<(), Box<dyn Error>> {
+    match cmd {
+        ConfigCommand::States(params) => {
+            let stdin = std::io::stdin();
+            if params.wizard && !stdin.is_tty() {
+                return Err(
+                    "the wizard can only be used interactively; specify the states \
+                    via --states (and optionally --initial, --in-progress and --done) instead"
+                        .into(),
+                );
+            }
+            let task_state_config =
+                cmd::config_states(store, &params, stdin.lock(), std::io::stdout())?;
+            display::task_state_config(&task_state_config)
+        }
+        ConfigCommand::BreakTags(params) => {
+            display::break_tags(&cmd::config_break_tags(store, &params)?)
+        }
+        ConfigCommand::Rounding(params) => {
+            display::rounding(&cmd::config_rounding(store, &params)?)
+        }
+        ConfigCommand::WeekStart(params) => {
+            display::week_start(&cmd::config_week_start(store, &params)?)
+        }
+        ConfigCommand::StopTolerance(params) => {
+            display::stop_tolerance(&cmd::config_stop_tolerance(store, &params)?)
+        }
+        ConfigCommand::DurationFormat(params) => {
+            display::duration_format(&cmd::config_duration_format(store, &params)?)
+        }
+    }
+    Ok(())
+}
+
+fn export(store: &Store, cmd: ExportCommand) -> Result<(), Box<dyn Error>> {
+    match cmd {
+        ExportCommand::Sqlite(params) => {
+            display::exported(&params.output, &cmd::export_sqlite(store, &params)?)
+        }
+        ExportCommand::Logs(params) => {
+            let count = cmd::export_logs(store, &params, std::io::stdout().lock())?;
+            if let Some(path) = params.maybe_output.as_ref() {
+                display::logs_exported(path, count);
+            }
+        }
+        ExportCommand::Markdown(params) => {
+            let count = cmd::export_markdown(store, &params, std::io::stdout().lock())?;
+            if let Some(path) = params.maybe_output.as_ref() {
+                display::logs_exported(path, count);
+            }
+        }
+        ExportCommand::Ical(params) => {
+            let count = cmd::export_ical(store, &params, std::io::stdout().lock())?;
+            if let Some(path) = params.maybe_output.as_ref() {
+                display::logs_exported(path, count);
+            }
+        }
+        ExportCommand::Json(params) => {
+            let summary = cmd::export_json(store, &params, std::io::stdout().lock())?;
+            if let Some(path) = params.maybe_output.as_ref() {
+                display::exported(path, &summary);
+            }
+        }
     }
     Ok(())
 }
@@ -192,29 +507,6 @@ fn remote(store: &Store, cmd: RemoteCommand) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn task_done(store: &Store, cmd: DoneCommand) -> Result<(), Box<dyn Error>> {
-    let config = store.config()?;
-    let project = store.project(&cmd.project_id)?;
-    let done = project
-        .task_state_config()
-        .unwrap_or_else(|| config.task_state_config())
-        .done();
-    update(
-        store,
-        UpdateCommand::Task(cmd::UpdateTask {
-            project_id: cmd.project_id,
-            task_ids: cmd.task_ids,
-            maybe_state: Some(done.to_string()),
-            maybe_description: None,
-            maybe_priority: None,
-            maybe_deadline: None,
-            maybe_tags: None,
-            maybe_github_issue: None,
-            maybe_github_pr: None,
-        }),
-    )
-}
-
 fn main() {
     let opt = Opt::from_args();
     simple_logger::init_with_level(if opt.verbose {
@@ -223,6 +515,7 @@ fn main() {
         Level::Info
     })
     .unwrap();
+    display::set_color(opt.color);
 
     if let Err(e) = execute(opt) {
         error!("{}", e);
@@ -1,14 +1,20 @@
 //! User-oriented functionality for interacting with Loiter stores.
 
+use crate::strings::{csv_field, ical_line, ical_text, slugify};
 use crate::{
-    is_dir, is_file, Duration, DurationFilter, Error, FilterSpec, Log, LogField, LogFilter, LogId,
-    Project, ProjectField, ProjectFilter, ProjectId, SortSpec, Store, Task, TaskField, TaskFilter,
-    TaskId, TaskPriority, TaskState, Timestamp, TimestampFilter,
+    is_dir, is_file, split_by_day, split_by_hour, ActiveLog, AuditEntry, AuditOperation, Config,
+    Duration, DurationFilter, DurationFormat, Error, Filter, FilterSpec, Inconsistency, Log,
+    LogField, LogFilter, LogId, LogTotals, LogView, Period, Project, ProjectExport, ProjectField,
+    ProjectFilter, ProjectId, ProjectView, RegexPattern, Repair, SortSpec, State, Store, Task,
+    TaskField, TaskFilter, TaskId, TaskPriority, TaskState, TaskStateConfig, TaskView, Timestamp,
+    TimestampFilter, WeekStart,
 };
-use log::debug;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
     ffi::OsStr,
+    io::Write,
     num::NonZeroU32,
     path::{Path, PathBuf},
     process::{Command, ExitStatus},
@@ -16,8 +22,16 @@ use std::{
 };
 use structopt::StructOpt;
 
+// Applied when listing logs, unless overridden by the project whose logs are
+// being listed.
+const DEFAULT_LOG_START_FILTER: &str = "today";
+const DEFAULT_LOG_SORT: &str = "start";
+// Used to edit comments if neither $VISUAL nor $EDITOR is set.
+const DEFAULT_EDITOR: &str = "vi";
+
 const GITIGNORE: &str = r#"*.swp
 *.bak
+.lock
 "#;
 
 /// Add a new project.
@@ -41,6 +55,37 @@ pub struct AddProject {
     #[structopt(name = "tags", long)]
     #[serde(rename = "tags")]
     pub maybe_tags: Option<String>,
+
+    /// Optionally set the start time filter applied by default when listing
+    /// only this project's logs (e.g. "this-month").
+    #[structopt(name = "default-log-filter", long)]
+    #[serde(rename = "default_log_filter")]
+    pub maybe_default_log_filter: Option<String>,
+
+    /// Optionally set how to sort this project's logs by default when listing
+    /// only this project's logs (e.g. "duration:desc").
+    #[structopt(name = "default-log-sort", long)]
+    #[serde(rename = "default_log_sort")]
+    pub maybe_default_log_sort: Option<String>,
+
+    /// Optionally set the hourly rate at which time spent on this project is
+    /// billed (e.g. "100" or "72.50").
+    #[structopt(name = "rate", long)]
+    #[serde(rename = "rate")]
+    pub maybe_rate: Option<f64>,
+
+    /// Optionally set the currency in which this project's time is billed
+    /// (e.g. "USD").
+    #[structopt(name = "currency", long)]
+    #[serde(rename = "currency")]
+    pub maybe_currency: Option<String>,
+
+    /// If another project already has the ID derived from this project's
+    /// name, append a number to the name (e.g. "Project 2", with ID
+    /// "project-2") instead of failing.
+    #[structopt(long)]
+    #[serde(default)]
+    pub auto_suffix: bool,
 }
 
 impl TryFrom<&AddProject> for Project {
@@ -50,6 +95,10 @@ impl TryFrom<&AddProject> for Project {
         Project::new(&cmd.name)
             .with_maybe_description(cmd.maybe_description.clone())
             .with_maybe_deadline(cmd.maybe_deadline)
+            .with_maybe_default_log_filter(cmd.maybe_default_log_filter.clone())?
+            .with_maybe_default_log_sort(cmd.maybe_default_log_sort.clone())?
+            .with_maybe_rate(cmd.maybe_rate)?
+            .with_maybe_currency(cmd.maybe_currency.clone())
             .with_tags(parse_comma_separated(cmd.maybe_tags.clone()))
     }
 }
@@ -61,6 +110,43 @@ pub struct RemoveProject {
     pub id: String,
 }
 
+/// Remove one or more tasks, along with their work logs.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct RemoveTask {
+    /// The ID of the project whose task(s) must be removed.
+    pub project_id: ProjectId,
+
+    /// The ID(s) of the task(s) to remove (comma-separated).
+    pub task_ids: String,
+
+    /// Remove the task(s) even if they still have work logs, which are
+    /// removed along with them.
+    #[structopt(long)]
+    pub force: bool,
+}
+
+/// Rename a project, which also changes its ID.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct RenameProject {
+    /// The ID of the project to rename.
+    pub old_id: ProjectId,
+
+    /// The new name for the project. The project's new ID will be derived from
+    /// this name.
+    pub new_name: String,
+}
+
+/// Rename a tag on every project, task and work log that has it.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct RenameTag {
+    /// The tag to rename.
+    pub old: String,
+
+    /// The new name for the tag. Objects that already have this tag keep
+    /// just one copy of it.
+    pub new: String,
+}
+
 /// Add a new task for a project.
 #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
 pub struct AddTask {
@@ -72,8 +158,10 @@ pub struct AddTask {
 
     /// The desired priority of the task (lower values correspond to higher
     /// priority, i.e. 1 is the highest priority, and 10 is the lowest).
-    #[structopt(short, long, default_value = "10")]
-    pub priority: TaskPriority,
+    /// Defaults to the configured default task priority.
+    #[structopt(name = "priority", short, long)]
+    #[serde(rename = "priority")]
+    pub maybe_priority: Option<TaskPriority>,
 
     /// The desired state of the task once added.
     #[structopt(name = "state", short, long)]
@@ -100,22 +188,59 @@ pub struct AddTask {
     #[structopt(name = "github-pr", long)]
     #[serde(rename = "github_pr")]
     pub maybe_github_pr: Option<NonZeroU32>,
+
+    /// Optionally estimate how long this task will take (e.g. "2h30m").
+    #[structopt(name = "estimate", long)]
+    #[serde(rename = "estimate")]
+    pub maybe_estimate: Option<Duration>,
+
+    /// The IDs of the tasks in the same project that must be completed
+    /// before this one can be started (comma-separated).
+    #[structopt(name = "blocked-by", long)]
+    #[serde(rename = "blocked_by")]
+    pub maybe_blocked_by: Option<String>,
+
+    /// Do not apply the default task tags from the configuration.
+    #[structopt(long)]
+    pub no_default_tags: bool,
 }
 
 impl TryFrom<&AddTask> for Task {
     type Error = Error;
 
     fn try_from(cmd: &AddTask) -> Result<Self, Self::Error> {
-        Ok(Task::new(&cmd.project_id, &cmd.description)
-            .with_priority(cmd.priority)?
+        let mut task = Task::new(&cmd.project_id, &cmd.description);
+        if let Some(priority) = cmd.maybe_priority {
+            task = task.with_priority(priority)?;
+        }
+        Ok(task
             .with_maybe_state(cmd.maybe_state.clone())
             .with_maybe_deadline(cmd.maybe_deadline)
             .with_tags(parse_comma_separated(cmd.maybe_tags.clone()))?
             .with_maybe_github_issue(cmd.maybe_github_issue)
-            .with_maybe_github_pr(cmd.maybe_github_pr))
+            .with_maybe_github_pr(cmd.maybe_github_pr)
+            .with_maybe_estimate(cmd.maybe_estimate)
+            .with_blocked_by(parse_blocker_ids(cmd.maybe_blocked_by.as_deref())?))
     }
 }
 
+/// Update a specific project.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct UpdateProject {
+    /// The ID of the project to update.
+    pub id: ProjectId,
+
+    /// Archive the project, hiding it when listing projects.
+    #[structopt(long, conflicts_with = "unarchive")]
+    #[serde(default)]
+    pub archive: bool,
+
+    /// Unarchive a previously archived project.
+    #[structopt(long)]
+    #[serde(default)]
+    pub unarchive: bool,
+}
+
 /// Update one or more specific tasks.
 #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
 pub struct UpdateTask {
@@ -159,6 +284,46 @@ pub struct UpdateTask {
     #[structopt(name = "github-pr", long)]
     #[serde(rename = "github_pr")]
     pub maybe_github_pr: Option<NonZeroU32>,
+
+    /// Update the estimate of how long the task will take.
+    #[structopt(name = "estimate", long)]
+    #[serde(rename = "estimate")]
+    pub maybe_estimate: Option<Duration>,
+
+    /// Replace the IDs of the tasks that block this one (comma-separated). An
+    /// empty value removes all of the task's blockers.
+    #[structopt(name = "blocked-by", long)]
+    #[serde(rename = "blocked_by")]
+    pub maybe_blocked_by: Option<String>,
+}
+
+/// Shortcut to move one or more tasks into a particular state from the
+/// project's (or the global) task state configuration.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct MarkTasks {
+    /// The ID of the project whose task(s) must be updated.
+    pub project_id: ProjectId,
+
+    /// The ID(s) of the task(s) to update (comma-separated).
+    pub task_ids: String,
+}
+
+impl MarkTasks {
+    fn to_update_task(&self, state: TaskState) -> UpdateTask {
+        UpdateTask {
+            project_id: self.project_id.clone(),
+            task_ids: self.task_ids.clone(),
+            maybe_description: None,
+            maybe_priority: None,
+            maybe_state: Some(state),
+            maybe_deadline: None,
+            maybe_tags: None,
+            maybe_github_issue: None,
+            maybe_github_pr: None,
+            maybe_estimate: None,
+            maybe_blocked_by: None,
+        }
+    }
 }
 
 impl UpdateTask {
@@ -186,6 +351,12 @@ impl UpdateTask {
         if let Some(github_pr) = self.maybe_github_pr {
             task = task.with_github_pr(github_pr)?;
         }
+        if let Some(estimate) = self.maybe_estimate {
+            task = task.with_estimate(estimate);
+        }
+        if let Some(blocked_by) = &self.maybe_blocked_by {
+            task = task.with_blocked_by(parse_blocker_ids(Some(blocked_by))?);
+        }
         Ok(task)
     }
 }
@@ -203,8 +374,15 @@ pub struct AddLog {
     pub maybe_task_id: Option<TaskId>,
 
     /// Specify the start time for this log.
-    #[structopt(name = "from", short, long)]
-    pub start: Timestamp,
+    #[structopt(name = "from", short, long, required_unless = "ago")]
+    #[serde(rename = "start")]
+    pub maybe_start: Option<Timestamp>,
+
+    /// Alternatively, specify how long ago this log started (e.g. "2h"),
+    /// instead of its start time.
+    #[structopt(name = "ago", long, conflicts_with = "from")]
+    #[serde(rename = "ago")]
+    pub maybe_ago: Option<Duration>,
 
     /// Optionally specify the stop time for this log (cannot be used with
     /// duration).
@@ -223,19 +401,40 @@ pub struct AddLog {
     #[serde(rename = "comment")]
     pub maybe_comment: Option<String>,
 
+    /// Write the comment (which may span multiple lines) in an external
+    /// editor ($VISUAL or $EDITOR) instead. This is implied if no comment is
+    /// given and standard input is a terminal.
+    #[structopt(long, conflicts_with = "comment")]
+    #[serde(default)]
+    pub editor: bool,
+
     /// Tags to associate with this work log, separated by commas (e.g.
     /// "work,coding").
     #[structopt(name = "tags", long)]
     #[serde(rename = "tags")]
     pub maybe_tags: Option<String>,
+
+    /// Do not apply the default log tags from the configuration.
+    #[structopt(long)]
+    pub no_default_tags: bool,
+
+    /// If the task to which this work log relates is closed (i.e. done or in
+    /// another terminal state), move it back to the in-progress state. By
+    /// default, closed tasks are left as they are.
+    #[structopt(long)]
+    pub reopen: bool,
 }
 
 impl TryFrom<&AddLog> for Log {
     type Error = Error;
 
     fn try_from(cmd: &AddLog) -> Result<Self, Self::Error> {
+        let start = match resolve_start(cmd.maybe_start, cmd.maybe_ago)? {
+            Some(start) => start,
+            None => return Err(Error::MissingStart),
+        };
         Log::new(&cmd.project_id)
-            .with_start(cmd.start)
+            .with_start(start)
             .with_maybe_task_id(cmd.maybe_task_id)
             .with_maybe_duration_or_stop(cmd.maybe_duration, cmd.maybe_stop)?
             .with_maybe_comment(cmd.maybe_comment.clone())
@@ -257,8 +456,15 @@ pub struct StartLog {
 
     /// Optionally, the date/time from which to start this log. Defaults to the
     /// current local time.
-    #[structopt(name = "from", short, long, default_value = "now")]
-    pub start: Timestamp,
+    #[structopt(name = "from", short, long)]
+    #[serde(rename = "start")]
+    pub maybe_start: Option<Timestamp>,
+
+    /// Alternatively, specify how long ago this log started (e.g. "25m"),
+    /// instead of its start time.
+    #[structopt(name = "ago", long, conflicts_with = "from")]
+    #[serde(rename = "ago")]
+    pub maybe_ago: Option<Duration>,
 
     /// An optional comment describing what is/was being done in this work log.
     #[structopt(name = "comment", short, long)]
@@ -268,24 +474,94 @@ pub struct StartLog {
     /// "work,coding").
     #[structopt(name = "tags", long)]
     pub maybe_tags: Option<String>,
+
+    /// Do not apply the default log tags from the configuration.
+    #[structopt(long)]
+    pub no_default_tags: bool,
+
+    /// If the task to which this work log relates is closed (i.e. done or in
+    /// another terminal state), move it back to the in-progress state. By
+    /// default, closed tasks are left as they are.
+    #[structopt(long)]
+    pub reopen: bool,
+
+    /// If other work logs are currently active, stop them at this date/time
+    /// instead of the current date/time (e.g. if you forgot to stop them).
+    #[structopt(name = "stop-previous-at", long)]
+    #[serde(rename = "stop_previous_at")]
+    pub maybe_stop_previous_at: Option<Timestamp>,
+
+    /// Keep any currently active work logs running alongside this one,
+    /// instead of stopping them.
+    #[structopt(long, conflicts_with = "stop-previous-at")]
+    #[serde(default)]
+    pub keep_active: bool,
+
+    /// Set an estimate of how long the task to which this work log relates
+    /// will take, unless the task already has an estimate.
+    #[structopt(name = "estimate", long, requires = "task")]
+    #[serde(rename = "estimate")]
+    pub maybe_estimate: Option<Duration>,
+
+    /// Overwrite the task's existing estimate (if any) with the one given via
+    /// --estimate.
+    #[structopt(long, requires = "estimate")]
+    pub force: bool,
+
+    /// Start a new work log even if an identical one (i.e. for the same
+    /// project and task, without a different comment) is already active. By
+    /// default, starting an identical log again is an error. Implied by
+    /// --stop-previous-at, since stopping the active log is then explicit.
+    #[structopt(long)]
+    #[serde(default)]
+    pub restart: bool,
 }
 
 impl TryFrom<&StartLog> for Log {
     type Error = Error;
 
     fn try_from(cmd: &StartLog) -> Result<Self, Self::Error> {
+        let start = match resolve_start(cmd.maybe_start, cmd.maybe_ago)? {
+            Some(start) => start,
+            None => Timestamp::now()?,
+        };
         Log::new(&cmd.project_id)
             .with_maybe_task_id(cmd.maybe_task_id)
-            .with_start(cmd.start)
+            .with_start(start)
             .with_maybe_comment(cmd.maybe_comment.clone())
             .with_tags(parse_comma_separated(cmd.maybe_tags.clone()))
     }
 }
 
+/// Create a work log from a single line of free-form text, e.g.
+/// "project-1 14:00-15:30 fixed the parser #bug".
+///
+/// The first word is the project ID. A time range ("HH:MM-HH:MM") gives the
+/// start and stop time of the log today, while a single time ("HH:MM") starts
+/// a new active log from that time today. Words starting with "#" are tags,
+/// and all other words make up the log's comment.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct LogFromLine {
+    /// The line of text from which to create the log.
+    #[structopt(name = "line", required_unless = "paste")]
+    #[serde(rename = "line")]
+    pub maybe_line: Option<String>,
+
+    /// Read the line of text from standard input instead (e.g. pasted from the
+    /// clipboard).
+    #[structopt(long, conflicts_with = "line")]
+    pub paste: bool,
+
+    /// Do not apply the default log tags from the configuration.
+    #[structopt(long)]
+    pub no_default_tags: bool,
+}
+
 /// Stop a work log.
 ///
 /// By default this stops the currently active work log, unless a project and
-/// log ID (and possibly task ID) are provided.
+/// log ID (and possibly task ID) are provided. If more than one log is active,
+/// the log to stop must be specified.
 #[derive(Debug, Clone, Default, StructOpt, Serialize, Deserialize)]
 pub struct StopLog {
     /// Automatically mark the associated task, if any, as done.
@@ -320,17 +596,36 @@ pub struct StopLog {
     #[serde(rename = "comment")]
     pub maybe_comment: Option<String>,
 
+    /// Text to append to the work log's existing comment (on a new line),
+    /// rather than replacing it.
+    #[structopt(name = "comment-append", long, conflicts_with = "comment")]
+    #[serde(rename = "comment_append")]
+    pub maybe_comment_append: Option<String>,
+
+    /// Write the comment (which may span multiple lines) in an external
+    /// editor ($VISUAL or $EDITOR) instead. This is implied if no comment is
+    /// given and standard input is a terminal.
+    #[structopt(long, conflicts_with_all = &["comment", "comment-append"])]
+    #[serde(default)]
+    pub editor: bool,
+
     /// Tags to associate with this work log, separated by commas (e.g.
     /// "work,coding").
     #[structopt(name = "tags", long)]
     #[serde(rename = "tags")]
     pub maybe_tags: Option<String>,
+
+    /// If the work log spans multiple calendar days, split it into one work
+    /// log per day.
+    #[structopt(long)]
+    pub split_by_day: bool,
 }
 
 /// Cancel a work log.
 ///
 /// By default this cancels the currently active work log, unless a project and
-/// log ID (and possibly task ID) are provided.
+/// log ID (and possibly task ID) are provided. If more than one log is active,
+/// the log to cancel must be specified.
 #[derive(Debug, Clone, Default, StructOpt, Serialize, Deserialize)]
 pub struct CancelLog {
     /// For specifying a specific work log to cancel.
@@ -346,6 +641,132 @@ pub struct CancelLog {
     pub maybe_id: Option<LogId>,
 }
 
+/// Start a new work log continuing the most recently stopped one, with the
+/// same project, task, comment and tags.
+#[derive(Debug, Clone, Default, StructOpt, Serialize, Deserialize)]
+pub struct ContinueLog {
+    /// Only continue the most recently stopped work log of this project.
+    #[structopt(name = "project", short, long)]
+    #[serde(rename = "project_id")]
+    pub maybe_project_id: Option<ProjectId>,
+}
+
+/// Link a project-level work log (i.e. one created without a task) to one of
+/// the project's tasks.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct LinkLog {
+    /// The ID of the project to which the work log belongs.
+    #[structopt(name = "project")]
+    pub project_id: ProjectId,
+
+    /// The ID of the project-level work log to link.
+    #[structopt(name = "log")]
+    pub id: LogId,
+
+    /// The ID of the task to which to link the work log.
+    #[structopt(name = "task", long)]
+    pub task_id: TaskId,
+}
+
+/// Edit a work log's start time, duration, comment and/or tags, and/or move
+/// it to a different project and/or task.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct UpdateLog {
+    /// The ID of the project to which the work log belongs.
+    #[structopt(name = "project", long)]
+    pub project_id: ProjectId,
+
+    /// The ID of the task to which the work log belongs (if any).
+    #[structopt(name = "task", long)]
+    #[serde(rename = "task_id")]
+    pub maybe_task_id: Option<TaskId>,
+
+    /// The ID of the work log to edit.
+    #[structopt(name = "id", long)]
+    pub id: LogId,
+
+    /// Update the start time of the work log. Unless a new stop time is given,
+    /// the work log keeps its duration.
+    #[structopt(name = "from", short, long)]
+    #[serde(rename = "start")]
+    pub maybe_start: Option<Timestamp>,
+
+    /// Update the duration of the work log (cannot be used with stop time).
+    #[structopt(name = "duration", short, long)]
+    #[serde(rename = "duration")]
+    pub maybe_duration: Option<Duration>,
+
+    /// Update the stop time of the work log (cannot be used with duration).
+    #[structopt(name = "stop", long)]
+    #[serde(rename = "stop")]
+    pub maybe_stop: Option<Timestamp>,
+
+    /// Update the work log's comment.
+    #[structopt(name = "comment", short, long)]
+    #[serde(rename = "comment")]
+    pub maybe_comment: Option<String>,
+
+    /// Text to append to the work log's existing comment (on a new line),
+    /// rather than replacing it.
+    #[structopt(name = "comment-append", long, conflicts_with = "comment")]
+    #[serde(rename = "comment_append")]
+    pub maybe_comment_append: Option<String>,
+
+    /// Replace the work log's tags (comma-separated).
+    #[structopt(name = "tags", long)]
+    #[serde(rename = "tags")]
+    pub maybe_tags: Option<String>,
+
+    /// The ID of the project to which to move the work log, where it is
+    /// assigned a new ID. Defaults to its current project if only --to-task is
+    /// given.
+    #[structopt(name = "to-project", long)]
+    #[serde(default, rename = "to_project_id")]
+    pub maybe_to_project_id: Option<ProjectId>,
+
+    /// The ID of the task to which to move the work log. If only --to-project
+    /// is given, the work log is moved to that project itself.
+    #[structopt(name = "to-task", long)]
+    #[serde(default, rename = "to_task_id")]
+    pub maybe_to_task_id: Option<TaskId>,
+}
+
+/// Move a work log to a different project and/or task (e.g. if it was started
+/// on the wrong task).
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct MoveLog {
+    /// The ID of the project to which the work log currently belongs.
+    #[structopt(name = "project", long)]
+    pub project_id: ProjectId,
+
+    /// The ID of the task to which the work log currently belongs (if any).
+    #[structopt(name = "task", long)]
+    #[serde(rename = "task_id")]
+    pub maybe_task_id: Option<TaskId>,
+
+    /// The ID of the work log to move.
+    #[structopt(name = "id", long)]
+    pub id: LogId,
+
+    /// The ID of the project to which to move the work log. Defaults to its
+    /// current project.
+    #[structopt(name = "to-project", long, required_unless = "to-task")]
+    #[serde(rename = "to_project_id")]
+    pub maybe_to_project_id: Option<ProjectId>,
+
+    /// The ID of the task to which to move the work log. If not given, the
+    /// work log is moved to the project itself.
+    #[structopt(name = "to-task", long)]
+    #[serde(rename = "to_task_id")]
+    pub maybe_to_task_id: Option<TaskId>,
+
+    /// Text to append to the work log's existing comment (on a new line),
+    /// e.g. to note why it was moved.
+    #[structopt(name = "comment-append", long)]
+    #[serde(rename = "comment_append")]
+    pub maybe_comment_append: Option<String>,
+}
+
 /// List all projects.
 #[derive(Debug, Clone, Default, StructOpt, Serialize, Deserialize)]
 pub struct ListProjects {
@@ -361,12 +782,33 @@ pub struct ListProjects {
     #[structopt(name = "tags", long)]
     pub maybe_tags_filter: Option<String>,
 
+    /// Only return projects whose names match this regular expression.
+    #[structopt(name = "regex", long)]
+    pub maybe_regex_filter: Option<String>,
+
     /// Optionally sort the projects by specific fields (e.g. "name" will sort
     /// projects in ascending order by name; "name:desc" will sort by name in
     /// descending order; "deadline,name" will first sort by deadline and then
     /// by name).
     #[structopt(short, long, default_value = "id")]
     pub sort: String,
+
+    /// Reverse the order of the results after sorting. Unlike sorting in
+    /// descending order, this also reverses the relative order of projects that
+    /// compare as equal under the sort specification.
+    #[structopt(short, long)]
+    pub reverse: bool,
+
+    /// Group projects into an indented tree by the prefixes their IDs share,
+    /// where IDs are split into segments on "-". Projects sharing leading
+    /// segments are nested under a group named after the longest such shared
+    /// prefix (e.g. "client-a-web" and "client-a-api" under "client-a").
+    #[structopt(long)]
+    pub tree: bool,
+
+    /// Also list archived projects, which are hidden by default.
+    #[structopt(long)]
+    pub include_archived: bool,
 }
 
 /// List all of the tasks for a project.
@@ -396,9 +838,12 @@ pub struct ListTasks {
     pub maybe_priority_filter: Option<String>,
 
     /// Only return tasks whose states match one or more of these states
-    /// (comma-separated). By default, we only list tasks that are not done. To
-    /// return tasks with any state, use "any".
-    #[structopt(name = "state", long, default_value = "!done")]
+    /// (comma-separated). A single state prefixed with "!" returns tasks whose
+    /// state is not that state, and a lone "!" returns tasks that aren't
+    /// closed according to their project's task states. By default, we only
+    /// list tasks that are not closed. To return tasks with any state, use
+    /// "any".
+    #[structopt(name = "state", long, default_value = "!")]
     pub state_filter: String,
 
     /// Only return tasks whose deadline matches the given filter.
@@ -420,11 +865,37 @@ pub struct ListTasks {
     #[structopt(name = "github-pr", long)]
     pub maybe_github_pr_filter: Option<String>,
 
+    /// Only return tasks whose descriptions match this regular expression.
+    #[structopt(name = "regex", long)]
+    pub maybe_regex_filter: Option<String>,
+
+    /// Only return tasks that are due within this many days from now (i.e.
+    /// whose deadlines fall between now and the start of that day).
+    #[structopt(name = "due-within", long)]
+    #[serde(rename = "due_within")]
+    pub maybe_due_within: Option<u16>,
+
+    /// Only return tasks whose deadlines have passed and that haven't been
+    /// closed yet.
+    #[structopt(long)]
+    #[serde(default)]
+    pub overdue: bool,
+
+    /// Only return tasks that aren't blocked by any task that is still open.
+    #[structopt(long)]
+    pub unblocked: bool,
+
     /// Optionally sort the tasks by specific fields (e.g. "id" will sort tasks
     /// in ascending order by ID; "id:desc" will sort by ID in descending order;
     /// "deadline,id" will first sort by deadline and then by ID).
     #[structopt(short, long, default_value = "priority,project,id")]
     pub sort: String,
+
+    /// Reverse the order of the results after sorting. Unlike sorting in
+    /// descending order, this also reverses the relative order of tasks that
+    /// compare as equal under the sort specification.
+    #[structopt(short, long)]
+    pub reverse: bool,
 }
 
 /// List all of the logs for a project or task.
@@ -471,11 +942,15 @@ pub struct ListLogs {
     #[structopt(name = "task-tags", long)]
     pub maybe_task_tags_filter: Option<String>,
 
-    /// Only return logs whose start time matches this filter.
-    #[structopt(name = "start", long, default_value = "today")]
-    pub start_filter: String,
+    /// Only return logs whose start time matches this filter. Defaults to
+    /// "today", unless only one project's logs are listed and that project has
+    /// its own default.
+    #[structopt(name = "start", long)]
+    #[serde(rename = "start_filter")]
+    pub maybe_start_filter: Option<String>,
 
-    /// Only return logs whose duration matches this filter.
+    /// Only return logs whose duration matches this filter (e.g. "<1h", or an
+    /// inclusive range like "30m..2h" or ">=30m <=2h").
     #[structopt(name = "duration", long)]
     pub maybe_duration_filter: Option<String>,
 
@@ -484,81 +959,966 @@ pub struct ListLogs {
     #[structopt(name = "tags", long)]
     pub maybe_tags_filter: Option<String>,
 
+    /// Only return logs whose comments match this regular expression (e.g.
+    /// "^fix" for comments starting with "fix").
+    #[structopt(name = "regex", long)]
+    pub maybe_regex_filter: Option<String>,
+
+    /// Only return logs with a comment.
+    #[structopt(long, conflicts_with = "no-comment")]
+    pub has_comment: bool,
+
+    /// Only return logs without a comment (or with a blank one).
+    #[structopt(long)]
+    pub no_comment: bool,
+
+    /// Only return logs started on a Saturday or Sunday.
+    #[structopt(long, conflicts_with = "weekdays")]
+    pub weekends: bool,
+
+    /// Only return logs started on a weekday (Monday to Friday).
+    #[structopt(long)]
+    pub weekdays: bool,
+
     /// Optionally sort the logs by specific fields (e.g. "id" will sort logs in
     /// ascending order by ID; "id:desc" will sort by ID in descending order;
-    /// "duration,id" will first sort by duration and then by ID).
-    #[structopt(short, long, default_value = "start")]
-    pub sort: String,
+    /// "duration,id" will first sort by duration and then by ID). Defaults to
+    /// "start", unless only one project's logs are listed and that project has
+    /// its own default.
+    #[structopt(name = "sort", short, long)]
+    #[serde(rename = "sort")]
+    pub maybe_sort: Option<String>,
+
+    /// Reverse the order of the results after sorting. Unlike sorting in
+    /// descending order, this also reverses the relative order of logs that
+    /// compare as equal under the sort specification.
+    #[structopt(short, long)]
+    pub reverse: bool,
+
+    /// Show each log's stop time, as computed from its start time and
+    /// duration.
+    #[structopt(long)]
+    pub show_stop: bool,
+
+    /// Show the gap between each log's start time and the stop time of the log
+    /// started before it on the same day.
+    #[structopt(long)]
+    pub show_gaps: bool,
+
+    /// Instead of listing the matching logs, list the pairs of them whose
+    /// times overlap (e.g. because a timer wasn't stopped).
+    #[structopt(long)]
+    pub check_overlaps: bool,
+
+    /// Only list up to this many logs (after sorting).
+    #[structopt(name = "limit", long)]
+    #[serde(rename = "limit")]
+    pub maybe_limit: Option<usize>,
+
+    /// Skip this many logs (after sorting) before listing any.
+    #[structopt(long, default_value = "0")]
+    pub offset: usize,
+
+    /// Also show the billed total, with each log's duration rounded up to this
+    /// increment (e.g. "15m") instead of the configured rounding increment.
+    #[structopt(name = "round", long)]
+    #[serde(rename = "round")]
+    pub maybe_round: Option<Duration>,
 }
 
+/// Show a timeline of all work logs on a particular day.
 #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
-pub struct TaskStates {
-    #[structopt(name = "project", short, long)]
-    pub maybe_project_id: Option<ProjectId>,
+pub struct Day {
+    /// Any date/time falling on the day whose logs must be shown. Defaults to
+    /// today.
+    #[structopt(default_value = "now")]
+    pub date: Timestamp,
 }
 
-/// Initialize the local storage so it can be pushed to a remote store.
-///
-/// Initializes the Loiter home directory as a Git repository and adds a remote
-/// as its origin.
+/// Fill the days within a date range on which nothing was logged with
+/// placeholder work logs, e.g. so that no workday is left blank on a
+/// timesheet.
 #[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
-pub struct RemoteInit {
-    /// The default branch to use when initializing the repository.
-    #[structopt(short, long, default_value = "main")]
-    pub branch: String,
+pub struct FillDays {
+    /// The ID of the project to which to add the placeholder work logs.
+    #[structopt(name = "project")]
+    pub project_id: ProjectId,
 
-    /// The remote Git repository to set as origin.
-    pub origin: String,
-}
+    /// Optionally, the ID of the task to which the placeholder work logs
+    /// relate.
+    #[structopt(name = "task")]
+    #[serde(rename = "task_id")]
+    pub maybe_task_id: Option<TaskId>,
 
-/// Add a new project to the given store.
-pub fn add_project(store: &Store, params: &AddProject) -> Result<Project, Error> {
-    let project = Project::try_from(params)?;
-    store.save_project(&project)?;
-    debug!("Created new project {}", project.name());
-    Ok(project)
-}
+    /// Any date/time falling on the first day of the range.
+    #[structopt(name = "from", long)]
+    pub from: Timestamp,
 
-/// Remove a project and all of its related data from the store.
-pub fn remove_project(store: &Store, params: &RemoveProject) -> Result<ProjectId, Error> {
-    store.remove_project(&params.id)?;
-    debug!("Removed project {}", params.id);
-    Ok(params.id.clone())
+    /// Any date/time falling on the last day of the range (inclusive).
+    /// Defaults to today.
+    #[structopt(name = "to", long, default_value = "now")]
+    pub to: Timestamp,
+
+    /// The time of day at which each placeholder work log starts.
+    #[structopt(name = "at", long, default_value = "09:00")]
+    pub at: Timestamp,
+
+    /// The duration of each placeholder work log.
+    #[structopt(name = "duration", short, long, default_value = "8h")]
+    pub duration: Duration,
+
+    /// An optional comment for each placeholder work log.
+    #[structopt(name = "comment", short, long)]
+    #[serde(rename = "comment")]
+    pub maybe_comment: Option<String>,
+
+    /// Tags to associate with each placeholder work log, separated by commas.
+    #[structopt(name = "tags", long)]
+    #[serde(rename = "tags")]
+    pub maybe_tags: Option<String>,
+
+    /// Also fill weekends. By default, only weekdays are filled.
+    #[structopt(long)]
+    pub include_weekends: bool,
+
+    /// Dates to leave blank (comma-separated, e.g. public holidays like
+    /// "2021-12-24,2021-12-31").
+    #[structopt(name = "skip", long)]
+    #[serde(rename = "skip")]
+    pub maybe_skip: Option<String>,
+
+    /// Only show which placeholder work logs would be added, without adding
+    /// them.
+    #[structopt(long)]
+    pub dry_run: bool,
 }
 
-/// Add a new task for a specific project to the store.
-pub fn add_task(store: &Store, params: &AddTask) -> Result<Task, Error> {
-    let task = Task::try_from(params)?;
-    let task = store.save_task(&task)?;
-    debug!(
-        "Added task {} for project {}",
-        task.id().unwrap(),
-        task.project_id().unwrap()
-    );
-    Ok(task)
+/// Report on the total time logged per project, or optionally as a time
+/// series.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Report {
+    /// Only include logs whose project matches these project IDs
+    /// (comma-separated).
+    #[structopt(name = "projects")]
+    pub maybe_project_ids: Option<String>,
+
+    /// Only include logs whose start time matches this filter.
+    #[structopt(name = "start", long, default_value = "this-week")]
+    pub start_filter: String,
+
+    /// Only include logs whose tags match one or more of these tags
+    /// (comma-separated).
+    #[structopt(name = "tags", long)]
+    pub maybe_tags_filter: Option<String>,
+
+    /// Bucket logs into calendar periods ("day", "week" or "month"), reporting
+    /// one total per period.
+    #[structopt(name = "by", long)]
+    #[serde(rename = "by")]
+    pub maybe_period: Option<Period>,
+
+    /// Break down each period's total by project. Totals are always broken
+    /// down by project if not bucketed into periods.
+    #[structopt(name = "by-project", long)]
+    pub by_project: bool,
+
+    /// Report the total time logged per tag instead. Logs with more than one
+    /// tag count in full towards each of their tags' totals.
+    #[structopt(
+        name = "by-tag",
+        long,
+        conflicts_with_all = &["by", "by-project", "round-and-report-diff", "round"]
+    )]
+    #[serde(default)]
+    pub by_tag: bool,
+
+    /// Also report the rounded (billed) totals, and how much rounding adds to
+    /// the raw totals. Requires a rounding increment to be configured.
+    #[structopt(name = "round-and-report-diff", long)]
+    pub round_and_report_diff: bool,
+
+    /// Round each log's duration up to this increment (e.g. "15m") instead of
+    /// the configured rounding increment. Implies --round-and-report-diff.
+    #[structopt(name = "round", long)]
+    #[serde(rename = "round")]
+    pub maybe_round: Option<Duration>,
 }
 
-/// Update one or more fields of one or more specific tasks.
-pub fn update_tasks(store: &Store, params: &UpdateTask) -> Result<Vec<Task>, Error> {
-    let task_ids = parse_comma_separated(Some(params.task_ids.clone()))
-        .iter()
-        .map(|s| TaskId::from_str(s))
-        .collect::<Result<Vec<TaskId>, std::num::ParseIntError>>()
-        .map_err(|e| Error::InvalidTaskIds(params.task_ids.clone(), e))?;
-    let project_filter = FilterSpec::new(ProjectFilter::Ids(vec![params.project_id.clone()]));
-    let task_filter = FilterSpec::new(TaskFilter::Ids(task_ids));
-    let tasks = store.tasks(&project_filter, &task_filter, false)?;
-    let updated_tasks = tasks
-        .into_iter()
-        .map(|task| store.save_task(&params.apply(&task)?))
-        .collect::<Result<Vec<Task>, Error>>()?;
-    Ok(updated_tasks)
+impl Report {
+    /// Whether rounded (billed) totals are to be reported.
+    pub fn with_rounding(&self) -> bool {
+        self.round_and_report_diff || self.maybe_round.is_some()
+    }
 }
 
-/// Add a new log for a project or task.
-pub fn add_log(store: &Store, params: &AddLog) -> Result<Log, Error> {
-    let log = Log::try_from(params)?;
-    let log = store.save_log(&log)?;
+/// Bill the time logged for a project at the project's hourly rate.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Invoice {
+    /// The ID of the project to bill.
+    pub project_id: ProjectId,
+
+    /// Only bill logs whose start time matches this filter.
+    #[structopt(name = "start", long, default_value = "this-month")]
+    pub start_filter: String,
+}
+
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct TaskStates {
+    #[structopt(name = "project", short, long)]
+    pub maybe_project_id: Option<ProjectId>,
+
+    /// Show the states that tasks are actually in, along with how many tasks
+    /// are in each, instead of the configured states.
+    #[structopt(long)]
+    pub in_use: bool,
+}
+
+/// Configure the default task states, either from the given arguments or
+/// interactively.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ConfigStates {
+    /// The task states, in order, separated by commas (e.g.
+    /// "todo,in-progress,done").
+    #[structopt(name = "states", long, required_unless = "wizard")]
+    #[serde(rename = "states")]
+    pub maybe_states: Option<String>,
+
+    /// The state in which new tasks start. Defaults to the first state.
+    #[structopt(name = "initial", long)]
+    #[serde(rename = "initial")]
+    pub maybe_initial: Option<TaskState>,
+
+    /// The state of tasks being worked on. Defaults to the second state.
+    #[structopt(name = "in-progress", long)]
+    #[serde(rename = "in_progress")]
+    pub maybe_in_progress: Option<TaskState>,
+
+    /// The state of completed tasks. Defaults to the last state.
+    #[structopt(name = "done", long)]
+    #[serde(rename = "done")]
+    pub maybe_done: Option<TaskState>,
+
+    /// Other states in which tasks are considered closed, like the done state
+    /// (comma-separated, e.g. "cancelled").
+    #[structopt(name = "terminal-states", long)]
+    #[serde(rename = "terminal_states")]
+    pub maybe_terminal_states: Option<String>,
+
+    /// Prompt for each of the settings interactively.
+    #[structopt(
+        long,
+        conflicts_with_all = &["states", "initial", "in-progress", "done", "terminal-states"]
+    )]
+    pub wizard: bool,
+}
+
+/// Configure the tags marking work logs as breaks (e.g. lunch), whose time
+/// is subtracted from net time totals.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ConfigBreakTags {
+    /// The break tags (comma-separated, e.g. "lunch,break"). Leave empty to
+    /// stop treating any logs as breaks.
+    #[structopt(name = "tags", default_value = "")]
+    #[serde(rename = "tags")]
+    pub tags: String,
+}
+
+/// Configure the increment to which each work log's duration is rounded up
+/// when computing billed time.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ConfigRounding {
+    /// The rounding increment (e.g. "15m"). Leave empty to disable rounding.
+    #[structopt(name = "increment")]
+    #[serde(rename = "increment")]
+    pub maybe_increment: Option<Duration>,
+}
+
+/// Configure the day on which weeks start.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ConfigWeekStart {
+    /// The day on which weeks start ("monday" or "sunday").
+    #[structopt(name = "day")]
+    pub week_start: WeekStart,
+}
+
+/// Configure how durations are rendered in JSON output and exports.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ConfigDurationFormat {
+    /// The format in which to render durations in addition to whole seconds
+    /// ("seconds" or "iso8601").
+    #[structopt(name = "format")]
+    pub duration_format: DurationFormat,
+}
+
+/// Configure how far in the future work logs' stop times may be.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ConfigStopTolerance {
+    /// How far beyond the current time a work log's stop time may be (e.g.
+    /// "5m"), to allow for clock skew.
+    #[structopt(name = "tolerance")]
+    pub tolerance: Duration,
+}
+
+/// Show statistics about logged time.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Stats {
+    /// Only include logs whose project matches these project IDs
+    /// (comma-separated).
+    #[structopt(name = "projects")]
+    pub maybe_project_ids: Option<String>,
+
+    /// Only include logs whose start time matches this filter.
+    #[structopt(name = "start", long, default_value = "this-month")]
+    pub start_filter: String,
+
+    /// Only include logs whose tags match one or more of these tags
+    /// (comma-separated).
+    #[structopt(name = "tags", long)]
+    pub maybe_tags_filter: Option<String>,
+
+    /// Show how much time was logged during each hour of each day of the week.
+    #[structopt(long)]
+    pub punchcard: bool,
+}
+
+/// Apply a batch of changes to projects, tasks and/or work logs, read from
+/// standard input as a JSON array of patches.
+///
+/// Each patch has a "type" of "project", "task" or "log", the ID(s) of the
+/// object to change, and the fields to change.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Patch {
+    /// Stop at the first patch that fails, rather than attempting the rest.
+    #[structopt(long)]
+    pub strict: bool,
+}
+
+/// A change to a single project, task (or tasks) or work log. Only the fields
+/// given are changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ObjectPatch {
+    Project(ProjectPatch),
+    Task(UpdateTask),
+    Log(LogPatch),
+}
+
+/// A change to a specific project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectPatch {
+    /// The ID of the project to change.
+    pub id: ProjectId,
+    #[serde(rename = "description")]
+    pub maybe_description: Option<String>,
+    #[serde(rename = "deadline")]
+    pub maybe_deadline: Option<Timestamp>,
+    /// Comma-separated tags.
+    #[serde(rename = "tags")]
+    pub maybe_tags: Option<String>,
+    #[serde(rename = "default_log_filter")]
+    pub maybe_default_log_filter: Option<String>,
+    #[serde(rename = "default_log_sort")]
+    pub maybe_default_log_sort: Option<String>,
+}
+
+/// A change to a specific work log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogPatch {
+    /// The ID of the project to which the log belongs.
+    pub project_id: ProjectId,
+    /// The ID of the task to which the log belongs, if any.
+    #[serde(rename = "task_id")]
+    pub maybe_task_id: Option<TaskId>,
+    /// The ID of the log to change.
+    pub id: LogId,
+    #[serde(rename = "start")]
+    pub maybe_start: Option<Timestamp>,
+    #[serde(rename = "duration")]
+    pub maybe_duration: Option<Duration>,
+    #[serde(rename = "comment")]
+    pub maybe_comment: Option<String>,
+    /// Comma-separated tags.
+    #[serde(rename = "tags")]
+    pub maybe_tags: Option<String>,
+}
+
+impl ObjectPatch {
+    /// A short description of the object(s) targeted by this patch.
+    pub fn target(&self) -> String {
+        match self {
+            Self::Project(patch) => format!("project {}", patch.id),
+            Self::Task(patch) => {
+                format!("task(s) {} of project {}", patch.task_ids, patch.project_id)
+            }
+            Self::Log(patch) => format!(
+                "log {} of project {}{}",
+                patch.id,
+                patch.project_id,
+                patch
+                    .maybe_task_id
+                    .map(|task_id| format!(", task {}", task_id))
+                    .unwrap_or_default()
+            ),
+        }
+    }
+
+    fn apply(&self, store: &Store) -> Result<(), Error> {
+        match self {
+            Self::Project(patch) => {
+                let mut project = store.project(&patch.id)?;
+                if let Some(description) = &patch.maybe_description {
+                    project = project.with_description(description);
+                }
+                if let Some(deadline) = patch.maybe_deadline {
+                    project = project.with_deadline(deadline);
+                }
+                if let Some(tags) = &patch.maybe_tags {
+                    project = project.with_tags(parse_comma_separated(Some(tags.clone())))?;
+                }
+                if let Some(filter) = &patch.maybe_default_log_filter {
+                    project = project.with_default_log_filter(filter)?;
+                }
+                if let Some(sort) = &patch.maybe_default_log_sort {
+                    project = project.with_default_log_sort(sort)?;
+                }
+                store.save_project(&project)?;
+                record_audit(
+                    store,
+                    project_audit_entry(AuditOperation::UpdateProject, project.id())?,
+                )
+            }
+            Self::Task(patch) => {
+                let updated_task_ids = update_tasks(store, patch)?
+                    .iter()
+                    .filter_map(Task::id)
+                    .collect::<Vec<TaskId>>();
+                match parse_task_ids(&patch.task_ids)?
+                    .into_iter()
+                    .find(|task_id| !updated_task_ids.contains(task_id))
+                {
+                    Some(task_id) => Err(Error::TaskNotFound(patch.project_id.clone(), task_id)),
+                    None => Ok(()),
+                }
+            }
+            Self::Log(patch) => {
+                let mut log = store.log(&patch.project_id, patch.maybe_task_id, patch.id)?;
+                if let Some(start) = patch.maybe_start {
+                    log = log.with_start(start);
+                }
+                if let Some(duration) = patch.maybe_duration {
+                    log = log.with_duration(duration);
+                }
+                if let Some(comment) = &patch.maybe_comment {
+                    log = log.with_comment(comment);
+                }
+                if let Some(tags) = &patch.maybe_tags {
+                    log = log.with_tags(parse_comma_separated(Some(tags.clone())))?;
+                }
+                let log = store.save_log(&log)?;
+                record_audit(store, log_audit_entry(AuditOperation::UpdateLog, &log)?)
+            }
+        }
+    }
+}
+
+/// Check the store for problems.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Check {
+    /// Attempt to recover orphaned work logs by creating placeholder projects
+    /// for the directories in which they are found.
+    #[structopt(long)]
+    pub recover: bool,
+}
+
+/// Verify the integrity of the whole store.
+#[derive(Debug, Clone, Default, StructOpt, Serialize, Deserialize)]
+pub struct Verify {
+    /// Attempt to repair the inconsistencies found: colliding IDs are
+    /// renumbered, invalid task states are reset to the initial task state
+    /// and work logs of tasks without metadata are moved to their project.
+    #[structopt(long)]
+    pub fix: bool,
+}
+
+/// Export all projects, tasks and work logs to an SQLite database for ad-hoc
+/// querying.
+///
+/// This is a read-only snapshot of the store: changes made to the database are
+/// not reflected in the store.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ExportSqlite {
+    /// The path of the SQLite database file to create. Must not already exist.
+    #[structopt(name = "output", short, long)]
+    #[serde(rename = "output")]
+    pub output: PathBuf,
+}
+
+/// Export the work logs matching the given filters as comma-separated values
+/// (as per RFC 4180), e.g. for importing into a spreadsheet.
+///
+/// Each row contains the project ID, task ID, log ID, start, stop, duration in
+/// seconds, comment and tags (comma-separated) of a log, preceded by a header
+/// row.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ExportLogs {
+    /// The path of the CSV file to create. Must not already exist. Defaults to
+    /// writing to standard output.
+    #[structopt(name = "output", short, long)]
+    #[serde(rename = "output")]
+    pub maybe_output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub filters: ListLogs,
+}
+
+/// Export the work logs matching the given filters as a Markdown document
+/// (e.g. for a stand-up summary), grouped by project with a total for each.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ExportMarkdown {
+    /// The path of the Markdown file to create. Must not already exist.
+    /// Defaults to writing to standard output.
+    #[structopt(name = "output", short, long)]
+    #[serde(rename = "output")]
+    pub maybe_output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub filters: ListLogs,
+}
+
+/// Export the work logs matching the given filters as iCalendar (RFC 5545)
+/// events, e.g. for viewing in a calendar app.
+///
+/// Logs without a start time, as well as running logs, are skipped.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ExportIcal {
+    /// The path of the iCalendar (.ics) file to create. Must not already
+    /// exist. Defaults to writing to standard output.
+    #[structopt(name = "output", short, long)]
+    #[serde(rename = "output")]
+    pub maybe_output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub filters: ListLogs,
+}
+
+/// Export all projects, along with their tasks and work logs, as a JSON
+/// document that can be imported into another store.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct ExportJson {
+    /// The path of the JSON file to create. Must not already exist. Defaults
+    /// to writing to standard output.
+    #[structopt(name = "output", short, long)]
+    #[serde(rename = "output")]
+    pub maybe_output: Option<PathBuf>,
+}
+
+/// Import projects, tasks and work logs from a JSON document created by
+/// exporting them as JSON.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Import {
+    /// The JSON file to import.
+    #[structopt(name = "path")]
+    pub path: PathBuf,
+}
+
+/// Back up the entire store to a gzipped tarball.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Backup {
+    /// The path of the backup file to create (e.g. "backup.tar.gz"). Must not
+    /// already exist, and must be outside of the store.
+    #[structopt(name = "output", short, long)]
+    #[serde(rename = "output")]
+    pub output: PathBuf,
+}
+
+/// Restore a backup of a store created by the backup command.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Restore {
+    /// The backup file to restore.
+    #[structopt(name = "input")]
+    #[serde(rename = "input")]
+    pub input: PathBuf,
+
+    /// The path into which to restore the backup, which must be empty.
+    /// Defaults to the store's path.
+    #[structopt(name = "target", long)]
+    #[serde(rename = "target")]
+    pub maybe_target: Option<PathBuf>,
+}
+
+/// View the store's audit trail of changes.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct Audit {
+    /// Only show entries for this type of operation (e.g. "add_task",
+    /// "stop_log").
+    #[structopt(name = "operation", long)]
+    #[serde(rename = "operation")]
+    pub maybe_operation: Option<AuditOperation>,
+
+    /// Only show entries affecting this project.
+    #[structopt(name = "project", long)]
+    #[serde(rename = "project_id")]
+    pub maybe_project_id: Option<ProjectId>,
+
+    /// Only show entries whose timestamps match this filter (e.g. "today",
+    /// "from 2021-11-29").
+    #[structopt(name = "when", long)]
+    #[serde(rename = "when")]
+    pub maybe_when: Option<String>,
+
+    /// Only show the most recent entries, up to this number.
+    #[structopt(name = "limit", long)]
+    #[serde(rename = "limit")]
+    pub maybe_limit: Option<usize>,
+}
+
+/// Initialize the local storage so it can be pushed to a remote store.
+///
+/// Initializes the Loiter home directory as a Git repository and adds a remote
+/// as its origin.
+#[derive(Debug, Clone, StructOpt, Serialize, Deserialize)]
+pub struct RemoteInit {
+    /// The default branch to use when initializing the repository.
+    #[structopt(short, long, default_value = "main")]
+    pub branch: String,
+
+    /// The remote Git repository to set as origin.
+    pub origin: String,
+}
+
+/// Add a new project to the given store.
+pub fn add_project(store: &Store, params: &AddProject) -> Result<Project, Error> {
+    let _lock = store.lock()?;
+    let mut project = Project::try_from(params)?;
+    let mut suffix = 1;
+    loop {
+        match store.create_project(&project) {
+            Err(Error::ProjectAlreadyExists(_)) if params.auto_suffix => {
+                suffix += 1;
+                project = project.with_name(format!("{} {}", params.name, suffix));
+            }
+            result => break result?,
+        }
+    }
+    record_audit(
+        store,
+        project_audit_entry(AuditOperation::AddProject, project.id())?,
+    )?;
+    debug!("Created new project {}", project.name());
+    Ok(project)
+}
+
+/// Remove a project and all of its related data from the store.
+pub fn remove_project(store: &Store, params: &RemoveProject) -> Result<ProjectId, Error> {
+    store.remove_project(&params.id)?;
+    record_audit(
+        store,
+        project_audit_entry(AuditOperation::RemoveProject, &params.id)?,
+    )?;
+    debug!("Removed project {}", params.id);
+    Ok(params.id.clone())
+}
+
+/// Remove the given tasks. Unless forced, tasks that still have work logs are
+/// not removed, and nothing is removed if any of the tasks can't be. If the
+/// active log belongs to one of the removed tasks, there is no longer an
+/// active log.
+pub fn remove_task(store: &Store, params: &RemoveTask) -> Result<Vec<TaskId>, Error> {
+    let _lock = store.lock()?;
+    let task_ids = parse_task_ids(&params.task_ids)?;
+    let mut tasks = Vec::new();
+    for task_id in task_ids.iter() {
+        let task = store.task(&params.project_id, *task_id, false)?;
+        let log_count = store
+            .logs_for_project_or_task(
+                &params.project_id,
+                Some(*task_id),
+                &FilterSpec::new(LogFilter::All),
+                false,
+                None,
+            )?
+            .len();
+        if log_count > 0 && !params.force {
+            return Err(Error::TaskHasLogs(
+                params.project_id.clone(),
+                *task_id,
+                log_count,
+            ));
+        }
+        tasks.push(task);
+    }
+    for task in tasks.iter() {
+        store.remove_task(&params.project_id, task.id().unwrap())?;
+        record_audit(store, task_audit_entry(AuditOperation::RemoveTask, task)?)?;
+        debug!(
+            "Removed task {} of project {}",
+            task.id().unwrap(),
+            params.project_id
+        );
+    }
+
+    let mut state = store.state()?;
+    for (project_id, maybe_task_id, log_id) in state.active_logs().to_vec() {
+        match maybe_task_id {
+            Some(task_id) if project_id == params.project_id && task_ids.contains(&task_id) => {
+                state = state.without_active_log(&project_id, maybe_task_id, log_id);
+                store.save_state(&state)?;
+                debug!("Active log was removed along with task {}", task_id);
+            }
+            _ => (),
+        }
+    }
+    Ok(task_ids)
+}
+
+/// Rename a project, updating the project IDs of any active logs belonging to
+/// the renamed project.
+pub fn rename_project(store: &Store, params: &RenameProject) -> Result<Project, Error> {
+    let _lock = store.lock()?;
+    let project = store.project(&params.old_id)?.with_name(&params.new_name);
+    store.rename_project(&params.old_id, &project)?;
+    record_audit(
+        store,
+        project_audit_entry(AuditOperation::RenameProject, project.id())?,
+    )?;
+    debug!("Renamed project {} to {}", params.old_id, project.id());
+    let mut state = store.state()?;
+    for active_log in state.active_logs().to_vec() {
+        if active_log.0 == params.old_id {
+            let (_, maybe_task_id, log_id) = active_log;
+            state = state.with_replaced_active_log(
+                &active_log,
+                (project.id().to_string(), maybe_task_id, log_id),
+            );
+            store.save_state(&state)?;
+            debug!("Updated active log's project ID to {}", project.id());
+        }
+    }
+    Ok(project)
+}
+
+/// Rename a tag across the whole store, returning the number of projects,
+/// tasks and logs changed.
+pub fn rename_tag(store: &Store, params: &RenameTag) -> Result<usize, Error> {
+    let _lock = store.lock()?;
+    let changed = store.rename_tag(&params.old, &params.new)?;
+    if changed > 0 {
+        record_audit(
+            store,
+            AuditEntry::new(AuditOperation::RenameTag, Timestamp::now()?),
+        )?;
+    }
+    Ok(changed)
+}
+
+/// Add a new task for a specific project to the store.
+pub fn add_task(store: &Store, params: &AddTask) -> Result<Task, Error> {
+    let mut task = Task::try_from(params)?;
+    check_blockers(store, &task)?;
+    let config = store.config()?;
+    if params.maybe_priority.is_none() {
+        // The configuration file may have been edited by hand, so its default
+        // priority is only checked once it's needed.
+        task = task
+            .with_priority(config.default_task_priority())
+            .map_err(|e| match e {
+                Error::InvalidTaskPriority(priority, min, max) => {
+                    Error::InvalidDefaultTaskPriority(priority, min, max)
+                }
+                e => e,
+            })?;
+    }
+    if !params.no_default_tags {
+        let tags = merge_tags(task.tags(), config.default_task_tags());
+        task = task.with_tags(tags)?;
+    }
+    let task = store.save_task(&task)?;
+    record_audit(store, task_audit_entry(AuditOperation::AddTask, &task)?)?;
+    debug!(
+        "Added task {} for project {}",
+        task.id().unwrap(),
+        task.project_id().unwrap()
+    );
+    Ok(task)
+}
+
+/// Archive or unarchive a specific project.
+pub fn update_project(store: &Store, params: &UpdateProject) -> Result<Project, Error> {
+    let _lock = store.lock()?;
+    let mut project = store.project(&params.id)?;
+    if params.archive {
+        project = project.with_archived(true);
+    } else if params.unarchive {
+        project = project.with_archived(false);
+    }
+    store.save_project(&project)?;
+    record_audit(
+        store,
+        project_audit_entry(AuditOperation::UpdateProject, project.id())?,
+    )?;
+    debug!("Updated project {}", project.id());
+    Ok(project)
+}
+
+/// Update one or more fields of one or more specific tasks.
+pub fn update_tasks(store: &Store, params: &UpdateTask) -> Result<Vec<Task>, Error> {
+    let task_ids = parse_task_ids(&params.task_ids)?;
+    let project_filter = FilterSpec::new(ProjectFilter::Ids(vec![params.project_id.clone()]));
+    let task_filter = FilterSpec::new(TaskFilter::Ids(task_ids));
+    let tasks = store.tasks(&project_filter, &task_filter, false)?;
+    let updated_tasks = apply_task_update(store, params, tasks)?;
+    save_updated_tasks(store, updated_tasks)
+}
+
+// Applies the given update to each of the given tasks and validates the
+// results, without saving anything.
+fn apply_task_update(
+    store: &Store,
+    params: &UpdateTask,
+    tasks: Vec<Task>,
+) -> Result<Vec<Task>, Error> {
+    let task_state_config = store.task_state_config(&params.project_id)?;
+    tasks
+        .into_iter()
+        .map(|task| {
+            let task = params.apply(&task)?;
+            task_state_config.validate_or_initial(task.state())?;
+            if params.maybe_blocked_by.is_some() {
+                check_blockers(store, &task)?;
+            }
+            Ok(task)
+        })
+        .collect()
+}
+
+fn save_updated_tasks(store: &Store, tasks: Vec<Task>) -> Result<Vec<Task>, Error> {
+    tasks
+        .into_iter()
+        .map(|task| {
+            let task = store.save_task(&task)?;
+            record_audit(store, task_audit_entry(AuditOperation::UpdateTask, &task)?)?;
+            Ok(task)
+        })
+        .collect()
+}
+
+// Ensures that all of the given task's blockers exist in its project, and that
+// none of them are (directly or indirectly) blocked by the task itself.
+fn check_blockers(store: &Store, task: &Task) -> Result<(), Error> {
+    let project_id = task
+        .project_id()
+        .ok_or_else(|| Error::TaskMissingProjectId(task.clone()))?;
+    let blockers = task.blocked_by().collect::<Vec<TaskId>>();
+    if blockers.is_empty() {
+        return Ok(());
+    }
+    let tasks = store
+        .project_tasks(project_id, &FilterSpec::new(TaskFilter::All), false)?
+        .into_iter()
+        .filter_map(|t| t.id().map(|id| (id, t)))
+        .collect::<BTreeMap<TaskId, Task>>();
+    for blocker_id in blockers.iter() {
+        if !tasks.contains_key(blocker_id) {
+            return Err(Error::TaskNotFound(project_id.to_string(), *blocker_id));
+        }
+    }
+    // A new task doesn't have an ID yet, so nothing can be blocked by it.
+    let task_id = match task.id() {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    for blocker_id in blockers {
+        let mut pending = vec![blocker_id];
+        let mut visited = BTreeSet::new();
+        while let Some(id) = pending.pop() {
+            if id == task_id {
+                return Err(Error::CircularTaskDependency(
+                    project_id.to_string(),
+                    task_id,
+                    blocker_id,
+                ));
+            }
+            if visited.insert(id) {
+                if let Some(t) = tasks.get(&id) {
+                    pending.extend(t.blocked_by());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Mark the given tasks as done, using the done state configured for the
+/// tasks' project.
+///
+/// Any active logs associated with the tasks are stopped first, and returned
+/// alongside the updated tasks.
+pub fn mark_tasks_done(store: &Store, params: &MarkTasks) -> Result<(Vec<Task>, Vec<Log>), Error> {
+    let task_ids = parse_task_ids(&params.task_ids)?;
+    let done = store
+        .task_state_config(&params.project_id)?
+        .done()
+        .to_string();
+    // Load and validate all of the tasks before stopping any logs, so that a
+    // mistyped task ID doesn't leave the user's timers stopped.
+    let tasks = task_ids
+        .iter()
+        .map(|task_id| store.task(&params.project_id, *task_id, false))
+        .collect::<Result<Vec<Task>, Error>>()?;
+    let tasks = apply_task_update(store, &params.to_update_task(done), tasks)?;
+    let mut stopped_logs = Vec::new();
+    for (project_id, maybe_task_id, log_id) in store.checked_state()?.active_logs() {
+        match maybe_task_id {
+            Some(task_id) if *project_id == params.project_id && task_ids.contains(task_id) => {
+                stopped_logs.push(stop_log(
+                    store,
+                    &StopLog {
+                        maybe_project_id: Some(project_id.clone()),
+                        maybe_task_id: *maybe_task_id,
+                        maybe_id: Some(*log_id),
+                        ..StopLog::default()
+                    },
+                )?);
+            }
+            _ => (),
+        }
+    }
+    let tasks = save_updated_tasks(store, tasks)?;
+    Ok((tasks, stopped_logs))
+}
+
+/// Mark the given tasks as being in progress, using the in-progress state
+/// configured for the tasks' project.
+pub fn mark_tasks_in_progress(store: &Store, params: &MarkTasks) -> Result<Vec<Task>, Error> {
+    let in_progress = store
+        .task_state_config(&params.project_id)?
+        .in_progress()
+        .to_string();
+    update_tasks(store, &params.to_update_task(in_progress))
+}
+
+/// Add a new log for a project or task.
+pub fn add_log(store: &Store, params: &AddLog) -> Result<Log, Error> {
+    add_log_with_stop_check(store, params, true)
+}
+
+// Adds a log, optionally rejecting stop times too far beyond the current time.
+fn add_log_with_stop_check(store: &Store, params: &AddLog, check_stop: bool) -> Result<Log, Error> {
+    let config = store.config()?;
+    let mut log = Log::try_from(params)?;
+    if check_stop {
+        log = with_checked_stop(log, &config)?;
+    }
+    if !params.no_default_tags {
+        let tags = merge_tags(log.tags(), config.default_log_tags());
+        log = log.with_tags(tags)?;
+    }
+    let log = store.create_log(&log)?;
+    record_audit(store, log_audit_entry(AuditOperation::AddLog, &log)?)?;
+    update_log_task_state(store, &log, false, params.reopen)?;
     debug!(
         "Added log {} for project {}{}",
         log.id().unwrap(),
@@ -570,76 +1930,388 @@ pub fn add_log(store: &Store, params: &AddLog) -> Result<Log, Error> {
     Ok(log)
 }
 
-/// Start tracking time for a new log.
-pub fn start_log(store: &Store, params: &StartLog) -> Result<Log, Error> {
-    let state = store.state()?;
-    // Stop any active log
-    if state.active_log().is_some() {
-        let _ = stop_log(store, &StopLog::default())?;
+// Checks the stop time implied by the log's start and duration, however they
+// were given (e.g. as a duration rather than a stop time), so that negative
+// durations and stop times too far in the future are rejected.
+fn with_checked_stop(log: Log, config: &Config) -> Result<Log, Error> {
+    match log.stop() {
+        Some(stop) => log.with_stop_checked(stop, Timestamp::now()?, config.stop_tolerance()),
+        None => Ok(log),
+    }
+}
+
+/// Start tracking time for a new log.
+pub fn start_log(store: &Store, params: &StartLog) -> Result<Log, Error> {
+    // Hold the lock across reading and writing the state so that concurrent
+    // attempts to start logs are serialized.
+    let _lock = store.lock()?;
+    let mut state = store.checked_state()?;
+    let mut log = Log::try_from(params)?;
+    // Make sure that the log can be created before touching any active logs,
+    // so that a mistyped project or task ID doesn't stop the current timer.
+    let project_id = log.project_id().unwrap();
+    store.project(project_id)?;
+    if let Some(task_id) = log.task_id() {
+        store.task(project_id, task_id, false)?;
+    }
+    if !params.no_default_tags {
+        let config = store.config()?;
+        let tags = merge_tags(log.tags(), config.default_log_tags());
+        log = log.with_tags(tags)?;
+    }
+    if !params.restart && params.maybe_stop_previous_at.is_none() {
+        if let Some(log_id) = identical_active_log(store, &state, &log)? {
+            return Err(Error::LogAlreadyActive(log_id));
+        }
+    }
+    // Stop any active logs, unless they're to be kept running
+    if !params.keep_active && !state.active_logs().is_empty() {
+        for (project_id, maybe_task_id, log_id) in state.active_logs().to_vec() {
+            let _ = stop_log_locked(
+                store,
+                &StopLog {
+                    maybe_project_id: Some(project_id),
+                    maybe_task_id,
+                    maybe_id: Some(log_id),
+                    maybe_stop_time: params.maybe_stop_previous_at,
+                    ..StopLog::default()
+                },
+            )?;
+        }
+        state = store.state()?;
+    } else if params.maybe_stop_previous_at.is_some() {
+        debug!("No active log to stop - ignoring --stop-previous-at parameter.");
+    }
+    let log = store.create_log(&log)?;
+    record_audit(store, log_audit_entry(AuditOperation::StartLog, &log)?)?;
+    let state = state.with_active_log(log.project_id().unwrap(), log.task_id(), log.id().unwrap());
+    store.save_state(&state)?;
+    update_log_task_state(store, &log, true, params.reopen)?;
+    if let Some(estimate) = params.maybe_estimate {
+        set_log_task_estimate(store, &log, estimate, params.force)?;
+    }
+
+    debug!(
+        "Started log {} for project {}{} at {}",
+        log.id().unwrap(),
+        log.project_id().unwrap(),
+        log.task_id()
+            .map(|task_id| format!(", task {},", task_id))
+            .unwrap_or_else(|| "".to_string()),
+        log.start().unwrap(),
+    );
+    Ok(log)
+}
+
+// Returns the ID of the active log that the given new log would merely
+// duplicate (i.e. one for the same project and task), if any. A new log only
+// differs from such an active log if it has a different comment.
+fn identical_active_log(store: &Store, state: &State, log: &Log) -> Result<Option<LogId>, Error> {
+    for (project_id, maybe_task_id, log_id) in state.active_logs() {
+        if Some(project_id.as_str()) != log.project_id() || *maybe_task_id != log.task_id() {
+            continue;
+        }
+        let active = store.log(project_id, *maybe_task_id, *log_id)?;
+        let same_comment = match log.comment().map(str::trim) {
+            Some(comment) => active.comment().map(str::trim) == Some(comment),
+            None => true,
+        };
+        if same_comment {
+            return Ok(Some(*log_id));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the task to which the given log relates if that task is closed
+/// (i.e. done or in another terminal state). Logging time against a closed
+/// task usually means that it should be reopened.
+pub fn closed_log_task(store: &Store, log: &Log) -> Result<Option<Task>, Error> {
+    Ok(log_task(store, log)?
+        .filter(|(task, task_state_config)| {
+            task.state()
+                .map(|state| task_state_config.is_closed(state))
+                .unwrap_or(false)
+        })
+        .map(|(task, _)| task))
+}
+
+// Moves the task to which a newly added or started log relates (if any) to
+// the in-progress state. Closed tasks are only moved if they're to be
+// reopened, and open ones only if `progress` is set.
+fn update_log_task_state(
+    store: &Store,
+    log: &Log,
+    progress: bool,
+    reopen: bool,
+) -> Result<(), Error> {
+    let (task, task_state_config) = match log_task(store, log)? {
+        Some(task) => task,
+        None => return Ok(()),
+    };
+    let is_closed = task
+        .state()
+        .map(|state| task_state_config.is_closed(state))
+        .unwrap_or(false);
+    if (is_closed && !reopen) || (!is_closed && !progress) {
+        debug!("Leaving task state as is: {:?}", task);
+        return Ok(());
+    }
+    if task.state() == Some(task_state_config.in_progress()) {
+        return Ok(());
+    }
+    let task = store.save_task(&task.with_state(task_state_config.in_progress()))?;
+    if is_closed {
+        record_audit(store, task_audit_entry(AuditOperation::UpdateTask, &task)?)?;
+    }
+    debug!("Updated task state for task: {:?}", task);
+    Ok(())
+}
+
+// Sets the estimate of the task to which the given log relates (if any),
+// leaving an existing estimate as is unless forced to overwrite it.
+fn set_log_task_estimate(
+    store: &Store,
+    log: &Log,
+    estimate: Duration,
+    force: bool,
+) -> Result<(), Error> {
+    let task = match log.task_id() {
+        Some(task_id) => store.task(log.project_id().unwrap(), task_id, false)?,
+        None => return Ok(()),
+    };
+    if task.estimate().is_some() && !force {
+        debug!("Leaving existing task estimate as is: {:?}", task);
+        return Ok(());
     }
-    let log = Log::try_from(params)?;
-    let log = store.save_log(&log)?;
-    let state = state.with_active_log(log.project_id().unwrap(), log.task_id(), log.id().unwrap());
-    store.save_state(&state)?;
-    if let Some(task_id) = log.task_id() {
-        let project = store.project(log.project_id().unwrap())?;
-        let task = store.task(log.project_id().unwrap(), task_id, false)?;
-        let config = store.config()?;
-        let task_state_config = project
-            .task_state_config()
-            .unwrap_or_else(|| config.task_state_config());
-        let task = task.with_state(task_state_config.in_progress());
-        let task = store.save_task(&task)?;
-        debug!("Updated task state for task: {:?}", task);
+    let task = store.save_task(&task.with_estimate(estimate))?;
+    record_audit(store, task_audit_entry(AuditOperation::UpdateTask, &task)?)?;
+    debug!("Updated task estimate for task: {:?}", task);
+    Ok(())
+}
+
+// Loads the task to which the given log relates (if any), along with the task
+// state configuration applicable to it.
+fn log_task(store: &Store, log: &Log) -> Result<Option<(Task, TaskStateConfig)>, Error> {
+    let task_id = match log.task_id() {
+        Some(task_id) => task_id,
+        None => return Ok(None),
+    };
+    let project = store.project(log.project_id().unwrap())?;
+    let task = store.task(project.id(), task_id, false)?;
+    let config = store.config()?;
+    let task_state_config = project
+        .task_state_config()
+        .unwrap_or_else(|| config.task_state_config())
+        .clone();
+    Ok(Some((task, task_state_config)))
+}
+
+/// Create (and, if it has no stop time, start) a work log from a single line
+/// of text. If the `paste` parameter is set, the line is read from the given
+/// reader.
+pub fn log_from_line<R: std::io::BufRead>(
+    store: &Store,
+    params: &LogFromLine,
+    mut reader: R,
+) -> Result<Log, Error> {
+    let line = match &params.maybe_line {
+        Some(line) if !params.paste => line.clone(),
+        _ => {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            line
+        }
+    };
+    let mut words = line.split_whitespace();
+    let project_id = words
+        .next()
+        .ok_or_else(|| Error::InvalidLogLine(line.trim().to_string()))?
+        .to_string();
+    let mut maybe_times = None;
+    let mut tags = Vec::new();
+    let mut comment = Vec::new();
+    for word in words {
+        if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            tags.push(tag.to_string());
+            continue;
+        }
+        if maybe_times.is_none() {
+            if let Some(times) = parse_time_range(word)? {
+                maybe_times = Some(times);
+                continue;
+            }
+        }
+        comment.push(word);
+    }
+    let (start, maybe_stop) =
+        maybe_times.ok_or_else(|| Error::InvalidLogLine(line.trim().to_string()))?;
+    let maybe_comment = if comment.is_empty() {
+        None
+    } else {
+        Some(comment.join(" "))
+    };
+    let maybe_tags = if tags.is_empty() {
+        None
+    } else {
+        Some(tags.join(","))
+    };
+    debug!("Parsed log line: {}", line.trim());
+    match maybe_stop {
+        // Both times of an explicit range refer to today, and a range may be
+        // logged before it ends (e.g. a meeting), so its stop time isn't
+        // checked against the current time.
+        Some(stop) => add_log_with_stop_check(
+            store,
+            &AddLog {
+                project_id,
+                maybe_task_id: None,
+                maybe_start: Some(start),
+                maybe_ago: None,
+                maybe_stop: Some(stop),
+                maybe_duration: None,
+                maybe_comment,
+                editor: false,
+                maybe_tags,
+                no_default_tags: params.no_default_tags,
+                reopen: false,
+            },
+            false,
+        ),
+        None => start_log(
+            store,
+            &StartLog {
+                project_id,
+                maybe_task_id: None,
+                maybe_start: Some(start),
+                maybe_ago: None,
+                maybe_comment,
+                maybe_tags,
+                no_default_tags: params.no_default_tags,
+                reopen: false,
+                maybe_stop_previous_at: None,
+                keep_active: false,
+                maybe_estimate: None,
+                force: false,
+                restart: false,
+            },
+        ),
+    }
+}
+
+// Resolves a log's start time from either an explicit start time or a
+// duration before now, of which at most one may be given.
+fn resolve_start(
+    maybe_start: Option<Timestamp>,
+    maybe_ago: Option<Duration>,
+) -> Result<Option<Timestamp>, Error> {
+    match (maybe_start, maybe_ago) {
+        (Some(_), Some(_)) => Err(Error::CannotAcceptStartAndAgo),
+        (Some(start), None) => Ok(Some(start)),
+        (None, Some(ago)) => Ok(Some(Timestamp::now()? - ago)),
+        (None, None) => Ok(None),
+    }
+}
+
+// Parses either a single time of day ("HH:MM") or a range of times
+// ("HH:MM-HH:MM") for today. Returns `None` if the word is neither.
+fn parse_time_range(word: &str) -> Result<Option<(Timestamp, Option<Timestamp>)>, Error> {
+    let (start, maybe_stop) = match word.split_once('-') {
+        Some((start, stop)) => (start, Some(stop)),
+        None => (word, None),
+    };
+    if !is_time_of_day(start) || !maybe_stop.map(is_time_of_day).unwrap_or(true) {
+        return Ok(None);
+    }
+    // Timestamps expect zero-padded hours
+    let parse = |time: &str| Timestamp::from_str(&format!("{:0>5}", time));
+    Ok(Some((parse(start)?, maybe_stop.map(parse).transpose()?)))
+}
+
+fn is_time_of_day(s: &str) -> bool {
+    match s.split_once(':') {
+        Some((hours, mins)) => {
+            (1..=2).contains(&hours.len())
+                && mins.len() == 2
+                && hours
+                    .chars()
+                    .chain(mins.chars())
+                    .all(|c| c.is_ascii_digit())
+        }
+        None => false,
     }
+}
 
+// Saves the given (stopped) log, splitting it at every midnight it crosses.
+// The first day's portion keeps the log's ID, and each subsequent day's portion
+// is saved as a new log. Returns the last of the saved logs.
+fn save_log_split_by_day(store: &Store, log: &Log) -> Result<Log, Error> {
+    let intervals = split_by_day(log.start().unwrap(), log.stop().unwrap());
+    if intervals.len() < 2 {
+        return store.save_log(log);
+    }
+    let (first_start, first_stop) = intervals[0];
+    let mut saved_log = store.save_log(&log.clone().with_duration(first_stop - first_start))?;
+    for (start, stop) in intervals.iter().skip(1) {
+        let day_log = Log::new(log.project_id().unwrap())
+            .with_maybe_task_id(log.task_id())
+            .with_start(*start)
+            .with_duration(*stop - *start)
+            .with_maybe_comment(log.comment().map(String::from))
+            .with_tags(log.tags())?;
+        saved_log = store.create_log(&day_log)?;
+    }
     debug!(
-        "Started log {} for project {}{} at {}",
+        "Split log {} for project {} into {} logs, one per day",
         log.id().unwrap(),
         log.project_id().unwrap(),
-        log.task_id()
-            .map(|task_id| format!(", task {},", task_id))
-            .unwrap_or_else(|| "".to_string()),
-        log.start().unwrap(),
+        intervals.len(),
     );
-    Ok(log)
+    Ok(saved_log)
 }
 
 /// Stop tracking time for the currently active log.
 pub fn stop_log(store: &Store, params: &StopLog) -> Result<Log, Error> {
+    let _lock = store.lock()?;
+    stop_log_locked(store, params)
+}
+
+// Stops a log, assuming the caller already holds the store lock.
+fn stop_log_locked(store: &Store, params: &StopLog) -> Result<Log, Error> {
     let invalid_log = params.maybe_project_id.is_some() ^ params.maybe_id.is_some();
     if invalid_log {
         return Err(Error::BothProjectAndLogIdRequired);
     }
-    let state = store.state()?;
-    let mut selected_active_log = false;
-    let (project_id, maybe_task_id, log_id) =
-        if let Some(project_id) = params.maybe_project_id.as_ref() {
-            (
-                project_id.clone(),
-                params.maybe_task_id,
-                params.maybe_id.unwrap(),
-            )
-        } else {
-            selected_active_log = true;
-            state.active_log().ok_or(Error::NoActiveLog)?
-        };
+    let state = store.checked_state()?;
+    let (project_id, maybe_task_id, log_id) = select_log(
+        &state,
+        params.maybe_project_id.as_ref(),
+        params.maybe_task_id,
+        params.maybe_id,
+    )?;
 
-    let mut log = store
+    let log = store
         .log(&project_id, maybe_task_id, log_id)?
         .with_duration_or_stop_or_now(params.maybe_duration, params.maybe_stop_time)?;
+    let mut log = with_checked_stop(log, &store.config()?)?;
 
     // Optionally update the comment and tags
-    if let Some(comment) = &params.maybe_comment {
-        log = log.with_comment(comment);
-    }
+    log = with_edited_comment(
+        log,
+        params.maybe_comment.as_deref(),
+        params.maybe_comment_append.as_deref(),
+    );
     if let Some(tags) = &params.maybe_tags {
         log = log.with_tags(parse_comma_separated(Some(tags.clone())))?;
     }
-    let mut log = store.save_log(&log)?;
-    if selected_active_log {
-        let state = state.with_no_active_log();
-        store.save_state(&state)?;
+    let mut log = if params.split_by_day {
+        save_log_split_by_day(store, &log)?
+    } else {
+        store.save_log(&log)?
+    };
+    record_audit(store, log_audit_entry(AuditOperation::StopLog, &log)?)?;
+    if state.is_active(&(project_id.clone(), maybe_task_id, log_id)) {
+        store.save_state(&state.without_active_log(&project_id, maybe_task_id, log_id))?;
     }
     if params.done {
         if let Some(task_id) = log.task_id() {
@@ -651,6 +2323,7 @@ pub fn stop_log(store: &Store, params: &StopLog) -> Result<Log, Error> {
 
             task = task.with_state(tsc.done());
             task = store.save_task(&task)?;
+            record_audit(store, task_audit_entry(AuditOperation::UpdateTask, &task)?)?;
             debug!(
                 "Automatically marked task {} of project {} as {}",
                 task.id().unwrap(),
@@ -675,32 +2348,201 @@ pub fn stop_log(store: &Store, params: &StopLog) -> Result<Log, Error> {
     Ok(log)
 }
 
+// Replaces and/or appends to a log's comment, as requested by the --comment
+// and --comment-append options of the commands that edit logs.
+fn with_edited_comment(
+    mut log: Log,
+    maybe_comment: Option<&str>,
+    maybe_comment_append: Option<&str>,
+) -> Log {
+    if let Some(comment) = maybe_comment {
+        log = log.with_comment(comment);
+    }
+    if let Some(comment) = maybe_comment_append {
+        log = log.with_appended_comment(comment);
+    }
+    log
+}
+
+/// Moves a project-level work log into the given task's work logs, where it
+/// is assigned a new ID. If it is the active log, the state is updated to
+/// refer to its new location.
+pub fn link_log(store: &Store, params: &LinkLog) -> Result<Log, Error> {
+    let _lock = store.lock()?;
+    // Ensure the task exists before touching the log
+    let _ = store.task(&params.project_id, params.task_id, false)?;
+    let linked = move_log_locked(
+        store,
+        (&params.project_id, None, params.id),
+        (&params.project_id, Some(params.task_id)),
+    )?;
+    record_audit(store, log_audit_entry(AuditOperation::LinkLog, &linked)?)?;
+    debug!(
+        "Linked log {} of project {} to task {} as log {}",
+        params.id,
+        params.project_id,
+        params.task_id,
+        linked.id().unwrap()
+    );
+    Ok(linked)
+}
+
+/// Edits a work log, first moving it to a different project and/or task if
+/// requested. If the active log is given a duration or stop time, it is no
+/// longer the active log.
+pub fn update_log(store: &Store, params: &UpdateLog) -> Result<Log, Error> {
+    let _lock = store.lock()?;
+    let moving = params.maybe_to_project_id.is_some() || params.maybe_to_task_id.is_some();
+    let mut log = if moving {
+        let moved = move_log_checked_locked(
+            store,
+            (&params.project_id, params.maybe_task_id, params.id),
+            params.maybe_to_project_id.as_deref(),
+            params.maybe_to_task_id,
+        )?;
+        record_audit(store, log_audit_entry(AuditOperation::MoveLog, &moved)?)?;
+        moved
+    } else {
+        store.log(&params.project_id, params.maybe_task_id, params.id)?
+    };
+    let edits_fields = params.maybe_start.is_some()
+        || params.maybe_duration.is_some()
+        || params.maybe_stop.is_some()
+        || params.maybe_comment.is_some()
+        || params.maybe_comment_append.is_some()
+        || params.maybe_tags.is_some();
+    if moving && !edits_fields {
+        return Ok(log);
+    }
+    if let Some(start) = params.maybe_start {
+        log = log.with_start(start);
+    }
+    log = log.with_maybe_duration_or_stop(params.maybe_duration, params.maybe_stop)?;
+    if params.maybe_start.is_some()
+        || params.maybe_duration.is_some()
+        || params.maybe_stop.is_some()
+    {
+        log = with_checked_stop(log, &store.config()?)?;
+    }
+    log = with_edited_comment(
+        log,
+        params.maybe_comment.as_deref(),
+        params.maybe_comment_append.as_deref(),
+    );
+    if let Some(tags) = &params.maybe_tags {
+        log = log.with_tags(parse_comma_separated(Some(tags.clone())))?;
+    }
+    let log = store.save_log(&log)?;
+    record_audit(store, log_audit_entry(AuditOperation::UpdateLog, &log)?)?;
+
+    let state = store.checked_state()?;
+    let log_ref = (
+        log.project_id().unwrap().to_string(),
+        log.task_id(),
+        log.id().unwrap(),
+    );
+    if log.duration().is_some() && state.is_active(&log_ref) {
+        store.save_state(&state.without_active_log(&log_ref.0, log_ref.1, log_ref.2))?;
+        debug!("Log {} is no longer active", log_ref.2);
+    }
+    Ok(log)
+}
+
+/// Moves a work log to a different project and/or task, where it is assigned
+/// a new ID. If it is the active log, the state is updated to refer to its
+/// new location.
+pub fn move_log(store: &Store, params: &MoveLog) -> Result<Log, Error> {
+    let _lock = store.lock()?;
+    let mut moved = move_log_checked_locked(
+        store,
+        (&params.project_id, params.maybe_task_id, params.id),
+        params.maybe_to_project_id.as_deref(),
+        params.maybe_to_task_id,
+    )?;
+    if params.maybe_comment_append.is_some() {
+        moved = store.save_log(&with_edited_comment(
+            moved,
+            None,
+            params.maybe_comment_append.as_deref(),
+        ))?;
+    }
+    record_audit(store, log_audit_entry(AuditOperation::MoveLog, &moved)?)?;
+    Ok(moved)
+}
+
+// Moves a log to the given project (defaulting to its current one) and/or task
+// after checking that the task exists, assuming the caller already holds the
+// store lock.
+fn move_log_checked_locked(
+    store: &Store,
+    from: (&str, Option<TaskId>, LogId),
+    maybe_to_project_id: Option<&str>,
+    maybe_to_task_id: Option<TaskId>,
+) -> Result<Log, Error> {
+    let to_project_id = maybe_to_project_id.unwrap_or(from.0);
+    if let Some(task_id) = maybe_to_task_id {
+        let _ = store.task(to_project_id, task_id, false)?;
+    }
+    move_log_locked(store, from, (to_project_id, maybe_to_task_id))
+}
+
+// Moves a log, updating the active log if necessary, assuming the caller
+// already holds the store lock.
+fn move_log_locked(
+    store: &Store,
+    from: (&str, Option<TaskId>, LogId),
+    to: (&str, Option<TaskId>),
+) -> Result<Log, Error> {
+    let moved = store.move_log(from, to)?;
+    let state = store.state()?;
+    let from = (from.0.to_string(), from.1, from.2);
+    if state.is_active(&from) {
+        let to = (to.0.to_string(), to.1, moved.id().unwrap());
+        store.save_state(&state.with_replaced_active_log(&from, to))?;
+        debug!("Updated active log to log {}", moved.id().unwrap());
+    }
+    Ok(moved)
+}
+
+// Selects the log identified by the given project, task and log IDs if given,
+// otherwise the only active log. If more than one log is active, the caller
+// has to specify which one is meant.
+fn select_log(
+    state: &State,
+    maybe_project_id: Option<&ProjectId>,
+    maybe_task_id: Option<TaskId>,
+    maybe_id: Option<LogId>,
+) -> Result<ActiveLog, Error> {
+    if let (Some(project_id), Some(log_id)) = (maybe_project_id, maybe_id) {
+        return Ok((project_id.clone(), maybe_task_id, log_id));
+    }
+    match state.active_logs() {
+        [] => Err(Error::NoActiveLog),
+        [active_log] => Ok(active_log.clone()),
+        active_logs => Err(Error::MultipleActiveLogs(active_logs.len())),
+    }
+}
+
 /// Cancels the active work log, if any.
 pub fn cancel_log(store: &Store, params: &CancelLog) -> Result<Option<Log>, Error> {
     let invalid_log = params.maybe_project_id.is_some() ^ params.maybe_id.is_some();
     if invalid_log {
         return Err(Error::BothProjectAndLogIdRequired);
     }
-    let state = store.state()?;
-    let mut selected_active_log = false;
-    let (project_id, maybe_task_id, log_id) =
-        if let Some(project_id) = params.maybe_project_id.as_ref() {
-            (
-                project_id.clone(),
-                params.maybe_task_id,
-                params.maybe_id.unwrap(),
-            )
-        } else {
-            selected_active_log = true;
-            state.active_log().ok_or(Error::NoActiveLog)?
-        };
+    let state = store.checked_state()?;
+    let (project_id, maybe_task_id, log_id) = select_log(
+        &state,
+        params.maybe_project_id.as_ref(),
+        params.maybe_task_id,
+        params.maybe_id,
+    )?;
 
     let log = store.log(&project_id, maybe_task_id, log_id)?;
 
     store.delete_log(log.project_id().unwrap(), log.task_id(), log.id().unwrap())?;
-    if selected_active_log {
-        let state = state.with_no_active_log();
-        store.save_state(&state)?;
+    record_audit(store, log_audit_entry(AuditOperation::CancelLog, &log)?)?;
+    if state.is_active(&(project_id.clone(), maybe_task_id, log_id)) {
+        store.save_state(&state.without_active_log(&project_id, maybe_task_id, log_id))?;
     }
     debug!(
         "Cancelled log {} for project {}{}",
@@ -713,19 +2555,82 @@ pub fn cancel_log(store: &Store, params: &CancelLog) -> Result<Option<Log>, Erro
     Ok(Some(log))
 }
 
+/// Starts a new work log continuing the most recently stopped one (i.e. the
+/// one with the latest stop time), optionally within a specific project. Like
+/// [`start_log`], this stops any active logs first.
+pub fn continue_log(store: &Store, params: &ContinueLog) -> Result<Log, Error> {
+    let project_filter = FilterSpec::new(match &params.maybe_project_id {
+        Some(project_id) => ProjectFilter::Ids(vec![store.project(project_id)?.id().to_string()]),
+        None => ProjectFilter::All,
+    });
+    let previous = store
+        .logs(
+            &project_filter,
+            &FilterSpec::new(TaskFilter::All),
+            &FilterSpec::new(LogFilter::All),
+            false,
+        )?
+        .into_iter()
+        .filter(|log| log.stop().is_some())
+        .max_by_key(|log| log.stop())
+        .ok_or(Error::NoStoppedLog)?;
+    let tags = previous.tags().collect::<Vec<&str>>();
+    let log = start_log(
+        store,
+        &StartLog {
+            project_id: previous.project_id().unwrap().to_string(),
+            maybe_task_id: previous.task_id(),
+            maybe_start: None,
+            maybe_ago: None,
+            maybe_comment: previous.comment().map(String::from),
+            maybe_tags: if tags.is_empty() {
+                None
+            } else {
+                Some(tags.join(","))
+            },
+            // The previous log's tags already include any default tags it got.
+            no_default_tags: true,
+            reopen: false,
+            maybe_stop_previous_at: None,
+            keep_active: false,
+            maybe_estimate: None,
+            force: false,
+            restart: false,
+        },
+    )?;
+    debug!(
+        "Continued log {} of project {} as log {}",
+        previous.id().unwrap(),
+        previous.project_id().unwrap(),
+        log.id().unwrap()
+    );
+    Ok(log)
+}
+
 /// List projects, optionally sorting them.
 ///
 /// Returns the rendered table containing the results.
 pub fn list_projects(store: &Store, params: &ListProjects) -> Result<Vec<Project>, Error> {
-    let filter = build_project_filter(
+    let config = store.config()?;
+    let mut filter = build_project_filter(
         None,
         params.maybe_deadline_filter.clone(),
         params.maybe_tags_filter.clone(),
+        &config,
     )?;
+    if let Some(regex) = params.maybe_regex_filter.as_ref() {
+        filter = filter.and_then(ProjectFilter::NameMatches(RegexPattern::from_str(regex)?));
+    }
+    if !params.include_archived {
+        filter = filter.and_then(ProjectFilter::Archived(false));
+    }
 
     let mut projects = store.projects(&filter)?;
     let sort_spec = SortSpec::<ProjectField>::from_str(&params.sort)?;
-    projects = sort_spec.sort(projects);
+    projects = sort_spec.sort(projects, Timestamp::now()?);
+    if params.reverse {
+        projects.reverse();
+    }
     Ok(projects)
 }
 
@@ -733,6 +2638,7 @@ fn build_project_filter(
     maybe_project_ids: Option<String>,
     maybe_deadline: Option<String>,
     maybe_tags: Option<String>,
+    config: &Config,
 ) -> Result<FilterSpec<ProjectFilter>, Error> {
     let mut filter = FilterSpec::new(ProjectFilter::All);
     if let Some(project_ids) = maybe_project_ids.as_ref() {
@@ -741,8 +2647,8 @@ fn build_project_filter(
         ))));
     }
     if let Some(deadline) = maybe_deadline {
-        filter = filter.and_then(ProjectFilter::Deadline(TimestampFilter::from_str(
-            &deadline,
+        filter = filter.and_then(ProjectFilter::Deadline(parse_timestamp_filter(
+            &deadline, config,
         )?));
     }
     if let Some(tags) = maybe_tags {
@@ -752,6 +2658,7 @@ fn build_project_filter(
 }
 
 fn build_task_filter(
+    store: &Store,
     maybe_priorities: Option<String>,
     maybe_states: Option<String>,
     maybe_deadline: Option<String>,
@@ -771,15 +2678,21 @@ fn build_task_filter(
     if let Some(states) = maybe_states {
         let states = parse_comma_separated(Some(states));
         if states.len() == 1 && states[0].starts_with('!') {
-            filter = filter.and_then(TaskFilter::StateNot(
-                states[0].trim_start_matches('!').to_string(),
-            ));
+            match states[0].trim_start_matches('!') {
+                // Exclude all closed states
+                "" => filter = filter.and_then(TaskFilter::Open(store.task_state_configs()?)),
+                "any" | "all" => return Err(Error::InvalidTaskStateFilter(states[0].clone())),
+                state => filter = filter.and_then(TaskFilter::StateNot(state.to_string())),
+            }
         } else if !states.is_empty() && states[0] != "any" && states[0] != "all" {
             filter = filter.and_then(TaskFilter::State(states));
         }
     }
     if let Some(deadline) = maybe_deadline {
-        filter = filter.and_then(TaskFilter::Deadline(TimestampFilter::from_str(&deadline)?));
+        filter = filter.and_then(TaskFilter::Deadline(parse_timestamp_filter(
+            &deadline,
+            &store.config()?,
+        )?));
     }
     if let Some(tags) = maybe_tags {
         filter = filter.and_then(TaskFilter::Tags(parse_comma_separated(Some(tags))));
@@ -809,12 +2722,15 @@ fn build_task_filter(
 ///
 /// Returns the rendered table containing the results.
 pub fn list_tasks(store: &Store, params: &ListTasks) -> Result<Vec<Task>, Error> {
+    let config = store.config()?;
     let project_filter = build_project_filter(
         params.maybe_project_ids.clone(),
         params.maybe_project_deadline_filter.clone(),
         params.maybe_project_tags_filter.clone(),
+        &config,
     )?;
-    let task_filter = build_task_filter(
+    let mut task_filter = build_task_filter(
+        store,
         params.maybe_priority_filter.clone(),
         Some(params.state_filter.clone()),
         params.maybe_deadline_filter.clone(),
@@ -822,19 +2738,39 @@ pub fn list_tasks(store: &Store, params: &ListTasks) -> Result<Vec<Task>, Error>
         params.maybe_github_issue_filter.clone(),
         params.maybe_github_pr_filter.clone(),
     )?;
+    if let Some(regex) = params.maybe_regex_filter.as_ref() {
+        task_filter = task_filter.and_then(TaskFilter::DescriptionMatches(RegexPattern::from_str(
+            regex,
+        )?));
+    }
+    if let Some(days) = params.maybe_due_within {
+        task_filter = task_filter.and_then(TaskFilter::DueWithin(days));
+    }
+    if params.overdue {
+        task_filter = task_filter.and_then(TaskFilter::Overdue(store.task_state_configs()?));
+    }
 
-    let mut tasks = store.tasks(&project_filter, &task_filter, true)?;
+    let mut tasks = if params.unblocked {
+        store.unblocked_tasks(&project_filter, &task_filter, true)?
+    } else {
+        store.tasks(&project_filter, &task_filter, true)?
+    };
     let sort_spec = SortSpec::<TaskField>::from_str(&params.sort)?;
-    tasks = sort_spec.sort(tasks);
+    tasks = sort_spec.sort(tasks, Timestamp::now()?);
+    if params.reverse {
+        tasks.reverse();
+    }
     Ok(tasks)
 }
 
 fn build_log_filter(
     task_filter: &FilterSpec<TaskFilter>,
     maybe_task_ids: Option<String>,
-    maybe_start: Option<String>,
+    maybe_start: Option<TimestampFilter>,
     maybe_duration: Option<String>,
     maybe_tags: Option<String>,
+    maybe_has_comment: Option<bool>,
+    maybe_weekends: Option<bool>,
 ) -> Result<FilterSpec<LogFilter>, Error> {
     let mut filter = FilterSpec::new(LogFilter::All);
     if let Some(task_ids) = maybe_task_ids {
@@ -848,7 +2784,7 @@ fn build_log_filter(
         filter = filter.and_then(LogFilter::HasTask);
     }
     if let Some(start) = maybe_start {
-        filter = filter.and_then(LogFilter::Start(TimestampFilter::from_str(&start)?));
+        filter = filter.and_then(LogFilter::Start(start));
     }
     if let Some(duration) = maybe_duration {
         filter = filter.and_then(LogFilter::Duration(DurationFilter::from_str(&duration)?));
@@ -856,72 +2792,746 @@ fn build_log_filter(
     if let Some(tags) = maybe_tags {
         filter = filter.and_then(LogFilter::Tags(parse_comma_separated(Some(tags))));
     }
+    match maybe_has_comment {
+        Some(true) => filter = filter.and_then(LogFilter::HasComment),
+        Some(false) => filter = filter.and_then(LogFilter::NoComment),
+        None => (),
+    }
+    match maybe_weekends {
+        Some(true) => filter = filter.and_then(LogFilter::Weekends),
+        Some(false) => filter = filter.and_then(LogFilter::Weekdays),
+        None => (),
+    }
     Ok(filter)
 }
 
 /// List work logs, filtered and ordered by the given parameters.
 pub fn list_logs(store: &Store, params: &ListLogs) -> Result<Vec<Log>, Error> {
+    let maybe_project = single_project(store, params)?;
+    let sort = params
+        .maybe_sort
+        .as_deref()
+        .or_else(|| maybe_project.as_ref().and_then(Project::default_log_sort))
+        .unwrap_or(DEFAULT_LOG_SORT);
+    let (project_filter, task_filter, log_filter) =
+        list_logs_filters(store, params, maybe_project.as_ref())?;
+    // Sorting doesn't depend on task details, so these are only loaded for
+    // the logs that end up being listed.
+    let mut logs = match single_task_logs(
+        store,
+        params,
+        maybe_project.as_ref(),
+        &project_filter,
+        &task_filter,
+        &log_filter,
+    )? {
+        Some(logs) => logs,
+        None => store.logs(&project_filter, &task_filter, &log_filter, false)?,
+    };
+    let sort_spec = SortSpec::<LogField>::from_str(sort)?;
+    logs = sort_spec.sort(logs, Timestamp::now()?);
+    if params.reverse {
+        logs.reverse();
+    }
+    let logs = logs
+        .into_iter()
+        .skip(params.offset)
+        .take(params.maybe_limit.unwrap_or(usize::MAX))
+        .collect::<Vec<Log>>();
+    if params.detailed {
+        with_task_details(store, logs)
+    } else {
+        Ok(logs)
+    }
+}
+
+/// Count the work logs matching the given parameters, ignoring any limit or
+/// offset.
+pub fn count_logs(store: &Store, params: &ListLogs) -> Result<usize, Error> {
+    let maybe_project = single_project(store, params)?;
+    let (project_filter, task_filter, log_filter) =
+        list_logs_filters(store, params, maybe_project.as_ref())?;
+    store.log_count(&project_filter, &task_filter, &log_filter)
+}
+
+// Attaches to each log the task to which it belongs, if any, loading each task
+// only once.
+fn with_task_details(store: &Store, logs: Vec<Log>) -> Result<Vec<Log>, Error> {
+    let mut tasks: BTreeMap<(ProjectId, TaskId), Task> = BTreeMap::new();
+    logs.into_iter()
+        .map(|log| {
+            let task_id = match log.task_id() {
+                Some(task_id) => task_id,
+                None => return Ok(log),
+            };
+            let project_id = log.project_id().unwrap().to_string();
+            let task = match tasks.entry((project_id, task_id)) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => {
+                    let task = store.task(&entry.key().0, task_id, false)?;
+                    entry.insert(task).clone()
+                }
+            };
+            Ok(log.with_maybe_task(Some(task)))
+        })
+        .collect()
+}
+
+/// List the pairs of work logs matching the given parameters whose times
+/// overlap (see [`Store::overlapping_logs`]).
+pub fn overlapping_logs(store: &Store, params: &ListLogs) -> Result<Vec<(Log, Log)>, Error> {
+    let maybe_project = single_project(store, params)?;
+    let (project_filter, task_filter, log_filter) =
+        list_logs_filters(store, params, maybe_project.as_ref())?;
+    store.overlapping_logs(&project_filter, &task_filter, &log_filter)
+}
+
+// A single project's own defaults take precedence over the global ones when
+// listing logs, so load the project if only one was given.
+fn single_project(store: &Store, params: &ListLogs) -> Result<Option<Project>, Error> {
+    let project_ids = parse_comma_separated(params.maybe_project_ids.clone());
+    match project_ids.as_slice() {
+        [project_id] => Ok(Some(store.project(project_id)?)),
+        _ => Ok(None),
+    }
+}
+
+// If exactly one project and one task are specified, only that task's logs
+// can match, so there's no need to scan the rest of the project's tasks.
+// Returns `None` if the logs need to be looked up across the store.
+fn single_task_logs(
+    store: &Store,
+    params: &ListLogs,
+    maybe_project: Option<&Project>,
+    project_filter: &FilterSpec<ProjectFilter>,
+    task_filter: &FilterSpec<TaskFilter>,
+    log_filter: &FilterSpec<LogFilter>,
+) -> Result<Option<Vec<Log>>, Error> {
+    let task_ids = parse_comma_separated(params.maybe_task_ids_filter.clone());
+    let (project, task_id) = match (maybe_project, task_ids.as_slice()) {
+        (Some(project), [task_id]) => match TaskId::from_str(task_id) {
+            Ok(task_id) => (project, task_id),
+            Err(_) => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let now = Timestamp::now()?;
+    if !project_filter.matches(project, now) {
+        return Ok(Some(Vec::new()));
+    }
+    let task = match store.task(project.id(), task_id, false) {
+        Ok(task) => task,
+        Err(Error::TaskNotFound(_, _)) => return Ok(Some(Vec::new())),
+        Err(e) => return Err(e),
+    };
+    if !task_filter.matches(&task, now) {
+        return Ok(Some(Vec::new()));
+    }
+    store
+        .logs_for_task(project.id(), task_id, log_filter)
+        .map(Some)
+}
+
+// The project, task and log filters with which to list logs.
+type ListLogsFilters = (
+    FilterSpec<ProjectFilter>,
+    FilterSpec<TaskFilter>,
+    FilterSpec<LogFilter>,
+);
+
+fn list_logs_filters(
+    store: &Store,
+    params: &ListLogs,
+    maybe_project: Option<&Project>,
+) -> Result<ListLogsFilters, Error> {
+    let config = store.config()?;
+    let start_filter = params
+        .maybe_start_filter
+        .as_deref()
+        .or_else(|| maybe_project.and_then(Project::default_log_filter))
+        .unwrap_or(DEFAULT_LOG_START_FILTER);
     let project_filter = build_project_filter(
         params.maybe_project_ids.clone(),
-        params.maybe_project_deadline_filter.clone(),
-        params.maybe_project_tags_filter.clone(),
+        params.maybe_project_deadline_filter.clone(),
+        params.maybe_project_tags_filter.clone(),
+        &config,
+    )?;
+    let task_filter = build_task_filter(
+        store,
+        params.maybe_task_priority_filter.clone(),
+        params.maybe_task_state_filter.clone(),
+        params.maybe_task_deadline_filter.clone(),
+        params.maybe_task_tags_filter.clone(),
+        None,
+        None,
+    )?;
+    let mut log_filter = build_log_filter(
+        &task_filter,
+        params.maybe_task_ids_filter.clone(),
+        Some(parse_timestamp_filter(start_filter, &config)?),
+        params.maybe_duration_filter.clone(),
+        params.maybe_tags_filter.clone(),
+        match (params.has_comment, params.no_comment) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        match (params.weekends, params.weekdays) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+    )?;
+    if let Some(regex) = params.maybe_regex_filter.as_ref() {
+        log_filter = log_filter.and_then(LogFilter::CommentMatches(RegexPattern::from_str(regex)?));
+    }
+    Ok((project_filter, task_filter, log_filter))
+}
+
+/// List all work logs across all projects and tasks for a particular day,
+/// ordered by their start time.
+pub fn day(store: &Store, params: &Day) -> Result<Vec<Log>, Error> {
+    store.logs_on_day(params.date)
+}
+
+/// Add a placeholder work log on each of the days in the given range on which
+/// nothing was logged at all, skipping weekends (unless included) and any
+/// explicitly skipped dates. Returns the placeholder logs, which are only
+/// saved if this isn't a dry run.
+pub fn fill_days(store: &Store, params: &FillDays) -> Result<Vec<Log>, Error> {
+    let _lock = store.lock()?;
+    match params.maybe_task_id {
+        Some(task_id) => {
+            let _ = store.task(&params.project_id, task_id, false)?;
+        }
+        None => {
+            let _ = store.project(&params.project_id)?;
+        }
+    }
+    let skip = parse_comma_separated(params.maybe_skip.clone())
+        .iter()
+        .map(|date| Ok(Timestamp::from_str(date)?.format_date()))
+        .collect::<Result<Vec<String>, Error>>()?;
+    let tags = parse_comma_separated(params.maybe_tags.clone());
+    // The start times of all the logs in the range, loaded up front rather
+    // than day by day.
+    let log_filter = FilterSpec::new(LogFilter::Start(TimestampFilter::Starting(
+        params.from.today(),
+    )))
+    .and_then(LogFilter::Start(TimestampFilter::Before(
+        params.to.tomorrow(),
+    )));
+    let starts = store
+        .logs(
+            &FilterSpec::new(ProjectFilter::All),
+            &FilterSpec::new(TaskFilter::All),
+            &log_filter,
+            false,
+        )?
+        .iter()
+        .filter_map(Log::start)
+        .collect::<BTreeSet<Timestamp>>();
+
+    let mut logs = Vec::new();
+    let mut day = params.from.today();
+    while day <= params.to.today() {
+        let is_skipped =
+            (day.is_weekend() && !params.include_weekends) || skip.contains(&day.format_date());
+        if !is_skipped && starts.range(day..day.tomorrow()).next().is_none() {
+            let log = Log::new(&params.project_id)
+                .with_maybe_task_id(params.maybe_task_id)
+                .with_start(day.at_time_of_day(params.at))
+                .with_duration(params.duration)
+                .with_maybe_comment(params.maybe_comment.clone())
+                .with_tags(tags.clone())?;
+            logs.push(if params.dry_run {
+                log
+            } else {
+                let log = store.create_log(&log)?;
+                record_audit(store, log_audit_entry(AuditOperation::AddLog, &log)?)?;
+                log
+            });
+        }
+        day = day.tomorrow();
+    }
+    debug!(
+        "{} {} placeholder log(s)",
+        if params.dry_run { "Would add" } else { "Added" },
+        logs.len()
+    );
+    Ok(logs)
+}
+
+/// A project's work logs, along with their totals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectLogs {
+    pub project_id: ProjectId,
+    pub logs: Vec<Log>,
+    pub totals: LogTotals,
+}
+
+/// Group the given logs by project (in order of project ID), keeping the
+/// logs' order within each project, and total each group. Running logs are
+/// counted up until `now`.
+pub fn logs_by_project(logs: Vec<Log>, config: &Config, now: Timestamp) -> Vec<ProjectLogs> {
+    let mut groups: BTreeMap<ProjectId, Vec<Log>> = BTreeMap::new();
+    for log in logs {
+        let project_id = log.project_id().unwrap_or_default().to_string();
+        groups.entry(project_id).or_default().push(log);
+    }
+    groups
+        .into_iter()
+        .map(|(project_id, logs)| ProjectLogs {
+            totals: LogTotals::new(&logs, config, now),
+            project_id,
+            logs,
+        })
+        .collect()
+}
+
+/// The total time logged within a particular period of a report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportPeriod {
+    /// The start of the period (inclusive).
+    pub start: Timestamp,
+    /// The end of the period (exclusive).
+    pub end: Timestamp,
+    /// The net time logged within the period, i.e. excluding breaks.
+    pub total: Duration,
+    /// The gross time logged within the period, i.e. including breaks.
+    pub gross: Duration,
+    /// The time taken for breaks within the period.
+    pub breaks: Duration,
+    /// The net time logged within the period per project. Only populated if a
+    /// breakdown by project was requested, or if the report isn't bucketed
+    /// into periods.
+    pub by_project: BTreeMap<ProjectId, Duration>,
+    /// The net time logged within the period with each log's duration rounded
+    /// up to the configured rounding increment. Equal to `total` if no
+    /// rounding is configured.
+    pub rounded: Duration,
+    /// The rounded net time per project, populated alongside `by_project`.
+    pub rounded_by_project: BTreeMap<ProjectId, Duration>,
+}
+
+impl ReportPeriod {
+    /// The percentage of the period's total time logged for the given project,
+    /// if any time was logged for it.
+    pub fn project_percentage(&self, project_id: &str) -> Option<f64> {
+        let project_total = self.by_project.get(project_id)?;
+        if self.total == Duration::zero() {
+            return None;
+        }
+        Some(100.0 * project_total.whole_seconds() as f64 / self.total.whole_seconds() as f64)
+    }
+
+    /// How much rounding adds to the period's net total.
+    pub fn rounding_diff(&self) -> Duration {
+        self.rounded - self.total
+    }
+
+    /// How much rounding adds to the given project's net total, if any time
+    /// was logged for it.
+    pub fn project_rounding_diff(&self, project_id: &str) -> Option<Duration> {
+        Some(*self.rounded_by_project.get(project_id)? - *self.by_project.get(project_id)?)
+    }
+}
+
+/// Load the store's configuration, overriding its rounding increment with the
+/// given one (if any).
+pub fn rounding_config(store: &Store, maybe_round: Option<Duration>) -> Result<Config, Error> {
+    let config = store.config()?;
+    let maybe_rounding = maybe_round.or_else(|| config.maybe_rounding());
+    config.with_maybe_rounding(maybe_rounding)
+}
+
+/// Compute the total time logged across the logs matching the given filters.
+///
+/// If a period is given, logs are bucketed into consecutive calendar periods
+/// spanning the filter range, including periods in which nothing was logged.
+/// Otherwise a single total is reported for the whole range. Running logs are
+/// counted up until the current time. Logs carrying one of the configured break
+/// tags count towards breaks rather than gross time (see [`LogTotals`]).
+pub fn report(store: &Store, params: &Report) -> Result<Vec<ReportPeriod>, Error> {
+    let now = Timestamp::now()?;
+    let config = rounding_config(store, params.maybe_round)?;
+    if params.with_rounding() && config.maybe_rounding().is_none() {
+        return Err(Error::NoRoundingConfigured);
+    }
+    let logs = started_logs(
+        store,
+        params.maybe_project_ids.clone(),
+        &params.start_filter,
+        params.maybe_tags_filter.clone(),
+    )?;
+
+    let (maybe_from, maybe_to) = parse_timestamp_filter(&params.start_filter, &config)?.bounds(now);
+    let maybe_from = maybe_from.or_else(|| logs.iter().filter_map(Log::start).min());
+    let maybe_last_start = logs.iter().filter_map(Log::start).max();
+    let (from, to) = match (maybe_from, maybe_to, maybe_last_start) {
+        (Some(from), Some(to), _) => (from, to),
+        (Some(from), None, Some(last_start)) => (
+            from,
+            match params.maybe_period {
+                Some(period) => period.next(last_start, config.week_start()),
+                None => logs
+                    .iter()
+                    .filter_map(Log::stop)
+                    .max()
+                    .unwrap_or(last_start),
+            },
+        ),
+        _ => {
+            debug!("No logs found and no bounded range for report");
+            return Ok(Vec::new());
+        }
+    };
+
+    let ranges = match params.maybe_period {
+        Some(period) => {
+            let mut ranges = Vec::new();
+            let mut start = period.start_of(from, config.week_start());
+            while start < to {
+                let end = period.next(start, config.week_start());
+                ranges.push((start, end));
+                start = end;
+            }
+            ranges
+        }
+        None => vec![(from, to)],
+    };
+    // Accumulate gross time and breaks per period (and per project), since
+    // only then can net time be computed.
+    let mut totals = vec![(LogTotals::default(), BTreeMap::new()); ranges.len()];
+    for log in logs {
+        let start = log.start().unwrap();
+        let maybe_idx = match params.maybe_period {
+            Some(_) => ranges
+                .iter()
+                .position(|(from, to)| start >= *from && start < *to),
+            None => Some(0),
+        };
+        if let Some((period_totals, by_project)) = maybe_idx.map(|idx| &mut totals[idx]) {
+            period_totals.add(&log, &config, now);
+            if params.by_project || params.maybe_period.is_none() {
+                by_project
+                    .entry(log.project_id().unwrap_or_default().to_string())
+                    .or_insert_with(LogTotals::default)
+                    .add(&log, &config, now);
+            }
+        }
+    }
+    Ok(ranges
+        .into_iter()
+        .zip(totals)
+        .map(|((start, end), (totals, by_project))| {
+            let by_project: BTreeMap<ProjectId, LogTotals> = by_project;
+            ReportPeriod {
+                start,
+                end,
+                total: totals.net(),
+                gross: totals.gross,
+                breaks: totals.breaks,
+                rounded: totals.rounded_net(),
+                rounded_by_project: by_project
+                    .iter()
+                    .map(|(project_id, totals)| (project_id.clone(), totals.rounded_net()))
+                    .collect(),
+                by_project: by_project
+                    .into_iter()
+                    .map(|(project_id, totals)| (project_id, totals.net()))
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+// Parses a timestamp filter, with weekly filters starting on the configured
+// day of the week.
+fn parse_timestamp_filter(s: &str, config: &Config) -> Result<TimestampFilter, Error> {
+    Ok(TimestampFilter::from_str(s)?.with_week_start(config.week_start()))
+}
+
+/// A single work log billed as part of an invoice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceItem {
+    pub log: Log,
+    /// The log's duration in hours.
+    pub hours: f64,
+    /// The amount billed for the log, rounded to two decimal places.
+    pub amount: f64,
+}
+
+/// The line items and total billed for a project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceSummary {
+    pub project_id: ProjectId,
+    pub rate: f64,
+    pub currency: Option<String>,
+    pub items: Vec<InvoiceItem>,
+    /// The total time billed.
+    pub duration: Duration,
+    /// The sum of the line items' amounts.
+    pub total: f64,
+}
+
+/// Compute the amounts billed for each of a project's stopped work logs
+/// matching the given start time filter, at the project's hourly rate.
+///
+/// Running logs and logs marked as breaks aren't billed.
+pub fn invoice(store: &Store, params: &Invoice) -> Result<InvoiceSummary, Error> {
+    let config = store.config()?;
+    let project = store.project(&params.project_id)?;
+    let rate = project
+        .rate()
+        .ok_or_else(|| Error::ProjectHasNoRate(project.id().to_string()))?;
+    let mut logs = started_logs(
+        store,
+        Some(project.id().to_string()),
+        &params.start_filter,
+        None,
+    )?;
+    logs.sort_by_key(Log::start);
+    let items = logs
+        .into_iter()
+        .filter(|log| !config.is_break(log))
+        .filter_map(|log| {
+            let hours = log.duration()?.whole_seconds() as f64 / 3600.0;
+            Some(InvoiceItem {
+                log,
+                hours,
+                amount: round_amount(hours * rate),
+            })
+        })
+        .collect::<Vec<InvoiceItem>>();
+    let duration = items
+        .iter()
+        .filter_map(|item| item.log.duration())
+        .fold(Duration::zero(), |acc, d| acc + d);
+    let total = round_amount(items.iter().map(|item| item.amount).sum());
+    Ok(InvoiceSummary {
+        project_id: project.id().to_string(),
+        rate,
+        currency: project.currency().map(String::from),
+        items,
+        duration,
+        total,
+    })
+}
+
+fn round_amount(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Total the durations of the logs matching the report's filters per tag,
+/// ordered from the largest total to the smallest.
+///
+/// Each log's full duration is added to the total of every one of its tags, so
+/// a log with multiple tags is counted more than once and the totals can add
+/// up to more than the time actually logged. Untagged logs aren't counted.
+pub fn report_by_tag(store: &Store, params: &Report) -> Result<Vec<(String, Duration)>, Error> {
+    let now = Timestamp::now()?;
+    let logs = started_logs(
+        store,
+        params.maybe_project_ids.clone(),
+        &params.start_filter,
+        params.maybe_tags_filter.clone(),
     )?;
-    let task_filter = build_task_filter(
-        params.maybe_task_priority_filter.clone(),
-        params.maybe_task_state_filter.clone(),
-        params.maybe_task_deadline_filter.clone(),
-        params.maybe_task_tags_filter.clone(),
+    let mut totals: BTreeMap<String, Duration> = BTreeMap::new();
+    for log in logs.iter() {
+        let duration = log.effective_duration(now);
+        for tag in log.tags() {
+            *totals.entry(tag.to_string()).or_insert_with(Duration::zero) += duration;
+        }
+    }
+    let mut totals = totals.into_iter().collect::<Vec<(String, Duration)>>();
+    // Ties are kept in alphabetical order by tag
+    totals.sort_by(|(_, a), (_, b)| b.cmp(a));
+    Ok(totals)
+}
+
+// Loads all logs (including running ones) matching the given project, start
+// time and tag filters.
+fn started_logs(
+    store: &Store,
+    maybe_project_ids: Option<String>,
+    start_filter: &str,
+    maybe_tags_filter: Option<String>,
+) -> Result<Vec<Log>, Error> {
+    let config = store.config()?;
+    let project_filter = build_project_filter(maybe_project_ids, None, None, &config)?;
+    let task_filter = FilterSpec::new(TaskFilter::All);
+    let log_filter = build_log_filter(
+        &task_filter,
+        None,
+        Some(parse_timestamp_filter(start_filter, &config)?),
+        None,
+        maybe_tags_filter,
         None,
         None,
     )?;
-    let log_filter = build_log_filter(
-        &task_filter,
-        params.maybe_task_ids_filter.clone(),
-        Some(params.start_filter.clone()),
-        params.maybe_duration_filter.clone(),
+    Ok(store
+        .logs(&project_filter, &task_filter, &log_filter, false)?
+        .into_iter()
+        .filter(|log| log.start().is_some())
+        .collect())
+}
+
+/// Time logged per hour of each day of the week, indexed by the number of
+/// days from Monday and then by the hour of the day.
+pub type Punchcard = [[Duration; 24]; 7];
+
+/// Statistics about the time logged across a set of work logs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogStats {
+    /// The number of (stopped) work logs.
+    pub log_count: usize,
+    /// The total time logged.
+    pub total: Duration,
+    /// The time logged per hour of each day of the week. Only populated if
+    /// requested.
+    #[serde(rename = "punchcard")]
+    pub maybe_punchcard: Option<Punchcard>,
+}
+
+/// Compute statistics about the logs matching the given filters. Running logs
+/// are counted up until the current time.
+///
+/// Logs spanning multiple hours have their time distributed across the hours
+/// they span in the punchcard.
+pub fn stats(store: &Store, params: &Stats) -> Result<LogStats, Error> {
+    let now = Timestamp::now()?;
+    let logs = started_logs(
+        store,
+        params.maybe_project_ids.clone(),
+        &params.start_filter,
         params.maybe_tags_filter.clone(),
     )?;
-
-    let mut logs = store.logs(&project_filter, &task_filter, &log_filter, params.detailed)?;
-    let sort_spec = SortSpec::<LogField>::from_str(&params.sort)?;
-    logs = sort_spec.sort(logs);
-    Ok(logs)
+    let mut total = Duration::zero();
+    let mut punchcard = [[Duration::zero(); 24]; 7];
+    for log in logs.iter() {
+        total += log.effective_duration(now);
+        if params.punchcard {
+            let stop = log.stop().unwrap_or(now);
+            for (start, stop) in split_by_hour(log.start().unwrap(), stop) {
+                punchcard[start.days_from_monday() as usize][start.hour() as usize] += stop - start;
+            }
+        }
+    }
+    Ok(LogStats {
+        log_count: logs.len(),
+        total,
+        maybe_punchcard: if params.punchcard {
+            Some(punchcard)
+        } else {
+            None
+        },
+    })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogStatus {
+    /// The active log, along with its task (if it has one).
     pub log: Log,
     pub active_for: Duration,
     pub maybe_task_description: Option<String>,
+    /// Progress against the estimate of the active log's task, if it has one.
+    #[serde(rename = "estimate_progress")]
+    pub maybe_estimate_progress: Option<EstimateProgress>,
 }
 
-/// Returns the status of the currently active log.
-pub fn active_log_status(store: &Store) -> Result<Option<LogStatus>, Error> {
-    let state = store.state()?;
-    match state.active_log() {
-        Some((project_id, maybe_task_id, log_id)) => {
-            let log = store.log(&project_id, maybe_task_id, log_id)?;
-            let start = log.start().unwrap();
-            let active_for = Timestamp::now()? - start;
-            let maybe_task_description = match maybe_task_id {
-                Some(task_id) => Some(
-                    store
-                        .task(&project_id, task_id, false)?
-                        .description()
-                        .to_string(),
-                ),
-                None => None,
-            };
-            Ok(Some(LogStatus {
-                log,
-                active_for,
-                maybe_task_description,
-            }))
+/// Progress against a task's time estimate.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EstimateProgress {
+    /// The total time logged against the task, including any running log.
+    pub logged: Duration,
+    pub estimate: Duration,
+}
+
+impl EstimateProgress {
+    /// How much of the estimate is left (zero if the estimate has been
+    /// exceeded).
+    pub fn remaining(&self) -> Duration {
+        if self.logged < self.estimate {
+            self.estimate - self.logged
+        } else {
+            Duration::zero()
+        }
+    }
+
+    /// By how much the estimate has been exceeded, if at all.
+    pub fn overrun(&self) -> Option<Duration> {
+        if self.logged > self.estimate {
+            Some(self.logged - self.estimate)
+        } else {
+            None
         }
-        None => Ok(None),
     }
 }
 
+/// Returns all tags used across the store, along with the number of projects,
+/// tasks and logs using each of them.
+pub fn tags(store: &Store) -> Result<BTreeMap<String, usize>, Error> {
+    store.tag_counts()
+}
+
+/// Returns the status of the most recently started active log, if any.
+pub fn active_log_status(store: &Store) -> Result<Option<LogStatus>, Error> {
+    store
+        .checked_state()?
+        .active_log()
+        .map(|active_log| log_status(store, active_log))
+        .transpose()
+}
+
+/// Returns the statuses of all of the active logs, in the order in which they
+/// were started.
+pub fn active_log_statuses(store: &Store) -> Result<Vec<LogStatus>, Error> {
+    store
+        .checked_state()?
+        .active_logs()
+        .iter()
+        .map(|active_log| log_status(store, active_log.clone()))
+        .collect()
+}
+
+fn log_status(store: &Store, active_log: ActiveLog) -> Result<LogStatus, Error> {
+    let (project_id, maybe_task_id, log_id) = active_log;
+    let log = store.log(&project_id, maybe_task_id, log_id)?;
+    let start = log.start().unwrap();
+    let active_for = Timestamp::now()? - start;
+    let maybe_task = match maybe_task_id {
+        Some(task_id) => Some(
+            store
+                .task(&project_id, task_id, true)
+                .map_err(|e| match e {
+                    Error::TaskNotFound(_, _) => {
+                        Error::ActiveLogTaskNotFound(project_id.clone(), task_id, log_id)
+                    }
+                    e => e,
+                })?,
+        ),
+        None => None,
+    };
+    let maybe_estimate_progress = maybe_task.as_ref().and_then(|task| {
+        task.estimate().map(|estimate| EstimateProgress {
+            // Task stats only include stopped logs
+            logged: task
+                .stats()
+                .map(|stats| stats.time_logged)
+                .unwrap_or_else(Duration::zero)
+                + active_for,
+            estimate,
+        })
+    });
+    Ok(LogStatus {
+        log: log.with_maybe_task(maybe_task.clone()),
+        active_for,
+        maybe_task_description: maybe_task.map(|task| task.description().to_string()),
+        maybe_estimate_progress,
+    })
+}
+
 /// Shows a list of task states. If no project is supplied, the default
 /// configuration will be shown.
 pub fn task_states(store: &Store, params: &TaskStates) -> Result<Vec<TaskState>, Error> {
@@ -942,6 +3552,1040 @@ pub fn task_states(store: &Store, params: &TaskStates) -> Result<Vec<TaskState>,
     Ok(states)
 }
 
+/// Returns the states that tasks (optionally of a specific project) are
+/// actually in, along with the number of tasks in each state.
+pub fn states_in_use(
+    store: &Store,
+    params: &TaskStates,
+) -> Result<BTreeMap<TaskState, usize>, Error> {
+    let project_filter = match &params.maybe_project_id {
+        Some(id) => FilterSpec::new(ProjectFilter::Ids(vec![store
+            .project(id)?
+            .id()
+            .to_string()])),
+        None => FilterSpec::new(ProjectFilter::All),
+    };
+    store.states_in_use(&project_filter)
+}
+
+/// Configure the default task states and save them to the store's
+/// configuration.
+///
+/// In wizard mode, the user is prompted (via `output`) for each setting, and
+/// their answers are read from `input`. Empty answers accept the suggested
+/// default.
+pub fn config_states<R, W>(
+    store: &Store,
+    params: &ConfigStates,
+    mut input: R,
+    mut output: W,
+) -> Result<TaskStateConfig, Error>
+where
+    R: std::io::BufRead,
+    W: std::io::Write,
+{
+    let config = store.config()?;
+    let current = config.task_state_config();
+    let task_state_config = if params.wizard {
+        let states = prompt(
+            &mut input,
+            &mut output,
+            "Task states, in order (comma-separated)",
+            &current.states().collect::<Vec<&str>>().join(","),
+        )?;
+        let states = parse_comma_separated(Some(states));
+        // Suggest the current states for each role if they're still valid
+        let suggest = |current: &str, fallback: Option<&String>| {
+            if states.iter().any(|state| state == current) {
+                current.to_string()
+            } else {
+                fallback.cloned().unwrap_or_default()
+            }
+        };
+        let initial = suggest(current.initial(), states.first());
+        let initial = prompt(&mut input, &mut output, "Initial state", &initial)?;
+        let in_progress = suggest(current.in_progress(), states.get(1));
+        let in_progress = prompt(&mut input, &mut output, "In-progress state", &in_progress)?;
+        let done = suggest(current.done(), states.last());
+        let done = prompt(&mut input, &mut output, "Done state", &done)?;
+        let terminal = current
+            .terminal_states()
+            .filter(|state| states.iter().any(|s| s == state))
+            .collect::<Vec<&str>>()
+            .join(",");
+        let terminal = prompt(
+            &mut input,
+            &mut output,
+            "Other terminal states (comma-separated)",
+            &terminal,
+        )?;
+        TaskStateConfig::new(states, initial, in_progress, done)?.with_terminal_states(
+            parse_comma_separated(Some(terminal).filter(|terminal| !terminal.is_empty())),
+        )?
+    } else {
+        let states = parse_comma_separated(params.maybe_states.clone());
+        let role = |maybe_state: &Option<TaskState>, fallback: Option<&String>| {
+            maybe_state
+                .clone()
+                .or_else(|| fallback.cloned())
+                .unwrap_or_default()
+        };
+        TaskStateConfig::new(
+            states.clone(),
+            role(&params.maybe_initial, states.first()),
+            role(&params.maybe_in_progress, states.get(1)),
+            role(&params.maybe_done, states.last()),
+        )?
+        .with_terminal_states(parse_comma_separated(params.maybe_terminal_states.clone()))?
+    };
+    store.save_config(&config.with_task_state_config(&task_state_config))?;
+    record_audit(
+        store,
+        AuditEntry::new(AuditOperation::ConfigStates, Timestamp::now()?),
+    )?;
+    debug!("Saved task state configuration: {:?}", task_state_config);
+    Ok(task_state_config)
+}
+
+/// Set (or clear) the configured rounding increment, returning the updated
+/// configuration.
+pub fn config_rounding(store: &Store, params: &ConfigRounding) -> Result<Config, Error> {
+    let config = store
+        .config()?
+        .with_maybe_rounding(params.maybe_increment)?;
+    store.save_config(&config)?;
+    record_audit(
+        store,
+        AuditEntry::new(AuditOperation::ConfigRounding, Timestamp::now()?),
+    )?;
+    debug!("Saved rounding increment: {:?}", config.maybe_rounding());
+    Ok(config)
+}
+
+/// Configure the day on which weeks start, returning the updated
+/// configuration.
+pub fn config_week_start(store: &Store, params: &ConfigWeekStart) -> Result<Config, Error> {
+    let config = store.config()?.with_week_start(params.week_start);
+    store.save_config(&config)?;
+    record_audit(
+        store,
+        AuditEntry::new(AuditOperation::ConfigWeekStart, Timestamp::now()?),
+    )?;
+    debug!("Saved week start: {}", config.week_start());
+    Ok(config)
+}
+
+/// Configure how durations are rendered in JSON output and exports, returning
+/// the updated configuration.
+pub fn config_duration_format(
+    store: &Store,
+    params: &ConfigDurationFormat,
+) -> Result<Config, Error> {
+    let config = store.config()?.with_duration_format(params.duration_format);
+    store.save_config(&config)?;
+    record_audit(
+        store,
+        AuditEntry::new(AuditOperation::ConfigDurationFormat, Timestamp::now()?),
+    )?;
+    debug!("Saved duration format: {}", config.duration_format());
+    Ok(config)
+}
+
+/// Configure how far beyond the current time work logs' stop times may be,
+/// returning the updated configuration.
+pub fn config_stop_tolerance(store: &Store, params: &ConfigStopTolerance) -> Result<Config, Error> {
+    let config = store.config()?.with_stop_tolerance(params.tolerance)?;
+    store.save_config(&config)?;
+    record_audit(
+        store,
+        AuditEntry::new(AuditOperation::ConfigStopTolerance, Timestamp::now()?),
+    )?;
+    debug!("Saved stop time tolerance: {}", config.stop_tolerance());
+    Ok(config)
+}
+
+/// Replace the configured break tags, returning the updated configuration.
+pub fn config_break_tags(store: &Store, params: &ConfigBreakTags) -> Result<Config, Error> {
+    let config = store.config()?.with_break_tags(parse_comma_separated(
+        Some(params.tags.clone()).filter(|tags| !tags.trim().is_empty()),
+    ))?;
+    store.save_config(&config)?;
+    record_audit(
+        store,
+        AuditEntry::new(AuditOperation::ConfigBreakTags, Timestamp::now()?),
+    )?;
+    debug!(
+        "Saved break tags: {:?}",
+        config.break_tags().collect::<Vec<&str>>()
+    );
+    Ok(config)
+}
+
+// Asks the given question, returning the trimmed answer, or the default if the
+// answer is empty.
+fn prompt<R, W>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: &str,
+) -> Result<String, Error>
+where
+    R: std::io::BufRead,
+    W: std::io::Write,
+{
+    write!(output, "{} [{}]: ", question, default)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer.to_string()
+    })
+}
+
+/// The outcome of applying a single patch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchOutcome {
+    /// A short description of the object(s) targeted by the patch.
+    pub target: String,
+    /// The reason the patch failed, if it did.
+    #[serde(rename = "error")]
+    pub maybe_error: Option<String>,
+}
+
+/// Apply the JSON array of patches read from the given reader, each through
+/// the usual validation and saving logic.
+///
+/// A failing patch does not prevent the remaining patches from being applied
+/// unless strict mode is enabled, in which case the first failure is returned
+/// as an error (patches applied prior to it remain applied).
+pub fn patch<R: std::io::Read>(
+    store: &Store,
+    params: &Patch,
+    mut reader: R,
+) -> Result<Vec<PatchOutcome>, Error> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let patches: Vec<ObjectPatch> =
+        serde_json::from_str(&input).map_err(|e| Error::Serialize(e, input.clone()))?;
+    let mut outcomes = Vec::new();
+    for patch in patches.iter() {
+        let maybe_error = match patch.apply(store) {
+            Ok(_) => None,
+            Err(e) if params.strict => return Err(e),
+            Err(e) => Some(e.to_string()),
+        };
+        debug!(
+            "Applied patch to {}: {}",
+            patch.target(),
+            maybe_error.as_deref().unwrap_or("ok")
+        );
+        outcomes.push(PatchOutcome {
+            target: patch.target(),
+            maybe_error,
+        });
+    }
+    Ok(outcomes)
+}
+
+/// The results of checking the store for problems.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CheckReport {
+    /// Work log files found beneath directories without project metadata.
+    pub orphaned_logs: Vec<PathBuf>,
+    /// Groups of task directories or work log files whose names parse to the
+    /// same ID.
+    pub id_collisions: Vec<Vec<PathBuf>>,
+    /// The IDs of placeholder projects created to recover orphaned logs.
+    pub recovered_projects: Vec<ProjectId>,
+}
+
+/// Check the store for problems, optionally attempting to recover from them.
+pub fn check(store: &Store, params: &Check) -> Result<CheckReport, Error> {
+    let orphaned_logs = store.orphaned_logs()?;
+    let mut recovered_projects = Vec::new();
+    if params.recover {
+        let store_path = store.path();
+        for log_path in orphaned_logs.iter() {
+            let project_id = match log_path
+                .strip_prefix(&store_path)
+                .ok()
+                .and_then(|p| p.components().next())
+                .and_then(|c| c.as_os_str().to_str())
+            {
+                Some(project_id) => project_id.to_string(),
+                None => continue,
+            };
+            if recovered_projects.contains(&project_id) {
+                continue;
+            }
+            if slugify(&project_id) != project_id {
+                debug!(
+                    "Cannot create placeholder project for directory \"{}\" - not a valid project ID",
+                    project_id
+                );
+                continue;
+            }
+            store.save_project(&Project::new(&project_id))?;
+            record_audit(
+                store,
+                project_audit_entry(AuditOperation::AddProject, &project_id)?,
+            )?;
+            debug!("Created placeholder project {}", project_id);
+            recovered_projects.push(project_id);
+        }
+    }
+    Ok(CheckReport {
+        orphaned_logs,
+        id_collisions: store.id_collisions()?,
+        recovered_projects,
+    })
+}
+
+/// The results of verifying the store.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyReport {
+    /// The changes made while repairing inconsistencies.
+    pub repairs: Vec<Repair>,
+    /// The inconsistencies found (after any repairs).
+    pub inconsistencies: Vec<Inconsistency>,
+}
+
+/// Verify the store's integrity, optionally repairing the inconsistencies
+/// found where possible.
+pub fn verify(store: &Store, params: &Verify) -> Result<VerifyReport, Error> {
+    if !params.fix {
+        return Ok(VerifyReport {
+            repairs: Vec::new(),
+            inconsistencies: store.verify()?,
+        });
+    }
+    let _lock = store.lock()?;
+    let mut repairs = Vec::new();
+    for inconsistency in store.verify()? {
+        repairs.extend(store.repair(&inconsistency)?);
+    }
+    if !repairs.is_empty() {
+        record_audit(
+            store,
+            AuditEntry::new(AuditOperation::RepairStore, Timestamp::now()?),
+        )?;
+    }
+    Ok(VerifyReport {
+        repairs,
+        inconsistencies: store.verify()?,
+    })
+}
+
+/// The number of objects exported to an SQLite database.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub projects: usize,
+    pub tasks: usize,
+    pub logs: usize,
+}
+
+/// Export the logs matching the given filters as CSV, either to the configured
+/// output file or, if none is configured, to the given writer (e.g. standard
+/// output). Returns the number of logs exported.
+pub fn export_logs<W: std::io::Write>(
+    store: &Store,
+    params: &ExportLogs,
+    output: W,
+) -> Result<usize, Error> {
+    check_output_file(params.maybe_output.as_ref())?;
+    let logs = list_logs(store, &params.filters)?;
+    write_export(params.maybe_output.as_ref(), output, |w| {
+        write_logs_csv(&logs, w)
+    })?;
+    debug!("Exported {} log(s) as CSV", logs.len());
+    Ok(logs.len())
+}
+
+/// Export the logs matching the given filters as a Markdown document, with a
+/// heading, list of logs and total per project, either to the configured
+/// output file or, if none is configured, to the given writer (e.g. standard
+/// output). Returns the number of logs exported.
+pub fn export_markdown<W: std::io::Write>(
+    store: &Store,
+    params: &ExportMarkdown,
+    output: W,
+) -> Result<usize, Error> {
+    check_output_file(params.maybe_output.as_ref())?;
+    let logs = list_logs(store, &params.filters)?;
+    let log_count = logs.len();
+    let groups = logs_by_project(logs, &store.config()?, Timestamp::now()?);
+    let projects = groups
+        .iter()
+        .map(|group| store.project(&group.project_id))
+        .collect::<Result<Vec<Project>, Error>>()?;
+    write_export(params.maybe_output.as_ref(), output, |w| {
+        write_logs_markdown(&groups, &projects, w)
+    })?;
+    debug!("Exported {} log(s) as Markdown", log_count);
+    Ok(log_count)
+}
+
+/// Export the logs matching the given filters as an iCalendar document with
+/// one event per log, either to the configured output file or, if none is
+/// configured, to the given writer (e.g. standard output). Returns the number
+/// of logs exported.
+pub fn export_ical<W: std::io::Write>(
+    store: &Store,
+    params: &ExportIcal,
+    output: W,
+) -> Result<usize, Error> {
+    check_output_file(params.maybe_output.as_ref())?;
+    let (logs, skipped): (Vec<Log>, Vec<Log>) = list_logs(store, &params.filters)?
+        .into_iter()
+        .partition(|log| log.start().is_some() && log.duration().is_some());
+    for log in skipped.iter() {
+        warn!(
+            "Skipping log {} for {}{} without a start time or duration",
+            log.id().unwrap_or_default(),
+            log.project_id().unwrap_or_default(),
+            log.task_id()
+                .map(|task_id| format!(", task {}", task_id))
+                .unwrap_or_default(),
+        );
+    }
+    let now = Timestamp::now()?;
+    write_export(params.maybe_output.as_ref(), output, |w| {
+        write_logs_ical(&logs, now, w)
+    })?;
+    debug!("Exported {} log(s) as iCalendar events", logs.len());
+    Ok(logs.len())
+}
+
+// Writes the given (started and stopped) logs as iCalendar events, stamped
+// with the given time.
+fn write_logs_ical(logs: &[Log], now: Timestamp, w: &mut dyn std::io::Write) -> Result<(), Error> {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Loiter//Loiter//EN".to_string(),
+    ];
+    for log in logs {
+        let project_id = log.project_id().unwrap_or_default();
+        let mut summary = project_id.to_string();
+        if let Some(task_id) = log.task_id() {
+            summary.push_str(&format!(" (task {})", task_id));
+        }
+        if let Some(comment) = log.comment() {
+            summary.push_str(&format!(": {}", comment));
+        }
+        let mut tags = log.tags().map(ical_text).collect::<Vec<String>>();
+        tags.sort_unstable();
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!(
+                "UID:{}-{}-{}@loiter",
+                project_id,
+                log.task_id().map(|id| id.to_string()).unwrap_or_default(),
+                log.id().unwrap_or_default()
+            ),
+            format!("DTSTAMP:{}", now.to_ical()),
+            format!("DTSTART:{}", log.start().unwrap().to_ical()),
+            format!("DTEND:{}", log.stop().unwrap().to_ical()),
+            format!("SUMMARY:{}", ical_text(&summary)),
+        ]);
+        if !tags.is_empty() {
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    for line in lines {
+        write!(w, "{}", ical_line(&line))?;
+    }
+    Ok(())
+}
+
+// Exports must never overwrite existing files.
+fn check_output_file(maybe_output: Option<&PathBuf>) -> Result<(), Error> {
+    match maybe_output {
+        Some(path) if path.exists() => Err(Error::OutputFileAlreadyExists(path.clone())),
+        _ => Ok(()),
+    }
+}
+
+// Writes an export either to a new file at the given path or, if there's no
+// path, to the given writer.
+fn write_export<W, F>(maybe_output: Option<&PathBuf>, mut output: W, write: F) -> Result<(), Error>
+where
+    W: std::io::Write,
+    F: FnOnce(&mut dyn std::io::Write) -> Result<(), Error>,
+{
+    match maybe_output {
+        Some(path) => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            write(&mut file)?;
+            std::io::Write::flush(&mut file)?;
+        }
+        None => write(&mut output)?,
+    }
+    Ok(())
+}
+
+// Writes the given groups of logs as Markdown, with a heading per project.
+fn write_logs_markdown(
+    groups: &[ProjectLogs],
+    projects: &[Project],
+    w: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    for (i, (group, project)) in groups.iter().zip(projects).enumerate() {
+        if i > 0 {
+            writeln!(w)?;
+        }
+        writeln!(w, "## {}", project.name())?;
+        writeln!(w)?;
+        for log in group.logs.iter() {
+            writeln!(
+                w,
+                "- {} ({}){}{}",
+                log.start()
+                    .map(|start| start.to_string())
+                    .unwrap_or_default(),
+                log.duration()
+                    .map(|duration| duration.to_string())
+                    .unwrap_or_else(|| "running".to_string()),
+                log.task_id()
+                    .map(|task_id| format!(", task {}", task_id))
+                    .unwrap_or_default(),
+                log.comment()
+                    .map(|comment| format!(": {}", comment))
+                    .unwrap_or_default(),
+            )?;
+        }
+        writeln!(w)?;
+        writeln!(w, "**Total: {}**", group.totals.net())?;
+    }
+    Ok(())
+}
+
+// Writes the given logs, one per row, as CSV to the given writer.
+fn write_logs_csv(logs: &[Log], w: &mut dyn std::io::Write) -> Result<(), Error> {
+    writeln!(
+        w,
+        "project_id,task_id,log_id,start,stop,duration_seconds,comment,tags"
+    )?;
+    for log in logs {
+        let mut tags = log.tags().collect::<Vec<&str>>();
+        tags.sort_unstable();
+        let fields = [
+            log.project_id().unwrap_or_default().to_string(),
+            log.task_id().map(|id| id.to_string()).unwrap_or_default(),
+            log.id().map(|id| id.to_string()).unwrap_or_default(),
+            log.start().map(|t| t.to_rfc3339()).unwrap_or_default(),
+            log.stop().map(|t| t.to_rfc3339()).unwrap_or_default(),
+            log.duration()
+                .map(|d| d.whole_seconds().to_string())
+                .unwrap_or_default(),
+            log.comment().unwrap_or_default().to_string(),
+            tags.join(","),
+        ];
+        writeln!(
+            w,
+            "{}",
+            fields
+                .iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<String>>()
+                .join(",")
+        )?;
+    }
+    Ok(())
+}
+
+const SQLITE_SCHEMA: &str = r#"
+CREATE TABLE projects (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    deadline TEXT,
+    tags TEXT NOT NULL
+);
+CREATE TABLE tasks (
+    project_id TEXT NOT NULL REFERENCES projects(id),
+    id INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    state TEXT,
+    priority INTEGER NOT NULL,
+    deadline TEXT,
+    estimate INTEGER,
+    tags TEXT NOT NULL,
+    PRIMARY KEY (project_id, id)
+);
+CREATE TABLE logs (
+    project_id TEXT NOT NULL REFERENCES projects(id),
+    task_id INTEGER,
+    id INTEGER NOT NULL,
+    start TEXT,
+    stop TEXT,
+    duration INTEGER,
+    comment TEXT,
+    tags TEXT NOT NULL,
+    FOREIGN KEY (project_id, task_id) REFERENCES tasks(project_id, id)
+);
+-- NULLs are distinct in unique constraints, so project-level logs (without a
+-- task) need their own index.
+CREATE UNIQUE INDEX task_log_ids ON logs (project_id, task_id, id) WHERE task_id IS NOT NULL;
+CREATE UNIQUE INDEX project_log_ids ON logs (project_id, id) WHERE task_id IS NULL;
+"#;
+
+/// Export all projects, tasks and work logs in the store to a new SQLite
+/// database.
+///
+/// Durations are stored as whole seconds, timestamps as RFC 3339 text and tags
+/// as comma-separated text.
+pub fn export_sqlite(store: &Store, params: &ExportSqlite) -> Result<ExportSummary, Error> {
+    if params.output.exists() {
+        return Err(Error::OutputFileAlreadyExists(params.output.clone()));
+    }
+    let projects = store.projects(&FilterSpec::new(ProjectFilter::All))?;
+    let tasks = store.tasks(
+        &FilterSpec::new(ProjectFilter::All),
+        &FilterSpec::new(TaskFilter::All),
+        false,
+    )?;
+    let logs = store.logs(
+        &FilterSpec::new(ProjectFilter::All),
+        &FilterSpec::new(TaskFilter::All),
+        &FilterSpec::new(LogFilter::All),
+        false,
+    )?;
+
+    // The database is written to a temporary file alongside the output, which
+    // only becomes the output once the export has succeeded, so that a failed
+    // export never leaves a partial database behind.
+    let mut tmp_file_name = params
+        .output
+        .file_name()
+        .ok_or_else(|| Error::InvalidPath(params.output.clone()))?
+        .to_os_string();
+    tmp_file_name.push(".tmp");
+    let tmp_path = params.output.with_file_name(tmp_file_name);
+    if is_file(&tmp_path) {
+        std::fs::remove_file(&tmp_path)?;
+    }
+    let result = write_sqlite_export(&tmp_path, &projects, &tasks, &logs)
+        .and_then(|()| Ok(std::fs::rename(&tmp_path, &params.output)?));
+    if result.is_err() && is_file(&tmp_path) {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result?;
+    debug!(
+        "Exported {} project(s), {} task(s) and {} work log(s) to {}",
+        projects.len(),
+        tasks.len(),
+        logs.len(),
+        params.output.display()
+    );
+    Ok(ExportSummary {
+        projects: projects.len(),
+        tasks: tasks.len(),
+        logs: logs.len(),
+    })
+}
+
+// Writes the given projects, tasks and work logs to a new SQLite database at
+// the given path.
+fn write_sqlite_export(
+    path: &Path,
+    projects: &[Project],
+    tasks: &[Task],
+    logs: &[Log],
+) -> Result<(), Error> {
+    let mut conn = rusqlite::Connection::open(path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SQLITE_SCHEMA)?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO projects (id, name, description, deadline, tags) VALUES (?, ?, ?, ?, ?)",
+        )?;
+        for project in projects.iter() {
+            stmt.execute(rusqlite::params![
+                project.id(),
+                project.name(),
+                project.description(),
+                project.deadline().map(|d| d.to_rfc3339()),
+                project.tags().collect::<Vec<&str>>().join(","),
+            ])?;
+        }
+        let mut stmt = tx.prepare(
+            "INSERT INTO tasks (project_id, id, description, state, priority, deadline, estimate, tags) \
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for task in tasks.iter() {
+            stmt.execute(rusqlite::params![
+                task.project_id(),
+                task.id(),
+                task.description(),
+                task.state(),
+                task.priority(),
+                task.deadline().map(|d| d.to_rfc3339()),
+                task.estimate().map(|d| d.whole_seconds()),
+                task.tags().collect::<Vec<&str>>().join(","),
+            ])?;
+        }
+        let mut stmt = tx.prepare(
+            "INSERT INTO logs (project_id, task_id, id, start, stop, duration, comment, tags) \
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for log in logs.iter() {
+            stmt.execute(rusqlite::params![
+                log.project_id(),
+                log.task_id(),
+                log.id(),
+                log.start().map(|t| t.to_rfc3339()),
+                log.stop().map(|t| t.to_rfc3339()),
+                log.duration().map(|d| d.whole_seconds()),
+                log.comment(),
+                log.tags().collect::<Vec<&str>>().join(","),
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Export all projects in the store, each along with its tasks and work logs,
+/// as a JSON array of [`ProjectExport`]s, either to the configured output file
+/// or, if none is configured, to the given writer (e.g. standard output).
+pub fn export_json<W: std::io::Write>(
+    store: &Store,
+    params: &ExportJson,
+    output: W,
+) -> Result<ExportSummary, Error> {
+    check_output_file(params.maybe_output.as_ref())?;
+    let duration_format = store.config()?.duration_format();
+    let mut summary = ExportSummary::default();
+    let exports = store
+        .projects(&FilterSpec::new(ProjectFilter::All))?
+        .iter()
+        .map(|project| {
+            let tasks =
+                store.project_tasks(project.id(), &FilterSpec::new(TaskFilter::All), false)?;
+            let logs = store.logs(
+                &FilterSpec::new(ProjectFilter::Ids(vec![project.id().to_string()])),
+                &FilterSpec::new(TaskFilter::All),
+                &FilterSpec::new(LogFilter::All),
+                false,
+            )?;
+            summary.projects += 1;
+            summary.tasks += tasks.len();
+            summary.logs += logs.len();
+            Ok(ProjectExport {
+                project: ProjectView::from(project),
+                tasks: tasks.iter().map(TaskView::from).collect(),
+                logs: logs
+                    .iter()
+                    .map(|log| LogView::new(log, duration_format))
+                    .collect(),
+            })
+        })
+        .collect::<Result<Vec<ProjectExport>, Error>>()?;
+    write_export(params.maybe_output.as_ref(), output, |w| {
+        serde_json::to_writer_pretty(&mut *w, &exports).map_err(std::io::Error::from)?;
+        writeln!(w)?;
+        Ok(())
+    })?;
+    debug!(
+        "Exported {} project(s), {} task(s) and {} work log(s) as JSON",
+        summary.projects, summary.tasks, summary.logs
+    );
+    Ok(summary)
+}
+
+/// Something that couldn't be imported exactly as it was exported, because it
+/// clashed with what's already in the store.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ImportConflict {
+    /// A project with the same ID already exists. Its details are left as they
+    /// are, but the imported tasks and work logs are still added to it.
+    ProjectExists(ProjectId),
+    /// A task with the same ID already exists in the project, so the imported
+    /// task (with the old ID) was given a new ID.
+    TaskRenumbered(ProjectId, TaskId, TaskId),
+    /// A work log with the same ID already exists, so the imported work log
+    /// (with the old ID) was given a new ID.
+    LogRenumbered(ProjectId, Option<TaskId>, LogId, LogId),
+}
+
+impl std::fmt::Display for ImportConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ProjectExists(project_id) => write!(
+                f,
+                "project \"{}\" already exists - kept its existing details",
+                project_id
+            ),
+            Self::TaskRenumbered(project_id, old_id, new_id) => write!(
+                f,
+                "task {} of project \"{}\" already exists - imported as task {}",
+                old_id, project_id, new_id
+            ),
+            Self::LogRenumbered(project_id, maybe_task_id, old_id, new_id) => write!(
+                f,
+                "work log {} of project \"{}\"{} already exists - imported as log {}",
+                old_id,
+                project_id,
+                maybe_task_id
+                    .map(|task_id| format!(", task {},", task_id))
+                    .unwrap_or_default(),
+                new_id
+            ),
+        }
+    }
+}
+
+/// The results of importing projects, tasks and work logs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub projects: usize,
+    pub tasks: usize,
+    pub logs: usize,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+/// Import the projects, tasks and work logs in the given JSON document (as
+/// created by [`export_json`]) into the store.
+///
+/// IDs are preserved where possible. Tasks and work logs whose IDs are
+/// already taken are given new ones, and existing projects are never
+/// overwritten. All such conflicts are reported in the returned summary.
+pub fn import(store: &Store, params: &Import) -> Result<ImportSummary, Error> {
+    let _lock = store.lock()?;
+    let file = std::io::BufReader::new(std::fs::File::open(&params.path)?);
+    let exports: Vec<ProjectExport> =
+        serde_json::from_reader(file).map_err(|e| Error::InvalidImport(params.path.clone(), e))?;
+    let mut summary = ImportSummary::default();
+    for export in exports {
+        let project = Project::from(export.project);
+        let project_id = project.id().to_string();
+        match store.create_project(&project) {
+            Ok(()) => summary.projects += 1,
+            Err(Error::ProjectAlreadyExists(_)) => {
+                summary
+                    .conflicts
+                    .push(ImportConflict::ProjectExists(project_id.clone()));
+            }
+            Err(e) => return Err(e),
+        }
+
+        // Imported task IDs mapped to the IDs with which they were saved
+        let mut task_ids: BTreeMap<TaskId, TaskId> = BTreeMap::new();
+        let mut tasks = Vec::new();
+        for mut view in export.tasks {
+            view.project_id = Some(project_id.clone());
+            let maybe_old_id = view.id;
+            if let Some(old_id) = maybe_old_id {
+                match store.task(&project_id, old_id, false) {
+                    Err(Error::TaskNotFound(_, _)) => (),
+                    _ => view.id = None,
+                }
+            }
+            let task = store.save_task(&Task::from(view))?;
+            let new_id = task.id().unwrap();
+            if let Some(old_id) = maybe_old_id {
+                if old_id != new_id {
+                    summary.conflicts.push(ImportConflict::TaskRenumbered(
+                        project_id.clone(),
+                        old_id,
+                        new_id,
+                    ));
+                }
+                task_ids.insert(old_id, new_id);
+            }
+            tasks.push(task);
+            summary.tasks += 1;
+        }
+        // Blockers may have been renumbered after the tasks they block were
+        // saved, so they can only be remapped once all tasks are saved.
+        for task in tasks {
+            let blocked_by = task
+                .blocked_by()
+                .map(|task_id| task_ids.get(&task_id).copied().unwrap_or(task_id))
+                .collect::<BTreeSet<TaskId>>();
+            if !blocked_by.iter().copied().eq(task.blocked_by()) {
+                store.save_task(&task.with_blocked_by(blocked_by))?;
+            }
+        }
+
+        for mut view in export.logs {
+            view.project_id = Some(project_id.clone());
+            view.task_id = view
+                .task_id
+                .map(|task_id| task_ids.get(&task_id).copied().unwrap_or(task_id));
+            let maybe_old_id = view.id;
+            let log = match store.create_log(&Log::from(view.clone())) {
+                Err(Error::LogAlreadyExists(_, _, _)) => {
+                    view.id = None;
+                    store.create_log(&Log::from(view))?
+                }
+                result => result?,
+            };
+            if let Some(old_id) = maybe_old_id {
+                let new_id = log.id().unwrap();
+                if old_id != new_id {
+                    summary.conflicts.push(ImportConflict::LogRenumbered(
+                        project_id.clone(),
+                        log.task_id(),
+                        old_id,
+                        new_id,
+                    ));
+                }
+            }
+            summary.logs += 1;
+        }
+    }
+    record_audit(
+        store,
+        AuditEntry::new(AuditOperation::Import, Timestamp::now()?),
+    )?;
+    debug!(
+        "Imported {} project(s), {} task(s) and {} work log(s) from {} ({} conflict(s))",
+        summary.projects,
+        summary.tasks,
+        summary.logs,
+        params.path.display(),
+        summary.conflicts.len()
+    );
+    Ok(summary)
+}
+
+/// Back up the entire store to a new gzipped tarball. Returns the paths
+/// (relative to the root of the store) of the files that were backed up.
+pub fn backup(store: &Store, params: &Backup) -> Result<Vec<PathBuf>, Error> {
+    if params.output.exists() {
+        return Err(Error::OutputFileAlreadyExists(params.output.clone()));
+    }
+    // Otherwise the backup would include itself (and any earlier backups)
+    let output_dir = match params.output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    if output_dir
+        .canonicalize()?
+        .starts_with(store.path().canonicalize()?)
+    {
+        return Err(Error::BackupInsideStore(params.output.clone()));
+    }
+    let file = std::fs::File::create(&params.output)?;
+    store.backup(file).inspect_err(|_| {
+        // Don't leave a partial backup lying around
+        let _ = std::fs::remove_file(&params.output);
+    })
+}
+
+/// Restore a backup into the given target path (or the store's path if none
+/// is given), which must be empty. Returns the path into which the backup was
+/// restored.
+pub fn restore(store: &Store, params: &Restore) -> Result<PathBuf, Error> {
+    let target = params.maybe_target.clone().unwrap_or_else(|| store.path());
+    let file = std::fs::File::open(&params.input)?;
+    Store::restore(file, &target)?;
+    Ok(target)
+}
+
+/// Load the entries in the store's audit trail matching the given filters,
+/// from oldest to newest.
+pub fn audit(store: &Store, params: &Audit) -> Result<Vec<AuditEntry>, Error> {
+    let now = Timestamp::now()?;
+    let week_start = store.config()?.week_start();
+    let maybe_ts_filter = params
+        .maybe_when
+        .as_ref()
+        .map(|when| {
+            TimestampFilter::parse(when, now).map(|filter| filter.with_week_start(week_start))
+        })
+        .transpose()?;
+    let mut entries = store
+        .audit_entries()?
+        .into_iter()
+        .filter(|entry| {
+            params
+                .maybe_operation
+                .map(|op| op == entry.operation())
+                .unwrap_or(true)
+                && params
+                    .maybe_project_id
+                    .as_ref()
+                    .map(|id| Some(id.as_str()) == entry.project_id())
+                    .unwrap_or(true)
+                && maybe_ts_filter
+                    .map(|ts_filter| ts_filter.matches(now, entry.timestamp()))
+                    .unwrap_or(true)
+        })
+        .collect::<Vec<AuditEntry>>();
+    if let Some(limit) = params.maybe_limit {
+        entries = entries.split_off(entries.len().saturating_sub(limit));
+    }
+    Ok(entries)
+}
+
+/// Something with which the user can edit a file, e.g. an external text
+/// editor.
+pub trait FileEditor {
+    /// Edit the file at the given path, returning once editing is done.
+    fn edit(&self, path: &Path) -> Result<(), Error>;
+}
+
+/// The user's preferred text editor, as given by the `VISUAL` or `EDITOR`
+/// environment variables (falling back to `vi`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExternalEditor;
+
+impl FileEditor for ExternalEditor {
+    fn edit(&self, path: &Path) -> Result<(), Error> {
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+        // The editor may include arguments (e.g. "code --wait"), so it is run
+        // by way of the shell, like Git does.
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg(&editor)
+            .arg(path)
+            .status()?;
+        if !status.success() {
+            return Err(Error::EditorFailed(editor, status));
+        }
+        Ok(())
+    }
+}
+
+/// Capture a (possibly multi-line) comment with the given editor, starting
+/// from the given initial text. Returns `None` if the resulting comment is
+/// blank.
+pub fn edit_comment<E: FileEditor>(
+    editor: &E,
+    maybe_initial: Option<&str>,
+) -> Result<Option<String>, Error> {
+    // The file gets a unique name, is only readable by the current user and
+    // is removed when dropped, whether or not editing succeeds.
+    let mut file = tempfile::Builder::new()
+        .prefix("loiter-comment-")
+        .suffix(".txt")
+        .tempfile()?;
+    file.write_all(maybe_initial.unwrap_or_default().as_bytes())?;
+    file.flush()?;
+    editor.edit(file.path())?;
+    let comment = std::fs::read_to_string(file.path())?;
+    debug!("Read {} byte(s) of comment from editor", comment.len());
+    // Editors usually terminate the last line with a newline
+    let comment = comment.trim_end();
+    Ok(if comment.trim().is_empty() {
+        None
+    } else {
+        Some(comment.to_string())
+    })
+}
+
 /// Initialize the Loiter root path as a Git repository.
 pub fn remote_init(store: &Store, params: &RemoteInit) -> Result<PathBuf, Error> {
     let store_path = store.path();
@@ -1049,6 +4693,34 @@ fn subprocess(cmd: &mut Command) -> Result<(ExitStatus, String, String), Error>
     Ok((result.status, stdout, stderr))
 }
 
+// Appends the given entry to the store's audit trail.
+fn record_audit(store: &Store, entry: AuditEntry) -> Result<(), Error> {
+    store.append_audit_entry(&entry)
+}
+
+fn project_audit_entry(operation: AuditOperation, project_id: &str) -> Result<AuditEntry, Error> {
+    Ok(AuditEntry::new(operation, Timestamp::now()?).with_project_id(project_id))
+}
+
+fn task_audit_entry(operation: AuditOperation, task: &Task) -> Result<AuditEntry, Error> {
+    Ok(project_audit_entry(operation, task.project_id().unwrap())?.with_maybe_task_id(task.id()))
+}
+
+fn log_audit_entry(operation: AuditOperation, log: &Log) -> Result<AuditEntry, Error> {
+    Ok(project_audit_entry(operation, log.project_id().unwrap())?
+        .with_maybe_task_id(log.task_id())
+        .with_maybe_log_id(log.id()))
+}
+
+// Combines an object's existing tags with the given default tags.
+fn merge_tags<'a, I, D>(tags: I, default_tags: D) -> Vec<String>
+where
+    I: Iterator<Item = &'a str>,
+    D: Iterator<Item = &'a str>,
+{
+    tags.chain(default_tags).map(|t| t.to_string()).collect()
+}
+
 fn parse_comma_separated(maybe_str: Option<String>) -> Vec<String> {
     maybe_str
         .map(|s| {
@@ -1058,3 +4730,25 @@ fn parse_comma_separated(maybe_str: Option<String>) -> Vec<String> {
         })
         .unwrap_or_else(Vec::new)
 }
+
+// An empty list of blockers is allowed, so that they can be cleared.
+fn parse_blocker_ids(maybe_task_ids: Option<&str>) -> Result<Vec<TaskId>, Error> {
+    match maybe_task_ids {
+        Some(task_ids) if !task_ids.trim().is_empty() => parse_task_ids(task_ids),
+        _ => Ok(Vec::new()),
+    }
+}
+
+// Repeated task IDs are only returned once, in the order in which they first
+// appear.
+fn parse_task_ids(task_ids: &str) -> Result<Vec<TaskId>, Error> {
+    let mut seen = BTreeSet::new();
+    Ok(parse_comma_separated(Some(task_ids.to_string()))
+        .iter()
+        .map(|s| TaskId::from_str(s))
+        .collect::<Result<Vec<TaskId>, std::num::ParseIntError>>()
+        .map_err(|e| Error::InvalidTaskIds(task_ids.to_string(), e))?
+        .into_iter()
+        .filter(|task_id| seen.insert(*task_id))
+        .collect())
+}
@@ -4,7 +4,7 @@ use std::path::PathBuf;
 
 use thiserror::Error;
 
-use crate::{Log, LogId, ProjectId, Task, TaskId, TaskPriority, TaskState};
+use crate::{Duration, Log, LogId, ProjectId, Task, TaskId, TaskPriority, TaskState, Timestamp};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -12,10 +12,24 @@ pub enum Error {
     FileNotFound(PathBuf),
     #[error("project \"{0}\" not found")]
     ProjectNotFound(String),
+    #[error("project \"{0}\" not found - did you mean: {}?", .1.join(", "))]
+    ProjectNotFoundDidYouMean(String, Vec<ProjectId>),
     #[error("project \"{0}\" already exists")]
     ProjectAlreadyExists(String),
+    #[error("project \"{0}\" has no hourly rate")]
+    ProjectHasNoRate(ProjectId),
+    #[error("invalid hourly rate: {0} (must be a finite, non-negative amount)")]
+    InvalidRate(f64),
+    #[error("log for project \"{0}\"{} with ID {2} already exists", .1.map(|task_id| format!(", task ID {}", task_id)).unwrap_or_else(|| "".to_string()))]
+    LogAlreadyExists(ProjectId, Option<TaskId>, LogId),
     #[error("task for project \"{0}\" with ID {1} does not exist")]
     TaskNotFound(String, TaskId),
+    #[error(
+        "task {1} of project \"{0}\" still has {2} work log(s) - use --force to remove it anyway"
+    )]
+    TaskHasLogs(ProjectId, TaskId, usize),
+    #[error("task {1} of project \"{0}\" cannot be blocked by task {2}, since that would create a dependency cycle")]
+    CircularTaskDependency(ProjectId, TaskId, TaskId),
     #[error("multiple tasks found for project \"{0}\" with ID {1} - please fix your local Loiter storage directory")]
     MultipleTasks(String, TaskId, Vec<Task>),
     #[error("task is missing its project ID: {0:?}")]
@@ -36,14 +50,44 @@ pub enum Error {
     LogMissingId(Log),
     #[error("there is currently no active log")]
     NoActiveLog,
+    #[error("there are no stopped logs to continue")]
+    NoStoppedLog,
+    #[error(
+        "log {0} is already active for this project and task - use --restart to start a new one"
+    )]
+    LogAlreadyActive(LogId),
+    #[error(
+        "there are {0} active logs - specify which one with --project and --id (see \"status\")"
+    )]
+    MultipleActiveLogs(usize),
+    #[error("the active log {2} belongs to task {1} of project \"{0}\", which no longer exists")]
+    ActiveLogTaskNotFound(ProjectId, TaskId, LogId),
     #[error("invalid path: {0}")]
     InvalidPath(PathBuf),
+    #[error("the IDs of {} collide - see \"verify --fix\"", .0.iter().map(|p| format!("\"{}\"", p.display())).collect::<Vec<String>>().join(", "))]
+    IdCollision(Vec<PathBuf>),
     #[error("invalid task file name: \"{0}\"")]
     InvalidTaskFilename(PathBuf),
     #[error("failed to parse task number from filename \"{0}\": {1}")]
     InvalidTaskNumber(PathBuf, std::num::ParseIntError),
     #[error("invalid task state: \"{0}\" (supported values: {})", .1.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(", "))]
     InvalidTaskState(TaskState, Vec<TaskState>),
+    #[error("timed out waiting for another process to release the store lock ({0})")]
+    StoreLocked(PathBuf),
+    #[error("invalid ISO 8601 duration: \"{0}\"")]
+    InvalidIso8601Duration(String),
+    #[error("invalid ID width: {0} (must be between 1 and {1})")]
+    InvalidIdWidth(usize, usize),
+    #[error("cannot create a log from \"{0}\": expected a project ID followed by a time (\"HH:MM\") or time range (\"HH:MM-HH:MM\")")]
+    InvalidLogLine(String),
+    #[error("invalid period: \"{0}\" (supported values: day, week, month)")]
+    InvalidPeriod(String),
+    #[error("invalid week start day: \"{0}\" (supported values: monday, sunday)")]
+    InvalidWeekStart(String),
+    #[error("invalid duration format: \"{0}\" (supported values: seconds, iso8601)")]
+    InvalidDurationFormat(String),
+    #[error("invalid task state filter: \"{0}\"")]
+    InvalidTaskStateFilter(String),
     #[error("task states must be unique; duplicate found in \"{}\"", .0.join(", "))]
     DuplicateTaskStates(Vec<TaskState>),
     #[error("too few task states ({0}) - there must be at least {1}")]
@@ -52,8 +96,24 @@ pub enum Error {
     InvalidTaskIds(String, std::num::ParseIntError),
     #[error("failed to parse log number from filename \"{0}\": {1}")]
     InvalidLogNumber(PathBuf, std::num::ParseIntError),
+    #[error("invalid regular expression \"{0}\": {1}")]
+    InvalidRegex(String, regex::Error),
+    #[error("invalid audit operation: \"{0}\"")]
+    InvalidAuditOperation(String),
+    #[error("failed to parse audit log entry in {}: {1}", .0.display())]
+    InvalidAuditEntry(PathBuf, serde_json::Error),
+    #[error("failed to parse import file {}: {1}", .0.display())]
+    InvalidImport(PathBuf, serde_json::Error),
     #[error("I/O failure: {0}")]
     Io(#[from] std::io::Error),
+    #[error("SQLite failure: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("output file already exists: {}", .0.display())]
+    OutputFileAlreadyExists(PathBuf),
+    #[error("cannot write a backup inside the store itself: {}", .0.display())]
+    BackupInsideStore(PathBuf),
+    #[error("cannot restore into a path that is not empty: {}", .0.display())]
+    RestoreTargetNotEmpty(PathBuf),
     #[error("serialization failed: {0}\n{1}")]
     Serialize(serde_json::Error, String),
     #[error("invalid date/time format: {0}")]
@@ -66,6 +126,8 @@ pub enum Error {
     DurationMustStartWithNumber(String),
     #[error("invalid amount for duration: \"{0}\" ({1})")]
     InvalidDurationAmount(String, std::num::ParseIntError),
+    #[error("invalid decimal amount for duration: \"{0}\" ({1})")]
+    InvalidDecimalDurationAmount(String, std::num::ParseFloatError),
     #[error("invalid units for duration: \"{0}\"")]
     InvalidDurationUnit(String),
     #[error("invalid duration: \"{0}{1}\"")]
@@ -82,6 +144,12 @@ pub enum Error {
     UnrecognizedSortOrder(String),
     #[error("cannot accept both duration and stop time - please supply only one of these")]
     CannotAcceptDurationAndStop,
+    #[error(
+        "cannot accept both a start time and a duration ago - please supply only one of these"
+    )]
+    CannotAcceptStartAndAgo,
+    #[error("a start time (or a duration ago) is required")]
+    MissingStart,
     #[error("sort specification cannot have empty components: {0}")]
     SortSpecHasEmptyComponent(String),
     #[error("sort specification \"{0}\" has too many parts in \"{1}\" (only a single colon is allowed for each field)")]
@@ -94,8 +162,12 @@ pub enum Error {
     InvalidDurationFilter(String),
     #[error("invalid duration filter operator \"{0}\" in filter: \"{1}\"")]
     InvalidDurationFilterOp(String, String),
+    #[error("invalid duration filter range (lower bound is greater than upper bound): \"{0}\"")]
+    InvalidDurationFilterRange(String),
     #[error("both project and log ID are required in order to reference a specific log")]
     BothProjectAndLogIdRequired,
+    #[error("editor \"{0}\" failed ({1})")]
+    EditorFailed(String, std::process::ExitStatus),
     #[error("path already initialized as a Git repository: {}", .0.display())]
     RemoteAlreadyInitialized(PathBuf),
     #[error("remote initialization failed - see debug logs for details")]
@@ -104,8 +176,24 @@ pub enum Error {
     NotRemote(PathBuf),
     #[error("remote push failed: {0}")]
     RemotePushFailed(String),
+    #[error("store requires a version of Loiter supporting store format version {0} or later, but this version only supports up to {1} - please upgrade Loiter")]
+    StoreTooNew(u32, u32),
+    #[error("invalid rounding increment (must be positive): {0}")]
+    InvalidRounding(Duration),
+    #[error("no rounding increment configured (see the \"config rounding\" command)")]
+    NoRoundingConfigured,
+    #[error("invalid stop time tolerance (must not be negative): {0}")]
+    InvalidStopTolerance(Duration),
+    #[error(
+        "stop time {0} is more than {1} in the future (see the \"config stop-tolerance\" command)"
+    )]
+    LogStopInFuture(Timestamp, Duration),
     #[error("invalid task priority ({0}) - must be between {1} and {2}")]
     InvalidTaskPriority(TaskPriority, TaskPriority, TaskPriority),
+    #[error(
+        "invalid default task priority ({0}) in the configuration - must be between {1} and {2}"
+    )]
+    InvalidDefaultTaskPriority(TaskPriority, TaskPriority, TaskPriority),
     #[error("failed to parse task priority \"{0}\": {1}")]
     CannotParseTaskPriority(String, std::num::ParseIntError),
     #[error("failed to parse URL \"{0}\": {1}")]
@@ -7,8 +7,10 @@ mod storage;
 mod strings;
 mod time;
 mod types;
+mod views;
 
 pub use crate::time::*;
 pub use error::*;
 pub use storage::*;
 pub use types::*;
+pub use views::*;
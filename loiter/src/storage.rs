@@ -4,9 +4,14 @@
 //! directory structure is used to organize data:
 //!
 //! ```ignore
+//! |_ .lock               - Lock file to serialize access to the store
+//! |_ audit.log           - Append-only audit trail (JSON lines)
+//! |_ audit.log.1         - Older, rotated audit trail entries
 //! |_ state.json          - Current global time tracking state
+//! |_ state.json.bak      - The previous global time tracking state
 //! |_ project1/           - All files relating to "Project 1"
 //! |  |_ project.json     - The project's metadata.
+//! |  |_ counters.json    - The IDs to assign to the project's next tasks/logs.
 //! |  |_ logs/            - Work logs related to "Project 1".
 //! |  |  |_ 00001.json    - Work log 1 for "Project 1" (no task).
 //! |  |_ tasks/           - Tasks related to "Project 1".
@@ -22,24 +27,52 @@
 //! |_ some-other-project/
 //! ```
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::OsStr;
-use std::fs;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration as StdDuration, Instant};
 
-use log::debug;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use fs2::FileExt;
+
+use log::{debug, warn};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::strings::slugify;
+use crate::strings::{levenshtein, slugify};
+use crate::types::validate_tag;
 use crate::{
-    Config, Duration, Error, Filter, FilterSpec, Log, LogFilter, LogId, Project, ProjectFilter,
-    ProjectId, State, Task, TaskFilter, TaskId, TaskStats, Timestamp,
+    ActiveLog, AuditEntry, Config, Duration, Error, Filter, FilterSpec, Log, LogField, LogFilter,
+    LogId, Order, Project, ProjectFilter, ProjectId, SortSpec, State, Task, TaskFilter, TaskId,
+    TaskState, TaskStateConfig, TaskStats, Timestamp, TimestampFilter, DEFAULT_LOG_ID_WIDTH,
+    DEFAULT_TASK_ID_WIDTH, MAX_ID_WIDTH, STORE_FORMAT_VERSION,
 };
 
+/// The name of the directory searched for when discovering a store (see
+/// [`Store::discover`]).
+pub const STORE_DIR_NAME: &str = ".loiter";
+
+// Once the audit log reaches this size, it is rotated before the next entry is
+// appended.
+const DEFAULT_AUDIT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const AUDIT_LOG_FILENAME: &str = "audit.log";
+
+// Mistyped project IDs within this edit distance of an existing project's ID
+// are reported along with suggestions.
+const MAX_PROJECT_ID_SUGGESTION_DISTANCE: usize = 2;
+
 const STARTING_TASK_ID: TaskId = 1;
 const STARTING_LOG_ID: LogId = 1;
 
+/// How long to wait to acquire the store lock before giving up, by default.
+pub const DEFAULT_LOCK_TIMEOUT: StdDuration = StdDuration::from_secs(5);
+// How long to wait between attempts to acquire the store lock.
+const LOCK_RETRY_INTERVAL: StdDuration = StdDuration::from_millis(20);
+
 /// A file system-based data store for Loiter.
 ///
 /// This struct provides a minimal interface for retrieving and
@@ -48,15 +81,132 @@ const STARTING_LOG_ID: LogId = 1;
 pub struct Store {
     // Absolute path to the root of the store.
     path: PathBuf,
+    // Whether to skip files that cannot be parsed when loading lists of
+    // objects, instead of failing.
+    skip_corrupt: bool,
+    // The files skipped so far, along with the reason they were skipped.
+    skipped: Mutex<Vec<(PathBuf, String)>>,
+    // The size (in bytes) at which the audit log is rotated.
+    audit_log_max_size: u64,
+    // Whether to leave a state whose active log no longer exists as is,
+    // instead of clearing its active log.
+    strict_state: bool,
+    // How long to wait to acquire the store lock before giving up.
+    lock_timeout: StdDuration,
 }
 
 impl Store {
+    /// Open the store at the given path, creating its directory if necessary.
+    ///
+    /// Fails if the store was written in a format that is too new for this
+    /// version of Loiter to read.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let path = path.as_ref();
         ensure_dir_exists(path)?;
-        Ok(Self {
+        let store = Self {
             path: path.canonicalize()?,
-        })
+            skip_corrupt: false,
+            skipped: Mutex::new(Vec::new()),
+            audit_log_max_size: DEFAULT_AUDIT_LOG_MAX_SIZE,
+            strict_state: false,
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+        };
+        store.check_format_version()?;
+        Ok(store)
+    }
+
+    // Only the version is read from the configuration here, since a store
+    // written by a newer version of Loiter may not be fully understood.
+    fn check_format_version(&self) -> Result<(), Error> {
+        #[derive(Deserialize)]
+        struct FormatVersion {
+            #[serde(default)]
+            min_reader_version: u32,
+        }
+
+        let config_path = self.config_path();
+        if !is_file(&config_path) {
+            return Ok(());
+        }
+        match load_from_json_file::<_, FormatVersion>(&config_path) {
+            Ok(version) if version.min_reader_version > STORE_FORMAT_VERSION => Err(
+                Error::StoreTooNew(version.min_reader_version, STORE_FORMAT_VERSION),
+            ),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                // Leave it up to whatever reads the configuration to fail
+                debug!("Unable to read store format version: {}", e);
+                Ok(())
+            }
+        }
+    }
+
+    /// When loading lists of projects, tasks or logs, skip (with a warning)
+    /// any files that cannot be parsed (e.g. because they're partially
+    /// written) instead of failing. Skipped files can be obtained via
+    /// [`Store::skipped_files`].
+    ///
+    /// Loading individual objects remains strict.
+    pub fn with_skip_corrupt(mut self, skip_corrupt: bool) -> Self {
+        self.skip_corrupt = skip_corrupt;
+        self
+    }
+
+    /// By default, if the state's active log no longer exists (e.g. because
+    /// its file was deleted by hand), the active log is cleared (with a
+    /// warning) when the state is checked via [`Store::checked_state`]. In
+    /// strict mode the state is left as is, so that loading the active log
+    /// fails instead, which can help with debugging.
+    pub fn with_strict_state(mut self, strict_state: bool) -> Self {
+        self.strict_state = strict_state;
+        self
+    }
+
+    /// Wait up to the given amount of time for other processes to release the
+    /// store lock (see [`Store::lock`]), instead of the default of 5 seconds.
+    pub fn with_lock_timeout(mut self, lock_timeout: StdDuration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// Rotate the audit log once it reaches the given size (in bytes), instead
+    /// of the default of 10MiB.
+    pub fn with_audit_log_max_size(mut self, max_size: u64) -> Self {
+        self.audit_log_max_size = max_size;
+        self
+    }
+
+    /// The files skipped so far because they could not be parsed, along with
+    /// the reason each was skipped.
+    pub fn skipped_files(&self) -> Vec<(PathBuf, String)> {
+        self.skipped.lock().unwrap().clone()
+    }
+
+    // Called when loading an object from the given path as part of a list
+    // fails. Returns `None` if the failure should be skipped.
+    fn skip_if_corrupt<T>(&self, path: &Path, e: Error) -> Option<Result<T, Error>> {
+        match e {
+            Error::Serialize(_, _) if self.skip_corrupt => {
+                let mut skipped = self.skipped.lock().unwrap();
+                // The same file may be loaded more than once per command
+                if !skipped.iter().any(|(skipped_path, _)| skipped_path == path) {
+                    warn!("Skipping corrupt file {}: {}", path.display(), e);
+                    skipped.push((path.to_path_buf(), e.to_string()));
+                }
+                None
+            }
+            e => Some(Err(e)),
+        }
+    }
+
+    /// Search the given directory and each of its parents (in that order) for
+    /// a store directory (named `.loiter`), much like Git does for `.git`.
+    /// Returns the path to the first store directory found, if any.
+    pub fn discover<P: AsRef<Path>>(from: P) -> Option<PathBuf> {
+        from.as_ref()
+            .ancestors()
+            .map(|dir| dir.join(STORE_DIR_NAME))
+            .find(|path| is_dir(path))
     }
 
     /// Get the path to the root of the Loiter store.
@@ -68,42 +218,262 @@ impl Store {
         self.path.join("state.json")
     }
 
+    fn state_backup_path(&self) -> PathBuf {
+        self.path.join("state.json.bak")
+    }
+
     /// Get the current Loiter state.
     ///
-    /// If no state file exists, one will be created.
+    /// If no state file exists, one will be created. If the state file cannot
+    /// be parsed, the state is recovered from its backup (if one exists).
     pub fn state(&self) -> Result<State, Error> {
         let state_path = self.state_path();
         if !is_file(&state_path) {
             let state = State::default();
             save_to_json_file(&state_path, &state)?;
-            Ok(state)
-        } else {
-            load_from_json_file(self.state_path())
+            return Ok(state);
+        }
+        match load_from_json_file(&state_path) {
+            Ok(state) => Ok(state),
+            Err(Error::Serialize(e, content)) => {
+                let backup_path = self.state_backup_path();
+                if !is_file(&backup_path) {
+                    return Err(Error::Serialize(e, content));
+                }
+                warn!(
+                    "Failed to parse {} ({}), recovering state from {}",
+                    state_path.display(),
+                    e,
+                    backup_path.display()
+                );
+                load_from_json_file(&backup_path)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the current Loiter state, clearing any of its active logs (with a
+    /// warning) that no longer exist, e.g. because their files were deleted
+    /// by hand. In strict mode (see [`Store::with_strict_state`]) the state is
+    /// returned as is.
+    ///
+    /// This should only be used when the store is consistent, i.e. not in the
+    /// middle of moving logs around.
+    pub fn checked_state(&self) -> Result<State, Error> {
+        let mut state = self.state()?;
+        if self.strict_state {
+            return Ok(state);
+        }
+        let mut changed = false;
+        for (project_id, maybe_task_id, log_id) in state.active_logs().to_vec() {
+            if !is_file(self.log_path(&project_id, maybe_task_id, log_id)?) {
+                warn!(
+                    "Active log {} for project {}{} no longer exists, clearing it",
+                    log_id,
+                    project_id,
+                    maybe_task_id
+                        .map(|task_id| format!(", task {}", task_id))
+                        .unwrap_or_default()
+                );
+                state = state.without_active_log(&project_id, maybe_task_id, log_id);
+                changed = true;
+            }
+        }
+        if changed {
+            self.save_state(&state)?;
         }
+        Ok(state)
     }
 
     /// Save the current global time tracking state.
+    ///
+    /// The state is only written if it has changed. The new state is written
+    /// atomically, after which it is also written to a backup, so that the
+    /// last state saved successfully can be recovered if the state file is
+    /// later corrupted.
     pub fn save_state(&self, state: &State) -> Result<(), Error> {
-        save_to_json_file(self.state_path(), state)
-    }
-
-    /// Returns the details of the currently active task if it's currently being
-    /// worked on (i.e. if there's an active work log associated with this
-    /// task).
-    pub fn active_task(&self) -> Result<Option<(ProjectId, TaskId)>, Error> {
-        let state = self.state()?;
-        match state.active_log() {
-            Some((project_id, maybe_task_id, _)) => {
-                if let Some(task_id) = maybe_task_id {
-                    Ok(Some((project_id, task_id)))
+        let state_path = self.state_path();
+        let s = serde_json::to_string_pretty(state)
+            .map_err(|e| Error::Serialize(e, format!("{:?}", state)))?;
+        if is_file(&state_path) && fs::read_to_string(&state_path)? == s {
+            debug!("State unchanged, not saving");
+            return Ok(());
+        }
+        write_atomically(&state_path, |writer| Ok(writer.write_all(s.as_bytes())?))?;
+        write_atomically(&self.state_backup_path(), |writer| {
+            Ok(writer.write_all(s.as_bytes())?)
+        })?;
+        debug!("Backed up state to {}", self.state_backup_path().display());
+        Ok(())
+    }
+
+    fn audit_log_path(&self) -> PathBuf {
+        self.path.join(AUDIT_LOG_FILENAME)
+    }
+
+    // The paths of the rotated audit logs, from oldest to newest.
+    fn rotated_audit_log_paths(&self) -> Vec<PathBuf> {
+        (1..)
+            .map(|n| self.path.join(format!("{}.{}", AUDIT_LOG_FILENAME, n)))
+            .take_while(|path| is_file(path))
+            .collect()
+    }
+
+    /// Append an entry to the store's audit trail.
+    ///
+    /// The audit log is never deleted. Once it grows too large, it is renamed
+    /// to `audit.log.N` (where `N` is the next available number) and a new
+    /// audit log is started.
+    pub fn append_audit_entry(&self, entry: &AuditEntry) -> Result<(), Error> {
+        let audit_log_path = self.audit_log_path();
+        let size = fs::metadata(&audit_log_path)
+            .map(|meta| meta.len())
+            .unwrap_or(0);
+        if size > 0 && size >= self.audit_log_max_size {
+            let rotated_path = self.path.join(format!(
+                "{}.{}",
+                AUDIT_LOG_FILENAME,
+                self.rotated_audit_log_paths().len() + 1
+            ));
+            fs::rename(&audit_log_path, &rotated_path)?;
+            debug!("Rotated audit log to {}", rotated_path.display());
+        }
+        let mut line = serde_json::to_string(entry)
+            .map_err(|e| Error::Serialize(e, format!("{:?}", entry)))?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&audit_log_path)?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Load all of the entries in the store's audit trail (including rotated
+    /// audit logs), from oldest to newest.
+    pub fn audit_entries(&self) -> Result<Vec<AuditEntry>, Error> {
+        let mut paths = self.rotated_audit_log_paths();
+        paths.push(self.audit_log_path());
+        let mut entries = Vec::new();
+        for path in paths.into_iter().filter(|path| is_file(path)) {
+            for line in fs::read_to_string(&path)?.lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                entries.push(
+                    serde_json::from_str(line)
+                        .map_err(|e| Error::InvalidAuditEntry(path.clone(), e))?,
+                );
+            }
+        }
+        Ok(entries)
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.path.join(".lock")
+    }
+
+    /// Write a gzipped tarball of the entire store to the given writer,
+    /// excluding the lock file and any temporary (`.tmp`) files.
+    ///
+    /// Holds the store lock while archiving so that the backup is consistent.
+    /// Returns the paths (relative to the root of the store) of the files
+    /// that were archived.
+    pub fn backup<W: Write>(&self, writer: W) -> Result<Vec<PathBuf>, Error> {
+        let _lock = self.lock()?;
+        let lock_path = self.lock_path();
+        let mut archive = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+        let mut archived = Vec::new();
+        let mut dirs = vec![self.path.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir)?
+                .map(|r| r.map(|e| e.path()))
+                .collect::<Result<Vec<PathBuf>, std::io::Error>>()?;
+            entries.sort();
+            for path in entries {
+                if path == lock_path || path.extension() == Some(OsStr::new("tmp")) {
+                    continue;
+                }
+                let rel_path = path.strip_prefix(&self.path).unwrap().to_path_buf();
+                if is_dir(&path) {
+                    archive.append_dir(&rel_path, &path)?;
+                    dirs.push(path);
                 } else {
-                    Ok(None)
+                    archive.append_path_with_name(&path, &rel_path)?;
+                    archived.push(rel_path);
+                }
+            }
+        }
+        archive.into_inner()?.finish()?;
+        debug!(
+            "Backed up {} file(s) from {}",
+            archived.len(),
+            self.path.display()
+        );
+        Ok(archived)
+    }
+
+    /// Unpack a backup created by [`Store::backup`] into the given path, which
+    /// must either not exist or be an empty directory.
+    pub fn restore<R: Read, P: AsRef<Path>>(reader: R, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if is_dir(path) {
+            if fs::read_dir(path)?.next().is_some() {
+                return Err(Error::RestoreTargetNotEmpty(path.to_path_buf()));
+            }
+        } else if path.exists() {
+            return Err(Error::RestoreTargetNotEmpty(path.to_path_buf()));
+        }
+        ensure_dir_exists(path)?;
+        tar::Archive::new(GzDecoder::new(reader)).unpack(path)?;
+        debug!("Restored backup to {}", path.display());
+        Ok(())
+    }
+
+    /// Acquire an exclusive lock on the store, waiting for other processes
+    /// holding the lock to release it.
+    ///
+    /// The lock is released when the returned guard is dropped. Returns
+    /// [`Error::StoreLocked`] if the lock cannot be acquired within the lock
+    /// timeout (see [`Store::with_lock_timeout`]).
+    pub fn lock(&self) -> Result<StoreLock, Error> {
+        let lock_path = self.lock_path();
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        let started = Instant::now();
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(_) => {
+                    debug!("Acquired store lock {}", lock_path.display());
+                    return Ok(StoreLock { file });
+                }
+                Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+                    if started.elapsed() >= self.lock_timeout {
+                        return Err(Error::StoreLocked(lock_path));
+                    }
+                    std::thread::sleep(LOCK_RETRY_INTERVAL);
                 }
+                Err(e) => return Err(e.into()),
             }
-            None => Ok(None),
         }
     }
 
+    /// Returns the project and task IDs of the tasks currently being worked
+    /// on, i.e. those with an active work log.
+    pub fn active_tasks(&self) -> Result<Vec<(ProjectId, TaskId)>, Error> {
+        Ok(self
+            .state()?
+            .active_logs()
+            .iter()
+            .filter_map(|(project_id, maybe_task_id, _)| {
+                maybe_task_id.map(|task_id| (project_id.clone(), task_id))
+            })
+            .collect())
+    }
+
     fn config_path(&self) -> PathBuf {
         self.path.join("config.json")
     }
@@ -138,50 +508,360 @@ impl Store {
 
     /// Get a list of all of the projects in the store.
     pub fn projects(&self, filter_spec: &FilterSpec<ProjectFilter>) -> Result<Vec<Project>, Error> {
+        self.iter_projects(filter_spec.clone()).collect()
+    }
+
+    /// Lazily load the projects in the store matching the given filter spec,
+    /// one at a time, so that callers can stop early without having loaded
+    /// every project.
+    pub fn iter_projects(
+        &self,
+        filter_spec: FilterSpec<ProjectFilter>,
+    ) -> impl Iterator<Item = Result<Project, Error>> + '_ {
         debug!("Attempting to filter projects by spec: {:?}", filter_spec);
-        let now = Timestamp::now()?;
-        let projects = fs::read_dir(&self.path)?
-            .into_iter()
-            .filter_map(|r| {
-                if let Ok(e) = r {
-                    let path = e.path();
+        let now = match Timestamp::now() {
+            Ok(now) => now,
+            Err(e) => return Box::new(std::iter::once(Err(e))) as Box<dyn Iterator<Item = _>>,
+        };
+        // If only specific projects can match, there's no need to look through
+        // the whole store for them.
+        let project_ids: Box<dyn Iterator<Item = ProjectId>> = match filter_spec.project_ids() {
+            Some(ids) => Box::new(
+                ids.iter()
+                    .filter(|id| is_valid_project_dir_name(id))
+                    .cloned()
+                    .collect::<Vec<ProjectId>>()
+                    .into_iter(),
+            ),
+            None => match fs::read_dir(&self.path) {
+                Ok(entries) => Box::new(entries.filter_map(|r| {
+                    let path = r.ok()?.path();
                     if is_dir(&path) {
-                        let project_id = path.file_name().unwrap().to_str().unwrap();
-                        let project_meta_path = self.project_meta_path(project_id);
-                        // We're only interested in this folder if it contains a
-                        // project metadata file.
-                        if is_file(project_meta_path) {
-                            // Filter out any projects we don't want
-                            // immediately to avoid unnecessarily loading them.
-                            return match self.project(project_id) {
-                                Ok(project) => {
-                                    if filter_spec.matches(&project, now) {
-                                        debug!("Project matches filter spec: {:?}", project);
-                                        Some(Ok(project))
-                                    } else {
-                                        debug!(
-                                            "Project does not match filter spec, skipping: {:?}",
-                                            project
-                                        );
-                                        None
-                                    }
-                                }
-                                Err(e) => Some(Err(e)),
-                            };
+                        path.file_name()?.to_str().map(String::from)
+                    } else {
+                        None
+                    }
+                })),
+                Err(e) => return Box::new(std::iter::once(Err(e.into()))),
+            },
+        };
+        Box::new(project_ids.filter_map(move |project_id| {
+            let project_meta_path = self.project_meta_path(&project_id);
+            // We're only interested in this folder if it contains a project
+            // metadata file.
+            if !is_file(&project_meta_path) {
+                return None;
+            }
+            // Filter out any projects we don't want immediately to avoid
+            // unnecessarily loading them.
+            match self.project(&project_id) {
+                Ok(project) => {
+                    if filter_spec.matches(&project, now) {
+                        debug!("Project matches filter spec: {:?}", project);
+                        Some(Ok(project))
+                    } else {
+                        debug!(
+                            "Project does not match filter spec, skipping: {:?}",
+                            project
+                        );
+                        None
+                    }
+                }
+                Err(e) => self.skip_if_corrupt(&project_meta_path, e),
+            }
+        }))
+    }
+
+    /// Find all work log files stored beneath directories in the store that
+    /// have no project metadata. Such logs are invisible to [`Store::logs`].
+    pub fn orphaned_logs(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut orphans = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            let is_hidden = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map(|name| name.starts_with('.'))
+                .unwrap_or(true);
+            if is_hidden || !is_dir(&path) || is_file(path.join("project.json")) {
+                continue;
+            }
+            orphans.extend(log_files_in(path.join("logs"))?);
+            let tasks_path = path.join("tasks");
+            if is_dir(&tasks_path) {
+                for entry in fs::read_dir(&tasks_path)? {
+                    orphans.extend(log_files_in(entry?.path())?);
+                }
+            }
+        }
+        orphans.sort();
+        debug!("Found {} orphaned log(s)", orphans.len());
+        Ok(orphans)
+    }
+
+    /// Find groups of task directories or work log files within the same
+    /// directory whose names parse to the same ID (e.g. `0003` and `3`). Only
+    /// one path from each group is reachable through the store.
+    pub fn id_collisions(&self) -> Result<Vec<Vec<PathBuf>>, Error> {
+        let mut collisions = Vec::new();
+        for project in self.projects(&FilterSpec::new(ProjectFilter::All))? {
+            collisions.extend(self.project_id_collisions(project.id())?);
+        }
+        collisions.sort();
+        debug!("Found {} ID collision(s)", collisions.len());
+        Ok(collisions)
+    }
+
+    fn project_id_collisions(&self, project_id: &str) -> Result<Vec<Vec<PathBuf>>, Error> {
+        let mut collisions = Vec::new();
+        let task_paths = self.task_paths(project_id)?;
+        for task_path in task_paths.iter() {
+            collisions.extend(group_colliding_ids(log_files_in(task_path)?, |path| {
+                log_id_from_path(path)
+            }));
+        }
+        collisions.extend(group_colliding_ids(task_paths, |path| {
+            task_id_from_path(path)
+        }));
+        collisions.extend(group_colliding_ids(
+            log_files_in(self.logs_path(project_id, None)?)?,
+            |path| log_id_from_path(path),
+        ));
+        Ok(collisions)
+    }
+
+    // The paths of all of the given project's task directories, regardless of
+    // whether they contain task metadata.
+    fn task_paths(&self, project_id: &str) -> Result<Vec<PathBuf>, Error> {
+        task_paths_in(&self.tasks_path(project_id))
+    }
+
+    /// Walk the whole store looking for inconsistencies (e.g. after manual
+    /// edits or a bad merge). Files that cannot be parsed are reported as
+    /// inconsistencies instead of aborting the scan.
+    pub fn verify(&self) -> Result<Vec<Inconsistency>, Error> {
+        let mut inconsistencies = self
+            .orphaned_logs()?
+            .into_iter()
+            .map(Inconsistency::OrphanedLog)
+            .collect::<Vec<Inconsistency>>();
+        if is_file(self.state_path()) {
+            load_verified::<State>(&self.state_path(), &mut inconsistencies)?;
+        }
+        let config = if is_file(self.config_path()) {
+            load_verified::<Config>(&self.config_path(), &mut inconsistencies)?.unwrap_or_default()
+        } else {
+            Config::default()
+        };
+        for entry in fs::read_dir(&self.path)? {
+            let project_id = match entry?.file_name().to_str() {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let project_meta_path = self.project_meta_path(&project_id);
+            if !is_file(&project_meta_path) {
+                continue;
+            }
+            let task_state_config =
+                match load_verified::<Project>(&project_meta_path, &mut inconsistencies)? {
+                    Some(project) => project
+                        .task_state_config()
+                        .unwrap_or_else(|| config.task_state_config())
+                        .clone(),
+                    None => config.task_state_config().clone(),
+                };
+            if is_file(self.id_counters_path(&project_id)) {
+                load_verified::<IdCounters>(
+                    &self.id_counters_path(&project_id),
+                    &mut inconsistencies,
+                )?;
+            }
+            let mut log_paths = log_files_in(self.logs_path(&project_id, None)?)?;
+            for task_path in self.task_paths(&project_id)? {
+                let task_meta_path = task_path.join("task.json");
+                let task_log_paths = log_files_in(&task_path)?;
+                if !is_file(&task_meta_path) {
+                    inconsistencies.extend(
+                        task_log_paths
+                            .iter()
+                            .cloned()
+                            .map(Inconsistency::LogWithoutTask),
+                    );
+                } else if let Some(task) =
+                    load_verified::<Task>(&task_meta_path, &mut inconsistencies)?
+                {
+                    if let Some(state) = task.state() {
+                        if !task_state_config.states().any(|s| s == state) {
+                            inconsistencies.push(Inconsistency::InvalidTaskState(
+                                project_id.clone(),
+                                task_id_from_path(&task_path)?,
+                                state.to_string(),
+                            ));
                         }
                     }
                 }
-                None
-            })
-            .collect::<Result<Vec<Project>, Error>>()?;
-        Ok(projects)
+                log_paths.extend(task_log_paths);
+            }
+            for log_path in log_paths {
+                load_verified::<Log>(&log_path, &mut inconsistencies)?;
+            }
+            inconsistencies.extend(
+                self.project_id_collisions(&project_id)?
+                    .into_iter()
+                    .map(Inconsistency::IdCollision),
+            );
+        }
+        debug!("Found {} inconsistencies", inconsistencies.len());
+        Ok(inconsistencies)
+    }
+
+    /// Attempt to repair the given inconsistency (see [`Store::verify`]),
+    /// returning the changes made. Inconsistencies that cannot be repaired
+    /// automatically (e.g. unparseable files) are left as they are.
+    pub fn repair(&self, inconsistency: &Inconsistency) -> Result<Vec<Repair>, Error> {
+        match inconsistency {
+            Inconsistency::InvalidTaskState(project_id, task_id, _) => {
+                let state = self.task_state_config(project_id)?.initial().to_string();
+                let task = self.task(project_id, *task_id, false)?.with_state(&state);
+                self.save_task(&task)?;
+                debug!(
+                    "Reset state of task {} of project {} to {}",
+                    task_id, project_id, state
+                );
+                Ok(vec![Repair::TaskStateReset(
+                    project_id.clone(),
+                    *task_id,
+                    state,
+                )])
+            }
+            Inconsistency::LogWithoutTask(path) => {
+                // The log is in <project>/tasks/<task>/, so it's moved to the
+                // project's own logs.
+                let project_id = path
+                    .ancestors()
+                    .nth(3)
+                    .and_then(Path::file_name)
+                    .and_then(OsStr::to_str)
+                    .ok_or_else(|| Error::InvalidPath(path.clone()))?;
+                let to = self.move_log_file(path, &self.logs_path(project_id, None)?)?;
+                // Only succeeds once the task directory is empty
+                if let Some(task_path) = path.parent() {
+                    let _ = fs::remove_dir(task_path);
+                }
+                Ok(vec![Repair::Moved(path.clone(), to)])
+            }
+            Inconsistency::IdCollision(paths) => {
+                // Only the least padded path is visible, so the others are
+                // given new IDs.
+                let mut paths = paths.clone();
+                paths.sort_by_key(|path| path.as_os_str().len());
+                let mut repairs = Vec::new();
+                for path in paths.iter().skip(1).filter(|path| path.exists()) {
+                    let dir = path
+                        .parent()
+                        .ok_or_else(|| Error::InvalidPath(path.clone()))?;
+                    let to = if is_dir(path) {
+                        let next_id = next_free_id(task_paths_in(dir)?, STARTING_TASK_ID);
+                        let to = dir.join(format!(
+                            "{:0w$}",
+                            next_id,
+                            w = self.config()?.task_id_width()
+                        ));
+                        fs::rename(path, &to)?;
+                        to
+                    } else {
+                        self.move_log_file(path, dir)?
+                    };
+                    repairs.push(Repair::Moved(path.clone(), to));
+                }
+                Ok(repairs)
+            }
+            Inconsistency::OrphanedLog(_) | Inconsistency::UnparseableFile(_, _) => Ok(Vec::new()),
+        }
+    }
+
+    // Moves the given work log file into the given directory, assigning it the
+    // next free log ID there.
+    fn move_log_file(&self, path: &Path, dir: &Path) -> Result<PathBuf, Error> {
+        let next_id = next_free_id(log_files_in(dir)?, STARTING_LOG_ID);
+        let to = dir.join(format!(
+            "{:0w$}.json",
+            next_id,
+            w = self.config()?.log_id_width()
+        ));
+        ensure_dir_exists(dir)?;
+        fs::rename(path, &to)?;
+        debug!("Moved {} to {}", path.display(), to.display());
+        self.follow_moved_active_log(path, &to)?;
+        Ok(to)
+    }
+
+    // Points the state at a moved log if it was the active one. Only a log
+    // without a duration can be active, which tells colliding logs apart: if
+    // the log left behind under the old ID is also running, both stay active.
+    // An unparseable state file is reported by verification on its own, so it
+    // is left alone here.
+    fn follow_moved_active_log(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let state = match self.state() {
+            Ok(state) => state,
+            Err(Error::Serialize(_, _)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let from = self.active_log_from_path(from)?;
+        if !state.is_active(&from) || !is_running_log_file(to) {
+            return Ok(());
+        }
+        let to = self.active_log_from_path(to)?;
+        let left_behind_running = match self.log_path(&from.0, from.1, from.2) {
+            Ok(path) => is_running_log_file(path),
+            // The remaining colliding logs are repaired separately, so any of
+            // them may still be the active one
+            Err(Error::IdCollision(_)) => true,
+            Err(e) => return Err(e),
+        };
+        let state = if left_behind_running {
+            state.with_active_log(&to.0, to.1, to.2)
+        } else {
+            state.with_replaced_active_log(&from, to.clone())
+        };
+        self.save_state(&state)?;
+        debug!("Updated active log {:?} to {:?}", from, to);
+        Ok(())
+    }
+
+    // The project ID, task ID (if any) and log ID of the work log at the given
+    // path, i.e. either <project>/logs/<log>.json or
+    // <project>/tasks/<task>/<log>.json.
+    fn active_log_from_path(&self, path: &Path) -> Result<ActiveLog, Error> {
+        let invalid_path = || Error::InvalidPath(path.to_path_buf());
+        let log_id = log_id_from_path(path)?;
+        let dir = path.parent().ok_or_else(invalid_path)?;
+        let (project_path, maybe_task_id) = if dir.file_name() == Some(OsStr::new("logs")) {
+            (dir.parent(), None)
+        } else {
+            (dir.ancestors().nth(2), Some(task_id_from_path(dir)?))
+        };
+        let project_id = project_path
+            .and_then(Path::file_name)
+            .and_then(OsStr::to_str)
+            .ok_or_else(invalid_path)?;
+        Ok((project_id.to_string(), maybe_task_id, log_id))
     }
 
     /// Attempt to get a specific project by its ID.
     pub fn project(&self, id: &str) -> Result<Project, Error> {
+        #[cfg(test)]
+        test::PROJECT_LOADS.with(|loads| loads.set(loads.get() + 1));
         let p: Project =
             load_from_json_file(self.project_meta_path(&slugify(id))).map_err(|e| match e {
-                Error::FileNotFound(_) => Error::ProjectNotFound(id.to_string()),
+                Error::FileNotFound(_) => {
+                    let similar_ids = self.similar_project_ids(id);
+                    if similar_ids.is_empty() {
+                        Error::ProjectNotFound(id.to_string())
+                    } else {
+                        Error::ProjectNotFoundDidYouMean(id.to_string(), similar_ids)
+                    }
+                }
                 e => e,
             })?;
         // Ensure that the ID is updated
@@ -189,11 +869,81 @@ impl Store {
         Ok(p.with_name(&project_name))
     }
 
+    // The IDs of the existing projects that are within a small edit distance of
+    // the given (presumably mistyped) project ID, closest first.
+    fn similar_project_ids(&self, id: &str) -> Vec<ProjectId> {
+        let id = slugify(id);
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut similar_ids = entries
+            .filter_map(|r| {
+                let project_id = r.ok()?.file_name().to_str()?.to_string();
+                if !is_file(self.project_meta_path(&project_id)) {
+                    return None;
+                }
+                let distance = levenshtein(&id, &project_id);
+                if distance <= MAX_PROJECT_ID_SUGGESTION_DISTANCE {
+                    Some((distance, project_id))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<(usize, ProjectId)>>();
+        similar_ids.sort();
+        similar_ids
+            .into_iter()
+            .map(|(_, project_id)| project_id)
+            .collect()
+    }
+
+    /// Get the task state configuration that applies to the project with the
+    /// given ID: the project's own configuration if it has one, otherwise the
+    /// global configuration.
+    pub fn task_state_config(&self, project_id: &str) -> Result<TaskStateConfig, Error> {
+        let project = self.project(project_id)?;
+        match project.task_state_config() {
+            Some(tsc) => Ok(tsc.clone()),
+            None => Ok(self.config()?.task_state_config().clone()),
+        }
+    }
+
+    /// Get the task state configuration that applies to each project in the
+    /// store, keyed by project ID.
+    pub fn task_state_configs(&self) -> Result<BTreeMap<ProjectId, TaskStateConfig>, Error> {
+        let config = self.config()?;
+        Ok(self
+            .projects(&FilterSpec::new(ProjectFilter::All))?
+            .into_iter()
+            .map(|project| {
+                let task_state_config = project
+                    .task_state_config()
+                    .unwrap_or_else(|| config.task_state_config())
+                    .clone();
+                (project.id().to_string(), task_state_config)
+            })
+            .collect())
+    }
+
     /// Create or update a project.
     pub fn save_project(&self, project: &Project) -> Result<(), Error> {
         save_to_json_file(self.project_meta_path(project.id()), &project)
     }
 
+    /// Create a new project.
+    ///
+    /// Since a project's ID is derived from its name, different names can map
+    /// to the same ID (e.g. "A!" and "A?"). If a project with the new
+    /// project's ID already exists, this fails with
+    /// [`Error::ProjectAlreadyExists`] instead of overwriting it.
+    pub fn create_project(&self, project: &Project) -> Result<(), Error> {
+        if is_dir(self.project_path(project.id())) {
+            return Err(Error::ProjectAlreadyExists(project.id().to_string()));
+        }
+        self.save_project(project)
+    }
+
     /// Remove the project with the given ID, along with all of its data.
     pub fn remove_project<S: AsRef<str>>(&self, id: S) -> Result<(), Error> {
         let project_path = self.project_path(id.as_ref());
@@ -208,11 +958,20 @@ impl Store {
 
     /// Attempts to rename the given project from its old ID to the one
     /// supplied.
+    ///
+    /// The project's tasks and logs move along with its directory. Since the
+    /// IDs of projects, tasks and logs are never read from their files, but
+    /// always derived from where the files are stored, they all report the new
+    /// project ID once loaded.
     pub fn rename_project<S: AsRef<str>>(&self, old_id: S, project: &Project) -> Result<(), Error> {
         let old_path = self.project_path(old_id.as_ref());
         if !is_dir(&old_path) {
             return Err(Error::ProjectNotFound(old_id.as_ref().to_string()));
         }
+        // Only the project's name changes if the new name maps to the same ID
+        if project.id() == old_id.as_ref() {
+            return self.save_project(project);
+        }
         let new_path = self.project_path(project.id());
         if is_dir(&new_path) {
             return Err(Error::ProjectAlreadyExists(project.id().to_string()));
@@ -238,8 +997,55 @@ impl Store {
         details: bool,
     ) -> Result<Vec<Task>, Error> {
         let projects = self.projects(project_filter)?;
+        self.tasks_for_projects(&projects, task_filter, details)
+    }
+
+    /// Returns the tasks matching the given filters that aren't blocked by any
+    /// task that is still open.
+    ///
+    /// A blocking task is open until it reaches one of its project's closed
+    /// states. Blockers that no longer exist don't block.
+    pub fn unblocked_tasks(
+        &self,
+        project_filter: &FilterSpec<ProjectFilter>,
+        task_filter: &FilterSpec<TaskFilter>,
+        details: bool,
+    ) -> Result<Vec<Task>, Error> {
+        let projects = self.projects(project_filter)?;
+        let config = self.config()?;
+        let mut open_tasks = Vec::new();
+        for project in projects.iter() {
+            let task_state_config = project
+                .task_state_config()
+                .unwrap_or_else(|| config.task_state_config());
+            for task in
+                self.project_tasks(project.id(), &FilterSpec::new(TaskFilter::All), false)?
+            {
+                let closed = task
+                    .state()
+                    .map(|state| task_state_config.is_closed(state))
+                    .unwrap_or(false);
+                if let (false, Some(task_id)) = (closed, task.id()) {
+                    open_tasks.push((project.id().to_string(), task_id));
+                }
+            }
+        }
+        let task_filter = task_filter
+            .clone()
+            .and_then(TaskFilter::Unblocked(open_tasks));
+        self.tasks_for_projects(&projects, &task_filter, details)
+    }
+
+    // Returns the tasks matching the given filter across the given (already
+    // loaded) projects, in project order.
+    fn tasks_for_projects(
+        &self,
+        projects: &[Project],
+        task_filter: &FilterSpec<TaskFilter>,
+        details: bool,
+    ) -> Result<Vec<Task>, Error> {
         let tasks = projects
-            .into_iter()
+            .iter()
             .map(|project| {
                 self.project_tasks(project.id(), task_filter, details)
                     .map(|tasks| {
@@ -247,7 +1053,7 @@ impl Store {
                             .into_iter()
                             .map(|task| {
                                 if details {
-                                    task.with_project(&project)
+                                    task.with_project(project)
                                 } else {
                                     task
                                 }
@@ -276,7 +1082,7 @@ impl Store {
         if !is_dir(&tasks_path) {
             return Ok(Vec::new());
         }
-        let tasks = fs::read_dir(&tasks_path)?
+        let mut tasks = fs::read_dir(&tasks_path)?
             .into_iter()
             .filter_map(|r| {
                 if let Ok(e) = r {
@@ -286,7 +1092,7 @@ impl Store {
                             Ok(task_id) => task_id,
                             Err(_) => return None,
                         };
-                        return match self.task(project_id, task_id, details) {
+                        return match self.task_at(&path, project_id, task_id, details) {
                             Ok(task) => {
                                 if task_filter.matches(&task, now) {
                                     debug!("Task matches filter spec: {:?}", task);
@@ -296,26 +1102,81 @@ impl Store {
                                     None
                                 }
                             }
-                            Err(e) => Some(Err(e)),
+                            Err(e) => self.skip_if_corrupt(&path.join("task.json"), e),
                         };
                     }
                 }
                 None
             })
             .collect::<Result<Vec<Task>, Error>>()?;
+        // Directory listings are ordered lexically (if at all), which doesn't
+        // hold for IDs of differing widths.
+        tasks.sort_by_key(Task::id);
         Ok(tasks)
     }
 
-    fn task_path(&self, project_id: &str, task_id: TaskId) -> PathBuf {
-        self.tasks_path(project_id).join(format!("{:04}", task_id))
+    // Task directories may have been created with different ID widths, so
+    // we look for an existing directory before falling back to the default
+    // width.
+    fn task_path(&self, project_id: &str, task_id: TaskId) -> Result<PathBuf, Error> {
+        padded_id_path(
+            self.tasks_path(project_id),
+            task_id,
+            "",
+            DEFAULT_TASK_ID_WIDTH,
+        )
     }
 
-    fn task_meta_path(&self, project_id: &str, task_id: TaskId) -> PathBuf {
-        self.task_path(project_id, task_id).join("task.json")
+    fn task_meta_path(&self, project_id: &str, task_id: TaskId) -> Result<PathBuf, Error> {
+        Ok(self.task_path(project_id, task_id)?.join("task.json"))
     }
 
-    fn task_stats(&self, project_id: &str, task_id: TaskId) -> Result<TaskStats, Error> {
-        let logs = self.logs_for_project_or_task(
+    /// Remove the task with the given ID, along with all of its work logs.
+    pub fn remove_task(&self, project_id: &str, task_id: TaskId) -> Result<(), Error> {
+        let task_path = self.task_path(project_id, task_id)?;
+        if is_dir(&task_path) {
+            fs::remove_dir_all(&task_path)?;
+            debug!("Removed directory: {}", task_path.display());
+            Ok(())
+        } else {
+            Err(Error::TaskNotFound(project_id.to_string(), task_id))
+        }
+    }
+
+    fn task_stats(
+        &self,
+        task_path: &Path,
+        project_id: &str,
+        task_id: TaskId,
+    ) -> Result<TaskStats, Error> {
+        Ok(TaskStats {
+            time_logged: self.sum_task_log_durations(task_path, project_id, task_id)?,
+        })
+    }
+
+    /// Returns the total time actually spent on the given task, i.e. the sum
+    /// of the durations of all of its logs. Logs that are still running are
+    /// not included.
+    pub fn task_actual_duration(
+        &self,
+        project_id: &str,
+        task_id: TaskId,
+    ) -> Result<Duration, Error> {
+        let task_path = self.task_path(project_id, task_id)?;
+        if !is_dir(&task_path) {
+            return Err(Error::TaskNotFound(project_id.to_string(), task_id));
+        }
+        self.sum_task_log_durations(&task_path, project_id, task_id)
+    }
+
+    fn sum_task_log_durations(
+        &self,
+        task_path: &Path,
+        project_id: &str,
+        task_id: TaskId,
+    ) -> Result<Duration, Error> {
+        let logs = self.logs_in(
+            task_path,
             project_id,
             Some(task_id),
             &FilterSpec::new(LogFilter::All),
@@ -329,7 +1190,7 @@ impl Store {
                     acc += d;
                     acc
                 });
-        Ok(TaskStats { time_logged })
+        Ok(time_logged)
     }
 
     /// Attempt to get a task by its ID and its project's ID.
@@ -339,29 +1200,79 @@ impl Store {
         task_id: TaskId,
         collect_stats: bool,
     ) -> Result<Task, Error> {
-        let task_path = self.task_path(project_id, task_id);
-        let task_meta_path = self.task_meta_path(project_id, task_id);
-        if !is_dir(&task_path) || !is_file(&task_meta_path) {
+        let task_path = self.task_path(project_id, task_id)?;
+        self.task_at(&task_path, project_id, task_id, collect_stats)
+    }
+
+    // Loads a task from its directory, once the directory has been found
+    // (e.g. by listing the project's tasks).
+    fn task_at(
+        &self,
+        task_path: &Path,
+        project_id: &str,
+        task_id: TaskId,
+        collect_stats: bool,
+    ) -> Result<Task, Error> {
+        let task_meta_path = task_path.join("task.json");
+        if !is_dir(task_path) || !is_file(&task_meta_path) {
             return Err(Error::TaskNotFound(project_id.to_string(), task_id));
         }
         let mut task = load_from_json_file::<&PathBuf, Task>(&task_meta_path)?
             .with_project_id(project_id)
             .with_id(task_id);
         if collect_stats {
-            let task_stats = self.task_stats(project_id, task_id)?;
+            let task_stats = self.task_stats(task_path, project_id, task_id)?;
             task = task.with_stats(task_stats);
         }
         Ok(task)
     }
 
-    fn next_task_id(&self, project_id: &str) -> Result<TaskId, Error> {
-        Ok(self
-            .project_tasks(project_id, &FilterSpec::new(TaskFilter::All), false)?
-            .into_iter()
-            .map(|task| task.id().unwrap())
-            .max()
-            .map(|highest_task_id| highest_task_id + 1)
-            .unwrap_or(STARTING_TASK_ID))
+    /// Returns the ID that the next new task saved for the given project will
+    /// be assigned, without modifying the store.
+    ///
+    /// This is only a preview: if another process saves a task for the same
+    /// project in the meantime, the ID actually assigned may differ.
+    pub fn peek_next_task_id(&self, project_id: &str) -> Result<TaskId, Error> {
+        let scan = || Ok(next_free_id(self.task_paths(project_id)?, STARTING_TASK_ID));
+        match self.id_counters(project_id)?.maybe_next_task_id {
+            // The counter lags behind if tasks were added by other means (e.g.
+            // by hand, or by merging stores).
+            Some(task_id) if is_dir(self.task_path(project_id, task_id)?) => {
+                Ok(task_id.max(scan()?))
+            }
+            Some(task_id) => Ok(task_id),
+            // Stores created before the counters were introduced
+            None => scan(),
+        }
+    }
+
+    // Records that the given task ID has been assigned, so that it is never
+    // assigned again, even if the task is removed.
+    fn record_task_id(&self, project_id: &str, task_id: TaskId) -> Result<(), Error> {
+        let mut counters = self.id_counters(project_id)?;
+        counters.maybe_next_task_id = match counters.maybe_next_task_id {
+            Some(next_task_id) if next_task_id > task_id => return Ok(()),
+            Some(_) => Some(task_id + 1),
+            None => Some(next_free_id(self.task_paths(project_id)?, STARTING_TASK_ID)),
+        };
+        self.save_id_counters(project_id, &counters)
+    }
+
+    fn id_counters_path(&self, project_id: &str) -> PathBuf {
+        self.project_path(project_id).join("counters.json")
+    }
+
+    fn id_counters(&self, project_id: &str) -> Result<IdCounters, Error> {
+        let path = self.id_counters_path(project_id);
+        if is_file(&path) {
+            load_from_json_file(&path)
+        } else {
+            Ok(IdCounters::default())
+        }
+    }
+
+    fn save_id_counters(&self, project_id: &str, counters: &IdCounters) -> Result<(), Error> {
+        save_to_json_file(self.id_counters_path(project_id), counters)
     }
 
     /// Create or update a task.
@@ -377,43 +1288,91 @@ impl Store {
         let state = task_state_config.validate_or_initial(task.state())?;
         let task_id = match task.id() {
             Some(id) => id,
-            None => self.next_task_id(project_id)?,
+            None => self.peek_next_task_id(project_id)?,
         };
         let task = task.clone().with_id(task_id).with_state(state);
-        let task_path = self.task_path(project_id, task_id);
+        let task_path = padded_id_path(
+            self.tasks_path(project_id),
+            task_id,
+            "",
+            config.task_id_width(),
+        )?;
         ensure_dir_exists(&task_path)?;
 
-        let task_meta_path = self.task_meta_path(project_id, task_id);
+        let task_meta_path = task_path.join("task.json");
         save_to_json_file(&task_meta_path, &task)?;
+        self.record_task_id(project_id, task_id)?;
         Ok(task)
     }
 
-    fn logs_path(&self, project_id: &str, maybe_task_id: Option<TaskId>) -> PathBuf {
+    fn logs_path(&self, project_id: &str, maybe_task_id: Option<TaskId>) -> Result<PathBuf, Error> {
         match maybe_task_id {
             Some(task_id) => self.task_path(project_id, task_id),
-            None => self.project_path(project_id).join("logs"),
+            None => Ok(self.project_path(project_id).join("logs")),
         }
     }
 
-    fn log_path(&self, project_id: &str, maybe_task_id: Option<TaskId>, id: LogId) -> PathBuf {
-        self.logs_path(project_id, maybe_task_id)
-            .join(format!("{:05}.json", id))
+    fn log_path(
+        &self,
+        project_id: &str,
+        maybe_task_id: Option<TaskId>,
+        id: LogId,
+    ) -> Result<PathBuf, Error> {
+        padded_id_path(
+            self.logs_path(project_id, maybe_task_id)?,
+            id,
+            ".json",
+            DEFAULT_LOG_ID_WIDTH,
+        )
     }
 
-    fn next_log_id(&self, project_id: &str, maybe_task_id: Option<TaskId>) -> Result<LogId, Error> {
-        Ok(self
-            .logs_for_project_or_task(
-                project_id,
-                maybe_task_id,
-                &FilterSpec::new(LogFilter::All),
-                false,
-                None,
-            )?
-            .into_iter()
-            .map(|log| log.id().unwrap())
-            .max()
-            .map(|highest_log_id| highest_log_id + 1)
-            .unwrap_or(STARTING_LOG_ID))
+    /// Returns the ID that the next new log saved for the given project (and,
+    /// optionally, task) will be assigned, without modifying the store.
+    ///
+    /// This is only a preview: if another process saves a log for the same
+    /// project or task in the meantime, the ID actually assigned may differ.
+    pub fn peek_next_log_id(
+        &self,
+        project_id: &str,
+        maybe_task_id: Option<TaskId>,
+    ) -> Result<LogId, Error> {
+        let scan = || {
+            Ok(next_free_id(
+                log_files_in(self.logs_path(project_id, maybe_task_id)?)?,
+                STARTING_LOG_ID,
+            ))
+        };
+        match self.id_counters(project_id)?.next_log_id(maybe_task_id) {
+            // The counter lags behind if logs were added by other means (e.g.
+            // by hand, or by merging stores).
+            Some(log_id) if is_file(self.log_path(project_id, maybe_task_id, log_id)?) => {
+                Ok(log_id.max(scan()?))
+            }
+            Some(log_id) => Ok(log_id),
+            // Stores created before the counters were introduced
+            None => scan(),
+        }
+    }
+
+    // Records that the given log ID has been assigned, so that it is never
+    // assigned again, even if the log is deleted.
+    fn record_log_id(
+        &self,
+        project_id: &str,
+        maybe_task_id: Option<TaskId>,
+        log_id: LogId,
+    ) -> Result<(), Error> {
+        let mut counters = self.id_counters(project_id)?;
+        let next_log_id = match counters.next_log_id(maybe_task_id) {
+            Some(next_log_id) if next_log_id > log_id => return Ok(()),
+            Some(_) => log_id + 1,
+            None => next_free_id(
+                log_files_in(self.logs_path(project_id, maybe_task_id)?)?,
+                STARTING_LOG_ID,
+            ),
+        };
+        counters.set_next_log_id(maybe_task_id, next_log_id);
+        self.save_id_counters(project_id, &counters)
     }
 
     /// Return all logs matching the given filter criteria.
@@ -425,7 +1384,7 @@ impl Store {
         detailed: bool,
     ) -> Result<Vec<Log>, Error> {
         let projects = self.projects(project_filter)?;
-        let tasks = self.tasks(project_filter, task_filter, false)?;
+        let tasks = self.tasks_for_projects(&projects, task_filter, false)?;
         let mut logs = projects
             .into_iter()
             .map(|project| {
@@ -458,6 +1417,212 @@ impl Store {
         Ok(logs)
     }
 
+    /// Counts the logs that [`Store::logs`] would return for the given
+    /// filters, without attaching their tasks' details.
+    ///
+    /// If the log filter matches all logs, the log files are only counted and
+    /// not read at all.
+    pub fn log_count(
+        &self,
+        project_filter: &FilterSpec<ProjectFilter>,
+        task_filter: &FilterSpec<TaskFilter>,
+        log_filter: &FilterSpec<LogFilter>,
+    ) -> Result<usize, Error> {
+        let mut count = 0;
+        for project in self.projects(project_filter)? {
+            let task_ids = self
+                .project_tasks(project.id(), task_filter, false)?
+                .iter()
+                .map(Task::id)
+                .collect::<Vec<Option<TaskId>>>();
+            for maybe_task_id in std::iter::once(None).chain(task_ids) {
+                count += if log_filter.is_passthrough() {
+                    log_files_in(self.logs_path(project.id(), maybe_task_id)?)?.len()
+                } else {
+                    self.logs_for_project_or_task(
+                        project.id(),
+                        maybe_task_id,
+                        log_filter,
+                        false,
+                        None,
+                    )?
+                    .len()
+                };
+            }
+        }
+        Ok(count)
+    }
+
+    /// Returns all of the tags used by projects, tasks and logs across the
+    /// store.
+    pub fn tags(&self) -> Result<BTreeSet<String>, Error> {
+        Ok(self.tag_counts()?.into_keys().collect())
+    }
+
+    /// Returns all of the tags used by projects, tasks and logs across the
+    /// store, along with the number of objects using each tag.
+    ///
+    /// Each project, task and log file is only read once.
+    pub fn tag_counts(&self) -> Result<BTreeMap<String, usize>, Error> {
+        let task_filter = FilterSpec::new(TaskFilter::All);
+        let log_filter = FilterSpec::new(LogFilter::All);
+        let mut counts = BTreeMap::new();
+        for project in self.projects(&FilterSpec::new(ProjectFilter::All))? {
+            count_tags(&mut counts, project.tags());
+            for log in
+                self.logs_for_project_or_task(project.id(), None, &log_filter, false, None)?
+            {
+                count_tags(&mut counts, log.tags());
+            }
+            for task in self.project_tasks(project.id(), &task_filter, false)? {
+                count_tags(&mut counts, task.tags());
+                for log in self.logs_for_project_or_task(
+                    project.id(),
+                    task.id(),
+                    &log_filter,
+                    false,
+                    None,
+                )? {
+                    count_tags(&mut counts, log.tags());
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Returns each distinct state held by the tasks of the matching
+    /// projects, along with the number of tasks in that state.
+    ///
+    /// Unlike the configured task states, this includes states that are no
+    /// longer configured (e.g. after the configuration has changed).
+    pub fn states_in_use(
+        &self,
+        project_filter: &FilterSpec<ProjectFilter>,
+    ) -> Result<BTreeMap<TaskState, usize>, Error> {
+        let mut counts = BTreeMap::new();
+        for task in self.tasks(project_filter, &FilterSpec::new(TaskFilter::All), false)? {
+            if let Some(state) = task.state() {
+                *counts.entry(state.to_string()).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Renames the given tag on every project, task and log across the store
+    /// that has it, returning the number of objects changed.
+    ///
+    /// Objects that already have the new tag keep just one copy of it, and
+    /// objects without the old tag are left untouched, so renaming is
+    /// idempotent.
+    pub fn rename_tag(&self, old: &str, new: &str) -> Result<usize, Error> {
+        let old = old.to_lowercase();
+        let new = validate_tag(new)?;
+        if old == new {
+            return Ok(0);
+        }
+        let task_filter = FilterSpec::new(TaskFilter::All);
+        let log_filter = FilterSpec::new(LogFilter::All);
+        let mut changed = 0;
+        for project in self.projects(&FilterSpec::new(ProjectFilter::All))? {
+            if let Some(tags) = renamed_tags(project.tags(), &old, &new) {
+                self.save_project(&project.clone().with_tags(tags)?)?;
+                changed += 1;
+            }
+            let mut logs =
+                self.logs_for_project_or_task(project.id(), None, &log_filter, false, None)?;
+            for task in self.project_tasks(project.id(), &task_filter, false)? {
+                logs.extend(self.logs_for_project_or_task(
+                    project.id(),
+                    task.id(),
+                    &log_filter,
+                    false,
+                    None,
+                )?);
+                if let Some(tags) = renamed_tags(task.tags(), &old, &new) {
+                    self.save_task(&task.with_tags(tags)?)?;
+                    changed += 1;
+                }
+            }
+            for log in logs {
+                if let Some(tags) = renamed_tags(log.tags(), &old, &new) {
+                    self.save_log(&log.with_tags(tags)?)?;
+                    changed += 1;
+                }
+            }
+        }
+        debug!("Renamed tag {} to {} on {} object(s)", old, new, changed);
+        Ok(changed)
+    }
+
+    /// Return all logs, across all projects and tasks, whose start time falls
+    /// on the same calendar day as the given timestamp.
+    ///
+    /// Logs are sorted by their start time.
+    pub fn logs_on_day(&self, day: Timestamp) -> Result<Vec<Log>, Error> {
+        let log_filter = FilterSpec::new(LogFilter::Start(TimestampFilter::Starting(day.today())))
+            .and_then(LogFilter::Start(TimestampFilter::Before(day.tomorrow())));
+        let logs = self.logs(
+            &FilterSpec::new(ProjectFilter::All),
+            &FilterSpec::new(TaskFilter::All),
+            &log_filter,
+            true,
+        )?;
+        Ok(SortSpec::new(LogField::Start, Order::Asc).sort(logs, Timestamp::now()?))
+    }
+
+    /// Return every pair of logs matching the given filters whose time
+    /// intervals intersect, e.g. because a timer wasn't stopped.
+    ///
+    /// Each log spans `[start, stop)`, where logs that are still running are
+    /// taken to extend until now. Logs without a start time, and logs marked
+    /// as breaks (which are taken within other logs by design), are ignored.
+    /// Pairs are ordered by the start time of their first log.
+    pub fn overlapping_logs(
+        &self,
+        project_filter: &FilterSpec<ProjectFilter>,
+        task_filter: &FilterSpec<TaskFilter>,
+        log_filter: &FilterSpec<LogFilter>,
+    ) -> Result<Vec<(Log, Log)>, Error> {
+        let now = Timestamp::now()?;
+        let config = self.config()?;
+        let logs = self.logs(project_filter, task_filter, log_filter, false)?;
+        let mut intervals = logs
+            .into_iter()
+            .filter(|log| !config.is_break(log))
+            .filter_map(|log| {
+                let start = log.start()?;
+                let stop = log.stop().unwrap_or(now);
+                Some((start, stop, log))
+            })
+            .collect::<Vec<(Timestamp, Timestamp, Log)>>();
+        intervals.sort_by_key(|(start, stop, _)| (*start, *stop));
+
+        let mut overlapping = Vec::new();
+        for (i, (_, stop, log)) in intervals.iter().enumerate() {
+            // Since intervals are sorted by start time, only those starting
+            // before this one stops can overlap it (unless they're empty).
+            overlapping.extend(
+                intervals[i + 1..]
+                    .iter()
+                    .take_while(|(other_start, _, _)| other_start < stop)
+                    .filter(|(other_start, other_stop, _)| other_start < other_stop)
+                    .map(|(_, _, other)| (log.clone(), other.clone())),
+            );
+        }
+        Ok(overlapping)
+    }
+
+    /// Get the logs belonging to a specific task, reading only that task's
+    /// logs directory.
+    pub fn logs_for_task(
+        &self,
+        project_id: &str,
+        task_id: TaskId,
+        filter: &FilterSpec<LogFilter>,
+    ) -> Result<Vec<Log>, Error> {
+        self.logs_for_project_or_task(project_id, Some(task_id), filter, false, None)
+    }
+
     /// Get all of the logs associated with the given project, and optionally
     /// with the given task.
     pub fn logs_for_project_or_task(
@@ -467,13 +1632,34 @@ impl Store {
         filter: &FilterSpec<LogFilter>,
         detailed: bool,
         maybe_task: Option<Task>,
+    ) -> Result<Vec<Log>, Error> {
+        let logs_path = self.logs_path(project_id, maybe_task_id)?;
+        self.logs_in(
+            &logs_path,
+            project_id,
+            maybe_task_id,
+            filter,
+            detailed,
+            maybe_task,
+        )
+    }
+
+    // Loads the logs in the given directory, once it has been found, without
+    // looking each log up by its ID again.
+    fn logs_in(
+        &self,
+        logs_path: &Path,
+        project_id: &str,
+        maybe_task_id: Option<TaskId>,
+        filter: &FilterSpec<LogFilter>,
+        detailed: bool,
+        maybe_task: Option<Task>,
     ) -> Result<Vec<Log>, Error> {
         let now = Timestamp::now()?;
-        let logs_path = self.logs_path(project_id, maybe_task_id);
-        if !is_dir(&logs_path) {
+        if !is_dir(logs_path) {
             return Ok(Vec::new());
         }
-        let logs = fs::read_dir(&logs_path)?
+        let mut logs = fs::read_dir(logs_path)?
             .into_iter()
             .filter_map(|r| {
                 if let Ok(e) = r {
@@ -483,7 +1669,7 @@ impl Store {
                             Ok(log_id) => log_id,
                             Err(_) => return None,
                         };
-                        return match self.log(project_id, maybe_task_id, log_id) {
+                        return match load_log(&path, project_id, maybe_task_id, log_id) {
                             Ok(log) => {
                                 if filter.matches(&log, now) {
                                     Some(Ok(if detailed {
@@ -495,13 +1681,14 @@ impl Store {
                                     None
                                 }
                             }
-                            Err(e) => Some(Err(e)),
+                            Err(e) => self.skip_if_corrupt(&path, e),
                         };
                     }
                 }
                 None
             })
             .collect::<Result<Vec<Log>, Error>>()?;
+        logs.sort_by_key(Log::id);
         Ok(logs)
     }
 
@@ -513,7 +1700,7 @@ impl Store {
         maybe_task_id: Option<TaskId>,
         id: LogId,
     ) -> Result<Log, Error> {
-        let log_path = self.log_path(project_id, maybe_task_id, id);
+        let log_path = self.log_path(project_id, maybe_task_id, id)?;
         if !is_file(&log_path) {
             return Err(Error::LogNotFound(
                 project_id.to_string(),
@@ -521,14 +1708,27 @@ impl Store {
                 id,
             ));
         }
-        Ok(load_from_json_file::<&PathBuf, Log>(&log_path)?
-            .with_id(id)
-            .with_project_id(project_id)
-            .with_maybe_task_id(maybe_task_id))
+        load_log(&log_path, project_id, maybe_task_id, id)
     }
 
     /// Create or update a work log.
+    ///
+    /// If the log has an ID, any existing log with that ID is overwritten. Use
+    /// [`Store::create_log`] to avoid this.
     pub fn save_log(&self, log: &Log) -> Result<Log, Error> {
+        self.write_log(log, true)
+    }
+
+    /// Create a new work log.
+    ///
+    /// Logs without an ID are assigned the next available ID. If the log has
+    /// an explicit ID that is already in use, this fails with
+    /// [`Error::LogAlreadyExists`] instead of overwriting the existing log.
+    pub fn create_log(&self, log: &Log) -> Result<Log, Error> {
+        self.write_log(log, false)
+    }
+
+    fn write_log(&self, log: &Log, overwrite: bool) -> Result<Log, Error> {
         let project_id = log
             .project_id()
             .ok_or_else(|| Error::LogMissingProjectId(log.clone()))?;
@@ -537,21 +1737,78 @@ impl Store {
             return Err(Error::ProjectNotFound(project_id.to_string()));
         }
         if let Some(task_id) = log.task_id() {
-            let task_path = self.task_path(project_id, task_id);
+            let task_path = self.task_path(project_id, task_id)?;
             if !is_dir(&task_path) {
                 return Err(Error::TaskNotFound(project_id.to_string(), task_id));
             }
         }
         let log_id = match log.id() {
             Some(id) => id,
-            None => self.next_log_id(project_id, log.task_id())?,
+            None => self.peek_next_log_id(project_id, log.task_id())?,
         };
-        let log_path = self.log_path(project_id, log.task_id(), log_id);
+        let log_path = padded_id_path(
+            self.logs_path(project_id, log.task_id())?,
+            log_id,
+            ".json",
+            self.config()?.log_id_width(),
+        )?;
+        if !overwrite && is_file(&log_path) {
+            return Err(Error::LogAlreadyExists(
+                project_id.to_string(),
+                log.task_id(),
+                log_id,
+            ));
+        }
         let log = log.clone().with_id(log_id);
         save_to_json_file(&log_path, &log)?;
+        self.record_log_id(project_id, log.task_id(), log_id)?;
         Ok(log)
     }
 
+    /// Move a work log to a different project and/or task, where it is
+    /// assigned the next available log ID. Returns the moved log.
+    ///
+    /// The log is written to its new location before being deleted from its
+    /// old one, so it is never lost if the move fails part way through.
+    pub fn move_log(
+        &self,
+        from: (&str, Option<TaskId>, LogId),
+        to: (&str, Option<TaskId>),
+    ) -> Result<Log, Error> {
+        let (from_project_id, from_maybe_task_id, from_id) = from;
+        let (to_project_id, to_maybe_task_id) = to;
+        let log = self.log(from_project_id, from_maybe_task_id, from_id)?;
+        if !is_dir(self.project_path(to_project_id)) {
+            return Err(Error::ProjectNotFound(to_project_id.to_string()));
+        }
+        if let Some(task_id) = to_maybe_task_id {
+            if !is_file(self.task_meta_path(to_project_id, task_id)?) {
+                return Err(Error::TaskNotFound(to_project_id.to_string(), task_id));
+            }
+        }
+        let new_id = self.peek_next_log_id(to_project_id, to_maybe_task_id)?;
+        let moved = self.create_log(
+            &log.with_project_id(to_project_id)
+                .with_maybe_task_id(to_maybe_task_id)
+                .with_id(new_id),
+        )?;
+        self.delete_log(from_project_id, from_maybe_task_id, from_id)?;
+        debug!(
+            "Moved log {} of project {}{} to log {} of project {}{}",
+            from_id,
+            from_project_id,
+            from_maybe_task_id
+                .map(|task_id| format!(", task {}", task_id))
+                .unwrap_or_default(),
+            new_id,
+            to_project_id,
+            to_maybe_task_id
+                .map(|task_id| format!(", task {}", task_id))
+                .unwrap_or_default(),
+        );
+        Ok(moved)
+    }
+
     /// Delete the given log from the store.
     pub fn delete_log(
         &self,
@@ -559,12 +1816,150 @@ impl Store {
         maybe_task_id: Option<TaskId>,
         id: TaskId,
     ) -> Result<(), Error> {
-        let log_path = self.log_path(project_id, maybe_task_id, id);
+        let log_path = self.log_path(project_id, maybe_task_id, id)?;
         fs::remove_file(&log_path)?;
         Ok(())
     }
 }
 
+// The IDs to assign to a project's next new task and next new logs. These are
+// persisted so that assigning an ID doesn't require scanning the project's
+// directory, and so that IDs are never reused once the tasks or logs holding
+// them are removed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct IdCounters {
+    #[serde(rename = "next_task_id")]
+    maybe_next_task_id: Option<TaskId>,
+    // For logs that don't relate to a task.
+    #[serde(rename = "next_log_id")]
+    maybe_next_log_id: Option<LogId>,
+    #[serde(default)]
+    next_task_log_ids: BTreeMap<TaskId, LogId>,
+}
+
+impl IdCounters {
+    fn next_log_id(&self, maybe_task_id: Option<TaskId>) -> Option<LogId> {
+        match maybe_task_id {
+            Some(task_id) => self.next_task_log_ids.get(&task_id).copied(),
+            None => self.maybe_next_log_id,
+        }
+    }
+
+    fn set_next_log_id(&mut self, maybe_task_id: Option<TaskId>, next_log_id: LogId) {
+        match maybe_task_id {
+            Some(task_id) => {
+                self.next_task_log_ids.insert(task_id, next_log_id);
+            }
+            None => self.maybe_next_log_id = Some(next_log_id),
+        }
+    }
+}
+
+/// A problem found when verifying a store (see [`Store::verify`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Inconsistency {
+    /// A work log file beneath a directory without project metadata.
+    OrphanedLog(PathBuf),
+    /// A work log file in a task directory without task metadata.
+    LogWithoutTask(PathBuf),
+    /// A task whose state is not one of its project's task states.
+    InvalidTaskState(ProjectId, TaskId, TaskState),
+    /// Task directories or work log files whose names parse to the same ID.
+    IdCollision(Vec<PathBuf>),
+    /// A file that could not be parsed, along with the parsing error.
+    UnparseableFile(PathBuf, String),
+}
+
+impl std::fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OrphanedLog(path) => write!(
+                f,
+                "work log {} has no associated project",
+                path.display()
+            ),
+            Self::LogWithoutTask(path) => write!(
+                f,
+                "work log {} has no associated task",
+                path.display()
+            ),
+            Self::InvalidTaskState(project_id, task_id, state) => write!(
+                f,
+                "task {} of project \"{}\" has state \"{}\", which is not one of the project's task states",
+                task_id, project_id, state
+            ),
+            Self::IdCollision(paths) => write!(
+                f,
+                "IDs collide (only one is visible): {}",
+                paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Self::UnparseableFile(path, e) => {
+                write!(f, "failed to parse {}: {}", path.display(), e)
+            }
+        }
+    }
+}
+
+/// A change made to a store while repairing an inconsistency (see
+/// [`Store::repair`]).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Repair {
+    /// A task's state was reset to its project's initial task state.
+    TaskStateReset(ProjectId, TaskId, TaskState),
+    /// A task directory or work log file was moved, and so given a new ID.
+    Moved(PathBuf, PathBuf),
+}
+
+impl std::fmt::Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TaskStateReset(project_id, task_id, state) => write!(
+                f,
+                "reset the state of task {} of project \"{}\" to \"{}\"",
+                task_id, project_id, state
+            ),
+            Self::Moved(from, to) => write!(f, "moved {} to {}", from.display(), to.display()),
+        }
+    }
+}
+
+/// An exclusive lock on a [`Store`], released when dropped.
+#[derive(Debug)]
+pub struct StoreLock {
+    file: File,
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            debug!("Failed to release store lock: {}", e);
+        }
+    }
+}
+
+// Loads an object from the given JSON file while verifying the store, recording
+// the file as an inconsistency instead of failing if it cannot be parsed.
+fn load_verified<O: DeserializeOwned>(
+    path: &Path,
+    inconsistencies: &mut Vec<Inconsistency>,
+) -> Result<Option<O>, Error> {
+    match load_from_json_file(path) {
+        Ok(o) => Ok(Some(o)),
+        Err(Error::Serialize(e, _)) => {
+            inconsistencies.push(Inconsistency::UnparseableFile(
+                path.to_path_buf(),
+                e.to_string(),
+            ));
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 fn load_from_json_file<P, O>(path: P) -> Result<O, Error>
 where
     P: AsRef<Path>,
@@ -593,9 +1988,41 @@ where
         fs::create_dir_all(parent_path)?;
         debug!("Created path: {}", parent_path.display());
     }
-    let s =
-        serde_json::to_string_pretty(obj).map_err(|e| Error::Serialize(e, format!("{:?}", obj)))?;
-    Ok(fs::write(path, &s)?)
+    write_atomically(path, |writer| {
+        serde_json::to_writer_pretty(writer, obj)
+            .map_err(|e| Error::Serialize(e, format!("{:?}", obj)))
+    })
+}
+
+// Writes a file by way of a temporary file in the same directory, which only
+// replaces the destination once it has been written in full. This way a crash
+// (or a failure) part way through writing never leaves a truncated destination
+// file behind. The temporary file is removed if writing fails.
+fn write_atomically<F>(path: &Path, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Error>,
+{
+    let mut tmp_file_name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidPath(path.to_path_buf()))?
+        .to_os_string();
+    tmp_file_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_file_name);
+    let result = File::create(&tmp_path)
+        .map_err(Error::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            let file = writer.into_inner().map_err(|e| e.into_error())?;
+            // Ensure the contents are on disk before they replace the
+            // destination
+            file.sync_all()?;
+            Ok(fs::rename(&tmp_path, path)?)
+        });
+    if result.is_err() && is_file(&tmp_path) {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
 }
 
 pub(crate) fn is_file<P: AsRef<Path>>(path: P) -> bool {
@@ -630,6 +2057,144 @@ fn log_id_from_path<P: AsRef<Path>>(path: P) -> Result<LogId, Error> {
     LogId::from_str(file_name).map_err(|e| Error::InvalidLogNumber(path.to_path_buf(), e))
 }
 
+// Returns the path of the file or directory within `dir` named after the given
+// ID (zero-padded to any width) followed by the given suffix, listing `dir`
+// only once to find it. If no such path exists, the ID is padded to the given
+// width. If more than one exists (e.g. both `3` and `0003`), their IDs
+// collide.
+fn padded_id_path<P: AsRef<Path>>(
+    dir: P,
+    id: u32,
+    suffix: &str,
+    width: usize,
+) -> Result<PathBuf, Error> {
+    let dir = dir.as_ref();
+    let mut paths = Vec::new();
+    if is_dir(dir) {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_id_path = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .and_then(|name| name.strip_suffix(suffix))
+                .filter(|digits| {
+                    !digits.is_empty()
+                        && digits.len() <= MAX_ID_WIDTH
+                        && digits.bytes().all(|b| b.is_ascii_digit())
+                })
+                .map(|digits| u32::from_str(digits) == Ok(id))
+                .unwrap_or(false);
+            if is_id_path {
+                paths.push(path);
+            }
+        }
+    }
+    match paths.len() {
+        0 => Ok(dir.join(format!("{:0w$}{}", id, suffix, w = width))),
+        1 => Ok(paths.remove(0)),
+        _ => {
+            paths.sort();
+            Err(Error::IdCollision(paths))
+        }
+    }
+}
+
+// Loads a work log from its file, which has already been found.
+fn load_log(
+    path: &Path,
+    project_id: &str,
+    maybe_task_id: Option<TaskId>,
+    id: LogId,
+) -> Result<Log, Error> {
+    Ok(load_from_json_file::<&Path, Log>(path)?
+        .with_id(id)
+        .with_project_id(project_id)
+        .with_maybe_task_id(maybe_task_id))
+}
+
+// Whether the given path is a parseable work log that is still running.
+fn is_running_log_file<P: AsRef<Path>>(path: P) -> bool {
+    is_file(&path)
+        && load_from_json_file::<P, Log>(path)
+            .map(|log| log.duration().is_none())
+            .unwrap_or(false)
+}
+
+// The paths of the task directories within the given directory.
+fn task_paths_in(tasks_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut task_paths = Vec::new();
+    if is_dir(tasks_path) {
+        for entry in fs::read_dir(tasks_path)? {
+            let path = entry?.path();
+            if is_dir(&path) && task_id_from_path(&path).is_ok() {
+                task_paths.push(path);
+            }
+        }
+    }
+    Ok(task_paths)
+}
+
+// The ID following the highest of the IDs parsed from the given task
+// directories or work log files.
+fn next_free_id(paths: Vec<PathBuf>, starting_id: u32) -> u32 {
+    paths
+        .iter()
+        .filter_map(|path| {
+            task_id_from_path(path)
+                .or_else(|_| log_id_from_path(path))
+                .ok()
+        })
+        .max()
+        .map(|highest_id| highest_id + 1)
+        .unwrap_or(starting_id)
+}
+
+// Groups the given paths by the IDs parsed from them, returning only those
+// groups containing more than one path.
+fn group_colliding_ids<F>(paths: Vec<PathBuf>, parse_id: F) -> Vec<Vec<PathBuf>>
+where
+    F: Fn(&Path) -> Result<u32, Error>,
+{
+    let mut paths_by_id = BTreeMap::<u32, Vec<PathBuf>>::new();
+    for path in paths {
+        if let Ok(id) = parse_id(&path) {
+            paths_by_id.entry(id).or_default().push(path);
+        }
+    }
+    paths_by_id
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|mut paths| {
+            paths.sort();
+            paths
+        })
+        .collect()
+}
+
+// Returns the paths of all of the log files directly within the given
+// directory, or nothing if the directory does not exist.
+fn log_files_in<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, Error> {
+    let path = path.as_ref();
+    if !is_dir(path) {
+        return Ok(Vec::new());
+    }
+    let mut log_files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        let is_json = path.extension().and_then(OsStr::to_str) == Some("json");
+        if is_file(&path) && is_json && log_id_from_path(&path).is_ok() {
+            log_files.push(path);
+        }
+    }
+    Ok(log_files)
+}
+
+// Whether the given project ID could name a directory directly within the
+// store (e.g. it isn't a relative path like "../elsewhere").
+fn is_valid_project_dir_name(id: &str) -> bool {
+    !id.is_empty() && !id.starts_with('.') && !id.contains(['/', '\\'])
+}
+
 fn ensure_dir_exists<P: AsRef<Path>>(path: P) -> Result<(), Error> {
     let path = path.as_ref();
     if !is_dir(path) {
@@ -638,3 +2203,128 @@ fn ensure_dir_exists<P: AsRef<Path>>(path: P) -> Result<(), Error> {
     }
     Ok(())
 }
+
+fn count_tags<'a, I>(counts: &mut BTreeMap<String, usize>, tags: I)
+where
+    I: Iterator<Item = &'a str>,
+{
+    for tag in tags {
+        *counts.entry(tag.to_string()).or_insert(0) += 1;
+    }
+}
+
+// Returns the given tags with the old tag replaced by the new one, or nothing
+// if the old tag isn't among them.
+fn renamed_tags<'a, I>(tags: I, old: &str, new: &str) -> Option<Vec<String>>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let tags = tags.collect::<Vec<&str>>();
+    if !tags.contains(&old) {
+        return None;
+    }
+    Some(
+        tags.into_iter()
+            .map(|tag| if tag == old { new } else { tag })
+            .map(String::from)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use tempfile::tempdir;
+
+    thread_local! {
+        // The number of times a project has been loaded on this thread.
+        pub(super) static PROJECT_LOADS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Debug)]
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("cannot serialize"))
+        }
+    }
+
+    #[test]
+    fn failed_write_leaves_existing_file_intact() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("nested").join("object.json");
+        save_to_json_file(&path, &vec!["original"]).unwrap();
+
+        assert!(matches!(
+            save_to_json_file(&path, &Unserializable),
+            Err(Error::Serialize(_, _))
+        ));
+        assert_eq!(
+            load_from_json_file::<_, Vec<String>>(&path).unwrap(),
+            vec!["original".to_string()]
+        );
+        // Only the original file remains, without any temporary file
+        let entries = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![OsStr::new("object.json").to_os_string()]);
+    }
+
+    #[test]
+    fn logs_load_each_project_once() {
+        let temp_dir = tempdir().unwrap();
+        let store = Store::new(temp_dir.path()).unwrap();
+        for name in ["Project 1", "Project 2"] {
+            let project = Project::new(name);
+            store.save_project(&project).unwrap();
+            let task = store
+                .save_task(&Task::new(project.id(), "Some task"))
+                .unwrap();
+            store.create_log(&Log::new(project.id())).unwrap();
+            store
+                .create_log(&Log::new(project.id()).with_maybe_task_id(task.id()))
+                .unwrap();
+        }
+
+        PROJECT_LOADS.with(|loads| loads.set(0));
+        let logs = store
+            .logs(
+                &FilterSpec::new(ProjectFilter::All),
+                &FilterSpec::new(TaskFilter::All),
+                &FilterSpec::new(LogFilter::All),
+                true,
+            )
+            .unwrap();
+        assert_eq!(logs.len(), 4);
+        assert_eq!(PROJECT_LOADS.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn iterating_projects_stops_early() {
+        let temp_dir = tempdir().unwrap();
+        let store = Store::new(temp_dir.path()).unwrap();
+        for name in ["Project 1", "Project 2", "Project 3"] {
+            store.save_project(&Project::new(name)).unwrap();
+        }
+
+        PROJECT_LOADS.with(|loads| loads.set(0));
+        let projects = store
+            .iter_projects(FilterSpec::new(ProjectFilter::All))
+            .take(1)
+            .collect::<Result<Vec<Project>, Error>>()
+            .unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(PROJECT_LOADS.with(Cell::get), 1);
+
+        assert_eq!(
+            store
+                .projects(&FilterSpec::new(ProjectFilter::All))
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
@@ -14,9 +14,64 @@ pub fn slugify(s: &str) -> String {
         .to_string()
 }
 
+/// Renders the given string as a CSV field as per RFC 4180, quoting it if it
+/// contains commas, quotes or line breaks, and doubling any embedded quotes.
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Escapes the given string for use as an iCalendar text value as per RFC
+/// 5545, i.e. escaping backslashes, semicolons, commas and line breaks.
+pub fn ical_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds the given iCalendar content line as per RFC 5545, so that no line is
+/// longer than 75 octets, and terminates each resulting line with CRLF.
+/// Characters are never split across lines.
+pub fn ical_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(c);
+        line_len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Computes the Levenshtein (edit) distance between the given strings, i.e.
+/// the number of single-character insertions, deletions and substitutions
+/// needed to turn one into the other.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut prev_row = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev_row[j] + usize::from(ca != *cb);
+            row[j + 1] = substitution.min(prev_row[j + 1] + 1).min(row[j] + 1);
+        }
+        prev_row = row;
+    }
+    prev_row[b.len()]
+}
+
 #[cfg(test)]
 mod test {
-    use super::slugify;
+    use super::{csv_field, ical_line, ical_text, levenshtein, slugify};
 
     #[test]
     fn test_slugify() {
@@ -43,4 +98,59 @@ mod test {
             assert_eq!(actual, expected.to_string());
         }
     }
+
+    #[test]
+    fn test_csv_field() {
+        const TEST_CASES: &[(&str, &str)] = &[
+            ("", ""),
+            ("plain", "plain"),
+            ("a,b", "\"a,b\""),
+            ("say \"hi\"", "\"say \"\"hi\"\"\""),
+            ("two\nlines", "\"two\nlines\""),
+        ];
+        for (s, expected) in TEST_CASES {
+            assert_eq!(csv_field(s), expected.to_string());
+        }
+    }
+
+    #[test]
+    fn test_ical_text() {
+        const TEST_CASES: &[(&str, &str)] = &[
+            ("plain", "plain"),
+            ("a,b;c", "a\\,b\\;c"),
+            ("back\\slash", "back\\\\slash"),
+            ("two\nlines", "two\\nlines"),
+        ];
+        for (s, expected) in TEST_CASES {
+            assert_eq!(ical_text(s), expected.to_string());
+        }
+    }
+
+    #[test]
+    fn test_ical_line() {
+        assert_eq!(ical_line("SUMMARY:short"), "SUMMARY:short\r\n");
+        let long = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = ical_line(&long);
+        let lines = folded.split_terminator("\r\n").collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines.concat().replacen(' ', "", 1), long);
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        const TEST_CASES: &[(&str, &str, usize)] = &[
+            ("", "", 0),
+            ("project-1", "project-1", 0),
+            ("", "abc", 3),
+            ("projetc-1", "project-1", 2),
+            ("project-1", "project-12", 1),
+            ("kitten", "sitting", 3),
+        ];
+        for (a, b, expected) in TEST_CASES {
+            assert_eq!(levenshtein(a, b), *expected, "{} -> {}", a, b);
+            assert_eq!(levenshtein(b, a), *expected, "{} -> {}", b, a);
+        }
+    }
 }
@@ -4,7 +4,9 @@ use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 use time::macros::time;
-use time::{format_description, Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+use time::{
+    format_description, Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset, Weekday,
+};
 
 use crate::Error;
 
@@ -30,9 +32,45 @@ const TIME_ONLY_FORMATS: &[&str] = &[
     "[hour]:[minute]:[second]",
 ];
 
+// Units of relative timestamps like "3 days ago". Only whole days and weeks
+// can be combined with a time of day (e.g. "3 days ago@14:00").
+const AGO_DATE_UNITS: &[(&str, time::Duration)] = &[
+    ("day", time::Duration::DAY),
+    ("days", time::Duration::DAY),
+    ("week", time::Duration::WEEK),
+    ("weeks", time::Duration::WEEK),
+];
+const AGO_TIME_UNITS: &[(&str, time::Duration)] = &[
+    ("hour", time::Duration::HOUR),
+    ("hours", time::Duration::HOUR),
+    ("minute", time::Duration::MINUTE),
+    ("minutes", time::Duration::MINUTE),
+    ("min", time::Duration::MINUTE),
+    ("mins", time::Duration::MINUTE),
+];
+
+// Full and abbreviated weekday names, for timestamps like "last monday".
+const WEEKDAY_NAMES: &[(&str, &str, Weekday)] = &[
+    ("monday", "mon", Weekday::Monday),
+    ("tuesday", "tue", Weekday::Tuesday),
+    ("wednesday", "wed", Weekday::Wednesday),
+    ("thursday", "thu", Weekday::Thursday),
+    ("friday", "fri", Weekday::Friday),
+    ("saturday", "sat", Weekday::Saturday),
+    ("sunday", "sun", Weekday::Sunday),
+];
+
 const DEFAULT_TIMESTAMP_FORMAT: &str =
     "[year]-[month]-[day] [hour]:[minute] [offset_hour sign:mandatory]";
 
+const TIME_OF_DAY_FORMAT: &str = "[hour]:[minute]";
+const DATE_FORMAT: &str = "[year]-[month]-[day]";
+const ICAL_TIMESTAMP_FORMAT: &str = "[year][month][day]T[hour][minute][second]Z";
+
+// The approximate lengths of a month and a year, in hours.
+const HOURS_PER_MONTH: i64 = 30 * 24;
+const HOURS_PER_YEAR: i64 = 365 * 24;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, PartialOrd, Ord, Eq, Hash)]
 pub struct Timestamp(#[serde(with = "timestamp_s18n")] OffsetDateTime);
 
@@ -51,81 +89,225 @@ impl Timestamp {
         Ok(Self(OffsetDateTime::now_local()?))
     }
 
+    /// Render only the time of day (hours and minutes) of this timestamp.
+    pub fn format_time(&self) -> String {
+        self.0
+            .format(&format_description::parse(TIME_OF_DAY_FORMAT).unwrap())
+            .unwrap()
+    }
+
+    /// Render this timestamp in UTC in the basic date-time format used by
+    /// iCalendar (RFC 5545), e.g. `20211201T123000Z`.
+    pub fn to_ical(&self) -> String {
+        self.0
+            .to_offset(UtcOffset::UTC)
+            .format(&format_description::parse(ICAL_TIMESTAMP_FORMAT).unwrap())
+            .unwrap()
+    }
+
+    /// Render this timestamp in RFC 3339 format (e.g.
+    /// `2021-12-01T14:30:00+02:00`).
+    pub fn to_rfc3339(&self) -> String {
+        self.0
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap()
+    }
+
+    /// Render only the date (year, month and day) of this timestamp.
+    pub fn format_date(&self) -> String {
+        self.0
+            .format(&format_description::parse(DATE_FORMAT).unwrap())
+            .unwrap()
+    }
+
+    /// The hour of the day (0-23) of this timestamp.
+    pub fn hour(&self) -> u8 {
+        self.0.hour()
+    }
+
+    /// The day of the week of this timestamp, as the number of days from
+    /// Monday (0-6).
+    pub fn days_from_monday(&self) -> u8 {
+        self.0.weekday().number_days_from_monday()
+    }
+
+    /// Whether this timestamp falls on a Saturday or Sunday.
+    pub fn is_weekend(&self) -> bool {
+        self.days_from_monday() >= 5
+    }
+
+    /// Timestamp as at the beginning of the next hour.
+    pub fn next_hour(&self) -> Self {
+        Self(
+            self.0
+                .replace_time(Time::from_hms(self.0.hour(), 0, 0).unwrap())
+                + time::Duration::HOUR,
+        )
+    }
+
     /// Return the timestamp of the beginning of the day today.
     pub fn today(&self) -> Self {
-        Self(self.0.replace_time(time!(00:00)))
+        self.start_of_day(self.0.date())
     }
 
     /// Timestamp as at the beginning of tomorrow.
     pub fn tomorrow(&self) -> Self {
-        Self(self.today().0 + time::Duration::DAY)
+        self.start_of_day(self.0.date().next_day().unwrap())
     }
 
     /// Timestamp as at the beginning of yesterday.
     pub fn yesterday(&self) -> Self {
-        Self(self.today().0 - time::Duration::DAY)
+        self.start_of_day(self.0.date().previous_day().unwrap())
     }
 
-    /// Return the timestamp of the beginning of the day on Monday of this week.
-    pub fn this_week(&self) -> Self {
-        let today = self.today().0;
-        let days_from_monday = today.weekday().number_days_from_monday();
-        // Subtract the number of days from Monday
-        let monday = today
-            - time::Duration::DAY
-                .checked_mul(days_from_monday.into())
-                .unwrap();
-        Self(monday)
+    /// Return the timestamp of the beginning of the day on which this week
+    /// started, for weeks starting on the given day.
+    pub fn this_week(&self, week_start: WeekStart) -> Self {
+        self.start_of_day(self.week_start_date(week_start))
     }
 
     /// Timestamp 1 week from the beginning of this week.
-    pub fn next_week(&self) -> Self {
-        Self(self.this_week().0 + time::Duration::DAY.checked_mul(7).unwrap())
+    pub fn next_week(&self, week_start: WeekStart) -> Self {
+        self.start_of_day(self.week_start_date(week_start) + time::Duration::WEEK)
+    }
+
+    /// Timestamp 1 week before the beginning of this week.
+    pub fn last_week(&self, week_start: WeekStart) -> Self {
+        self.start_of_day(self.week_start_date(week_start) - time::Duration::WEEK)
     }
 
     /// Return the timestamp of the beginning of the day of the given number of
     /// days back in time.
     pub fn days_back(&self, days: u16) -> Self {
-        Self(self.today().0 - time::Duration::DAY.checked_mul(days.into()).unwrap())
+        self.start_of_day(self.0.date() - time::Duration::days(days.into()))
     }
 
     /// Timestamp at the beginning of the day of the given number of days
     /// forward in time.
     pub fn days_forward(&self, days: u16) -> Self {
-        Self(self.today().0 + time::Duration::DAY.checked_mul(days.into()).unwrap())
+        self.start_of_day(self.0.date() + time::Duration::days(days.into()))
     }
 
     /// Return the timestamp of the beginning of the day on the first day of
     /// this month.
     pub fn this_month(&self) -> Self {
-        let today = self.today().0;
-        Self(today.replace_date(Date::from_calendar_date(today.year(), today.month(), 1).unwrap()))
+        let date = self.0.date();
+        self.start_of_day(Date::from_calendar_date(date.year(), date.month(), 1).unwrap())
+    }
+
+    /// Return the timestamp of the beginning of the day on the first day of
+    /// last month.
+    pub fn last_month(&self) -> Self {
+        let date = self.0.date();
+        let year = match date.month() {
+            Month::January => date.year() - 1,
+            _ => date.year(),
+        };
+        self.start_of_day(Date::from_calendar_date(year, date.month().previous(), 1).unwrap())
     }
 
     /// Timestamp as at the beginning of the day on the first day of next month.
     pub fn next_month(&self) -> Self {
-        let next_month = self.this_month().0 + (32 * time::Duration::DAY);
-        Self(next_month.replace_date(
-            Date::from_calendar_date(next_month.year(), next_month.month(), 1).unwrap(),
-        ))
+        let date = self.0.date();
+        let year = match date.month() {
+            Month::December => date.year() + 1,
+            _ => date.year(),
+        };
+        self.start_of_day(Date::from_calendar_date(year, date.month().next(), 1).unwrap())
+    }
+
+    /// Return the timestamp of the beginning of the day on the first day of
+    /// this calendar quarter (i.e. of January, April, July or October).
+    pub fn this_quarter(&self) -> Self {
+        self.start_of_day(self.quarter_start(0))
+    }
+
+    /// Timestamp as at the beginning of the day on the first day of next
+    /// calendar quarter.
+    pub fn next_quarter(&self) -> Self {
+        self.start_of_day(self.quarter_start(1))
+    }
+
+    /// Return the timestamp of the beginning of the day on the first day of
+    /// last calendar quarter.
+    pub fn last_quarter(&self) -> Self {
+        self.start_of_day(self.quarter_start(-1))
     }
 
     /// Return the timestamp of the beginning of the day on the first of January
     /// of this year.
     pub fn this_year(&self) -> Self {
-        let today = self.today().0;
-        Self(today.replace_date(Date::from_calendar_date(today.year(), Month::January, 1).unwrap()))
+        self.start_of_day(Date::from_calendar_date(self.0.year(), Month::January, 1).unwrap())
+    }
+
+    /// Return the timestamp of the beginning of the day on the first of January
+    /// of last year.
+    pub fn last_year(&self) -> Self {
+        self.start_of_day(Date::from_calendar_date(self.0.year() - 1, Month::January, 1).unwrap())
     }
 
     /// Timestamp as at the beginning of the day on the first of January of next
     /// year.
     pub fn next_year(&self) -> Self {
-        let next_year = self.this_year().0 + (366 * time::Duration::DAY);
-        Self(
-            next_year.replace_date(
-                Date::from_calendar_date(next_year.year(), Month::January, 1).unwrap(),
-            ),
-        )
+        self.start_of_day(Date::from_calendar_date(self.0.year() + 1, Month::January, 1).unwrap())
+    }
+
+    // The first day of the calendar quarter the given number of quarters away
+    // from this timestamp's quarter.
+    fn quarter_start(&self, quarters: i32) -> Date {
+        let date = self.0.date();
+        let quarter = date.year() * 4 + (date.month() as i32 - 1) / 3 + quarters;
+        let month = Month::try_from((quarter.rem_euclid(4) * 3 + 1) as u8).unwrap();
+        Date::from_calendar_date(quarter.div_euclid(4), month, 1).unwrap()
+    }
+
+    // The date on which this timestamp's week started.
+    fn week_start_date(&self, week_start: WeekStart) -> Date {
+        let days_since_start = (self.days_from_monday() + 7 - week_start.days_from_monday()) % 7;
+        self.0.date() - time::Duration::days(days_since_start.into())
+    }
+
+    // Midnight at the beginning of the given day. Days are stepped through by
+    // date rather than by adding multiples of 24 hours, so that a day which is
+    // 23 or 25 hours long because of a daylight saving time transition still
+    // ends at midnight.
+    fn start_of_day(&self, date: Date) -> Self {
+        Self(local_datetime(date.midnight(), self.0, |dt| {
+            UtcOffset::local_offset_at(dt).ok()
+        }))
+    }
+
+    /// Return the timestamp on the same day as this one, at the same
+    /// wall-clock time of day as the given timestamp (e.g. 09:00 on each of a
+    /// range of days, regardless of daylight saving time transitions).
+    pub fn at_time_of_day(&self, time_of_day: Timestamp) -> Self {
+        Self(local_datetime(
+            self.0.date().with_time(time_of_day.0.time()),
+            self.0,
+            |dt| UtcOffset::local_offset_at(dt).ok(),
+        ))
+    }
+}
+
+// Computes the given date and wall-clock time as a timestamp. If the reference
+// timestamp is in local time, the local offset in effect at that time is used
+// (which differs from the reference's offset across a daylight saving time
+// transition). Otherwise the reference's offset is kept.
+fn local_datetime<F>(
+    datetime: PrimitiveDateTime,
+    reference: OffsetDateTime,
+    local_offset_at: F,
+) -> OffsetDateTime
+where
+    F: Fn(OffsetDateTime) -> Option<UtcOffset>,
+{
+    let with_reference_offset = datetime.assume_offset(reference.offset());
+    if local_offset_at(reference) != Some(reference.offset()) {
+        return with_reference_offset;
+    }
+    match local_offset_at(with_reference_offset) {
+        Some(offset) => datetime.assume_offset(offset),
+        None => with_reference_offset,
     }
 }
 
@@ -178,6 +360,51 @@ impl std::ops::Sub for Timestamp {
     }
 }
 
+impl std::ops::Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Timestamp(self.0 + rhs.0)
+    }
+}
+
+impl std::ops::Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Timestamp(self.0 - rhs.0)
+    }
+}
+
+/// Serialization of optional [`Duration`]s as ISO 8601 durations (e.g.
+/// `PT1H30M`) for use with `#[serde(with = "...")]`, for interoperability
+/// with other systems.
+///
+/// Durations are otherwise serialized in Loiter's own compact format.
+pub mod maybe_duration_iso8601 {
+    use super::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(duration) => serializer.serialize_str(&duration.to_iso8601()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| Duration::from_iso8601(&s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
 mod timestamp_s18n {
     use serde::{Deserialize, Deserializer, Serializer};
     use time::{format_description::well_known::Rfc3339, OffsetDateTime};
@@ -203,6 +430,21 @@ fn parse_timestamp<S: AsRef<str>>(
     ts: S,
     local_now: &OffsetDateTime,
 ) -> Result<OffsetDateTime, Error> {
+    parse_timestamp_with_offsets(ts, local_now, |dt| UtcOffset::local_offset_at(dt).ok())
+}
+
+// Parses the given timestamp, where relative dates (e.g. "last monday") are
+// resolved using the local offset in effect on the day they refer to, rather
+// than the one in effect now.
+fn parse_timestamp_with_offsets<S, F>(
+    ts: S,
+    local_now: &OffsetDateTime,
+    local_offset_at: F,
+) -> Result<OffsetDateTime, Error>
+where
+    S: AsRef<str>,
+    F: Fn(OffsetDateTime) -> Option<UtcOffset>,
+{
     let ts_orig = ts.as_ref().to_string();
 
     if ts_orig.trim() == "now" {
@@ -228,18 +470,34 @@ fn parse_timestamp<S: AsRef<str>>(
         }
     }
 
-    let (prefix_offset, ts) = parse_prefix_offset(&ts_orig)?;
+    if let Some(ago) = parse_ago(&ts_orig, &[AGO_DATE_UNITS, AGO_TIME_UNITS]) {
+        return Ok(*local_now - ago);
+    }
+    if let Some(offset) = parse_weekday(&ts_orig, local_now.weekday()) {
+        let date = local_now.date() + offset;
+        return Ok(local_datetime(
+            date.midnight(),
+            *local_now,
+            &local_offset_at,
+        ));
+    }
+
+    let (prefix_offset, ts) = parse_prefix_offset(&ts_orig, local_now.weekday())?;
     for fmt in TIME_ONLY_FORMATS {
         if let Ok(t) = Time::parse(&ts, &format_description::parse(fmt)?) {
-            let dt = local_now.replace_time(t) + prefix_offset;
-            return Ok(dt);
+            let date = local_now.date() + prefix_offset;
+            return Ok(local_datetime(
+                date.with_time(t),
+                *local_now,
+                &local_offset_at,
+            ));
         }
     }
 
     Err(Error::InvalidDateTime(ts_orig))
 }
 
-fn parse_prefix_offset(ts: &str) -> Result<(time::Duration, String), Error> {
+fn parse_prefix_offset(ts: &str, today: Weekday) -> Result<(time::Duration, String), Error> {
     let ts = ts.to_string();
     let ts_lower = ts.to_lowercase();
     let ts_parts = ts_lower
@@ -262,7 +520,13 @@ fn parse_prefix_offset(ts: &str) -> Result<(time::Duration, String), Error> {
                 Ok(match prefix.as_str() {
                     "yesterday" | "yst" => -time::Duration::DAY,
                     "tomorrow" | "tmrw" => time::Duration::DAY,
-                    _ => return Err(Error::InvalidDateTime(ts)),
+                    other => match parse_ago(other, &[AGO_DATE_UNITS]) {
+                        Some(ago) => -ago,
+                        None => match parse_weekday(other, today) {
+                            Some(offset) => offset,
+                            None => return Err(Error::InvalidDateTime(ts)),
+                        },
+                    },
                 })
             })
             .unwrap_or(Ok(time::Duration::ZERO))?,
@@ -270,6 +534,224 @@ fn parse_prefix_offset(ts: &str) -> Result<(time::Duration, String), Error> {
     ))
 }
 
+// Parses relative phrases like "3 days ago" into how long ago they refer to,
+// as long as their unit is one of the given ones.
+fn parse_ago(s: &str, units: &[&[(&str, time::Duration)]]) -> Option<time::Duration> {
+    let s = s.trim().to_lowercase();
+    let mut parts = s.strip_suffix("ago")?.split_whitespace();
+    let (amount, unit) = (parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    let amount = i32::from_str(amount).ok()?;
+    let (_, unit_duration) = units
+        .iter()
+        .flat_map(|units| units.iter())
+        .find(|(name, _)| *name == unit)?;
+    unit_duration.checked_mul(amount)
+}
+
+// Parses weekday names like "monday", "last tue" or "next friday" into the
+// number of days from today to the nearest such weekday. A bare weekday name
+// refers to the most recent one (which may be today), while "last" and "next"
+// always refer to a weekday before or after today respectively.
+fn parse_weekday(s: &str, today: Weekday) -> Option<time::Duration> {
+    let s = s.trim().to_lowercase();
+    let mut parts = s.split_whitespace();
+    let (maybe_qualifier, name) = match (parts.next()?, parts.next(), parts.next()) {
+        (name, None, None) => (None, name),
+        (qualifier, Some(name), None) => (Some(qualifier), name),
+        _ => return None,
+    };
+    let (_, _, weekday) = WEEKDAY_NAMES
+        .iter()
+        .find(|(full, short, _)| name == *full || name == *short)?;
+    let days_since = (today.number_days_from_monday() as i64
+        - weekday.number_days_from_monday() as i64)
+        .rem_euclid(7);
+    let days = match maybe_qualifier {
+        None => -days_since,
+        Some("last") if days_since == 0 => -7,
+        Some("last") => -days_since,
+        Some("next") => 7 - days_since,
+        Some(_) => return None,
+    };
+    Some(time::Duration::days(days))
+}
+
+/// The day on which calendar weeks start.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    // The number of days from Monday to this day.
+    fn days_from_monday(&self) -> u8 {
+        match self {
+            Self::Monday => 0,
+            Self::Sunday => 6,
+        }
+    }
+}
+
+impl FromStr for WeekStart {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "monday" | "mon" => Self::Monday,
+            "sunday" | "sun" => Self::Sunday,
+            _ => return Err(Error::InvalidWeekStart(s.to_string())),
+        })
+    }
+}
+
+impl std::fmt::Display for WeekStart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Monday => "monday",
+                Self::Sunday => "sunday",
+            }
+        )
+    }
+}
+
+/// How durations are rendered in machine-readable (JSON) output, in addition
+/// to whole seconds.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationFormat {
+    /// Only as whole seconds.
+    #[default]
+    Seconds,
+    /// Also as ISO 8601 durations (e.g. `PT1H30M`).
+    Iso8601,
+}
+
+impl FromStr for DurationFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "seconds" | "secs" => Self::Seconds,
+            "iso8601" | "iso" => Self::Iso8601,
+            _ => return Err(Error::InvalidDurationFormat(s.to_string())),
+        })
+    }
+}
+
+impl std::fmt::Display for DurationFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Seconds => "seconds",
+                Self::Iso8601 => "iso8601",
+            }
+        )
+    }
+}
+
+/// A calendar period into which timestamps can be bucketed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Period {
+    /// A calendar day.
+    Day,
+    /// A calendar week (starting on the given week start day).
+    Week,
+    /// A calendar month.
+    Month,
+}
+
+impl Period {
+    /// Timestamp as at the beginning of the period in which the given
+    /// timestamp falls.
+    pub fn start_of(&self, ts: Timestamp, week_start: WeekStart) -> Timestamp {
+        match self {
+            Self::Day => ts.today(),
+            Self::Week => ts.this_week(week_start),
+            Self::Month => ts.this_month(),
+        }
+    }
+
+    /// Timestamp as at the beginning of the period following the one in which
+    /// the given timestamp falls.
+    pub fn next(&self, ts: Timestamp, week_start: WeekStart) -> Timestamp {
+        match self {
+            Self::Day => ts.tomorrow(),
+            Self::Week => ts.next_week(week_start),
+            Self::Month => ts.next_month(),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "day" | "daily" => Self::Day,
+            "week" | "weekly" => Self::Week,
+            "month" | "monthly" => Self::Month,
+            _ => return Err(Error::InvalidPeriod(s.to_string())),
+        })
+    }
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Day => "day",
+                Self::Week => "week",
+                Self::Month => "month",
+            }
+        )
+    }
+}
+
+/// Splits the interval from `start` (inclusive) to `stop` (exclusive) at every
+/// midnight it crosses, returning one `(start, stop)` pair per calendar day.
+pub fn split_by_day(start: Timestamp, stop: Timestamp) -> Vec<(Timestamp, Timestamp)> {
+    split_at_boundaries(start, stop, Timestamp::tomorrow)
+}
+
+/// Splits the interval from `start` (inclusive) to `stop` (exclusive) at the
+/// start of every hour it crosses, returning one `(start, stop)` pair per hour.
+pub fn split_by_hour(start: Timestamp, stop: Timestamp) -> Vec<(Timestamp, Timestamp)> {
+    split_at_boundaries(start, stop, Timestamp::next_hour)
+}
+
+// Splits the given interval at each boundary, where `next_boundary` gives the
+// first boundary after a given timestamp.
+fn split_at_boundaries<F>(
+    start: Timestamp,
+    stop: Timestamp,
+    next_boundary: F,
+) -> Vec<(Timestamp, Timestamp)>
+where
+    F: Fn(&Timestamp) -> Timestamp,
+{
+    let mut intervals = Vec::new();
+    let mut from = start;
+    while from < stop {
+        let to = std::cmp::min(next_boundary(&from), stop);
+        intervals.push((from, to));
+        from = to;
+    }
+    intervals
+}
+
 /// Provides parsing of somewhat human-friendly durations.
 ///
 /// Examples:
@@ -279,6 +761,11 @@ fn parse_prefix_offset(ts: &str) -> Result<(time::Duration, String), Error> {
 /// - `1h30m` is parsed to 1 hour and 30 minutes
 /// - `1d` is parsed to 1 day
 /// - `1w` is parsed to 1 week
+/// - `1mo` is parsed to 1 month
+/// - `1y` is parsed to 1 year
+///
+/// Months and years are approximations: a month is always 30 days and a year
+/// is always 365 days, regardless of the calendar.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Duration(time::Duration);
 
@@ -287,6 +774,11 @@ impl Duration {
         Self(time::Duration::ZERO)
     }
 
+    /// The number of whole seconds in this duration.
+    pub fn whole_seconds(&self) -> i64 {
+        self.0.whole_seconds()
+    }
+
     /// Return this duration in a tuple containing `(hours, minutes)`.
     pub fn hours_mins(&self) -> (i64, i64) {
         let mut hours = self.0.whole_hours();
@@ -326,6 +818,102 @@ impl Duration {
             })
             .unwrap_or_else(|| "".to_string())
     }
+
+    /// Round this duration up to the nearest multiple of the given increment
+    /// (e.g. up to the nearest 15 minutes). A zero (or negative) increment
+    /// leaves the duration unchanged.
+    pub fn round_up_to(&self, increment: Duration) -> Self {
+        let increment_secs = increment.whole_seconds();
+        if increment_secs <= 0 {
+            return *self;
+        }
+        let secs = self.whole_seconds();
+        let rounded = (secs + increment_secs - 1).div_euclid(increment_secs) * increment_secs;
+        Self(time::Duration::seconds(rounded))
+    }
+
+    /// Render this duration as an ISO 8601 duration (e.g. `PT1H30M`). Whole
+    /// days are rendered as days (e.g. `P1DT2H`).
+    pub fn to_iso8601(&self) -> String {
+        let total_secs = self.0.whole_seconds();
+        let mut secs = total_secs.abs();
+        let days = secs / 86_400;
+        secs %= 86_400;
+        let hours = secs / 3_600;
+        secs %= 3_600;
+        let mins = secs / 60;
+        secs %= 60;
+
+        let mut s = if total_secs < 0 { "-P" } else { "P" }.to_string();
+        if days > 0 {
+            s.push_str(&format!("{}D", days));
+        }
+        if days == 0 || hours > 0 || mins > 0 || secs > 0 {
+            s.push('T');
+            if hours > 0 {
+                s.push_str(&format!("{}H", hours));
+            }
+            if mins > 0 {
+                s.push_str(&format!("{}M", mins));
+            }
+            if secs > 0 || (hours == 0 && mins == 0) {
+                s.push_str(&format!("{}S", secs));
+            }
+        }
+        s
+    }
+
+    /// Parse an ISO 8601 duration (e.g. `PT1H30M` or `P1D`). Years and months
+    /// are not supported, since their lengths vary.
+    pub fn from_iso8601(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidIso8601Duration(s.to_string());
+        let (negative, rest) = match s.trim().strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.trim()),
+        };
+        let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+        let (date_part, maybe_time_part) = match rest.split_once('T') {
+            Some((date_part, time_part)) => (date_part, Some(time_part)),
+            None => (rest, None),
+        };
+        if maybe_time_part == Some("") || (date_part.is_empty() && maybe_time_part.is_none()) {
+            return Err(invalid());
+        }
+        let date_units = [('W', time::Duration::WEEK), ('D', time::Duration::DAY)];
+        let time_units = [
+            ('H', time::Duration::HOUR),
+            ('M', time::Duration::MINUTE),
+            ('S', time::Duration::SECOND),
+        ];
+        let mut duration = time::Duration::ZERO;
+        for (part, units) in [
+            (date_part, &date_units[..]),
+            (maybe_time_part.unwrap_or(""), &time_units[..]),
+        ] {
+            let mut amount = String::new();
+            // Units must appear in order, and at most once each.
+            let mut next_unit = 0;
+            for c in part.chars() {
+                if c.is_ascii_digit() {
+                    amount.push(c);
+                    continue;
+                }
+                let unit = next_unit
+                    + units[next_unit..]
+                        .iter()
+                        .position(|(u, _)| *u == c)
+                        .ok_or_else(invalid)?;
+                let amt = i32::from_str(&amount).map_err(|_| invalid())?;
+                duration += units[unit].1.checked_mul(amt).ok_or_else(invalid)?;
+                amount.clear();
+                next_unit = unit + 1;
+            }
+            if !amount.is_empty() {
+                return Err(invalid());
+            }
+        }
+        Ok(Self(if negative { -duration } else { duration }))
+    }
 }
 
 impl From<time::Duration> for Duration {
@@ -349,9 +937,9 @@ impl FromStr for Duration {
             components
                 .into_iter()
                 .map(|(amt_str, unit_str)| {
-                    let amt = i32::from_str(&amt_str)
-                        .map_err(|e| Error::InvalidDurationAmount(amt_str.clone(), e))?;
                     let unit = match unit_str.as_str() {
+                        "y" => time::Duration::hours(HOURS_PER_YEAR),
+                        "mo" => time::Duration::hours(HOURS_PER_MONTH),
                         "w" => time::Duration::WEEK,
                         "d" => time::Duration::DAY,
                         "h" => time::Duration::HOUR,
@@ -359,6 +947,22 @@ impl FromStr for Duration {
                         "s" => time::Duration::SECOND,
                         _ => return Err(Error::InvalidDurationUnit(unit_str)),
                     };
+                    if amt_str.ends_with('.') {
+                        return Err(Error::InvalidDuration(amt_str, unit_str));
+                    }
+                    if amt_str.contains('.') {
+                        // Fractional amounts (e.g. "1.5h") are rounded to the
+                        // nearest whole second
+                        let amt = f64::from_str(&amt_str)
+                            .map_err(|e| Error::InvalidDecimalDurationAmount(amt_str.clone(), e))?;
+                        let secs = (amt * unit.whole_seconds() as f64).round();
+                        if secs > i64::MAX as f64 {
+                            return Err(Error::InvalidDuration(amt_str, unit_str));
+                        }
+                        return Ok(time::Duration::seconds(secs as i64));
+                    }
+                    let amt = i32::from_str(&amt_str)
+                        .map_err(|e| Error::InvalidDurationAmount(amt_str.clone(), e))?;
                     let duration = unit
                         .checked_mul(amt)
                         .ok_or_else(|| Error::InvalidDuration(amt_str.clone(), unit_str))?;
@@ -373,8 +977,14 @@ impl FromStr for Duration {
 
 impl std::fmt::Display for Duration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (hours, mins) = self.hours_mins();
-        let s: String = [(hours, "h"), (mins, "m")]
+        // Only durations of at least a month are broken down into years and
+        // months, so that shorter durations are still shown in hours.
+        let (mut hours, mins) = self.hours_mins();
+        let years = hours / HOURS_PER_YEAR;
+        hours %= HOURS_PER_YEAR;
+        let months = hours / HOURS_PER_MONTH;
+        hours %= HOURS_PER_MONTH;
+        let s: String = [(years, "y"), (months, "mo"), (hours, "h"), (mins, "m")]
             .into_iter()
             .filter_map(|(amt, unit)| {
                 if amt > 0 {
@@ -422,6 +1032,14 @@ impl std::ops::AddAssign for Duration {
     }
 }
 
+impl std::ops::Sub for Duration {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
 enum DurationParserState {
     Begin,
     Amount,
@@ -445,7 +1063,7 @@ fn parse_duration_components<S: AsRef<str>>(s: S) -> Result<Vec<(String, String)
                 state = DurationParserState::Amount;
             }
             DurationParserState::Amount => {
-                if c.is_digit(10) {
+                if c.is_digit(10) || (c == '.' && !cur_amount.contains('.')) {
                     cur_amount.push(c);
                 } else {
                     state = DurationParserState::Unit;
@@ -475,11 +1093,14 @@ fn parse_duration_components<S: AsRef<str>>(s: S) -> Result<Vec<(String, String)
 
 #[cfg(test)]
 mod test {
-    use super::{parse_timestamp, Duration};
+    use super::{
+        local_datetime, parse_timestamp, parse_timestamp_with_offsets, split_by_day, split_by_hour,
+        Duration, Timestamp, WeekStart,
+    };
     use lazy_static::lazy_static;
     use std::str::FromStr;
-    use time::macros::datetime;
-    use time::OffsetDateTime;
+    use time::macros::{date, datetime, offset, time};
+    use time::{OffsetDateTime, UtcOffset};
 
     lazy_static! {
         static ref LOCAL_NOW: OffsetDateTime = datetime!(2021-11-04 17:00 -4);
@@ -507,6 +1128,25 @@ mod test {
             ("10:23:44".to_string(), datetime!(2021-11-04 10:23:44 -4),),
             ("now".to_string(), datetime!(2021-11-04 17:00 -4)),
             ("2021-11-01".to_string(), datetime!(2021-11-01 00:00:00 -4)),
+            ("3 days ago".to_string(), datetime!(2021-11-01 17:00 -4)),
+            ("1 day ago".to_string(), datetime!(2021-11-03 17:00 -4)),
+            ("2 weeks ago".to_string(), datetime!(2021-10-21 17:00 -4)),
+            ("5 hours ago".to_string(), datetime!(2021-11-04 12:00 -4)),
+            ("90 minutes ago".to_string(), datetime!(2021-11-04 15:30 -4)),
+            ("3 days ago@14:00".to_string(), datetime!(2021-11-01 14:00 -4)),
+            ("1 Week Ago@09:30".to_string(), datetime!(2021-10-28 09:30 -4)),
+            // LOCAL_NOW is a Thursday
+            ("monday".to_string(), datetime!(2021-11-01 00:00 -4)),
+            ("Mon".to_string(), datetime!(2021-11-01 00:00 -4)),
+            ("thursday".to_string(), datetime!(2021-11-04 00:00 -4)),
+            ("friday".to_string(), datetime!(2021-10-29 00:00 -4)),
+            ("last tuesday".to_string(), datetime!(2021-11-02 00:00 -4)),
+            ("last thu".to_string(), datetime!(2021-10-28 00:00 -4)),
+            ("next friday".to_string(), datetime!(2021-11-05 00:00 -4)),
+            ("next thursday".to_string(), datetime!(2021-11-11 00:00 -4)),
+            ("next wed".to_string(), datetime!(2021-11-10 00:00 -4)),
+            ("last sunday@14:00".to_string(), datetime!(2021-10-31 14:00 -4)),
+            ("next mon@09:30".to_string(), datetime!(2021-11-08 09:30 -4)),
         ];
         static ref DURATION_PARSE_TEST_CASES: Vec<(String, i64)> = vec![
             ("1m".to_string(), 60),
@@ -519,6 +1159,15 @@ mod test {
                 "1d4h12m".to_string(),
                 (24 * 60 * 60) + (4 * 60 * 60) + (12 * 60)
             ),
+            ("1.5h".to_string(), 90 * 60),
+            ("0.5d".to_string(), 12 * 60 * 60),
+            ("2.25m".to_string(), 135),
+            ("1.5h15m".to_string(), 105 * 60),
+            ("0.0001m".to_string(), 0),
+            ("1mo".to_string(), 30 * 24 * 60 * 60),
+            ("1y".to_string(), 365 * 24 * 60 * 60),
+            ("1y2mo3m".to_string(), ((365 + 60) * 24 * 60 * 60) + (3 * 60)),
+            ("0.5mo".to_string(), 15 * 24 * 60 * 60),
         ];
         static ref DURATION_FORMAT_TEST_CASES: Vec<(i64, String)> = vec![
             (60, "1m".to_string()),
@@ -532,6 +1181,22 @@ mod test {
                 "244h 5m".to_string()
             ),
             ((6 * 60) + 24, "7m".to_string()),
+            (30 * 24 * 60 * 60, "1mo".to_string()),
+            ((30 * 24 * 60 * 60) + (2 * 60 * 60), "1mo 2h".to_string()),
+            (29 * 24 * 60 * 60, "696h".to_string()),
+            (365 * 24 * 60 * 60, "1y".to_string()),
+            (
+                (2 * 365 * 24 * 60 * 60) + (3 * 30 * 24 * 60 * 60) + (90 * 60),
+                "2y 3mo 1h 30m".to_string()
+            ),
+        ];
+        static ref DURATION_ISO8601_TEST_CASES: Vec<(String, String)> = vec![
+            ("1h30m".to_string(), "PT1H30M".to_string()),
+            ("1d".to_string(), "P1D".to_string()),
+            ("1d2h".to_string(), "P1DT2H".to_string()),
+            ("1w".to_string(), "P7D".to_string()),
+            ("45s".to_string(), "PT45S".to_string()),
+            ("0m".to_string(), "PT0S".to_string()),
         ];
     }
 
@@ -543,12 +1208,82 @@ mod test {
         }
     }
 
+    #[test]
+    fn invalid_relative_timestamps() {
+        for ts in [
+            "days ago",
+            "3 days",
+            "three days ago",
+            "3 fortnights ago",
+            "3 days 2 hours ago",
+            "5 hours ago@14:00",
+            "mondays",
+            "this monday",
+            "next",
+            "last next friday",
+        ] {
+            assert!(parse_timestamp(ts, &LOCAL_NOW).is_err(), "{}", ts);
+        }
+    }
+
+    #[test]
+    fn relative_dates_across_dst_transition() {
+        // A Monday, the day after clocks sprang forward.
+        let local_now = datetime!(2022-03-28 10:00 +2);
+        for (ts, expected) in [
+            ("sunday", datetime!(2022-03-27 00:00 +1)),
+            ("last sat", datetime!(2022-03-26 00:00 +1)),
+            ("sunday@09:00", datetime!(2022-03-27 09:00 +2)),
+            ("yesterday@00:30", datetime!(2022-03-27 00:30 +1)),
+            ("monday", datetime!(2022-03-28 00:00 +2)),
+        ] {
+            let actual = parse_timestamp_with_offsets(ts, &local_now, cet_offset_at).unwrap();
+            assert_eq!(actual, expected, "{}", ts);
+        }
+    }
+
     #[test]
     fn duration_parsing() {
         for (s, expected) in DURATION_PARSE_TEST_CASES.iter() {
             let actual: time::Duration = Duration::from_str(s).unwrap().into();
             assert_eq!(actual.whole_seconds(), *expected);
         }
+        for s in ["1..5h", "1.5.h", ".5h", "1.h"] {
+            assert!(Duration::from_str(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn fractional_duration_display_round_trip() {
+        for s in ["1.5h", "0.5d", "0.25h"] {
+            let duration = Duration::from_str(s).unwrap();
+            assert_eq!(Duration::from_str(&duration.to_string()).unwrap(), duration);
+        }
+    }
+
+    #[test]
+    fn duration_rounding() {
+        let quarter_hour: Duration = time::Duration::minutes(15).into();
+        for (secs, expected) in [
+            (0, 0),
+            (1, 15 * 60),
+            (60, 15 * 60),
+            (10 * 60, 15 * 60),
+            (15 * 60, 15 * 60),
+            (16 * 60, 30 * 60),
+            (30 * 60, 30 * 60),
+            (61 * 60, 75 * 60),
+        ] {
+            let duration: Duration = time::Duration::seconds(secs).into();
+            assert_eq!(
+                duration.round_up_to(quarter_hour).whole_seconds(),
+                expected,
+                "{}",
+                secs
+            );
+        }
+        let duration: Duration = time::Duration::minutes(7).into();
+        assert_eq!(duration.round_up_to(Duration::zero()), duration);
     }
 
     #[test]
@@ -559,4 +1294,157 @@ mod test {
             assert_eq!(&actual, expected);
         }
     }
+
+    #[test]
+    fn duration_iso8601_round_trip() {
+        for (s, expected) in DURATION_ISO8601_TEST_CASES.iter() {
+            let duration = Duration::from_str(s).unwrap();
+            assert_eq!(&duration.to_iso8601(), expected);
+            assert_eq!(Duration::from_iso8601(expected).unwrap(), duration);
+        }
+        for s in [
+            "", "P", "PT", "1H", "P1Y", "PT1M1H", "PT1H1H", "P1DT", "PT1.5H",
+        ] {
+            assert!(Duration::from_iso8601(s).is_err(), "{}", s);
+        }
+    }
+
+    // Central European Time, which springs forward from +1 to +2 at 01:00 UTC
+    // on 2022-03-27.
+    fn cet_offset_at(dt: OffsetDateTime) -> Option<UtcOffset> {
+        if dt < datetime!(2022-03-27 01:00 UTC) {
+            Some(offset!(+1))
+        } else {
+            Some(offset!(+2))
+        }
+    }
+
+    #[test]
+    fn midnight_across_dst_transition() {
+        // Tomorrow is the next calendar day's midnight, even though the day of
+        // the transition is only 23 hours long.
+        let reference = datetime!(2022-03-27 00:30 +1);
+        let tomorrow = local_datetime(date!(2022 - 03 - 28).midnight(), reference, cet_offset_at);
+        assert_eq!(tomorrow, datetime!(2022-03-28 00:00 +2));
+        assert_eq!(
+            tomorrow - datetime!(2022-03-27 00:00 +1),
+            time::Duration::HOUR * 23
+        );
+
+        // Today is the current calendar day's midnight, before the transition.
+        let reference = datetime!(2022-03-27 10:00 +2);
+        let today = local_datetime(date!(2022 - 03 - 27).midnight(), reference, cet_offset_at);
+        assert_eq!(today, datetime!(2022-03-27 00:00 +1));
+
+        // Timestamps that aren't in local time keep their offset.
+        let reference = datetime!(2022-03-27 10:00 -4);
+        let tomorrow = local_datetime(date!(2022 - 03 - 28).midnight(), reference, cet_offset_at);
+        assert_eq!(tomorrow, datetime!(2022-03-28 00:00 -4));
+
+        // Later times of day on the day of the transition use the offset in
+        // effect at that time, rather than the one in effect at midnight.
+        let reference = datetime!(2022-03-27 00:00 +1);
+        let nine_am = local_datetime(
+            date!(2022 - 03 - 27).with_time(time!(09:00)),
+            reference,
+            cet_offset_at,
+        );
+        assert_eq!(nine_am, datetime!(2022-03-27 09:00 +2));
+    }
+
+    #[test]
+    fn calendar_boundaries() {
+        let ts = Timestamp::from(datetime!(2021-12-29 17:00 -4));
+        assert_eq!(
+            OffsetDateTime::from(ts.tomorrow()),
+            datetime!(2021-12-30 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.this_week(WeekStart::Monday)),
+            datetime!(2021-12-27 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.next_week(WeekStart::Monday)),
+            datetime!(2022-01-03 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.this_week(WeekStart::Sunday)),
+            datetime!(2021-12-26 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.next_week(WeekStart::Sunday)),
+            datetime!(2022-01-02 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.days_forward(3)),
+            datetime!(2022-01-01 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.next_month()),
+            datetime!(2022-01-01 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.next_year()),
+            datetime!(2022-01-01 00:00 -4)
+        );
+        assert_eq!(
+            OffsetDateTime::from(ts.this_month()),
+            datetime!(2021-12-01 00:00 -4)
+        );
+    }
+
+    #[test]
+    fn splitting_by_day() {
+        let start = Timestamp::from(datetime!(2021-11-04 20:00 -4));
+        let stop = Timestamp::from(datetime!(2021-11-06 22:00 -4));
+        let actual = split_by_day(start, stop)
+            .into_iter()
+            .map(|(from, to)| (from.into(), to.into()))
+            .collect::<Vec<(OffsetDateTime, OffsetDateTime)>>();
+        assert_eq!(
+            actual,
+            vec![
+                (
+                    datetime!(2021-11-04 20:00 -4),
+                    datetime!(2021-11-05 00:00 -4)
+                ),
+                (
+                    datetime!(2021-11-05 00:00 -4),
+                    datetime!(2021-11-06 00:00 -4)
+                ),
+                (
+                    datetime!(2021-11-06 00:00 -4),
+                    datetime!(2021-11-06 22:00 -4)
+                ),
+            ]
+        );
+        assert_eq!(split_by_day(start, start), vec![]);
+    }
+
+    #[test]
+    fn splitting_by_hour() {
+        let start = Timestamp::from(datetime!(2021-11-04 22:40 -4));
+        let stop = Timestamp::from(datetime!(2021-11-05 00:10 -4));
+        let actual = split_by_hour(start, stop)
+            .into_iter()
+            .map(|(from, to)| (from.into(), to.into()))
+            .collect::<Vec<(OffsetDateTime, OffsetDateTime)>>();
+        assert_eq!(
+            actual,
+            vec![
+                (
+                    datetime!(2021-11-04 22:40 -4),
+                    datetime!(2021-11-04 23:00 -4)
+                ),
+                (
+                    datetime!(2021-11-04 23:00 -4),
+                    datetime!(2021-11-05 00:00 -4)
+                ),
+                (
+                    datetime!(2021-11-05 00:00 -4),
+                    datetime!(2021-11-05 00:10 -4)
+                ),
+            ]
+        );
+    }
 }
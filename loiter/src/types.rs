@@ -1,12 +1,18 @@
 //! Data types used by Loiter.
 
-use std::{collections::HashSet, num::NonZeroU32, str::FromStr};
-
-use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    num::NonZeroU32,
+    str::FromStr,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
 use time::OffsetDateTime;
 use url::Url;
 
-use crate::{strings::slugify, Duration, Error, Timestamp};
+use crate::{strings::slugify, Duration, DurationFormat, Error, Timestamp, WeekStart};
 
 pub type ProjectId = String;
 pub type TaskId = u32;
@@ -40,12 +46,35 @@ pub const MAX_TASK_PRIORITY: TaskPriority = 10;
 /// By default, tasks have the lowest priority possible.
 pub const DEFAULT_TASK_PRIORITY: TaskPriority = MAX_TASK_PRIORITY;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// The version of the store format written by this version of Loiter.
+/// Stores whose configuration requires a newer version to read them are
+/// refused (see [`Config::min_reader_version`]).
+pub const STORE_FORMAT_VERSION: u32 = 1;
+
+/// The default width to which task IDs are zero-padded in file names.
+pub const DEFAULT_TASK_ID_WIDTH: usize = 4;
+
+/// The default width to which log IDs are zero-padded in file names.
+pub const DEFAULT_LOG_ID_WIDTH: usize = 5;
+
+/// The maximum width to which IDs can be zero-padded in file names (the number
+/// of digits in the largest possible ID).
+pub const MAX_ID_WIDTH: usize = 10;
+
+/// By default, how many minutes beyond the current time a work log's stop time
+/// may be before it is rejected.
+pub const DEFAULT_STOP_TOLERANCE_MINS: i64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
 pub struct TaskStateConfig {
     states: Vec<TaskState>,
     initial: TaskState,
     in_progress: TaskState,
     done: TaskState,
+    /// States other than the done state in which tasks are considered closed
+    /// (e.g. "cancelled").
+    #[serde(default)]
+    terminal: Vec<TaskState>,
 }
 
 impl Default for TaskStateConfig {
@@ -55,6 +84,7 @@ impl Default for TaskStateConfig {
             initial: DEFAULT_INITIAL_TASK_STATE.to_string(),
             in_progress: DEFAULT_IN_PROGRESS_TASK_STATE.to_string(),
             done: DEFAULT_DONE_TASK_STATE.to_string(),
+            terminal: Vec::new(),
         }
     }
 }
@@ -84,9 +114,31 @@ impl TaskStateConfig {
             initial,
             in_progress,
             done,
+            terminal: Vec::new(),
         })
     }
 
+    /// Set the states, other than the done state, in which tasks are
+    /// considered closed. Each must be one of the configured states.
+    pub fn with_terminal_states<S, T>(mut self, states: T) -> Result<Self, Error>
+    where
+        S: AsRef<str>,
+        T: IntoIterator<Item = S>,
+    {
+        let mut terminal = Vec::new();
+        for state in states {
+            let state = state.as_ref().to_string();
+            if !self.states.contains(&state) {
+                return Err(Error::InvalidTaskState(state, self.states.clone()));
+            }
+            if state != self.done && !terminal.contains(&state) {
+                terminal.push(state);
+            }
+        }
+        self.terminal = terminal;
+        Ok(self)
+    }
+
     /// If specified, checks whether the given string is a valid task state.
     ///
     /// If `maybe_state` is `None`, this returns the default initial task state
@@ -123,12 +175,94 @@ impl TaskStateConfig {
     pub fn done(&self) -> &str {
         self.done.as_str()
     }
+
+    /// The states, other than the done state, in which tasks are considered
+    /// closed.
+    pub fn terminal_states(&self) -> impl Iterator<Item = &str> {
+        self.terminal.iter().map(|s| s.as_str())
+    }
+
+    /// All of the states in which tasks are considered closed: the done state
+    /// followed by any other terminal states.
+    pub fn closed_states(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.done()).chain(self.terminal_states())
+    }
+
+    /// Whether tasks in the given state are considered closed.
+    pub fn is_closed(&self, state: &str) -> bool {
+        self.closed_states().any(|closed| closed == state)
+    }
 }
 
 /// Loiter global configuration.
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Config {
+    /// The version of the store format with which the store was last written.
+    #[serde(default = "default_format_version")]
+    format_version: u32,
+    /// The minimum version of the store format that a version of Loiter must
+    /// support in order to read the store.
+    #[serde(default = "default_format_version")]
+    min_reader_version: u32,
     task_state_config: TaskStateConfig,
+    /// Tags automatically applied to every newly created work log.
+    #[serde(default)]
+    default_log_tags: HashSet<String>,
+    /// Tags automatically applied to every newly created task.
+    #[serde(default)]
+    default_task_tags: HashSet<String>,
+    /// The width to which the IDs of newly created tasks are zero-padded in
+    /// their directory names.
+    #[serde(default = "default_task_id_width")]
+    task_id_width: usize,
+    /// The width to which the IDs of newly created work logs are zero-padded
+    /// in their file names.
+    #[serde(default = "default_log_id_width")]
+    log_id_width: usize,
+    /// Tags marking work logs as breaks (e.g. lunch) taken within other
+    /// tracked work logs. Breaks are subtracted from, rather than added to,
+    /// net time totals.
+    #[serde(default)]
+    break_tags: HashSet<String>,
+    /// If set, each work log's duration is rounded up to the nearest multiple
+    /// of this increment when computing billed (rounded) time totals.
+    #[serde(default, rename = "rounding")]
+    maybe_rounding: Option<Duration>,
+    /// The day on which weeks start, e.g. when filtering by "this-week" or
+    /// reporting weekly.
+    #[serde(default)]
+    week_start: WeekStart,
+    /// How far beyond the current time a work log's stop time may be, to
+    /// allow for clock skew, before it is rejected as a likely typo.
+    #[serde(default = "default_stop_tolerance")]
+    stop_tolerance: Duration,
+    /// The priority given to newly created tasks whose priority isn't
+    /// specified.
+    #[serde(default = "default_task_priority")]
+    default_task_priority: TaskPriority,
+    /// How durations are rendered in JSON output and exports.
+    #[serde(default)]
+    duration_format: DurationFormat,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            format_version: STORE_FORMAT_VERSION,
+            min_reader_version: STORE_FORMAT_VERSION,
+            task_state_config: TaskStateConfig::default(),
+            default_log_tags: HashSet::new(),
+            default_task_tags: HashSet::new(),
+            task_id_width: DEFAULT_TASK_ID_WIDTH,
+            log_id_width: DEFAULT_LOG_ID_WIDTH,
+            break_tags: HashSet::new(),
+            maybe_rounding: None,
+            week_start: WeekStart::default(),
+            stop_tolerance: default_stop_tolerance(),
+            default_task_priority: DEFAULT_TASK_PRIORITY,
+            duration_format: DurationFormat::default(),
+        }
+    }
 }
 
 impl Config {
@@ -137,44 +271,286 @@ impl Config {
         self
     }
 
+    pub fn with_default_log_tags<S, T>(mut self, tags: T) -> Result<Self, Error>
+    where
+        S: AsRef<str>,
+        T: IntoIterator<Item = S>,
+    {
+        self.default_log_tags = tags
+            .into_iter()
+            .map(|t| validate_tag(&t))
+            .collect::<Result<HashSet<String>, Error>>()?;
+        Ok(self)
+    }
+
+    pub fn with_default_task_tags<S, T>(mut self, tags: T) -> Result<Self, Error>
+    where
+        S: AsRef<str>,
+        T: IntoIterator<Item = S>,
+    {
+        self.default_task_tags = tags
+            .into_iter()
+            .map(|t| validate_tag(&t))
+            .collect::<Result<HashSet<String>, Error>>()?;
+        Ok(self)
+    }
+
+    pub fn with_break_tags<S, T>(mut self, tags: T) -> Result<Self, Error>
+    where
+        S: AsRef<str>,
+        T: IntoIterator<Item = S>,
+    {
+        self.break_tags = tags
+            .into_iter()
+            .map(|t| validate_tag(&t))
+            .collect::<Result<HashSet<String>, Error>>()?;
+        Ok(self)
+    }
+
+    pub fn with_maybe_rounding(mut self, maybe_rounding: Option<Duration>) -> Result<Self, Error> {
+        if let Some(rounding) = maybe_rounding {
+            if rounding <= Duration::zero() {
+                return Err(Error::InvalidRounding(rounding));
+            }
+        }
+        self.maybe_rounding = maybe_rounding;
+        Ok(self)
+    }
+
+    pub fn with_week_start(mut self, week_start: WeekStart) -> Self {
+        self.week_start = week_start;
+        self
+    }
+
+    pub fn with_duration_format(mut self, duration_format: DurationFormat) -> Self {
+        self.duration_format = duration_format;
+        self
+    }
+
+    pub fn with_stop_tolerance(mut self, tolerance: Duration) -> Result<Self, Error> {
+        if tolerance < Duration::zero() {
+            return Err(Error::InvalidStopTolerance(tolerance));
+        }
+        self.stop_tolerance = tolerance;
+        Ok(self)
+    }
+
+    pub fn with_default_task_priority(mut self, priority: TaskPriority) -> Result<Self, Error> {
+        self.default_task_priority = validate_task_priority(priority)?;
+        Ok(self)
+    }
+
+    pub fn with_task_id_width(mut self, width: usize) -> Result<Self, Error> {
+        self.task_id_width = validate_id_width(width)?;
+        Ok(self)
+    }
+
+    pub fn with_log_id_width(mut self, width: usize) -> Result<Self, Error> {
+        self.log_id_width = validate_id_width(width)?;
+        Ok(self)
+    }
+
     pub fn task_state_config(&self) -> &TaskStateConfig {
         &self.task_state_config
     }
+
+    pub fn default_log_tags(&self) -> impl Iterator<Item = &str> {
+        self.default_log_tags.iter().map(|t| t.as_str())
+    }
+
+    pub fn default_task_tags(&self) -> impl Iterator<Item = &str> {
+        self.default_task_tags.iter().map(|t| t.as_str())
+    }
+
+    pub fn break_tags(&self) -> impl Iterator<Item = &str> {
+        self.break_tags.iter().map(|t| t.as_str())
+    }
+
+    /// Whether the given log is a break, i.e. carries one of the configured
+    /// break tags.
+    pub fn is_break(&self, log: &Log) -> bool {
+        log.tags().any(|tag| self.break_tags.contains(tag))
+    }
+
+    pub fn format_version(&self) -> u32 {
+        self.format_version
+    }
+
+    pub fn min_reader_version(&self) -> u32 {
+        self.min_reader_version
+    }
+
+    pub fn maybe_rounding(&self) -> Option<Duration> {
+        self.maybe_rounding
+    }
+
+    pub fn week_start(&self) -> WeekStart {
+        self.week_start
+    }
+
+    pub fn stop_tolerance(&self) -> Duration {
+        self.stop_tolerance
+    }
+
+    pub fn default_task_priority(&self) -> TaskPriority {
+        self.default_task_priority
+    }
+
+    pub fn duration_format(&self) -> DurationFormat {
+        self.duration_format
+    }
+
+    /// The given log's effective duration (see [`Log::effective_duration`]),
+    /// rounded up to the configured rounding increment, if any.
+    pub fn rounded_duration(&self, log: &Log, now: Timestamp) -> Duration {
+        let duration = log.effective_duration(now);
+        match self.maybe_rounding {
+            Some(rounding) => duration.round_up_to(rounding),
+            None => duration,
+        }
+    }
+
+    pub fn task_id_width(&self) -> usize {
+        self.task_id_width
+    }
+
+    pub fn log_id_width(&self) -> usize {
+        self.log_id_width
+    }
+}
+
+fn default_format_version() -> u32 {
+    STORE_FORMAT_VERSION
+}
+
+fn default_task_id_width() -> usize {
+    DEFAULT_TASK_ID_WIDTH
+}
+
+fn default_log_id_width() -> usize {
+    DEFAULT_LOG_ID_WIDTH
+}
+
+fn default_stop_tolerance() -> Duration {
+    time::Duration::minutes(DEFAULT_STOP_TOLERANCE_MINS).into()
 }
 
+fn default_task_priority() -> TaskPriority {
+    DEFAULT_TASK_PRIORITY
+}
+
+fn validate_id_width(width: usize) -> Result<usize, Error> {
+    if width == 0 || width > MAX_ID_WIDTH {
+        Err(Error::InvalidIdWidth(width, MAX_ID_WIDTH))
+    } else {
+        Ok(width)
+    }
+}
+
+/// Identifies an active work log by its project ID, task ID (if any) and ID.
+pub type ActiveLog = (ProjectId, Option<TaskId>, LogId);
+
 /// For keeping track of the current global time tracking state.
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct State {
-    active_log: Option<(ProjectId, Option<TaskId>, LogId)>,
+    /// The active logs, in the order in which they were started. Older
+    /// versions of Loiter recorded at most one active log, as `active_log`.
+    #[serde(
+        default,
+        alias = "active_log",
+        deserialize_with = "deserialize_active_logs"
+    )]
+    active_logs: Vec<ActiveLog>,
 }
 
 impl State {
+    /// Add the given log to the active logs, if it isn't already active.
     pub fn with_active_log<S: AsRef<str>>(
         mut self,
         project_id: S,
         maybe_task_id: Option<TaskId>,
         log_id: LogId,
     ) -> Self {
-        self.active_log = Some((project_id.as_ref().to_string(), maybe_task_id, log_id));
+        let active_log = (project_id.as_ref().to_string(), maybe_task_id, log_id);
+        if !self.active_logs.contains(&active_log) {
+            self.active_logs.push(active_log);
+        }
+        self
+    }
+
+    /// Remove the given log from the active logs, if it's active.
+    pub fn without_active_log<S: AsRef<str>>(
+        mut self,
+        project_id: S,
+        maybe_task_id: Option<TaskId>,
+        log_id: LogId,
+    ) -> Self {
+        let project_id = project_id.as_ref();
+        self.active_logs.retain(|(pid, tid, lid)| {
+            !(pid == project_id && *tid == maybe_task_id && *lid == log_id)
+        });
+        self
+    }
+
+    /// Replace the given active log with another one (e.g. because it was
+    /// moved), keeping its place in the order in which logs were started.
+    pub fn with_replaced_active_log(mut self, from: &ActiveLog, to: ActiveLog) -> Self {
+        if let Some(active_log) = self.active_logs.iter_mut().find(|al| *al == from) {
+            *active_log = to;
+        }
         self
     }
 
     pub fn with_no_active_log(mut self) -> Self {
-        self.active_log = None;
+        self.active_logs.clear();
         self
     }
 
-    pub fn active_log(&self) -> Option<(ProjectId, Option<TaskId>, LogId)> {
-        self.active_log.clone()
+    /// All of the active logs, in the order in which they were started.
+    pub fn active_logs(&self) -> &[ActiveLog] {
+        &self.active_logs
+    }
+
+    /// The most recently started active log, if any.
+    pub fn active_log(&self) -> Option<ActiveLog> {
+        self.active_logs.last().cloned()
+    }
+
+    pub fn is_active(&self, active_log: &ActiveLog) -> bool {
+        self.active_logs.contains(active_log)
     }
 }
 
+// Accepts both a list of active logs and the single (possibly null) active log
+// recorded by older versions of Loiter.
+fn deserialize_active_logs<'de, D>(deserializer: D) -> Result<Vec<ActiveLog>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ActiveLogs {
+        One(ActiveLog),
+        Many(Vec<ActiveLog>),
+    }
+
+    Ok(match Option::<ActiveLogs>::deserialize(deserializer)? {
+        Some(ActiveLogs::One(active_log)) => vec![active_log],
+        Some(ActiveLogs::Many(active_logs)) => active_logs,
+        None => Vec::new(),
+    })
+}
+
 /// A type that facilitates comparisons of another type to order instances of
 /// that type.
 pub trait Comparator {
     type Type;
 
-    fn cmp(&self, a: &Self::Type, b: &Self::Type) -> std::cmp::Ordering;
+    /// Compares the two given items.
+    ///
+    /// Time needs to be supplied so that it stays the same throughout a sort
+    /// (e.g. when comparing running logs' durations).
+    fn cmp(&self, a: &Self::Type, b: &Self::Type, now: Timestamp) -> std::cmp::Ordering;
 }
 
 /// A specification as to how to sort a list of items using comparators.
@@ -200,13 +576,14 @@ where
         self
     }
 
-    /// Sort the given list of items by this sort specification.
-    pub fn sort(&self, mut items: Vec<C::Type>) -> Vec<C::Type> {
+    /// Sort the given list of items by this sort specification, as at the
+    /// given time.
+    pub fn sort(&self, mut items: Vec<C::Type>, now: Timestamp) -> Vec<C::Type> {
         items.sort_by(|a, b| {
             for (comparator, order) in self.0.iter() {
                 let cmp = match order {
-                    Order::Asc => comparator.cmp(a, b),
-                    Order::Desc => comparator.cmp(b, a),
+                    Order::Asc => comparator.cmp(a, b, now),
+                    Order::Desc => comparator.cmp(b, a, now),
                 };
                 match cmp {
                     std::cmp::Ordering::Less | std::cmp::Ordering::Greater => return cmp,
@@ -352,9 +729,15 @@ where
     }
 
     /// Builder.
+    ///
+    /// Adding a filter to a passthrough spec replaces the passthrough filter,
+    /// and adding a passthrough filter to a spec that already filters has no
+    /// effect.
     pub fn and_then(mut self, filter: F) -> Self {
         if self.is_passthrough() {
             self.0.clear();
+        } else if filter == F::default() {
+            return self;
         }
         self.0.push(filter);
         self
@@ -366,11 +749,80 @@ where
     }
 }
 
+impl FilterSpec<ProjectFilter> {
+    /// The IDs of the only projects that could possibly match this spec, if it
+    /// restricts projects to specific IDs.
+    pub fn project_ids(&self) -> Option<&[ProjectId]> {
+        self.0.iter().find_map(|filter| match filter {
+            ProjectFilter::Ids(ids) => Some(ids.as_slice()),
+            _ => None,
+        })
+    }
+}
+
 impl<F: Filter> Filter for FilterSpec<F> {
     type Type = F::Type;
 
     fn matches(&self, item: &Self::Type, now: Timestamp) -> bool {
-        !self.0.iter().any(|filter| !filter.matches(item, now))
+        // Filters are evaluated in order, stopping at the first one that
+        // doesn't match, so cheaper filters should be added first.
+        self.0.iter().all(|filter| filter.matches(item, now))
+    }
+}
+
+/// A regular expression used to filter objects by the contents of their text
+/// fields.
+///
+/// The pattern is compiled once, when the filter is constructed (or
+/// deserialized), so that invalid patterns are reported up front. Filters are
+/// compared and serialized by their pattern.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RegexPattern(Regex);
+
+impl RegexPattern {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_match(&self, s: &str) -> bool {
+        self.0.is_match(s)
+    }
+}
+
+impl FromStr for RegexPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            Regex::new(s).map_err(|e| Error::InvalidRegex(s.to_string(), e))?,
+        ))
+    }
+}
+
+impl TryFrom<String> for RegexPattern {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::from_str(&s)
+    }
+}
+
+impl From<RegexPattern> for String {
+    fn from(pattern: RegexPattern) -> Self {
+        pattern.as_str().to_string()
+    }
+}
+
+impl PartialEq for RegexPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialOrd for RegexPattern {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.as_str().partial_cmp(other.as_str())
     }
 }
 
@@ -392,7 +844,7 @@ impl Default for SortSpec<ProjectField> {
 impl Comparator for ProjectField {
     type Type = Project;
 
-    fn cmp(&self, a: &Self::Type, b: &Self::Type) -> std::cmp::Ordering {
+    fn cmp(&self, a: &Self::Type, b: &Self::Type, _now: Timestamp) -> std::cmp::Ordering {
         match self {
             Self::Id => a.id().cmp(b.id()),
             Self::Name => a.name().cmp(b.name()),
@@ -446,14 +898,24 @@ pub enum TimestampFilter {
     Tomorrow,
     /// All entries yesterday.
     Yesterday,
-    /// All entries this week (starting on a Monday).
-    ThisWeek,
+    /// All entries this week (starting on the given day).
+    ThisWeek(WeekStart),
+    /// All entries last week (starting on the given day).
+    LastWeek(WeekStart),
     /// All entries within the last given number of days.
     Days(u16),
     /// All entries this calendar month.
     ThisMonth,
+    /// All entries last calendar month.
+    LastMonth,
+    /// All entries this calendar quarter.
+    ThisQuarter,
+    /// All entries last calendar quarter.
+    LastQuarter,
     /// All entries this calendar year.
     ThisYear,
+    /// All entries last calendar year.
+    LastYear,
     /// All entries starting from the given timestamp (inclusive).
     Starting(Timestamp),
     /// All entries up to the given timestamp (exclusive).
@@ -468,9 +930,14 @@ impl TimestampFilter {
             "today" => Self::Today,
             "tomorrow" | "tmrw" => Self::Tomorrow,
             "yesterday" | "yst" => Self::Yesterday,
-            "week" | "this-week" => Self::ThisWeek,
+            "week" | "this-week" => Self::ThisWeek(WeekStart::default()),
             "month" | "this-month" => Self::ThisMonth,
+            "quarter" | "this-quarter" => Self::ThisQuarter,
             "year" | "this-year" => Self::ThisYear,
+            "last-week" => Self::LastWeek(WeekStart::default()),
+            "last-month" => Self::LastMonth,
+            "last-quarter" => Self::LastQuarter,
+            "last-year" => Self::LastYear,
             filter => Self::try_parse_complex(filter, now)?,
         })
     }
@@ -482,15 +949,62 @@ impl TimestampFilter {
             Self::Today => ts >= now.today() && ts < now.tomorrow(),
             Self::Tomorrow => ts >= now.tomorrow() && ts < now.days_forward(2),
             Self::Yesterday => ts >= now.yesterday() && ts < now.today(),
-            Self::ThisWeek => ts >= now.this_week() && ts < now.next_week(),
+            Self::ThisWeek(week_start) => {
+                ts >= now.this_week(*week_start) && ts < now.next_week(*week_start)
+            }
+            Self::LastWeek(week_start) => {
+                ts >= now.last_week(*week_start) && ts < now.this_week(*week_start)
+            }
             Self::Days(days) => ts >= now.days_back(*days) && ts < now,
             Self::ThisMonth => ts >= now.this_month() && ts < now.next_month(),
+            Self::LastMonth => ts >= now.last_month() && ts < now.this_month(),
+            Self::ThisQuarter => ts >= now.this_quarter() && ts < now.next_quarter(),
+            Self::LastQuarter => ts >= now.last_quarter() && ts < now.this_quarter(),
             Self::ThisYear => ts >= now.this_year() && ts < now.next_year(),
+            Self::LastYear => ts >= now.last_year() && ts < now.this_year(),
             Self::Starting(starting) => ts >= *starting,
             Self::Before(before) => ts < *before,
         }
     }
 
+    /// Given the current `now` value, returns the range of timestamps matched
+    /// by this filter as a `(from, to)` tuple, where `from` is inclusive and
+    /// `to` is exclusive. Unbounded ends are `None`.
+    pub fn bounds(&self, now: Timestamp) -> (Option<Timestamp>, Option<Timestamp>) {
+        match self {
+            Self::Today => (Some(now.today()), Some(now.tomorrow())),
+            Self::Tomorrow => (Some(now.tomorrow()), Some(now.days_forward(2))),
+            Self::Yesterday => (Some(now.yesterday()), Some(now.today())),
+            Self::ThisWeek(week_start) => (
+                Some(now.this_week(*week_start)),
+                Some(now.next_week(*week_start)),
+            ),
+            Self::LastWeek(week_start) => (
+                Some(now.last_week(*week_start)),
+                Some(now.this_week(*week_start)),
+            ),
+            Self::Days(days) => (Some(now.days_back(*days)), Some(now)),
+            Self::ThisMonth => (Some(now.this_month()), Some(now.next_month())),
+            Self::LastMonth => (Some(now.last_month()), Some(now.this_month())),
+            Self::ThisQuarter => (Some(now.this_quarter()), Some(now.next_quarter())),
+            Self::LastQuarter => (Some(now.last_quarter()), Some(now.this_quarter())),
+            Self::ThisYear => (Some(now.this_year()), Some(now.next_year())),
+            Self::LastYear => (Some(now.last_year()), Some(now.this_year())),
+            Self::Starting(starting) => (Some(*starting), None),
+            Self::Before(before) => (None, Some(*before)),
+        }
+    }
+
+    /// Use the given day as the start of the week for weekly filters. Weekly
+    /// filters start on a Monday by default.
+    pub fn with_week_start(self, week_start: WeekStart) -> Self {
+        match self {
+            Self::ThisWeek(_) => Self::ThisWeek(week_start),
+            Self::LastWeek(_) => Self::LastWeek(week_start),
+            other => other,
+        }
+    }
+
     // Try to parse a complex timestamp filter.
     fn try_parse_complex(s: &str, now: Timestamp) -> Result<Self, Error> {
         let parts = s.split(' ').map(|p| p.trim()).collect::<Vec<&str>>();
@@ -533,6 +1047,8 @@ pub enum DurationFilter {
     GreaterThan(Duration),
     GreaterThanOrEqual(Duration),
     Equal(Duration),
+    /// Durations between the given lower and upper bounds (inclusive).
+    Between(Duration, Duration),
 }
 
 impl DurationFilter {
@@ -543,15 +1059,20 @@ impl DurationFilter {
             Self::GreaterThan(d) => duration > *d,
             Self::GreaterThanOrEqual(d) => duration >= *d,
             Self::Equal(d) => duration == *d,
+            Self::Between(lower, upper) => duration >= *lower && duration <= *upper,
         }
     }
-}
 
-impl FromStr for DurationFilter {
-    type Err = Error;
+    // Constructs a range filter, ensuring that its bounds are ordered.
+    fn between(lower: Duration, upper: Duration, s: &str) -> Result<Self, Error> {
+        if lower > upper {
+            return Err(Error::InvalidDurationFilterRange(s.to_string()));
+        }
+        Ok(Self::Between(lower, upper))
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.trim();
+    // Parses a filter consisting of a single operator and duration.
+    fn parse_single(s: &str) -> Result<Self, Error> {
         // Find where the first digit starts
         let first_digit_idx = s
             .find(|c: char| c.is_ascii_digit())
@@ -573,6 +1094,41 @@ impl FromStr for DurationFilter {
     }
 }
 
+impl FromStr for DurationFilter {
+    type Err = Error;
+
+    /// Parses filters like "< 1h" and "30m", as well as inclusive ranges
+    /// like "1h..4h" or ">=30m <=2h".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((lower, upper)) = s.split_once("..") {
+            return Self::between(
+                Duration::from_str(lower.trim())?,
+                Duration::from_str(upper.trim())?,
+                s,
+            );
+        }
+        // A second comparison, if any, starts at the first operator following
+        // the first duration
+        let maybe_second_idx = s
+            .find(|c: char| c.is_ascii_digit())
+            .and_then(|idx| s[idx..].find(['<', '>']).map(|second| idx + second));
+        match maybe_second_idx {
+            Some(idx) => match (
+                Self::parse_single(s[..idx].trim())?,
+                Self::parse_single(s[idx..].trim())?,
+            ) {
+                (Self::GreaterThanOrEqual(lower), Self::LessThanOrEqual(upper))
+                | (Self::LessThanOrEqual(upper), Self::GreaterThanOrEqual(lower)) => {
+                    Self::between(lower, upper, s)
+                }
+                _ => Err(Error::InvalidDurationFilter(s.to_string())),
+            },
+            None => Self::parse_single(s),
+        }
+    }
+}
+
 /// For filtering projects by the contents of specific fields.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
 pub enum ProjectFilter {
@@ -585,6 +1141,10 @@ pub enum ProjectFilter {
     Deadline(TimestampFilter),
     /// Include all projects whose tags match at least one of the given tags.
     Tags(Vec<String>),
+    /// Include all projects whose names match the given regular expression.
+    NameMatches(RegexPattern),
+    /// Include all projects that are (or are not) archived.
+    Archived(bool),
 }
 
 impl Default for ProjectFilter {
@@ -615,6 +1175,8 @@ impl Filter for ProjectFilter {
                     .count()
                     > 0
             }
+            Self::NameMatches(pattern) => pattern.is_match(project.name()),
+            Self::Archived(archived) => project.is_archived() == *archived,
         }
     }
 }
@@ -637,6 +1199,19 @@ pub struct Project {
     maybe_github_project: Option<String>,
     #[serde(rename = "task_state_config")]
     maybe_task_state_config: Option<TaskStateConfig>,
+    #[serde(rename = "default_log_filter")]
+    maybe_default_log_filter: Option<String>,
+    #[serde(rename = "default_log_sort")]
+    maybe_default_log_sort: Option<String>,
+    /// The hourly rate at which time spent on this project is billed.
+    #[serde(rename = "rate")]
+    maybe_rate: Option<f64>,
+    #[serde(rename = "currency")]
+    maybe_currency: Option<String>,
+    /// Archived projects are hidden when listing projects, unless explicitly
+    /// requested.
+    #[serde(default)]
+    archived: bool,
 }
 
 impl Project {
@@ -652,6 +1227,11 @@ impl Project {
             maybe_github_org: None,
             maybe_github_project: None,
             maybe_task_state_config: None,
+            maybe_default_log_filter: None,
+            maybe_default_log_sort: None,
+            maybe_rate: None,
+            maybe_currency: None,
+            archived: false,
         }
     }
 
@@ -708,6 +1288,72 @@ impl Project {
         self
     }
 
+    /// Set the start time filter (e.g. "this-month") applied by default when
+    /// listing only this project's logs.
+    pub fn with_default_log_filter<S: AsRef<str>>(self, filter: S) -> Result<Self, Error> {
+        self.with_maybe_default_log_filter(Some(filter.as_ref().to_string()))
+    }
+
+    pub fn with_maybe_default_log_filter(
+        mut self,
+        maybe_filter: Option<String>,
+    ) -> Result<Self, Error> {
+        if let Some(filter) = &maybe_filter {
+            let _ = TimestampFilter::from_str(filter)?;
+        }
+        self.maybe_default_log_filter = maybe_filter;
+        Ok(self)
+    }
+
+    /// Set the sort specification (e.g. "duration:desc") applied by default
+    /// when listing only this project's logs.
+    pub fn with_default_log_sort<S: AsRef<str>>(self, sort: S) -> Result<Self, Error> {
+        self.with_maybe_default_log_sort(Some(sort.as_ref().to_string()))
+    }
+
+    pub fn with_maybe_default_log_sort(
+        mut self,
+        maybe_sort: Option<String>,
+    ) -> Result<Self, Error> {
+        if let Some(sort) = &maybe_sort {
+            let _ = SortSpec::<LogField>::from_str(sort)?;
+        }
+        self.maybe_default_log_sort = maybe_sort;
+        Ok(self)
+    }
+
+    /// Set the hourly rate at which time spent on this project is billed.
+    /// Must be a finite, non-negative amount.
+    pub fn with_rate(self, rate: f64) -> Result<Self, Error> {
+        self.with_maybe_rate(Some(rate))
+    }
+
+    pub fn with_maybe_rate(mut self, maybe_rate: Option<f64>) -> Result<Self, Error> {
+        if let Some(rate) = maybe_rate {
+            if !rate.is_finite() || rate < 0.0 {
+                return Err(Error::InvalidRate(rate));
+            }
+        }
+        self.maybe_rate = maybe_rate;
+        Ok(self)
+    }
+
+    /// Set the currency (e.g. "USD" or "$") in which this project's time is
+    /// billed.
+    pub fn with_currency<S: AsRef<str>>(self, currency: S) -> Self {
+        self.with_maybe_currency(Some(currency.as_ref().to_string()))
+    }
+
+    pub fn with_maybe_currency(mut self, maybe_currency: Option<String>) -> Self {
+        self.maybe_currency = maybe_currency;
+        self
+    }
+
+    pub fn with_archived(mut self, archived: bool) -> Self {
+        self.archived = archived;
+        self
+    }
+
     pub fn id(&self) -> &str {
         self.id.as_str()
     }
@@ -736,6 +1382,10 @@ impl Project {
         self.maybe_github_project.as_deref()
     }
 
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
     /// Computes the GitHub URL for this project from its organization and
     /// GitHub project fields, if present.
     ///
@@ -807,6 +1457,22 @@ impl Project {
         self.maybe_task_state_config.as_ref()
     }
 
+    pub fn default_log_filter(&self) -> Option<&str> {
+        self.maybe_default_log_filter.as_deref()
+    }
+
+    pub fn default_log_sort(&self) -> Option<&str> {
+        self.maybe_default_log_sort.as_deref()
+    }
+
+    pub fn rate(&self) -> Option<f64> {
+        self.maybe_rate
+    }
+
+    pub fn currency(&self) -> Option<&str> {
+        self.maybe_currency.as_deref()
+    }
+
     /// Convenience method to return an ordered list of task states supported by
     /// this project, using the given default config if this project has no
     /// custom task states.
@@ -845,7 +1511,7 @@ impl Default for SortSpec<TaskField> {
 impl Comparator for TaskField {
     type Type = Task;
 
-    fn cmp(&self, a: &Task, b: &Task) -> std::cmp::Ordering {
+    fn cmp(&self, a: &Task, b: &Task, _now: Timestamp) -> std::cmp::Ordering {
         match self {
             Self::Id => a.id().cmp(&b.id()),
             Self::ProjectId => a.project_id().cmp(&b.project_id()),
@@ -921,8 +1587,17 @@ pub enum TaskFilter {
     State(Vec<TaskState>),
     /// Tasks whose states do *not* include the given state.
     StateNot(TaskState),
+    /// Tasks that aren't closed according to the task state configuration of
+    /// their project (keyed by project ID).
+    Open(BTreeMap<ProjectId, TaskStateConfig>),
     /// Tasks whose deadline matches the given timestamp filter.
     Deadline(TimestampFilter),
+    /// Tasks whose deadline falls between now and the start of the day the
+    /// given number of days from now.
+    DueWithin(u16),
+    /// Tasks whose deadline has passed and that aren't closed according to
+    /// the task state configuration of their project (keyed by project ID).
+    Overdue(BTreeMap<ProjectId, TaskStateConfig>),
     /// Tasks whose tags match one or more of the given tags.
     Tags(Vec<String>),
     /// Tasks whose GitHub issue number matches one or more of the given values.
@@ -930,6 +1605,11 @@ pub enum TaskFilter {
     /// Tasks whose associated GitHub pull request number matches one or more of
     /// the given values.
     GitHubPullRequest(Vec<NonZeroU32>),
+    /// Tasks whose descriptions match the given regular expression.
+    DescriptionMatches(RegexPattern),
+    /// Tasks that aren't blocked by any of the given open tasks. Blockers
+    /// that aren't in this list are considered done (or no longer exist).
+    Unblocked(Vec<(ProjectId, TaskId)>),
 }
 
 impl Default for TaskFilter {
@@ -960,10 +1640,26 @@ impl Filter for TaskFilter {
                 .iter()
                 .any(|state| task.state().map(|ts| ts == state).unwrap_or(false)),
             Self::StateNot(state) => task.state().map(|ts| ts != state).unwrap_or(true),
+            Self::Open(task_state_configs) => match (task.project_id(), task.state()) {
+                (Some(project_id), Some(state)) => task_state_configs
+                    .get(project_id)
+                    .map(|task_state_config| !task_state_config.is_closed(state))
+                    .unwrap_or(true),
+                _ => true,
+            },
             Self::Deadline(ts_filter) => task
                 .deadline()
                 .map(|deadline| ts_filter.matches(now, deadline))
                 .unwrap_or(false),
+            Self::DueWithin(days) => task
+                .deadline()
+                .map(|deadline| now <= deadline && deadline <= now.days_forward(*days))
+                .unwrap_or(false),
+            Self::Overdue(task_state_configs) => task
+                .project_id()
+                .and_then(|project_id| task_state_configs.get(project_id))
+                .map(|task_state_config| task.is_overdue(now, task_state_config))
+                .unwrap_or(false),
             Self::Tags(tags) => {
                 task.tags()
                     .collect::<HashSet<&str>>()
@@ -979,6 +1675,15 @@ impl Filter for TaskFilter {
                 .github_pr()
                 .map(|pr_no| prs.iter().any(|p| pr_no == *p))
                 .unwrap_or(false),
+            Self::DescriptionMatches(pattern) => pattern.is_match(task.description()),
+            Self::Unblocked(open_tasks) => match task.project_id() {
+                Some(project_id) => !task.blocked_by().any(|blocker_id| {
+                    open_tasks
+                        .iter()
+                        .any(|(pid, tid)| pid == project_id && *tid == blocker_id)
+                }),
+                None => true,
+            },
         }
     }
 }
@@ -1002,6 +1707,12 @@ pub struct Task {
     maybe_github_issue: Option<NonZeroU32>,
     #[serde(rename = "github_pr")]
     maybe_github_pr: Option<NonZeroU32>,
+    #[serde(rename = "estimate")]
+    maybe_estimate: Option<Duration>,
+    /// IDs of the tasks in the same project that must be completed before
+    /// this one can be started.
+    #[serde(default)]
+    blocked_by: BTreeSet<TaskId>,
     // Cached data for display purposes.
     #[serde(skip)]
     maybe_stats: Option<TaskStats>,
@@ -1026,6 +1737,8 @@ impl Task {
             tags: HashSet::new(),
             maybe_github_issue: None,
             maybe_github_pr: None,
+            maybe_estimate: None,
+            blocked_by: BTreeSet::new(),
             maybe_stats: None,
             maybe_project: None,
         }
@@ -1042,14 +1755,7 @@ impl Task {
     }
 
     pub fn with_priority(mut self, priority: TaskPriority) -> Result<Self, Error> {
-        if !(MIN_TASK_PRIORITY..=MAX_TASK_PRIORITY).contains(&priority) {
-            return Err(Error::InvalidTaskPriority(
-                priority,
-                MIN_TASK_PRIORITY,
-                MAX_TASK_PRIORITY,
-            ));
-        }
-        self.maybe_priority = Some(priority);
+        self.maybe_priority = Some(validate_task_priority(priority)?);
         Ok(self)
     }
 
@@ -1124,6 +1830,23 @@ impl Task {
         Ok(self)
     }
 
+    /// Set an estimate of how long this task will take to complete.
+    pub fn with_estimate(self, estimate: Duration) -> Self {
+        self.with_maybe_estimate(Some(estimate))
+    }
+
+    pub fn with_maybe_estimate(mut self, maybe_estimate: Option<Duration>) -> Self {
+        self.maybe_estimate = maybe_estimate;
+        self
+    }
+
+    /// Set the IDs of the tasks (in the same project) that block this one,
+    /// replacing any existing ones.
+    pub fn with_blocked_by<I: IntoIterator<Item = TaskId>>(mut self, task_ids: I) -> Self {
+        self.blocked_by = task_ids.into_iter().collect();
+        self
+    }
+
     pub fn with_stats(mut self, stats: TaskStats) -> Self {
         self.maybe_stats = Some(stats);
         self
@@ -1158,6 +1881,20 @@ impl Task {
         self.maybe_deadline
     }
 
+    /// Whether this task's deadline has passed without it having been closed
+    /// (i.e. done or in another terminal state).
+    pub fn is_overdue(&self, now: Timestamp, task_state_config: &TaskStateConfig) -> bool {
+        let is_closed = self
+            .state()
+            .map(|state| task_state_config.is_closed(state))
+            .unwrap_or(false);
+        !is_closed
+            && self
+                .deadline()
+                .map(|deadline| deadline < now)
+                .unwrap_or(false)
+    }
+
     pub fn tags(&self) -> impl Iterator<Item = &str> {
         self.tags.iter().map(|t| t.as_str())
     }
@@ -1170,6 +1907,14 @@ impl Task {
         self.maybe_github_pr
     }
 
+    pub fn estimate(&self) -> Option<Duration> {
+        self.maybe_estimate
+    }
+
+    pub fn blocked_by(&self) -> impl Iterator<Item = TaskId> + '_ {
+        self.blocked_by.iter().copied()
+    }
+
     pub fn stats(&self) -> Option<&TaskStats> {
         self.maybe_stats.as_ref()
     }
@@ -1210,13 +1955,14 @@ impl Default for SortSpec<LogField> {
 impl Comparator for LogField {
     type Type = Log;
 
-    fn cmp(&self, a: &Log, b: &Log) -> std::cmp::Ordering {
+    fn cmp(&self, a: &Log, b: &Log, now: Timestamp) -> std::cmp::Ordering {
         match self {
             Self::Id => a.id().cmp(&b.id()),
             Self::ProjectId => a.project_id().cmp(&b.project_id()),
             Self::TaskId => a.task_id().cmp(&b.task_id()),
             Self::Start => a.start().cmp(&b.start()),
-            Self::Duration => a.duration().cmp(&b.duration()),
+            // Running logs are compared by how long they've been running
+            Self::Duration => a.effective_duration(now).cmp(&b.effective_duration(now)),
             Self::Comment => a.comment().cmp(&b.comment()),
         }
     }
@@ -1278,6 +2024,16 @@ pub enum LogFilter {
     Duration(DurationFilter),
     /// All logs whose tags match one or more of the given tags.
     Tags(Vec<String>),
+    /// All logs with a (non-blank) comment.
+    HasComment,
+    /// All logs without a comment, or whose comment is blank.
+    NoComment,
+    /// All logs started on a weekday (Monday to Friday).
+    Weekdays,
+    /// All logs started on a Saturday or Sunday.
+    Weekends,
+    /// All logs with a comment matching the given regular expression.
+    CommentMatches(RegexPattern),
 }
 
 impl Default for LogFilter {
@@ -1315,6 +2071,17 @@ impl Filter for LogFilter {
                     .count()
                     > 0
             }
+            Self::HasComment => log.has_comment(),
+            Self::NoComment => !log.has_comment(),
+            Self::Weekdays => log
+                .start()
+                .map(|start| !start.is_weekend())
+                .unwrap_or(false),
+            Self::Weekends => log.start().map(|start| start.is_weekend()).unwrap_or(false),
+            Self::CommentMatches(pattern) => log
+                .comment()
+                .map(|comment| pattern.is_match(comment))
+                .unwrap_or(false),
         }
     }
 }
@@ -1408,6 +2175,20 @@ impl Log {
         Ok(self)
     }
 
+    /// Like [`Log::with_stop`], but also rejects stop times more than the
+    /// given tolerance beyond `now` (e.g. because of a typo in the date).
+    pub fn with_stop_checked(
+        self,
+        stop: Timestamp,
+        now: Timestamp,
+        tolerance: Duration,
+    ) -> Result<Self, Error> {
+        if stop > now + tolerance {
+            return Err(Error::LogStopInFuture(stop, tolerance));
+        }
+        self.with_stop(stop)
+    }
+
     pub fn with_maybe_stop(self, maybe_stop: Option<Timestamp>) -> Result<Self, Error> {
         match maybe_stop {
             Some(stop) => self.with_stop(stop),
@@ -1462,6 +2243,17 @@ impl Log {
         self
     }
 
+    /// Appends the given text to this log's comment on a new line. If the log
+    /// has no comment yet, the text becomes its comment.
+    pub fn with_appended_comment<S: AsRef<str>>(mut self, comment: S) -> Self {
+        let comment = comment.as_ref();
+        self.maybe_comment = Some(match self.maybe_comment {
+            Some(existing) if !existing.is_empty() => format!("{}\n{}", existing, comment),
+            _ => comment.to_string(),
+        });
+        self
+    }
+
     pub fn with_tags<S, T>(mut self, tags: T) -> Result<Self, Error>
     where
         S: AsRef<str>,
@@ -1510,10 +2302,43 @@ impl Log {
         self.maybe_duration
     }
 
+    /// The duration of this log whether or not it is still running: its
+    /// stored duration if it has been stopped, otherwise the time elapsed
+    /// between its start and `now`. Zero if the log has no start time.
+    pub fn effective_duration(&self, now: Timestamp) -> Duration {
+        match (self.maybe_duration, self.maybe_start) {
+            (Some(duration), _) => duration,
+            (None, Some(start)) => now - start,
+            (None, None) => Duration::zero(),
+        }
+    }
+
+    /// Computes the gap between the stop time of the given previous log and
+    /// the start time of this log.
+    ///
+    /// If this log has no start time, or the previous log has no stop time,
+    /// this returns `None`. Overlapping logs have a zero gap.
+    pub fn gap_since(&self, previous: &Log) -> Option<Duration> {
+        let start = self.start()?;
+        let previous_stop = previous.stop()?;
+        if start > previous_stop {
+            Some(start - previous_stop)
+        } else {
+            Some(Duration::zero())
+        }
+    }
+
     pub fn comment(&self) -> Option<&str> {
         self.maybe_comment.as_deref()
     }
 
+    /// Whether this log has a comment that isn't blank.
+    pub fn has_comment(&self) -> bool {
+        self.comment()
+            .map(|comment| !comment.trim().is_empty())
+            .unwrap_or(false)
+    }
+
     pub fn tags(&self) -> impl Iterator<Item = &str> {
         self.tags.iter().map(|t| t.as_str())
     }
@@ -1523,7 +2348,260 @@ impl Log {
     }
 }
 
-fn validate_tag<S: AsRef<str>>(tag: S) -> Result<String, Error> {
+/// Gross and net totals of the time logged across a number of work logs.
+///
+/// Logs carrying one of the configured break tags (e.g. a lunch break) are
+/// assumed to have been taken during other logged work. The gross total is
+/// the time logged by all other logs, and so includes breaks, while the net
+/// total excludes the time taken for breaks.
+///
+/// If rounding is configured, the rounded gross total is the sum of each
+/// (non-break) log's duration rounded up individually, i.e. the billed time.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogTotals {
+    pub gross: Duration,
+    pub breaks: Duration,
+    pub rounded_gross: Duration,
+}
+
+impl Default for LogTotals {
+    fn default() -> Self {
+        Self {
+            gross: Duration::zero(),
+            breaks: Duration::zero(),
+            rounded_gross: Duration::zero(),
+        }
+    }
+}
+
+impl LogTotals {
+    /// Compute the totals for the given logs, counting running logs up until
+    /// `now`.
+    pub fn new<'a, I>(logs: I, config: &Config, now: Timestamp) -> Self
+    where
+        I: IntoIterator<Item = &'a Log>,
+    {
+        logs.into_iter().fold(Self::default(), |totals, log| {
+            totals.with_log(log, config, now)
+        })
+    }
+
+    pub fn with_log(mut self, log: &Log, config: &Config, now: Timestamp) -> Self {
+        self.add(log, config, now);
+        self
+    }
+
+    pub fn add(&mut self, log: &Log, config: &Config, now: Timestamp) {
+        let duration = log.effective_duration(now);
+        if config.is_break(log) {
+            self.breaks += duration;
+        } else {
+            self.gross += duration;
+            self.rounded_gross += config.rounded_duration(log, now);
+        }
+    }
+
+    /// The gross total less the time taken for breaks. Never negative.
+    pub fn net(&self) -> Duration {
+        net_of_breaks(self.gross, self.breaks)
+    }
+
+    /// The rounded gross total less the (unrounded) time taken for breaks.
+    /// Never negative.
+    pub fn rounded_net(&self) -> Duration {
+        net_of_breaks(self.rounded_gross, self.breaks)
+    }
+}
+
+fn net_of_breaks(gross: Duration, breaks: Duration) -> Duration {
+    if breaks >= gross {
+        Duration::zero()
+    } else {
+        gross - breaks
+    }
+}
+
+/// The kinds of mutating operations recorded in a store's audit trail.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    AddProject,
+    UpdateProject,
+    RemoveProject,
+    RenameProject,
+    AddTask,
+    UpdateTask,
+    RemoveTask,
+    AddLog,
+    UpdateLog,
+    StartLog,
+    StopLog,
+    CancelLog,
+    LinkLog,
+    MoveLog,
+    RenameTag,
+    ConfigStates,
+    ConfigBreakTags,
+    ConfigRounding,
+    ConfigWeekStart,
+    ConfigStopTolerance,
+    ConfigDurationFormat,
+    RepairStore,
+    Import,
+}
+
+impl AuditOperation {
+    const ALL: [Self; 23] = [
+        Self::AddProject,
+        Self::UpdateProject,
+        Self::RemoveProject,
+        Self::RenameProject,
+        Self::AddTask,
+        Self::UpdateTask,
+        Self::RemoveTask,
+        Self::AddLog,
+        Self::UpdateLog,
+        Self::StartLog,
+        Self::StopLog,
+        Self::CancelLog,
+        Self::LinkLog,
+        Self::MoveLog,
+        Self::RenameTag,
+        Self::ConfigStates,
+        Self::ConfigBreakTags,
+        Self::ConfigRounding,
+        Self::ConfigWeekStart,
+        Self::ConfigStopTolerance,
+        Self::ConfigDurationFormat,
+        Self::RepairStore,
+        Self::Import,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AddProject => "add_project",
+            Self::UpdateProject => "update_project",
+            Self::RemoveProject => "remove_project",
+            Self::RenameProject => "rename_project",
+            Self::AddTask => "add_task",
+            Self::UpdateTask => "update_task",
+            Self::RemoveTask => "remove_task",
+            Self::AddLog => "add_log",
+            Self::UpdateLog => "update_log",
+            Self::StartLog => "start_log",
+            Self::StopLog => "stop_log",
+            Self::CancelLog => "cancel_log",
+            Self::LinkLog => "link_log",
+            Self::MoveLog => "move_log",
+            Self::RenameTag => "rename_tag",
+            Self::ConfigStates => "config_states",
+            Self::ConfigBreakTags => "config_break_tags",
+            Self::ConfigRounding => "config_rounding",
+            Self::ConfigWeekStart => "config_week_start",
+            Self::ConfigStopTolerance => "config_stop_tolerance",
+            Self::ConfigDurationFormat => "config_duration_format",
+            Self::RepairStore => "repair_store",
+            Self::Import => "import",
+        }
+    }
+}
+
+impl FromStr for AuditOperation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.replace('-', "_");
+        Self::ALL
+            .into_iter()
+            .find(|op| op.as_str() == s)
+            .ok_or(Error::InvalidAuditOperation(s))
+    }
+}
+
+impl std::fmt::Display for AuditOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A single entry in a store's append-only audit trail, recording a mutating
+/// operation and the IDs of the object it affected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    timestamp: Timestamp,
+    operation: AuditOperation,
+    #[serde(
+        rename = "project_id",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    maybe_project_id: Option<ProjectId>,
+    #[serde(rename = "task_id", skip_serializing_if = "Option::is_none", default)]
+    maybe_task_id: Option<TaskId>,
+    #[serde(rename = "log_id", skip_serializing_if = "Option::is_none", default)]
+    maybe_log_id: Option<LogId>,
+}
+
+impl AuditEntry {
+    pub fn new(operation: AuditOperation, timestamp: Timestamp) -> Self {
+        Self {
+            timestamp,
+            operation,
+            maybe_project_id: None,
+            maybe_task_id: None,
+            maybe_log_id: None,
+        }
+    }
+
+    pub fn with_project_id<S: AsRef<str>>(mut self, project_id: S) -> Self {
+        self.maybe_project_id = Some(project_id.as_ref().to_string());
+        self
+    }
+
+    pub fn with_maybe_task_id(mut self, maybe_task_id: Option<TaskId>) -> Self {
+        self.maybe_task_id = maybe_task_id;
+        self
+    }
+
+    pub fn with_maybe_log_id(mut self, maybe_log_id: Option<LogId>) -> Self {
+        self.maybe_log_id = maybe_log_id;
+        self
+    }
+
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    pub fn operation(&self) -> AuditOperation {
+        self.operation
+    }
+
+    pub fn project_id(&self) -> Option<&str> {
+        self.maybe_project_id.as_deref()
+    }
+
+    pub fn task_id(&self) -> Option<TaskId> {
+        self.maybe_task_id
+    }
+
+    pub fn log_id(&self) -> Option<LogId> {
+        self.maybe_log_id
+    }
+}
+
+pub(crate) fn validate_task_priority(priority: TaskPriority) -> Result<TaskPriority, Error> {
+    if (MIN_TASK_PRIORITY..=MAX_TASK_PRIORITY).contains(&priority) {
+        Ok(priority)
+    } else {
+        Err(Error::InvalidTaskPriority(
+            priority,
+            MIN_TASK_PRIORITY,
+            MAX_TASK_PRIORITY,
+        ))
+    }
+}
+
+pub(crate) fn validate_tag<S: AsRef<str>>(tag: S) -> Result<String, Error> {
     let tag = tag.as_ref().to_lowercase();
     let has_invalid_chars = tag
         .chars()
@@ -1540,11 +2618,11 @@ fn validate_tag<S: AsRef<str>>(tag: S) -> Result<String, Error> {
 #[cfg(test)]
 mod test {
     use super::{
-        Duration, DurationFilter, Order, Project, ProjectField, SortSpec, Timestamp,
-        TimestampFilter,
+        Duration, DurationFilter, Error, Filter, FilterSpec, Log, Order, Project, ProjectField,
+        SortSpec, State, Timestamp, TimestampFilter, WeekStart,
     };
     use lazy_static::lazy_static;
-    use std::str::FromStr;
+    use std::{cell::Cell, rc::Rc, str::FromStr};
     use time::macros::datetime;
 
     lazy_static! {
@@ -1574,12 +2652,56 @@ mod test {
             ("today", TimestampFilter::Today),
             ("tomorrow", TimestampFilter::Tomorrow),
             ("tmrw", TimestampFilter::Tomorrow),
+            ("week", TimestampFilter::ThisWeek(WeekStart::Monday)),
+            ("last-week", TimestampFilter::LastWeek(WeekStart::Monday)),
+            ("Last-Month", TimestampFilter::LastMonth),
+            ("last-year", TimestampFilter::LastYear),
+            ("quarter", TimestampFilter::ThisQuarter),
+            ("this-quarter", TimestampFilter::ThisQuarter),
+            ("last-quarter", TimestampFilter::LastQuarter),
         ];
-        static ref DURATION_FILTER_PARSING_TEST_CASES: Vec<(&'static str, DurationFilter)> =
-            vec![(
+        static ref DURATION_FILTER_PARSING_TEST_CASES: Vec<(&'static str, DurationFilter)> = vec![
+            (
                 "< 1h",
                 DurationFilter::LessThan(Duration::from(time::Duration::HOUR))
-            ),];
+            ),
+            (
+                "30m",
+                DurationFilter::Equal(Duration::from(time::Duration::minutes(30)))
+            ),
+            (
+                "1h..4h",
+                DurationFilter::Between(
+                    Duration::from(time::Duration::HOUR),
+                    Duration::from(time::Duration::hours(4))
+                )
+            ),
+            (
+                "0m .. 1m",
+                DurationFilter::Between(Duration::zero(), Duration::from(time::Duration::MINUTE))
+            ),
+            (
+                ">=30m <=2h",
+                DurationFilter::Between(
+                    Duration::from(time::Duration::minutes(30)),
+                    Duration::from(time::Duration::hours(2))
+                )
+            ),
+            (
+                "<= 2h >= 30m",
+                DurationFilter::Between(
+                    Duration::from(time::Duration::minutes(30)),
+                    Duration::from(time::Duration::hours(2))
+                )
+            ),
+            (
+                "1h..1h",
+                DurationFilter::Between(
+                    Duration::from(time::Duration::HOUR),
+                    Duration::from(time::Duration::HOUR)
+                )
+            ),
+        ];
     }
 
     #[test]
@@ -1606,12 +2728,189 @@ mod test {
         }
     }
 
+    #[test]
+    fn previous_period_timestamp_filters() {
+        // TEST_NOW is Sunday, 5 December 2021
+        for (filter, matching, not_matching) in [
+            (
+                TimestampFilter::LastWeek(WeekStart::Monday),
+                vec![
+                    datetime!(2021-11-22 00:00 -05:00),
+                    datetime!(2021-11-28 23:59 -05:00),
+                ],
+                vec![
+                    datetime!(2021-11-21 23:59 -05:00),
+                    datetime!(2021-11-29 00:00 -05:00),
+                ],
+            ),
+            (
+                TimestampFilter::LastMonth,
+                vec![
+                    datetime!(2021-11-01 00:00 -05:00),
+                    datetime!(2021-11-30 23:59 -05:00),
+                ],
+                vec![
+                    datetime!(2021-10-31 22:00 -05:00),
+                    datetime!(2021-12-01 00:00 -05:00),
+                ],
+            ),
+            (
+                TimestampFilter::LastYear,
+                vec![
+                    datetime!(2020-01-01 00:00 -05:00),
+                    datetime!(2020-12-31 23:59 -05:00),
+                ],
+                vec![
+                    datetime!(2019-12-31 23:59 -05:00),
+                    datetime!(2021-01-01 00:00 -05:00),
+                ],
+            ),
+        ] {
+            for ts in matching {
+                assert!(
+                    filter.matches(*TEST_NOW, Timestamp::from(ts)),
+                    "{:?} {}",
+                    filter,
+                    ts
+                );
+            }
+            for ts in not_matching {
+                assert!(
+                    !filter.matches(*TEST_NOW, Timestamp::from(ts)),
+                    "{:?} {}",
+                    filter,
+                    ts
+                );
+            }
+        }
+
+        // Last month wraps around to the previous year in January
+        let january = Timestamp::from(datetime!(2022-01-15 12:00 -05:00));
+        assert_eq!(
+            TimestampFilter::LastMonth.bounds(january),
+            (
+                Some(Timestamp::from(datetime!(2021-12-01 00:00 -05:00))),
+                Some(Timestamp::from(datetime!(2022-01-01 00:00 -05:00))),
+            )
+        );
+    }
+
+    #[test]
+    fn week_start_changes_weekly_filters() {
+        // TEST_NOW is a Sunday, so it's at the end of a Monday-start week but
+        // at the beginning of a Sunday-start one.
+        let this_week = TimestampFilter::from_str("this-week").unwrap();
+        assert_eq!(
+            this_week.bounds(*TEST_NOW),
+            (
+                Some(Timestamp::from(datetime!(2021-11-29 00:00 -05:00))),
+                Some(Timestamp::from(datetime!(2021-12-06 00:00 -05:00))),
+            )
+        );
+        let sunday_week = this_week.with_week_start(WeekStart::Sunday);
+        assert_eq!(sunday_week, TimestampFilter::ThisWeek(WeekStart::Sunday));
+        assert_eq!(
+            sunday_week.bounds(*TEST_NOW),
+            (
+                Some(Timestamp::from(datetime!(2021-12-05 00:00 -05:00))),
+                Some(Timestamp::from(datetime!(2021-12-12 00:00 -05:00))),
+            )
+        );
+        let saturday = Timestamp::from(datetime!(2021-12-04 12:00 -05:00));
+        assert!(this_week.matches(*TEST_NOW, saturday));
+        assert!(!sunday_week.matches(*TEST_NOW, saturday));
+        assert!(TimestampFilter::LastWeek(WeekStart::Sunday).matches(*TEST_NOW, saturday));
+    }
+
+    #[test]
+    fn quarter_timestamp_filters() {
+        let march_31 = Timestamp::from(datetime!(2022-03-31 12:00 -05:00));
+        let april_1 = Timestamp::from(datetime!(2022-04-01 12:00 -05:00));
+        assert!(TimestampFilter::ThisQuarter.matches(march_31, march_31));
+        assert!(!TimestampFilter::ThisQuarter.matches(march_31, april_1));
+        assert!(TimestampFilter::ThisQuarter.matches(april_1, april_1));
+        assert!(!TimestampFilter::ThisQuarter.matches(april_1, march_31));
+        assert!(TimestampFilter::LastQuarter.matches(april_1, march_31));
+        assert!(!TimestampFilter::LastQuarter.matches(april_1, april_1));
+        assert_eq!(
+            TimestampFilter::ThisQuarter.bounds(march_31),
+            (
+                Some(Timestamp::from(datetime!(2022-01-01 00:00 -05:00))),
+                Some(Timestamp::from(datetime!(2022-04-01 00:00 -05:00))),
+            )
+        );
+        // Quarters wrap around year boundaries
+        assert_eq!(
+            TimestampFilter::LastQuarter.bounds(march_31),
+            (
+                Some(Timestamp::from(datetime!(2021-10-01 00:00 -05:00))),
+                Some(Timestamp::from(datetime!(2022-01-01 00:00 -05:00))),
+            )
+        );
+        assert_eq!(
+            TimestampFilter::ThisQuarter.bounds(*TEST_NOW),
+            (
+                Some(Timestamp::from(datetime!(2021-10-01 00:00 -05:00))),
+                Some(Timestamp::from(datetime!(2022-01-01 00:00 -05:00))),
+            )
+        );
+    }
+
     #[test]
     fn duration_filter_parsing() {
         for (s, expected) in DURATION_FILTER_PARSING_TEST_CASES.iter() {
             let actual = DurationFilter::from_str(s).unwrap();
             assert_eq!(actual, *expected);
         }
+        for s in ["", "<", "1h..", ">30m <2h", ">=30m >=2h", "==1h <=2h"] {
+            assert!(DurationFilter::from_str(s).is_err(), "{}", s);
+        }
+        for s in ["4h..1h", ">=2h <=30m"] {
+            assert!(
+                matches!(
+                    DurationFilter::from_str(s),
+                    Err(Error::InvalidDurationFilterRange(_))
+                ),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn duration_filter_range_matching() {
+        let filter = DurationFilter::from_str("30m..2h").unwrap();
+        for (mins, expected) in [
+            (0, false),
+            (29, false),
+            (30, true),
+            (120, true),
+            (121, false),
+        ] {
+            let duration = Duration::from(time::Duration::minutes(mins));
+            assert_eq!(filter.matches(duration), expected, "{}m", mins);
+        }
+    }
+
+    #[test]
+    fn log_effective_duration() {
+        let start = Timestamp::from(datetime!(2021-12-05 18:00 -05:00));
+        let completed = Log::new("test")
+            .with_start(start)
+            .with_duration(Duration::from(time::Duration::minutes(30)));
+        assert_eq!(
+            completed.effective_duration(*TEST_NOW),
+            Duration::from(time::Duration::minutes(30))
+        );
+
+        let running = Log::new("test").with_start(start);
+        assert_eq!(
+            running.effective_duration(*TEST_NOW),
+            Duration::from(time::Duration::minutes(102))
+        );
+
+        let startless = Log::new("test");
+        assert_eq!(startless.effective_duration(*TEST_NOW), Duration::zero());
     }
 
     #[test]
@@ -1631,4 +2930,85 @@ mod test {
             project.github_pr_url(123).unwrap().unwrap().to_string()
         );
     }
+
+    // A filter that counts how many times its expensive variant is evaluated.
+    #[derive(Debug, Clone, Default, PartialEq)]
+    enum CountingFilter {
+        #[default]
+        All,
+        Cheap(bool),
+        Expensive(Rc<Cell<usize>>),
+    }
+
+    impl Filter for CountingFilter {
+        type Type = ();
+
+        fn matches(&self, _item: &(), _now: Timestamp) -> bool {
+            match self {
+                Self::All => true,
+                Self::Cheap(result) => *result,
+                Self::Expensive(count) => {
+                    count.set(count.get() + 1);
+                    true
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filter_spec_short_circuits() {
+        let count = Rc::new(Cell::new(0));
+        let spec = FilterSpec::new(CountingFilter::Cheap(false))
+            .and_then(CountingFilter::Expensive(count.clone()));
+        assert!(!spec.matches(&(), *TEST_NOW));
+        assert_eq!(count.get(), 0);
+
+        let spec = FilterSpec::new(CountingFilter::Cheap(true))
+            .and_then(CountingFilter::Expensive(count.clone()));
+        assert!(spec.matches(&(), *TEST_NOW));
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn filter_spec_drops_passthrough() {
+        let spec = FilterSpec::new(CountingFilter::All)
+            .and_then(CountingFilter::Cheap(true))
+            .and_then(CountingFilter::All)
+            .and_then(CountingFilter::Cheap(false));
+        assert_eq!(
+            spec,
+            FilterSpec::new(CountingFilter::Cheap(true)).and_then(CountingFilter::Cheap(false))
+        );
+        assert!(!spec.is_passthrough());
+        assert!(FilterSpec::new(CountingFilter::All)
+            .and_then(CountingFilter::All)
+            .is_passthrough());
+    }
+
+    #[test]
+    fn state_accepts_single_active_log() {
+        let state: State = serde_json::from_str(r#"{"active_log": ["project-1", 2, 3]}"#).unwrap();
+        assert_eq!(
+            state.active_logs(),
+            &[("project-1".to_string(), Some(2), 3)]
+        );
+        let state: State = serde_json::from_str(r#"{"active_log": null}"#).unwrap();
+        assert!(state.active_logs().is_empty());
+        let state: State = serde_json::from_str("{}").unwrap();
+        assert!(state.active_logs().is_empty());
+
+        let state = State::default()
+            .with_active_log("project-1", None, 1)
+            .with_active_log("project-2", Some(1), 2);
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(
+            json,
+            r#"{"active_logs":[["project-1",null,1],["project-2",1,2]]}"#
+        );
+        assert_eq!(serde_json::from_str::<State>(&json).unwrap(), state);
+        assert_eq!(
+            state.active_log(),
+            Some(("project-2".to_string(), Some(1), 2))
+        );
+    }
 }
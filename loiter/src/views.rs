@@ -0,0 +1,190 @@
+//! Serializable views of projects, tasks and work logs.
+//!
+//! Projects, tasks and logs don't serialize their IDs, since these are
+//! determined by where they are stored. Views include them, along with some
+//! computed fields (e.g. a log's stop time), and are the shape in which these
+//! objects are rendered for machine-readable output.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Duration, DurationFormat, Log, LogId, Project, ProjectId, Task, TaskId, Timestamp};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectView {
+    pub id: ProjectId,
+    #[serde(flatten)]
+    pub project: Project,
+}
+
+impl From<&Project> for ProjectView {
+    fn from(project: &Project) -> Self {
+        Self {
+            id: project.id().to_string(),
+            project: project.clone(),
+        }
+    }
+}
+
+impl From<ProjectView> for Project {
+    /// A project's ID is always derived from its name, so the view's ID is
+    /// ignored.
+    fn from(view: ProjectView) -> Self {
+        let name = view.project.name().to_string();
+        view.project.with_name(name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskView {
+    pub project_id: Option<ProjectId>,
+    pub id: Option<TaskId>,
+    #[serde(flatten)]
+    pub task: Task,
+}
+
+impl From<&Task> for TaskView {
+    fn from(task: &Task) -> Self {
+        Self {
+            project_id: task.project_id().map(String::from),
+            id: task.id(),
+            task: task.clone(),
+        }
+    }
+}
+
+impl From<TaskView> for Task {
+    fn from(view: TaskView) -> Self {
+        let mut task = view.task;
+        if let Some(project_id) = view.project_id {
+            task = task.with_project_id(project_id);
+        }
+        if let Some(id) = view.id {
+            task = task.with_id(id);
+        }
+        task
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogView {
+    pub project_id: Option<ProjectId>,
+    pub task_id: Option<TaskId>,
+    pub id: Option<LogId>,
+    /// Computed from the log's start time and duration. Not available for
+    /// logs that are still running.
+    pub stop: Option<Timestamp>,
+    /// The log's duration in whole seconds, if it has been stopped.
+    pub duration_seconds: Option<i64>,
+    /// The log's duration as an ISO 8601 duration (e.g. "PT1H30M"), if it has
+    /// been stopped and durations are configured to be rendered that way.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::maybe_duration_iso8601"
+    )]
+    pub duration_iso8601: Option<Duration>,
+    #[serde(flatten)]
+    pub log: Log,
+}
+
+impl LogView {
+    /// A view of the given log, rendering its duration in the given format.
+    pub fn new(log: &Log, duration_format: DurationFormat) -> Self {
+        Self {
+            project_id: log.project_id().map(String::from),
+            task_id: log.task_id(),
+            id: log.id(),
+            stop: log.stop(),
+            duration_seconds: log.duration().map(|duration| duration.whole_seconds()),
+            duration_iso8601: match duration_format {
+                DurationFormat::Seconds => None,
+                DurationFormat::Iso8601 => log.duration(),
+            },
+            log: log.clone(),
+        }
+    }
+}
+
+impl From<&Log> for LogView {
+    fn from(log: &Log) -> Self {
+        Self::new(log, DurationFormat::default())
+    }
+}
+
+impl From<LogView> for Log {
+    /// Computed fields are ignored, since they're always derived from the
+    /// log itself.
+    fn from(view: LogView) -> Self {
+        let mut log = view.log.with_maybe_task_id(view.task_id);
+        if let Some(project_id) = view.project_id {
+            log = log.with_project_id(project_id);
+        }
+        if let Some(id) = view.id {
+            log = log.with_id(id);
+        }
+        log
+    }
+}
+
+/// A project along with all of its tasks and work logs. A JSON array of these
+/// is the document written by [`crate::cmd::export_json`] and read by
+/// [`crate::cmd::import`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectExport {
+    #[serde(flatten)]
+    pub project: ProjectView,
+    #[serde(default)]
+    pub tasks: Vec<TaskView>,
+    /// All of the project's work logs, including those of its tasks.
+    #[serde(default)]
+    pub logs: Vec<LogView>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn views_include_ids_and_round_trip() {
+        let log = Log::new("project-1")
+            .with_task_id(2)
+            .with_id(3)
+            .with_start(Timestamp::from_str("2021-11-29 09:00:00 +00:00").unwrap())
+            .with_duration(crate::Duration::from_str("1h30m").unwrap())
+            .with_comment("Did things");
+        let json = serde_json::to_value(LogView::from(&log)).unwrap();
+        assert_eq!(json["project_id"], "project-1");
+        assert_eq!(json["task_id"], 2);
+        assert_eq!(json["id"], 3);
+        assert_eq!(json["comment"], "Did things");
+        assert_eq!(json["duration_seconds"], 5400);
+        assert!(json.get("duration_iso8601").is_none());
+        let view: LogView = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            view.stop,
+            Some(Timestamp::from_str("2021-11-29 10:30:00 +00:00").unwrap())
+        );
+        assert_eq!(Log::from(view), log);
+
+        let json = serde_json::to_value(LogView::new(&log, DurationFormat::Iso8601)).unwrap();
+        assert_eq!(json["duration_seconds"], 5400);
+        assert_eq!(json["duration_iso8601"], "PT1H30M");
+        let view: LogView = serde_json::from_value(json).unwrap();
+        assert_eq!(view.duration_iso8601, log.duration());
+
+        let task = Task::new("project-1", "Do things").with_id(2);
+        let json = serde_json::to_value(TaskView::from(&task)).unwrap();
+        assert_eq!(json["project_id"], "project-1");
+        assert_eq!(json["id"], 2);
+        let view: TaskView = serde_json::from_value(json).unwrap();
+        assert_eq!(Task::from(view), task);
+
+        let project = Project::new("Project 1");
+        let json = serde_json::to_value(ProjectView::from(&project)).unwrap();
+        assert_eq!(json["id"], "project-1");
+        assert_eq!(json["name"], "Project 1");
+        let view: ProjectView = serde_json::from_value(json).unwrap();
+        assert_eq!(Project::from(view), project);
+    }
+}
@@ -1,7 +1,13 @@
 //! Integration testing for Loiter.
 
-use loiter::{Project, Store, Task};
+use loiter::{
+    cmd, AuditOperation, Duration, FilterSpec, Log, LogFilter, LogTotals, Project, ProjectFilter,
+    State, Store, Task, TaskFilter, TaskStateConfig, Timestamp, MAX_TASK_PRIORITY,
+};
+use std::str::FromStr;
+use structopt::StructOpt;
 use tempfile::tempdir;
+use time::macros::datetime;
 
 #[test]
 fn store_and_load() {
@@ -55,3 +61,4698 @@ fn rename_project() {
     let r = store.task("project-1", 1, false);
     assert!(r.is_err());
 }
+
+#[test]
+fn logs_on_day() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    let task1 = store
+        .save_task(&Task::new("project-2", "Some task"))
+        .unwrap();
+
+    let hour = Duration::from(time::Duration::HOUR);
+    let logs = [
+        // The day before
+        Log::new("project-1")
+            .with_start(Timestamp::from(datetime!(2021-12-04 23:30 -5)))
+            .with_duration(hour),
+        // The day in question, out of order and across projects and tasks
+        Log::new("project-2")
+            .with_task_id(task1.id().unwrap())
+            .with_start(Timestamp::from(datetime!(2021-12-05 14:00 -5)))
+            .with_duration(hour),
+        Log::new("project-1")
+            .with_start(Timestamp::from(datetime!(2021-12-05 09:00 -5)))
+            .with_duration(hour),
+        Log::new("project-2")
+            .with_start(Timestamp::from(datetime!(2021-12-05 11:00 -5)))
+            .with_duration(hour),
+        // The day after
+        Log::new("project-2")
+            .with_start(Timestamp::from(datetime!(2021-12-06 00:00 -5)))
+            .with_duration(hour),
+    ];
+    for log in logs.iter() {
+        store.save_log(log).unwrap();
+    }
+
+    let day_logs = store
+        .logs_on_day(Timestamp::from(datetime!(2021-12-05 12:00 -5)))
+        .unwrap();
+    let starts = day_logs
+        .iter()
+        .map(|log| log.start().unwrap())
+        .collect::<Vec<Timestamp>>();
+    assert_eq!(
+        starts,
+        vec![
+            Timestamp::from(datetime!(2021-12-05 09:00 -5)),
+            Timestamp::from(datetime!(2021-12-05 11:00 -5)),
+            Timestamp::from(datetime!(2021-12-05 14:00 -5)),
+        ]
+    );
+    assert_eq!(day_logs[2].task_id(), task1.id());
+    assert_eq!(
+        day_logs[1].gap_since(&day_logs[0]),
+        Some(Duration::from(time::Duration::HOUR))
+    );
+}
+
+#[test]
+fn default_tags() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let config = store
+        .config()
+        .unwrap()
+        .with_default_log_tags(["dev"])
+        .unwrap()
+        .with_default_task_tags(["work"])
+        .unwrap();
+    store.save_config(&config).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+
+    let task = cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Some task", "--tags", "ux"]),
+    )
+    .unwrap();
+    let mut task_tags = task.tags().collect::<Vec<&str>>();
+    task_tags.sort_unstable();
+    assert_eq!(task_tags, vec!["ux", "work"]);
+
+    let log = cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "--from", "09:00", "--duration", "1h"]),
+    )
+    .unwrap();
+    assert_eq!(log.tags().collect::<Vec<&str>>(), vec!["dev"]);
+
+    let log = cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "--from",
+            "10:00",
+            "--duration",
+            "1h",
+            "--no-default-tags",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(log.tags().count(), 0);
+}
+
+#[test]
+fn start_log_stops_previous_at() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+
+    let now = Timestamp::now().unwrap();
+    let first = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--from", "2021-12-05 09:00"]),
+    )
+    .unwrap();
+    let second = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter([
+            "start",
+            "project-1",
+            "--from",
+            "2021-12-05 11:00",
+            "--stop-previous-at",
+            "2021-12-05 10:00",
+        ]),
+    )
+    .unwrap();
+
+    let first = store.log("project-1", None, first.id().unwrap()).unwrap();
+    assert_eq!(first.duration(), Some(Duration::from(time::Duration::HOUR)));
+    assert_eq!(
+        first.stop(),
+        Some(Timestamp::parse("2021-12-05 10:00", now).unwrap())
+    );
+    let active = store.state().unwrap().active_log().unwrap();
+    assert_eq!(
+        active,
+        ("project-1".to_string(), None, second.id().unwrap())
+    );
+    assert!(store
+        .log("project-1", None, second.id().unwrap())
+        .unwrap()
+        .duration()
+        .is_none());
+
+    // The previous log cannot be stopped before it started.
+    let r = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter([
+            "start",
+            "project-1",
+            "--stop-previous-at",
+            "2021-12-05 10:30",
+        ]),
+    );
+    assert!(r.is_err());
+}
+
+#[test]
+fn default_task_state_filter_uses_configured_done_state() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let task_state_config = TaskStateConfig::new(
+        vec![
+            "todo".to_string(),
+            "doing".to_string(),
+            "finished".to_string(),
+        ],
+        "todo".to_string(),
+        "doing".to_string(),
+        "finished".to_string(),
+    )
+    .unwrap();
+    let config = store
+        .config()
+        .unwrap()
+        .with_task_state_config(&task_state_config);
+    store.save_config(&config).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+
+    for (description, state) in [
+        ("First", "todo"),
+        ("Second", "doing"),
+        ("Third", "finished"),
+    ] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", "project-1", description, "--state", state]),
+        )
+        .unwrap();
+    }
+
+    let task_descriptions = |tasks: Vec<Task>| {
+        tasks
+            .iter()
+            .map(|task| task.description().to_string())
+            .collect::<Vec<String>>()
+    };
+
+    let tasks = cmd::list_tasks(&store, &cmd::ListTasks::from_iter(["tasks"])).unwrap();
+    assert_eq!(task_descriptions(tasks), vec!["First", "Second"]);
+
+    let tasks = cmd::list_tasks(
+        &store,
+        &cmd::ListTasks::from_iter(["tasks", "--state", "!"]),
+    )
+    .unwrap();
+    assert_eq!(task_descriptions(tasks), vec!["First", "Second"]);
+
+    let tasks = cmd::list_tasks(
+        &store,
+        &cmd::ListTasks::from_iter(["tasks", "--state", "any"]),
+    )
+    .unwrap();
+    assert_eq!(task_descriptions(tasks), vec!["First", "Second", "Third"]);
+
+    let r = cmd::list_tasks(
+        &store,
+        &cmd::ListTasks::from_iter(["tasks", "--state", "!any"]),
+    );
+    assert!(r.is_err());
+}
+
+#[test]
+fn default_task_state_filter_uses_project_task_states() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let task_state_config = TaskStateConfig::new(
+        vec![
+            "todo".to_string(),
+            "doing".to_string(),
+            "finished".to_string(),
+        ],
+        "todo".to_string(),
+        "doing".to_string(),
+        "finished".to_string(),
+    )
+    .unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store
+        .save_project(&Project::new("Project 2").with_task_state_config(&task_state_config))
+        .unwrap();
+    for (project_id, description, state) in [
+        ("project-1", "First", "inbox"),
+        ("project-1", "Second", "done"),
+        ("project-2", "Third", "todo"),
+        ("project-2", "Fourth", "finished"),
+    ] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", project_id, description, "--state", state]),
+        )
+        .unwrap();
+    }
+
+    let tasks = cmd::list_tasks(&store, &cmd::ListTasks::from_iter(["tasks"])).unwrap();
+    let mut descriptions = tasks
+        .iter()
+        .map(|task| task.description().to_string())
+        .collect::<Vec<String>>();
+    descriptions.sort();
+    assert_eq!(descriptions, vec!["First", "Third"]);
+}
+
+#[test]
+fn state_recovered_from_backup() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    assert_eq!(store.state().unwrap(), State::default());
+
+    // The last state saved is recovered, not the one before it.
+    let active_state = State::default().with_active_log("project-1", Some(1), 2);
+    store.save_state(&State::default()).unwrap();
+    store.save_state(&active_state).unwrap();
+
+    std::fs::write(temp_dir.path().join("state.json"), "{ corrupt").unwrap();
+    assert_eq!(store.state().unwrap(), active_state);
+
+    // Saving over a corrupt state file makes the new state the backup.
+    store.save_state(&State::default()).unwrap();
+    std::fs::write(temp_dir.path().join("state.json"), "{ corrupt").unwrap();
+    assert_eq!(store.state().unwrap(), State::default());
+}
+
+#[test]
+fn weekly_report() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+
+    for (project_id, from, duration) in [
+        ("project-1", "2021-11-20 09:00", "4h"),
+        ("project-1", "2021-11-30 09:00", "1h"),
+        ("project-2", "2021-12-01 09:00", "30m"),
+        ("project-1", "2021-12-14 09:00", "2h"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", project_id, "--from", from, "--duration", duration]),
+        )
+        .unwrap();
+    }
+
+    let periods = cmd::report(
+        &store,
+        &cmd::Report::from_iter([
+            "report",
+            "--start",
+            "from 2021-11-29",
+            "--by",
+            "week",
+            "--by-project",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(
+        periods
+            .iter()
+            .map(|period| (period.start.format_date(), period.total))
+            .collect::<Vec<(String, Duration)>>(),
+        vec![
+            (
+                "2021-11-29".to_string(),
+                Duration::from_str("1h30m").unwrap()
+            ),
+            ("2021-12-06".to_string(), Duration::zero()),
+            ("2021-12-13".to_string(), Duration::from_str("2h").unwrap()),
+        ]
+    );
+    assert_eq!(
+        periods[0].by_project.get("project-2"),
+        Some(&Duration::from_str("30m").unwrap())
+    );
+    assert!(periods[1].by_project.is_empty());
+
+    let periods = cmd::report(
+        &store,
+        &cmd::Report::from_iter(["report", "--start", "from 2021-11-29"]),
+    )
+    .unwrap();
+    assert_eq!(periods.len(), 1);
+    assert_eq!(periods[0].total, Duration::from_str("3h30m").unwrap());
+}
+
+#[test]
+fn report_per_project() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+
+    // Overlapping logs across projects are each counted in full.
+    for (project_id, from, duration) in [
+        ("project-1", "2021-11-29 09:00", "2h"),
+        ("project-2", "2021-11-29 10:00", "1h"),
+        ("project-1", "2021-11-30 09:00", "1h"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", project_id, "--from", from, "--duration", duration]),
+        )
+        .unwrap();
+    }
+
+    let periods = cmd::report(
+        &store,
+        &cmd::Report::from_iter(["report", "--start", "from 2021-11-29"]),
+    )
+    .unwrap();
+    assert_eq!(periods.len(), 1);
+    let period = &periods[0];
+    assert_eq!(period.total, Duration::from_str("4h").unwrap());
+    assert_eq!(
+        period.by_project.get("project-1"),
+        Some(&Duration::from_str("3h").unwrap())
+    );
+    assert_eq!(
+        period.by_project.get("project-2"),
+        Some(&Duration::from_str("1h").unwrap())
+    );
+    assert_eq!(period.project_percentage("project-1"), Some(75.0));
+    assert_eq!(period.project_percentage("project-2"), Some(25.0));
+    assert_eq!(period.project_percentage("project-3"), None);
+}
+
+#[test]
+fn report_rounding_diff() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    for (project_id, from, duration) in [
+        ("project-1", "2021-11-29 09:00", "5m"),
+        ("project-1", "2021-11-29 10:00", "20m"),
+        ("project-1", "2021-11-29 11:00", "15m"),
+        ("project-2", "2021-11-29 12:00", "1m"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", project_id, "--from", from, "--duration", duration]),
+        )
+        .unwrap();
+    }
+    let params = cmd::Report::from_iter([
+        "report",
+        "--start",
+        "from 2021-11-29",
+        "--round-and-report-diff",
+    ]);
+    assert!(matches!(
+        cmd::report(&store, &params),
+        Err(loiter::Error::NoRoundingConfigured)
+    ));
+
+    let config =
+        cmd::config_rounding(&store, &cmd::ConfigRounding::from_iter(["rounding", "15m"])).unwrap();
+    assert_eq!(
+        config.maybe_rounding(),
+        Some(Duration::from_str("15m").unwrap())
+    );
+
+    let periods = cmd::report(&store, &params).unwrap();
+    assert_eq!(periods.len(), 1);
+    let period = &periods[0];
+    let minutes = |mins: i64| Duration::from(time::Duration::minutes(mins));
+    assert_eq!(period.total, minutes(41));
+    assert_eq!(period.rounded, minutes(75));
+    assert_eq!(period.rounding_diff(), minutes(34));
+    assert_eq!(
+        period.rounded_by_project.get("project-1"),
+        Some(&minutes(60))
+    );
+    assert_eq!(period.project_rounding_diff("project-1"), Some(minutes(20)));
+    assert_eq!(period.project_rounding_diff("project-2"), Some(minutes(14)));
+
+    // An explicit increment overrides the configured one.
+    let periods = cmd::report(
+        &store,
+        &cmd::Report::from_iter(["report", "--start", "from 2021-11-29", "--round", "30m"]),
+    )
+    .unwrap();
+    assert_eq!(periods[0].rounded, minutes(120));
+    assert!(matches!(
+        cmd::report(
+            &store,
+            &cmd::Report::from_iter(["report", "--start", "from 2021-11-29", "--round", "0m"]),
+        ),
+        Err(loiter::Error::InvalidRounding(_))
+    ));
+
+    // Clearing the rounding increment makes billed time equal to raw time.
+    cmd::config_rounding(&store, &cmd::ConfigRounding::from_iter(["rounding"])).unwrap();
+    let periods = cmd::report(
+        &store,
+        &cmd::Report::from_iter(["report", "--start", "from 2021-11-29"]),
+    )
+    .unwrap();
+    assert_eq!(periods[0].rounded, periods[0].total);
+}
+
+#[test]
+fn detect_overlapping_logs() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    for (project_id, from, duration) in [
+        ("project-1", "2021-11-29 09:00", "2h"),
+        ("project-2", "2021-11-29 10:30", "1h"),
+        // Starts exactly when the previous log stops
+        ("project-1", "2021-11-29 11:30", "1h"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", project_id, "--from", from, "--duration", duration]),
+        )
+        .unwrap();
+    }
+
+    let pairs = cmd::overlapping_logs(
+        &store,
+        &cmd::ListLogs::from_iter(["logs", "--start", "from 2021-11-29", "--check-overlaps"]),
+    )
+    .unwrap();
+    assert_eq!(
+        pairs
+            .iter()
+            .map(|(log, other)| (
+                (log.project_id().unwrap(), log.id().unwrap()),
+                (other.project_id().unwrap(), other.id().unwrap())
+            ))
+            .collect::<Vec<_>>(),
+        vec![(("project-1", 1), ("project-2", 1))]
+    );
+
+    // Only logs matching the filters are checked.
+    let pairs = cmd::overlapping_logs(
+        &store,
+        &cmd::ListLogs::from_iter(["logs", "project-1", "--start", "from 2021-11-29"]),
+    )
+    .unwrap();
+    assert!(pairs.is_empty());
+}
+
+#[test]
+fn detect_overlapping_logs_with_task_filter() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for args in [
+        vec!["task", "project-1", "Tagged", "--tags", "dev"],
+        vec!["task", "project-1", "Also tagged", "--tags", "dev"],
+        vec!["task", "project-1", "Untagged"],
+    ] {
+        cmd::add_task(&store, &cmd::AddTask::from_iter(args)).unwrap();
+    }
+    for (task_id, from) in [
+        ("1", "2021-11-29 09:00"),
+        ("3", "2021-11-29 09:30"),
+        ("2", "2021-11-29 10:15"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter([
+                "log",
+                "project-1",
+                task_id,
+                "--from",
+                from,
+                "--duration",
+                "1h",
+            ]),
+        )
+        .unwrap();
+    }
+    let overlapping_tasks = |args: &[&str]| {
+        let mut all_args = vec!["logs", "--start", "from 2021-11-29", "--check-overlaps"];
+        all_args.extend(args);
+        cmd::overlapping_logs(&store, &cmd::ListLogs::from_iter(all_args))
+            .unwrap()
+            .iter()
+            .map(|(log, other)| (log.task_id().unwrap(), other.task_id().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(overlapping_tasks(&[]), vec![(1, 3), (3, 2)]);
+    // The untagged task's log is what overlaps with the others, so once it's
+    // filtered out nothing overlaps.
+    assert!(overlapping_tasks(&["--task-tags", "dev"]).is_empty());
+}
+
+#[test]
+fn concurrent_state_updates_are_serialized() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().to_path_buf();
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let holder = {
+        let path = path.clone();
+        std::thread::spawn(move || {
+            let store = Store::new(&path).unwrap();
+            let _lock = store.lock().unwrap();
+            locked_tx.send(()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            store
+                .save_state(&State::default().with_active_log("project-1", None, 1))
+                .unwrap();
+        })
+    };
+    locked_rx.recv().unwrap();
+
+    let impatient = Store::new(&path)
+        .unwrap()
+        .with_lock_timeout(std::time::Duration::from_millis(50));
+    assert!(matches!(
+        impatient.lock(),
+        Err(loiter::Error::StoreLocked(_))
+    ));
+
+    // Waits for the other thread to finish saving its state before saving its
+    // own, rather than having its state clobbered.
+    let store = Store::new(&path).unwrap();
+    {
+        let _lock = store.lock().unwrap();
+        assert_eq!(
+            store.state().unwrap().active_log(),
+            Some(("project-1".to_string(), None, 1))
+        );
+        store
+            .save_state(&State::default().with_active_log("project-1", None, 2))
+            .unwrap();
+    }
+    holder.join().unwrap();
+    assert_eq!(
+        store.state().unwrap().active_log(),
+        Some(("project-1".to_string(), None, 2))
+    );
+}
+
+#[test]
+fn refuse_stores_that_are_too_new() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let config = store.config().unwrap();
+    assert_eq!(config.format_version(), loiter::STORE_FORMAT_VERSION);
+    assert_eq!(config.min_reader_version(), loiter::STORE_FORMAT_VERSION);
+    // Reopening a store written in the current format is fine
+    Store::new(temp_dir.path()).unwrap();
+
+    // A store written by a future version of Loiter, with configuration this
+    // version doesn't know about
+    let future_version = loiter::STORE_FORMAT_VERSION + 1;
+    std::fs::write(
+        temp_dir.path().join("config.json"),
+        format!(
+            r#"{{"format_version": {0}, "min_reader_version": {0}, "new_field": true}}"#,
+            future_version
+        ),
+    )
+    .unwrap();
+    match Store::new(temp_dir.path()) {
+        Err(e @ loiter::Error::StoreTooNew(required, supported)) => {
+            assert_eq!(required, future_version);
+            assert_eq!(supported, loiter::STORE_FORMAT_VERSION);
+            assert!(e.to_string().contains("please upgrade Loiter"));
+        }
+        r => panic!("expected the store to be refused, got: {:?}", r),
+    }
+}
+
+#[test]
+fn breaks_reduce_net_totals() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let config = cmd::config_break_tags(
+        &store,
+        &cmd::ConfigBreakTags::from_iter(["break-tags", "lunch"]),
+    )
+    .unwrap();
+    assert_eq!(config.break_tags().collect::<Vec<&str>>(), vec!["lunch"]);
+
+    for (from, duration, tags) in [
+        ("2021-11-29 09:00", "8h", "client"),
+        ("2021-11-29 12:00", "1h", "lunch"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter([
+                "log",
+                "project-1",
+                "--from",
+                from,
+                "--duration",
+                duration,
+                "--tags",
+                tags,
+            ]),
+        )
+        .unwrap();
+    }
+
+    let logs = cmd::day(&store, &cmd::Day::from_iter(["day", "2021-11-29"])).unwrap();
+    let totals = LogTotals::new(&logs, &store.config().unwrap(), Timestamp::now().unwrap());
+    assert_eq!(totals.gross, Duration::from_str("8h").unwrap());
+    assert_eq!(totals.breaks, Duration::from_str("1h").unwrap());
+    assert_eq!(totals.net(), Duration::from_str("7h").unwrap());
+
+    let periods = cmd::report(
+        &store,
+        &cmd::Report::from_iter([
+            "report",
+            "--start",
+            "from 2021-11-29",
+            "--by",
+            "day",
+            "--by-project",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(periods[0].total, Duration::from_str("7h").unwrap());
+    assert_eq!(periods[0].gross, Duration::from_str("8h").unwrap());
+    assert_eq!(periods[0].breaks, Duration::from_str("1h").unwrap());
+    assert_eq!(
+        periods[0].by_project.get("project-1"),
+        Some(&Duration::from_str("7h").unwrap())
+    );
+
+    // Without break tags, all logs count towards the total.
+    cmd::config_break_tags(&store, &cmd::ConfigBreakTags::from_iter(["break-tags"])).unwrap();
+    let periods = cmd::report(
+        &store,
+        &cmd::Report::from_iter(["report", "--start", "from 2021-11-29", "--by", "day"]),
+    )
+    .unwrap();
+    assert_eq!(periods[0].total, Duration::from_str("9h").unwrap());
+}
+
+#[test]
+fn logging_against_done_task() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let config = store.config().unwrap();
+    let task_state_config = config.task_state_config();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task 1"]),
+    )
+    .unwrap();
+    cmd::mark_tasks_done(
+        &store,
+        &cmd::MarkTasks::from_iter(["done", "project-1", "1"]),
+    )
+    .unwrap();
+
+    // By default, the task is left done, but reported as closed.
+    let log = cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "1", "--ago", "1h", "--duration", "30m"]),
+    )
+    .unwrap();
+    let closed_task = cmd::closed_log_task(&store, &log).unwrap().unwrap();
+    assert_eq!(closed_task.state(), Some(task_state_config.done()));
+    let log = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1"]),
+    )
+    .unwrap();
+    assert!(cmd::closed_log_task(&store, &log).unwrap().is_some());
+    assert_eq!(
+        store.task("project-1", 1, false).unwrap().state(),
+        Some(task_state_config.done())
+    );
+
+    let log = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1", "--reopen", "--restart"]),
+    )
+    .unwrap();
+    assert!(cmd::closed_log_task(&store, &log).unwrap().is_none());
+    assert_eq!(
+        store.task("project-1", 1, false).unwrap().state(),
+        Some(task_state_config.in_progress())
+    );
+}
+
+#[test]
+fn start_log_with_estimate() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task 1"]),
+    )
+    .unwrap();
+    let estimate = |task_id| store.task("project-1", task_id, false).unwrap().estimate();
+
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1", "--estimate", "2h"]),
+    )
+    .unwrap();
+    assert_eq!(estimate(1), Some(Duration::from_str("2h").unwrap()));
+
+    // An existing estimate is only overwritten if forced.
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1", "--estimate", "3h", "--restart"]),
+    )
+    .unwrap();
+    assert_eq!(estimate(1), Some(Duration::from_str("2h").unwrap()));
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter([
+            "start",
+            "project-1",
+            "1",
+            "--estimate",
+            "3h",
+            "--force",
+            "--restart",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(estimate(1), Some(Duration::from_str("3h").unwrap()));
+
+    // Estimates only apply to tasks.
+    assert!(cmd::StartLog::from_iter_safe(["start", "project-1", "--estimate", "1h"]).is_err());
+}
+
+#[test]
+fn remove_tasks() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for description in ["Task 1", "Task 2", "Task 3"] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", "project-1", description]),
+        )
+        .unwrap();
+    }
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "3"]),
+    )
+    .unwrap();
+    let task_ids = |store: &Store| {
+        store
+            .project_tasks("project-1", &FilterSpec::new(TaskFilter::All), false)
+            .unwrap()
+            .iter()
+            .filter_map(Task::id)
+            .collect::<Vec<_>>()
+    };
+
+    // Nothing is removed unless all of the tasks exist
+    assert!(matches!(
+        cmd::remove_task(
+            &store,
+            &cmd::RemoveTask::from_iter(["task", "project-1", "1,1,4"])
+        ),
+        Err(loiter::Error::TaskNotFound(_, 4))
+    ));
+    assert_eq!(task_ids(&store), vec![1, 2, 3]);
+
+    let removed = cmd::remove_task(
+        &store,
+        &cmd::RemoveTask::from_iter(["task", "project-1", "1,2,1"]),
+    )
+    .unwrap();
+    assert_eq!(removed, vec![1, 2]);
+    assert_eq!(task_ids(&store), vec![3]);
+    assert!(matches!(
+        cmd::remove_task(
+            &store,
+            &cmd::RemoveTask::from_iter(["task", "project-1", "1"])
+        ),
+        Err(loiter::Error::TaskNotFound(_, 1))
+    ));
+
+    // Tasks with logs are only removed if forced, along with their logs.
+    assert!(matches!(
+        cmd::remove_task(
+            &store,
+            &cmd::RemoveTask::from_iter(["task", "project-1", "3"])
+        ),
+        Err(loiter::Error::TaskHasLogs(_, 3, 1))
+    ));
+    assert_eq!(task_ids(&store), vec![3]);
+    cmd::remove_task(
+        &store,
+        &cmd::RemoveTask::from_iter(["task", "project-1", "3", "--force"]),
+    )
+    .unwrap();
+    assert!(task_ids(&store).is_empty());
+    assert!(store.state().unwrap().active_log().is_none());
+}
+
+#[test]
+fn mark_tasks_done_stops_active_log() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let task_state_config = TaskStateConfig::new(
+        vec![
+            "todo".to_string(),
+            "doing".to_string(),
+            "finished".to_string(),
+        ],
+        "todo".to_string(),
+        "doing".to_string(),
+        "finished".to_string(),
+    )
+    .unwrap();
+    store
+        .save_project(&Project::new("Project 1").with_task_state_config(&task_state_config))
+        .unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task 1"]),
+    )
+    .unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task 2"]),
+    )
+    .unwrap();
+
+    let tasks = cmd::mark_tasks_in_progress(
+        &store,
+        &cmd::MarkTasks::from_iter(["doing", "project-1", "2"]),
+    )
+    .unwrap();
+    assert_eq!(tasks[0].state(), Some("doing"));
+
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1"]),
+    )
+    .unwrap();
+    let (tasks, stopped_logs) = cmd::mark_tasks_done(
+        &store,
+        &cmd::MarkTasks::from_iter(["done", "project-1", "1"]),
+    )
+    .unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].state(), Some("finished"));
+    assert_eq!(stopped_logs.len(), 1);
+    let stopped_log = &stopped_logs[0];
+    assert_eq!(stopped_log.task_id(), Some(1));
+    assert!(stopped_log.duration().is_some());
+    assert!(store.state().unwrap().active_log().is_none());
+
+    // Marking a task as done that doesn't have the active log leaves the
+    // active log running.
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1"]),
+    )
+    .unwrap();
+    let (_, stopped_logs) = cmd::mark_tasks_done(
+        &store,
+        &cmd::MarkTasks::from_iter(["done", "project-1", "2"]),
+    )
+    .unwrap();
+    assert!(stopped_logs.is_empty());
+    assert!(store.state().unwrap().active_log().is_some());
+
+    // A task that doesn't exist leaves the active log running.
+    assert!(matches!(
+        cmd::mark_tasks_done(
+            &store,
+            &cmd::MarkTasks::from_iter(["done", "project-1", "1,3"]),
+        ),
+        Err(loiter::Error::TaskNotFound(_, 3))
+    ));
+    assert!(store.state().unwrap().active_log().is_some());
+}
+
+#[test]
+fn orphaned_logs() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "--from", "09:00", "--duration", "1h"]),
+    )
+    .unwrap();
+    assert!(store.orphaned_logs().unwrap().is_empty());
+
+    // Simulate a project whose metadata has been removed by hand.
+    let log_path = temp_dir
+        .path()
+        .join("project-1")
+        .join("logs")
+        .join("00001.json");
+    let orphan_logs_path = temp_dir.path().join("orphan").join("logs");
+    std::fs::create_dir_all(&orphan_logs_path).unwrap();
+    std::fs::copy(&log_path, orphan_logs_path.join("00001.json")).unwrap();
+    assert_eq!(
+        store.orphaned_logs().unwrap(),
+        vec![orphan_logs_path.join("00001.json")]
+    );
+
+    let report = cmd::check(&store, &cmd::Check::from_iter(["check"])).unwrap();
+    assert_eq!(report.orphaned_logs.len(), 1);
+    assert!(report.recovered_projects.is_empty());
+
+    let report = cmd::check(&store, &cmd::Check::from_iter(["check", "--recover"])).unwrap();
+    assert_eq!(report.recovered_projects, vec!["orphan".to_string()]);
+    assert!(store.orphaned_logs().unwrap().is_empty());
+    assert!(store.project("orphan").is_ok());
+}
+
+#[test]
+fn wide_task_ids() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store
+        .save_task(&Task::new("project-1", "Task 10000").with_id(10000))
+        .unwrap();
+    store
+        .save_task(&Task::new("project-1", "Task 9999").with_id(9999))
+        .unwrap();
+    let tasks_path = temp_dir.path().join("project-1").join("tasks");
+    assert!(tasks_path.join("9999").is_dir());
+    assert!(tasks_path.join("10000").is_dir());
+
+    let task_ids = |store: &Store| {
+        store
+            .project_tasks("project-1", &FilterSpec::new(TaskFilter::All), false)
+            .unwrap()
+            .iter()
+            .map(|task| task.id().unwrap())
+            .collect::<Vec<u32>>()
+    };
+    assert_eq!(task_ids(&store), vec![9999, 10000]);
+
+    // Widen the padding for new tasks and logs.
+    let config = store
+        .config()
+        .unwrap()
+        .with_task_id_width(6)
+        .unwrap()
+        .with_log_id_width(7)
+        .unwrap();
+    store.save_config(&config).unwrap();
+    let task = cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task"]),
+    )
+    .unwrap();
+    assert_eq!(task.id(), Some(10001));
+    assert!(tasks_path.join("010001").is_dir());
+    assert_eq!(task_ids(&store), vec![9999, 10000, 10001]);
+
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "10001",
+            "--from",
+            "09:00",
+            "--duration",
+            "1h",
+        ]),
+    )
+    .unwrap();
+    assert!(tasks_path.join("010001").join("0000001.json").is_file());
+    let task = store.task("project-1", 10001, true).unwrap();
+    assert_eq!(task.description(), "Task");
+
+    assert!(store.config().unwrap().with_task_id_width(11).is_err());
+}
+
+#[test]
+fn stop_log_split_by_day() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--from", "2021-12-01 20:00"]),
+    )
+    .unwrap();
+    let log = cmd::stop_log(
+        &store,
+        &cmd::StopLog::from_iter([
+            "stop",
+            "--at",
+            "2021-12-03 22:00",
+            "--comment",
+            "Long haul",
+            "--tags",
+            "dev",
+            "--split-by-day",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(log.start().unwrap().format_date(), "2021-12-03");
+    assert!(store.state().unwrap().active_log().is_none());
+
+    let logs = store
+        .logs_for_project_or_task(
+            "project-1",
+            None,
+            &FilterSpec::new(LogFilter::All),
+            false,
+            None,
+        )
+        .unwrap();
+    assert_eq!(
+        logs.iter()
+            .map(|log| (log.start().unwrap().format_time(), log.duration().unwrap()))
+            .collect::<Vec<(String, Duration)>>(),
+        vec![
+            ("20:00".to_string(), Duration::from_str("4h").unwrap()),
+            ("00:00".to_string(), Duration::from_str("24h").unwrap()),
+            ("00:00".to_string(), Duration::from_str("22h").unwrap()),
+        ]
+    );
+    for log in logs.iter() {
+        assert_eq!(log.comment(), Some("Long haul"));
+        assert_eq!(log.tags().collect::<Vec<&str>>(), vec!["dev"]);
+    }
+}
+
+#[test]
+fn concurrent_starts_are_serialized() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+
+    let handles = ["project-1", "project-2"]
+        .into_iter()
+        .map(|project_id| {
+            let path = temp_dir.path().to_path_buf();
+            std::thread::spawn(move || {
+                let store = Store::new(path).unwrap();
+                cmd::start_log(&store, &cmd::StartLog::from_iter(["start", project_id])).unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let logs = store
+        .logs(
+            &FilterSpec::new(ProjectFilter::All),
+            &FilterSpec::new(TaskFilter::All),
+            &FilterSpec::new(LogFilter::All),
+            false,
+        )
+        .unwrap();
+    assert_eq!(logs.len(), 2);
+    let running = logs
+        .iter()
+        .filter(|log| log.duration().is_none())
+        .collect::<Vec<&Log>>();
+    assert_eq!(running.len(), 1);
+    let (project_id, maybe_task_id, log_id) = store.state().unwrap().active_log().unwrap();
+    assert_eq!(running[0].project_id(), Some(project_id.as_str()));
+    assert_eq!(running[0].task_id(), maybe_task_id);
+    assert_eq!(running[0].id(), Some(log_id));
+}
+
+#[test]
+fn stop_log_comment_append() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--comment", "Started on it"]),
+    )
+    .unwrap();
+    let log = cmd::stop_log(
+        &store,
+        &cmd::StopLog::from_iter(["stop", "--comment-append", "Finished it"]),
+    )
+    .unwrap();
+    assert_eq!(log.comment(), Some("Started on it\nFinished it"));
+
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    let log = cmd::stop_log(
+        &store,
+        &cmd::StopLog::from_iter(["stop", "--comment-append", "Finished it"]),
+    )
+    .unwrap();
+    assert_eq!(log.comment(), Some("Finished it"));
+
+    assert!(cmd::StopLog::from_iter_safe([
+        "stop",
+        "--comment",
+        "Replaced",
+        "--comment-append",
+        "Appended"
+    ])
+    .is_err());
+}
+
+#[test]
+fn update_log_moves_and_edits() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+
+    let log = cmd::update_log(
+        &store,
+        &cmd::UpdateLog::from_iter([
+            "log",
+            "--project",
+            "project-1",
+            "--id",
+            "1",
+            "--to-task",
+            "1",
+            "--comment",
+            "On the right task",
+        ]),
+    )
+    .unwrap();
+    assert_eq!((log.task_id(), log.id()), (Some(1), Some(1)));
+    assert!(store.log("project-1", None, 1).is_err());
+    assert_eq!(
+        store.log("project-1", Some(1), 1).unwrap().comment(),
+        Some("On the right task")
+    );
+    // The moved log is still the active one
+    assert_eq!(
+        store.state().unwrap().active_logs(),
+        &[("project-1".to_string(), Some(1), 1)]
+    );
+}
+
+#[test]
+fn update_and_move_log_comment_append() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "--from",
+            "09:00",
+            "--duration",
+            "1h",
+            "--comment",
+            "Reviewing",
+        ]),
+    )
+    .unwrap();
+
+    let log = cmd::update_log(
+        &store,
+        &cmd::UpdateLog::from_iter([
+            "log",
+            "--project",
+            "project-1",
+            "--id",
+            "1",
+            "--comment-append",
+            "Found a bug",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(log.comment(), Some("Reviewing\nFound a bug"));
+
+    let log = cmd::move_log(
+        &store,
+        &cmd::MoveLog::from_iter([
+            "log",
+            "--project",
+            "project-1",
+            "--id",
+            "1",
+            "--to-task",
+            "1",
+            "--comment-append",
+            "Belongs to task 1",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(log.task_id(), Some(1));
+    assert_eq!(
+        store
+            .log("project-1", Some(1), log.id().unwrap())
+            .unwrap()
+            .comment(),
+        Some("Reviewing\nFound a bug\nBelongs to task 1")
+    );
+}
+
+#[test]
+fn list_logs_reverse() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for from in ["2021-12-01 11:00", "2021-12-01 09:00", "2021-12-01 10:00"] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--from", from, "--duration", "30m"]),
+        )
+        .unwrap();
+    }
+
+    let log_ids = |args: &[&str]| {
+        let mut all_args = vec!["logs", "--start", "from 2021-12-01"];
+        all_args.extend_from_slice(args);
+        cmd::list_logs(&store, &cmd::ListLogs::from_iter(all_args))
+            .unwrap()
+            .iter()
+            .map(|log| log.id().unwrap())
+            .collect::<Vec<u32>>()
+    };
+    assert_eq!(log_ids(&["--sort", "start"]), vec![2, 3, 1]);
+    assert_eq!(
+        log_ids(&["--sort", "start", "--reverse"]),
+        log_ids(&["--sort", "start:desc"])
+    );
+    assert_eq!(log_ids(&["--sort", "start", "-r"]), vec![1, 3, 2]);
+}
+
+#[test]
+fn task_id_collisions() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for description in ["Task 1", "Task 2", "Task 3"] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", "project-1", description]),
+        )
+        .unwrap();
+    }
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "3",
+            "--from",
+            "09:00",
+            "--duration",
+            "1h",
+        ]),
+    )
+    .unwrap();
+    assert!(store.id_collisions().unwrap().is_empty());
+
+    // Copy task 3 to a directory with an unpadded name, and one of its logs to
+    // an unpadded file name.
+    let tasks_path = temp_dir.path().join("project-1").join("tasks");
+    std::fs::create_dir(tasks_path.join("3")).unwrap();
+    std::fs::copy(
+        tasks_path.join("0003").join("task.json"),
+        tasks_path.join("3").join("task.json"),
+    )
+    .unwrap();
+    std::fs::copy(
+        tasks_path.join("0003").join("00001.json"),
+        tasks_path.join("0003").join("1.json"),
+    )
+    .unwrap();
+
+    let report = cmd::check(&store, &cmd::Check::from_iter(["check"])).unwrap();
+    assert_eq!(
+        report.id_collisions,
+        vec![
+            vec![tasks_path.join("0003"), tasks_path.join("3")],
+            vec![
+                tasks_path.join("0003").join("00001.json"),
+                tasks_path.join("0003").join("1.json"),
+            ],
+        ]
+    );
+    // Looking up a colliding ID reports the collision instead of picking one
+    // of the paths.
+    assert!(matches!(
+        store.task("project-1", 3, false),
+        Err(loiter::Error::IdCollision(paths))
+            if paths == vec![tasks_path.join("0003"), tasks_path.join("3")]
+    ));
+    assert!(store.task("project-1", 2, false).is_ok());
+}
+
+#[test]
+fn stats_punchcard() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    // 2021-12-01 is a Wednesday, and 2021-12-06 a Monday.
+    for (from, duration) in [
+        ("2021-12-01 09:30", "1h"),
+        ("2021-12-01 10:00", "20m"),
+        ("2021-12-06 23:45", "30m"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--from", from, "--duration", duration]),
+        )
+        .unwrap();
+    }
+
+    let stats = cmd::stats(
+        &store,
+        &cmd::Stats::from_iter(["stats", "--start", "from 2021-12-01", "--punchcard"]),
+    )
+    .unwrap();
+    assert_eq!(stats.log_count, 3);
+    assert_eq!(stats.total, Duration::from_str("1h50m").unwrap());
+
+    let punchcard = stats.maybe_punchcard.unwrap();
+    let mut cells = Vec::new();
+    for (day, hours) in punchcard.iter().enumerate() {
+        for (hour, duration) in hours.iter().enumerate() {
+            if *duration > Duration::zero() {
+                cells.push((day, hour, duration.to_string()));
+            }
+        }
+    }
+    assert_eq!(
+        cells,
+        vec![
+            (0, 23, "15m".to_string()),
+            (1, 0, "15m".to_string()),
+            (2, 9, "30m".to_string()),
+            (2, 10, "50m".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn patch_task_and_log() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task 1"]),
+    )
+    .unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "--from", "09:00", "--duration", "1h"]),
+    )
+    .unwrap();
+
+    let patches = r#"[
+        { "type": "task", "project_id": "project-1", "task_ids": "1", "priority": 2 },
+        { "type": "log", "project_id": "project-1", "id": 1, "comment": "Patched" }
+    ]"#;
+    let outcomes = cmd::patch(
+        &store,
+        &cmd::Patch::from_iter(["patch"]),
+        patches.as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes.iter().all(|outcome| outcome.maybe_error.is_none()));
+    assert_eq!(store.task("project-1", 1, false).unwrap().priority(), 2);
+    assert_eq!(
+        store.log("project-1", None, 1).unwrap().comment(),
+        Some("Patched")
+    );
+
+    // A failing patch doesn't prevent subsequent ones from being applied,
+    // unless in strict mode.
+    let patches = r#"[
+        { "type": "task", "project_id": "project-1", "task_ids": "2", "priority": 3 },
+        { "type": "project", "id": "project-1", "description": "Patched" }
+    ]"#;
+    let outcomes = cmd::patch(
+        &store,
+        &cmd::Patch::from_iter(["patch"]),
+        patches.as_bytes(),
+    )
+    .unwrap();
+    assert!(outcomes[0].maybe_error.is_some());
+    assert!(outcomes[1].maybe_error.is_none());
+    assert_eq!(
+        store.project("project-1").unwrap().description(),
+        Some("Patched")
+    );
+    assert!(cmd::patch(
+        &store,
+        &cmd::Patch::from_iter(["patch", "--strict"]),
+        patches.as_bytes()
+    )
+    .is_err());
+}
+
+#[test]
+fn list_logs_by_comment_presence() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for (from, comment) in [("09:00", "Did things"), ("10:00", ""), ("11:00", "  ")] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter([
+                "log",
+                "project-1",
+                "--from",
+                from,
+                "--duration",
+                "30m",
+                "--comment",
+                comment,
+            ]),
+        )
+        .unwrap();
+    }
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "--from", "12:00", "--duration", "30m"]),
+    )
+    .unwrap();
+
+    let log_ids = |flag: &str| {
+        cmd::list_logs(&store, &cmd::ListLogs::from_iter(["logs", flag]))
+            .unwrap()
+            .iter()
+            .map(|log| log.id().unwrap())
+            .collect::<Vec<u32>>()
+    };
+    assert_eq!(log_ids("--has-comment"), vec![1]);
+    assert_eq!(log_ids("--no-comment"), vec![2, 3, 4]);
+    assert!(cmd::ListLogs::from_iter_safe(["logs", "--has-comment", "--no-comment"]).is_err());
+}
+
+#[test]
+fn rename_project_with_active_log() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+
+    let project = cmd::rename_project(
+        &store,
+        &cmd::RenameProject::from_iter(["project", "project-1", "Renamed Project"]),
+    )
+    .unwrap();
+    assert_eq!(project.id(), "renamed-project");
+    assert!(store.project("project-1").is_err());
+
+    let status = cmd::active_log_status(&store).unwrap().unwrap();
+    assert_eq!(status.log.project_id().unwrap(), "renamed-project");
+    assert_eq!(status.log.id().unwrap(), 1);
+}
+
+#[test]
+fn rename_project_updates_task_and_log_ids() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    for args in [
+        vec![
+            "log",
+            "project-1",
+            "--from",
+            "2021-12-01 09:00",
+            "--duration",
+            "1h",
+        ],
+        vec![
+            "log",
+            "project-1",
+            "1",
+            "--from",
+            "2021-12-01 11:00",
+            "--duration",
+            "2h",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+    // Simulate a log file carrying a stale serialized project ID, which must
+    // be ignored in favour of the ID derived from its path.
+    let log_path = temp_dir
+        .path()
+        .join("project-1")
+        .join("logs")
+        .join("00001.json");
+    let mut log_json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&log_path).unwrap()).unwrap();
+    log_json["project_id"] = serde_json::Value::from("project-1");
+    std::fs::write(&log_path, log_json.to_string()).unwrap();
+
+    cmd::rename_project(
+        &store,
+        &cmd::RenameProject::from_iter(["project", "project-1", "Client Work"]),
+    )
+    .unwrap();
+
+    let logs = store
+        .logs(
+            &FilterSpec::new(ProjectFilter::All),
+            &FilterSpec::new(TaskFilter::All),
+            &FilterSpec::new(LogFilter::All),
+            true,
+        )
+        .unwrap();
+    assert_eq!(logs.len(), 2);
+    for log in logs.iter() {
+        assert_eq!(log.project_id(), Some("client-work"));
+        if let Some(task) = log.task() {
+            assert_eq!(task.project_id(), Some("client-work"));
+        }
+    }
+    assert_eq!(
+        logs.iter().map(Log::task_id).collect::<Vec<Option<u32>>>(),
+        vec![None, Some(1)]
+    );
+    let tasks = cmd::list_tasks(&store, &cmd::ListTasks::from_iter(["tasks"])).unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].project_id(), Some("client-work"));
+}
+
+#[test]
+fn tags_across_store() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    cmd::add_project(
+        &store,
+        &cmd::AddProject::from_iter(["project", "Project 1", "--tags", "client,web"]),
+    )
+    .unwrap();
+    cmd::add_project(
+        &store,
+        &cmd::AddProject::from_iter(["project", "Project 2"]),
+    )
+    .unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-2", "Task 1", "--tags", "web,bug"]),
+    )
+    .unwrap();
+    for args in [
+        vec![
+            "log",
+            "project-1",
+            "--from",
+            "2021-12-01 09:00",
+            "--duration",
+            "1h",
+            "--tags",
+            "client",
+        ],
+        vec![
+            "log",
+            "project-2",
+            "1",
+            "--from",
+            "2021-12-01 11:00",
+            "--duration",
+            "1h",
+            "--tags",
+            "bug,meeting",
+        ],
+        vec![
+            "log",
+            "project-2",
+            "--from",
+            "2021-12-01 13:00",
+            "--duration",
+            "1h",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+
+    assert_eq!(
+        store.tags().unwrap().into_iter().collect::<Vec<String>>(),
+        vec!["bug", "client", "meeting", "web"]
+    );
+    assert_eq!(
+        cmd::tags(&store)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<(String, usize)>>(),
+        vec![
+            ("bug".to_string(), 2),
+            ("client".to_string(), 2),
+            ("meeting".to_string(), 1),
+            ("web".to_string(), 2),
+        ]
+    );
+}
+
+#[test]
+fn rename_tag_across_store() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    cmd::add_project(
+        &store,
+        &cmd::AddProject::from_iter(["project", "Project 1", "--tags", "ux,client"]),
+    )
+    .unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task 1", "--tags", "ux,design"]),
+    )
+    .unwrap();
+    for args in [
+        vec![
+            "log",
+            "project-1",
+            "--from",
+            "2021-12-01 09:00",
+            "--duration",
+            "1h",
+            "--tags",
+            "ux",
+        ],
+        vec![
+            "log",
+            "project-1",
+            "1",
+            "--from",
+            "2021-12-01 11:00",
+            "--duration",
+            "1h",
+            "--tags",
+            "meeting",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+
+    let rename = cmd::RenameTag::from_iter(["tag", "ux", "design"]);
+    assert_eq!(cmd::rename_tag(&store, &rename).unwrap(), 3);
+    assert_eq!(
+        cmd::tags(&store)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<(String, usize)>>(),
+        vec![
+            ("client".to_string(), 1),
+            ("design".to_string(), 3),
+            ("meeting".to_string(), 1),
+        ]
+    );
+    // The task already had the new tag, so it's merged rather than duplicated.
+    assert_eq!(
+        store
+            .task("project-1", 1, false)
+            .unwrap()
+            .tags()
+            .collect::<Vec<&str>>(),
+        vec!["design"]
+    );
+    // Renaming again changes nothing.
+    assert_eq!(cmd::rename_tag(&store, &rename).unwrap(), 0);
+    assert!(cmd::rename_tag(
+        &store,
+        &cmd::RenameTag::from_iter(["tag", "design", "not valid"])
+    )
+    .is_err());
+}
+
+#[test]
+fn list_logs_for_project_only_reads_that_project() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    for args in [
+        vec![
+            "log",
+            "project-1",
+            "--from",
+            "2021-12-01 09:00",
+            "--duration",
+            "1h",
+        ],
+        vec![
+            "log",
+            "project-1",
+            "1",
+            "--from",
+            "2021-12-01 11:00",
+            "--duration",
+            "1h",
+        ],
+        vec![
+            "log",
+            "project-2",
+            "--from",
+            "2021-12-01 13:00",
+            "--duration",
+            "1h",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+    // Any attempt to load the other project now fails.
+    std::fs::write(
+        temp_dir.path().join("project-2").join("project.json"),
+        "{ corrupt",
+    )
+    .unwrap();
+
+    let params = |project_ids: &str| {
+        cmd::ListLogs::from_iter(["logs", project_ids, "--start", "from 2021-12-01"])
+    };
+    let logs = cmd::list_logs(&store, &params("project-1")).unwrap();
+    assert_eq!(logs.len(), 2);
+    assert!(logs.iter().all(|log| log.project_id() == Some("project-1")));
+    assert!(cmd::list_logs(&store, &params("project-1,project-2")).is_err());
+    // Project IDs can't reach outside of the store.
+    assert!(cmd::list_logs(&store, &params("project-1,../project-2"))
+        .unwrap()
+        .iter()
+        .all(|log| log.project_id() == Some("project-1")));
+}
+
+#[test]
+fn dangling_active_log_is_cleared() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    std::fs::remove_file(
+        temp_dir
+            .path()
+            .join("project-1")
+            .join("logs")
+            .join("00001.json"),
+    )
+    .unwrap();
+
+    // Strict mode leaves the dangling pointer in place
+    let strict_store = Store::new(temp_dir.path()).unwrap().with_strict_state(true);
+    assert!(cmd::active_log_status(&strict_store).is_err());
+    assert!(store.state().unwrap().active_log().is_some());
+
+    assert!(cmd::active_log_status(&store).unwrap().is_none());
+    assert!(store.state().unwrap().active_log().is_none());
+    // Other commands relying on the active log work again
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    assert!(cmd::active_log_status(&store).unwrap().is_some());
+}
+
+#[test]
+fn list_logs_on_weekends_and_weekdays() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    // One log per day from Sunday 2021-12-05 to Sunday 2021-12-12.
+    for day in 5..=12 {
+        let from = format!("2021-12-{:02} 10:00", day);
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--from", &from, "--duration", "1h"]),
+        )
+        .unwrap();
+    }
+
+    let log_dates = |flag: &str| {
+        cmd::list_logs(
+            &store,
+            &cmd::ListLogs::from_iter(["logs", "--start", "from 2021-12-06", flag]),
+        )
+        .unwrap()
+        .iter()
+        .map(|log| log.start().unwrap().format_date())
+        .collect::<Vec<String>>()
+    };
+    assert_eq!(log_dates("--weekends"), vec!["2021-12-11", "2021-12-12"]);
+    assert_eq!(
+        log_dates("--weekdays"),
+        vec![
+            "2021-12-06",
+            "2021-12-07",
+            "2021-12-08",
+            "2021-12-09",
+            "2021-12-10"
+        ]
+    );
+}
+
+#[test]
+fn peeked_ids_match_assigned_ids() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+
+    assert_eq!(store.peek_next_task_id("project-1").unwrap(), 1);
+    let task = store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    assert_eq!(task.id().unwrap(), 1);
+    let peeked = store.peek_next_task_id("project-1").unwrap();
+    // Peeking has no side effects.
+    assert_eq!(store.peek_next_task_id("project-1").unwrap(), peeked);
+    let task = store.save_task(&Task::new("project-1", "Task 2")).unwrap();
+    assert_eq!(task.id().unwrap(), peeked);
+
+    let start = Timestamp::from_str("2021-12-01 09:00").unwrap();
+    for maybe_task_id in [None, Some(1)] {
+        let peeked = store.peek_next_log_id("project-1", maybe_task_id).unwrap();
+        let log = Log::new("project-1")
+            .with_maybe_task_id(maybe_task_id)
+            .with_start(start)
+            .with_duration(Duration::from_str("1h").unwrap());
+        let log = store.save_log(&log).unwrap();
+        assert_eq!(log.id().unwrap(), peeked);
+        assert_eq!(
+            store.peek_next_log_id("project-1", maybe_task_id).unwrap(),
+            peeked + 1
+        );
+    }
+}
+
+#[test]
+fn log_from_line() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let from_line = |line: &str| {
+        cmd::log_from_line(
+            &store,
+            &cmd::LogFromLine::from_iter(["log", line]),
+            std::io::empty(),
+        )
+    };
+
+    let log = from_line("project-1 14:00-15:30 fixed the parser #bug #dev").unwrap();
+    assert_eq!(log.start().unwrap(), Timestamp::from_str("14:00").unwrap());
+    assert_eq!(log.stop().unwrap(), Timestamp::from_str("15:30").unwrap());
+    assert_eq!(log.comment().unwrap(), "fixed the parser");
+    let mut tags = log.tags().collect::<Vec<&str>>();
+    tags.sort_unstable();
+    assert_eq!(tags, vec!["bug", "dev"]);
+    assert!(store.state().unwrap().active_log().is_none());
+
+    // Tags and times can appear anywhere, and a second time range is just
+    // part of the comment.
+    let log = from_line("project-1 #review 9:00-9:45 looked at 10:00-11:00 PRs").unwrap();
+    assert_eq!(log.start().unwrap(), Timestamp::from_str("09:00").unwrap());
+    assert_eq!(log.comment().unwrap(), "looked at 10:00-11:00 PRs");
+    assert_eq!(log.tags().collect::<Vec<&str>>(), vec!["review"]);
+
+    // A single time starts an open log.
+    let log = from_line("project-1 16:00").unwrap();
+    assert_eq!(log.start().unwrap(), Timestamp::from_str("16:00").unwrap());
+    assert!(log.stop().is_none());
+    assert!(log.comment().is_none());
+    assert_eq!(
+        store.state().unwrap().active_log(),
+        Some(("project-1".to_string(), None, log.id().unwrap()))
+    );
+
+    assert!(from_line("project-1 no times here").is_err());
+    assert!(from_line("").is_err());
+
+    let log = cmd::log_from_line(
+        &store,
+        &cmd::LogFromLine::from_iter(["log", "--paste"]),
+        "project-1 08:00-08:30 standup\n".as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(log.comment().unwrap(), "standup");
+}
+
+#[test]
+fn discover_store_in_parent_dir() {
+    let temp_dir = tempdir().unwrap();
+    let root = temp_dir.path().canonicalize().unwrap();
+    let nested = root.join("code").join("src");
+    std::fs::create_dir_all(&nested).unwrap();
+    assert!(Store::discover(&nested).is_none());
+
+    let store = Store::new(root.join(".loiter")).unwrap();
+    assert_eq!(Store::discover(&nested), Some(store.path()));
+    assert_eq!(Store::discover(&root), Some(store.path()));
+
+    // The closest store wins.
+    let inner = Store::new(root.join("code").join(".loiter")).unwrap();
+    assert_eq!(Store::discover(&nested), Some(inner.path()));
+}
+
+#[test]
+fn config_states_wizard() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let answers = "backlog, todo, doing, review, done\n\ndoing\n\n";
+    let mut output = Vec::new();
+    let task_state_config = cmd::config_states(
+        &store,
+        &cmd::ConfigStates::from_iter(["states", "--wizard"]),
+        answers.as_bytes(),
+        &mut output,
+    )
+    .unwrap();
+
+    let expected = TaskStateConfig::new(
+        ["backlog", "todo", "doing", "review", "done"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        "backlog".to_string(),
+        "doing".to_string(),
+        "done".to_string(),
+    )
+    .unwrap();
+    assert_eq!(task_state_config, expected);
+    assert_eq!(store.config().unwrap().task_state_config(), &expected);
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .contains("Initial state [backlog]: "));
+
+    // Invalid answers are rejected without changing the configuration.
+    let result = cmd::config_states(
+        &store,
+        &cmd::ConfigStates::from_iter(["states", "--wizard"]),
+        "a,b,c\n\n\nd\n".as_bytes(),
+        std::io::sink(),
+    );
+    assert!(result.is_err());
+    assert_eq!(store.config().unwrap().task_state_config(), &expected);
+}
+
+#[test]
+fn project_default_log_filter() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store
+        .save_project(
+            &Project::new("Project 2")
+                .with_default_log_filter("7 days")
+                .unwrap()
+                .with_default_log_sort("start:desc")
+                .unwrap(),
+        )
+        .unwrap();
+    assert!(Project::new("Project 3")
+        .with_default_log_filter("sometime")
+        .is_err());
+
+    let today = Timestamp::now().unwrap().today();
+    let days_ago = |days: u16| Timestamp::now().unwrap().days_back(days).format_date();
+    for project_id in ["project-1", "project-2"] {
+        for from in [days_ago(3), days_ago(2), today.format_date()] {
+            cmd::add_log(
+                &store,
+                &cmd::AddLog::from_iter([
+                    "log",
+                    project_id,
+                    "--from",
+                    &format!("{} 00:00", from),
+                    "--duration",
+                    "1m",
+                ]),
+            )
+            .unwrap();
+        }
+    }
+
+    let log_ids = |args: &[&str]| {
+        cmd::list_logs(
+            &store,
+            &cmd::ListLogs::from_iter(["logs"].iter().chain(args.iter())),
+        )
+        .unwrap()
+        .iter()
+        .map(|log| (log.project_id().unwrap().to_string(), log.id().unwrap()))
+        .collect::<Vec<(String, u32)>>()
+    };
+    let p1 = |id| ("project-1".to_string(), id);
+    let p2 = |id| ("project-2".to_string(), id);
+    // The store default only shows today's logs.
+    assert_eq!(log_ids(&["project-1"]), vec![p1(3)]);
+    assert_eq!(log_ids(&[]), vec![p1(3), p2(3)]);
+    // Project 2's own defaults surface its older logs, most recent first.
+    assert_eq!(log_ids(&["project-2"]), vec![p2(3), p2(2), p2(1)]);
+    // Explicit parameters still take precedence.
+    assert_eq!(log_ids(&["project-2", "--start", "today"]), vec![p2(3)]);
+    assert_eq!(
+        log_ids(&["project-2", "--sort", "start"]),
+        vec![p2(1), p2(2), p2(3)]
+    );
+}
+
+#[test]
+fn skip_corrupt_files() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    for name in ["Project 1", "Project 2", "Project 3"] {
+        store.save_project(&Project::new(name)).unwrap();
+    }
+    for _ in 0..5 {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--from", "09:00", "--duration", "1h"]),
+        )
+        .unwrap();
+    }
+    let corrupt_log_path = temp_dir.path().join("project-1/logs/00003.json");
+    let corrupt_project_path = temp_dir.path().join("project-2/project.json");
+    // Simulate files caught mid-write
+    std::fs::write(&corrupt_log_path, "{\"start\": ").unwrap();
+    std::fs::write(&corrupt_project_path, "").unwrap();
+
+    // Strict by default
+    assert!(cmd::list_logs(&store, &cmd::ListLogs::from_iter(["logs", "project-1"])).is_err());
+    assert!(store
+        .projects(&FilterSpec::new(ProjectFilter::All))
+        .is_err());
+    assert!(store.skipped_files().is_empty());
+
+    let store = Store::new(temp_dir.path()).unwrap().with_skip_corrupt(true);
+    let logs = cmd::list_logs(&store, &cmd::ListLogs::from_iter(["logs", "project-1"])).unwrap();
+    assert_eq!(
+        logs.iter()
+            .map(|log| log.id().unwrap())
+            .collect::<Vec<u32>>(),
+        vec![1, 2, 4, 5]
+    );
+    let mut project_ids = store
+        .projects(&FilterSpec::new(ProjectFilter::All))
+        .unwrap()
+        .iter()
+        .map(|project| project.id().to_string())
+        .collect::<Vec<String>>();
+    project_ids.sort();
+    assert_eq!(project_ids, vec!["project-1", "project-3"]);
+
+    let skipped = store
+        .skipped_files()
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    // Listing project 1's logs doesn't read project 2, so its corrupt metadata
+    // is only skipped once all projects are listed.
+    assert_eq!(
+        skipped,
+        vec![
+            corrupt_log_path.canonicalize().unwrap(),
+            corrupt_project_path.canonicalize().unwrap(),
+        ]
+    );
+}
+
+#[test]
+fn link_log_to_task() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "1",
+            "--from",
+            "08:00",
+            "--duration",
+            "1h",
+        ]),
+    )
+    .unwrap();
+    let log = cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "--from",
+            "09:00",
+            "--duration",
+            "30m",
+            "--comment",
+            "Forgot the task",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(log.id().unwrap(), 1);
+
+    let link = |args: &[&str]| {
+        cmd::link_log(
+            &store,
+            &cmd::LinkLog::from_iter(["link"].iter().chain(args.iter())),
+        )
+    };
+    assert!(link(&["project-1", "1", "--task", "2"]).is_err());
+    assert!(store.log("project-1", None, 1).is_ok());
+
+    let linked = link(&["project-1", "1", "--task", "1"]).unwrap();
+    assert_eq!(linked.task_id(), Some(1));
+    assert_eq!(linked.id(), Some(2));
+    assert!(store.log("project-1", None, 1).is_err());
+    let stored = store.log("project-1", Some(1), 2).unwrap();
+    assert_eq!(stored.comment(), Some("Forgot the task"));
+    assert_eq!(stored.start(), log.start());
+    assert_eq!(stored.duration(), log.duration());
+}
+
+#[test]
+fn terminal_states_hidden_by_default() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let task_state_config = cmd::config_states(
+        &store,
+        &cmd::ConfigStates::from_iter([
+            "states",
+            "--states",
+            "todo,doing,done,cancelled",
+            "--done",
+            "done",
+            "--terminal-states",
+            "cancelled",
+        ]),
+        std::io::empty(),
+        std::io::sink(),
+    )
+    .unwrap();
+    assert!(task_state_config.is_closed("cancelled"));
+    assert!(task_state_config.is_closed("done"));
+    assert!(!task_state_config.is_closed("doing"));
+    assert!(task_state_config
+        .clone()
+        .with_terminal_states(["abandoned"])
+        .is_err());
+
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for (description, state) in [
+        ("First", "todo"),
+        ("Second", "done"),
+        ("Third", "cancelled"),
+        ("Fourth", "doing"),
+    ] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", "project-1", description, "--state", state]),
+        )
+        .unwrap();
+    }
+
+    let task_descriptions = |args: &[&str]| {
+        cmd::list_tasks(
+            &store,
+            &cmd::ListTasks::from_iter(["tasks"].iter().chain(args.iter())),
+        )
+        .unwrap()
+        .iter()
+        .map(|task| task.description().to_string())
+        .collect::<Vec<String>>()
+    };
+    assert_eq!(task_descriptions(&[]), vec!["First", "Fourth"]);
+    assert_eq!(
+        task_descriptions(&["--state", "!done"]),
+        vec!["First", "Third", "Fourth"]
+    );
+    assert_eq!(task_descriptions(&["--state", "cancelled"]), vec!["Third"]);
+}
+
+#[test]
+fn status_shows_remaining_estimate() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Estimated", "--estimate", "2h"]),
+    )
+    .unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "1",
+            "--from",
+            "2021-12-01 09:00",
+            "--duration",
+            "1h30m",
+        ]),
+    )
+    .unwrap();
+    let log = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1"]),
+    )
+    .unwrap();
+    // Pretend the log has been running for 20 minutes
+    let start = time::OffsetDateTime::now_local().unwrap() - time::Duration::minutes(20);
+    store
+        .save_log(&log.with_start(Timestamp::from(start)))
+        .unwrap();
+
+    let status = cmd::active_log_status(&store).unwrap().unwrap();
+    let progress = status.maybe_estimate_progress.unwrap();
+    assert_eq!(progress.estimate, Duration::from_str("2h").unwrap());
+    assert!(progress.overrun().is_none());
+    // Allow for the time elapsed while running the test (durations are
+    // displayed rounded up to the nearest minute)
+    assert_eq!(progress.remaining().to_string(), "10m");
+
+    cmd::update_tasks(
+        &store,
+        &cmd::UpdateTask::from_iter(["task", "project-1", "1", "--estimate", "1h"]),
+    )
+    .unwrap();
+    let status = cmd::active_log_status(&store).unwrap().unwrap();
+    let progress = status.maybe_estimate_progress.unwrap();
+    assert_eq!(progress.remaining(), Duration::zero());
+    assert_eq!(progress.overrun().unwrap().to_string(), "50m");
+}
+
+#[test]
+fn export_sqlite() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path().join("store")).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    let add_log = |args: &[&str]| {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log"].iter().chain(args.iter())),
+        )
+        .unwrap()
+    };
+    add_log(&["project-1", "1", "--from", "08:00", "--duration", "1h"]);
+    add_log(&["project-1", "--from", "09:00", "--duration", "30m"]);
+    add_log(&["project-2", "--from", "10:00", "--duration", "15m"]);
+
+    let output = temp_dir.path().join("export.sqlite");
+    let params = cmd::ExportSqlite::from_iter(["sqlite", "--output", output.to_str().unwrap()]);
+    let summary = cmd::export_sqlite(&store, &params).unwrap();
+    assert_eq!(summary.projects, 2);
+    assert_eq!(summary.tasks, 1);
+    assert_eq!(summary.logs, 3);
+    // Refuses to overwrite an existing database
+    assert!(cmd::export_sqlite(&store, &params).is_err());
+    assert!(!temp_dir.path().join("export.sqlite.tmp").exists());
+
+    let conn = rusqlite::Connection::open(&output).unwrap();
+    // Log IDs are unique per project and task, including for logs without a
+    // task.
+    assert!(conn
+        .execute(
+            "INSERT INTO logs (project_id, task_id, id, tags) VALUES ('project-1', NULL, 1, '')",
+            [],
+        )
+        .is_err());
+    let total: i64 = conn
+        .query_row("SELECT SUM(duration) FROM logs", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(total, 6300);
+    let project_1_total: i64 = conn
+        .query_row(
+            "SELECT SUM(duration) FROM logs WHERE project_id = ?",
+            ["project-1"],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(project_1_total, 5400);
+    let task_total: i64 = conn
+        .query_row(
+            "SELECT SUM(l.duration) FROM logs l \
+            JOIN tasks t ON l.project_id = t.project_id AND l.task_id = t.id \
+            WHERE t.description = ?",
+            ["Task 1"],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(task_total, 3600);
+}
+
+#[test]
+fn export_logs_csv() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path().join("store")).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    let add_log = |args: &[&str]| {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log"].iter().chain(args.iter())),
+        )
+        .unwrap()
+    };
+    add_log(&[
+        "project-1",
+        "1",
+        "--from",
+        "08:00",
+        "--duration",
+        "1h",
+        "--comment",
+        "Fixed \"the\" bug, finally",
+        "--tags",
+        "bug,urgent",
+    ]);
+    add_log(&["project-2", "--from", "10:00", "--duration", "15m"]);
+
+    let mut output = Vec::new();
+    let count = cmd::export_logs(
+        &store,
+        &cmd::ExportLogs::from_iter(["logs", "--start", "today"]),
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(count, 2);
+
+    let mut reader = csv::Reader::from_reader(output.as_slice());
+    assert_eq!(
+        reader.headers().unwrap().iter().collect::<Vec<&str>>(),
+        vec![
+            "project_id",
+            "task_id",
+            "log_id",
+            "start",
+            "stop",
+            "duration_seconds",
+            "comment",
+            "tags"
+        ]
+    );
+    let records = reader
+        .records()
+        .collect::<Result<Vec<csv::StringRecord>, csv::Error>>()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|record| record.len() == 8));
+    assert_eq!(&records[0][0], "project-1");
+    assert_eq!(&records[0][1], "1");
+    assert_eq!(&records[0][5], "3600");
+    assert_eq!(&records[0][6], "Fixed \"the\" bug, finally");
+    assert_eq!(&records[0][7], "bug,urgent");
+    assert_eq!(&records[1][0], "project-2");
+    assert_eq!(&records[1][1], "");
+    assert_eq!(&records[1][5], "900");
+
+    // Writing to a file refuses to overwrite an existing one
+    let path = temp_dir.path().join("logs.csv");
+    let params = cmd::ExportLogs::from_iter(["logs", "--output", path.to_str().unwrap()]);
+    assert_eq!(
+        cmd::export_logs(&store, &params, std::io::sink()).unwrap(),
+        2
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), output);
+    assert!(cmd::export_logs(&store, &params, std::io::sink()).is_err());
+}
+
+#[test]
+fn export_logs_markdown() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path().join("store")).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    for args in [
+        &[
+            "project-1",
+            "1",
+            "--from",
+            "2021-11-29 08:00",
+            "--duration",
+            "1h",
+            "--comment",
+            "Fixed the bug",
+        ][..],
+        &[
+            "project-1",
+            "--from",
+            "2021-11-29 10:00",
+            "--duration",
+            "30m",
+        ],
+        &[
+            "project-2",
+            "--from",
+            "2021-11-29 13:00",
+            "--duration",
+            "15m",
+            "--comment",
+            "Planning",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(["log"].iter().chain(args))).unwrap();
+    }
+
+    let mut output = Vec::new();
+    let count = cmd::export_markdown(
+        &store,
+        &cmd::ExportMarkdown::from_iter(["markdown", "--start", "from 2021-11-29"]),
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(count, 3);
+    let markdown = String::from_utf8(output).unwrap();
+    let lines = markdown.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[0], "## Project 1");
+    assert!(
+        lines[2].ends_with("(1h), task 1: Fixed the bug"),
+        "{}",
+        lines[2]
+    );
+    assert!(lines[3].ends_with("(30m)"), "{}", lines[3]);
+    assert!(lines.contains(&"**Total: 1h 30m**"));
+    assert!(lines.contains(&"## Project 2"));
+    assert!(lines.contains(&"**Total: 15m**"));
+    assert_eq!(
+        lines.iter().filter(|line| line.starts_with("## ")).count(),
+        2
+    );
+}
+
+#[test]
+fn export_logs_ical() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path().join("store")).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    for args in [
+        &[
+            "project-1",
+            "1",
+            "--from",
+            "2021-11-29 08:00 +00:00",
+            "--duration",
+            "1h",
+            "--comment",
+            "Fixed the bug, finally",
+            "--tags",
+            "bug,urgent",
+        ][..],
+        &[
+            "project-1",
+            "--from",
+            "2021-11-29 10:00 +00:00",
+            "--duration",
+            "30m",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(["log"].iter().chain(args))).unwrap();
+    }
+    // A running log can't be turned into an event
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+
+    let mut output = Vec::new();
+    let count = cmd::export_ical(
+        &store,
+        &cmd::ExportIcal::from_iter(["ical", "--start", "from 2021-11-29"]),
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(count, 2);
+
+    let ical = String::from_utf8(output).unwrap();
+    assert!(ical.ends_with("\r\n"));
+    // Unfold lines before parsing them
+    let ical = ical.replace("\r\n ", "");
+    let lines = ical.split_terminator("\r\n").collect::<Vec<&str>>();
+    assert_eq!(lines.first(), Some(&"BEGIN:VCALENDAR"));
+    assert_eq!(lines.last(), Some(&"END:VCALENDAR"));
+    let events = lines
+        .split(|line| *line == "BEGIN:VEVENT")
+        .skip(1)
+        .map(|event| {
+            event
+                .iter()
+                .take_while(|line| **line != "END:VEVENT")
+                .filter_map(|line| line.split_once(':'))
+                .collect::<std::collections::HashMap<&str, &str>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["DTSTART"], "20211129T080000Z");
+    assert_eq!(events[0]["DTEND"], "20211129T090000Z");
+    assert_eq!(
+        events[0]["SUMMARY"],
+        "project-1 (task 1): Fixed the bug\\, finally"
+    );
+    assert_eq!(events[0]["CATEGORIES"], "bug,urgent");
+    assert_eq!(events[1]["DTSTART"], "20211129T100000Z");
+    assert_eq!(events[1]["DTEND"], "20211129T103000Z");
+    assert_ne!(events[0]["UID"], events[1]["UID"]);
+}
+
+#[test]
+fn start_and_add_logs_ago() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+
+    let before = Timestamp::now().unwrap();
+    let log = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--ago", "25m"]),
+    )
+    .unwrap();
+    let after = Timestamp::now().unwrap();
+    let ago = Duration::from_str("25m").unwrap();
+    let start = log.start().unwrap();
+    assert!(start >= before - ago && start <= after - ago);
+    cmd::stop_log(&store, &cmd::StopLog::default()).unwrap();
+
+    let log = cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "--ago", "2h", "--duration", "90m"]),
+    )
+    .unwrap();
+    let ago = Duration::from_str("2h").unwrap();
+    let start = log.start().unwrap();
+    assert!(start >= before - ago && start <= Timestamp::now().unwrap() - ago);
+    assert_eq!(log.duration().unwrap(), Duration::from_str("90m").unwrap());
+
+    // A start time and a duration ago cannot be combined
+    assert!(cmd::AddLog::from_iter_safe([
+        "log",
+        "project-1",
+        "--ago",
+        "2h",
+        "--from",
+        "08:00",
+        "--duration",
+        "1h"
+    ])
+    .is_err());
+    // Nor can a stop time and a duration
+    assert!(cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "--ago",
+            "2h",
+            "--to",
+            "now",
+            "--duration",
+            "1h"
+        ]),
+    )
+    .is_err());
+}
+
+#[test]
+fn backup_and_restore() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path().join("store")).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "1",
+            "--from",
+            "08:00",
+            "--duration",
+            "1h",
+        ]),
+    )
+    .unwrap();
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    std::fs::write(store.path().join("state.json.tmp"), "partial").unwrap();
+
+    let output = temp_dir.path().join("backup.tar.gz");
+    let backup_params = cmd::Backup::from_iter(["backup", "--output", output.to_str().unwrap()]);
+    let backed_up = cmd::backup(&store, &backup_params).unwrap();
+    assert!(!backed_up.iter().any(|p| p.ends_with(".lock")));
+    assert!(!backed_up.iter().any(|p| p.ends_with("state.json.tmp")));
+    // Refuses to overwrite an existing backup
+    assert!(cmd::backup(&store, &backup_params).is_err());
+    // Refuses to back up into the store itself
+    for inside in [
+        store.path().join("backup.tar.gz"),
+        store.path().join("project-1").join("backup.tar.gz"),
+    ] {
+        assert!(matches!(
+            cmd::backup(
+                &store,
+                &cmd::Backup::from_iter(["backup", "--output", inside.to_str().unwrap()])
+            ),
+            Err(loiter::Error::BackupInsideStore(_))
+        ));
+        assert!(!inside.exists());
+    }
+
+    let target = temp_dir.path().join("restored");
+    let restore = |target: &std::path::Path| {
+        cmd::restore(
+            &store,
+            &cmd::Restore::from_iter([
+                "restore",
+                output.to_str().unwrap(),
+                "--target",
+                target.to_str().unwrap(),
+            ]),
+        )
+    };
+    restore(&target).unwrap();
+    // Refuses to restore into a non-empty directory
+    assert!(restore(&target).is_err());
+
+    for rel_path in backed_up.iter() {
+        assert_eq!(
+            std::fs::read(store.path().join(rel_path)).unwrap(),
+            std::fs::read(target.join(rel_path)).unwrap(),
+            "{}",
+            rel_path.display()
+        );
+    }
+    let restored = Store::new(&target).unwrap();
+    assert_eq!(
+        restored
+            .projects(&FilterSpec::new(ProjectFilter::All))
+            .unwrap(),
+        store
+            .projects(&FilterSpec::new(ProjectFilter::All))
+            .unwrap()
+    );
+    assert_eq!(restored.state().unwrap(), store.state().unwrap());
+    assert_eq!(
+        cmd::list_logs(&restored, &cmd::ListLogs::from_iter(["logs"]))
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn list_by_regex() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Other project")).unwrap();
+    store
+        .save_task(&Task::new("project-1", "Fix the parser"))
+        .unwrap();
+    store
+        .save_task(&Task::new("project-1", "Write docs"))
+        .unwrap();
+    for comment in ["fixed the parser", "more parser fixes", "Fixed typo"] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter([
+                "log",
+                "project-1",
+                "--from",
+                "08:00",
+                "--duration",
+                "1h",
+                "--comment",
+                comment,
+            ]),
+        )
+        .unwrap();
+    }
+
+    let log_comments = |args: &[&str]| {
+        cmd::list_logs(
+            &store,
+            &cmd::ListLogs::from_iter(["logs"].iter().chain(args.iter())),
+        )
+        .map(|logs| {
+            logs.into_iter()
+                .map(|log| log.comment().unwrap().to_string())
+                .collect::<Vec<String>>()
+        })
+    };
+    assert_eq!(
+        log_comments(&["--regex", "^fix"]).unwrap(),
+        vec!["fixed the parser"]
+    );
+    assert_eq!(
+        log_comments(&["--regex", "(?i)^fix"]).unwrap(),
+        vec!["fixed the parser", "Fixed typo"]
+    );
+    assert_eq!(
+        log_comments(&["--regex", "parser$"]).unwrap(),
+        vec!["fixed the parser"]
+    );
+    assert!(matches!(
+        log_comments(&["--regex", "(unclosed"]),
+        Err(loiter::Error::InvalidRegex(_, _))
+    ));
+
+    let tasks = cmd::list_tasks(
+        &store,
+        &cmd::ListTasks::from_iter(["tasks", "--regex", "^Fix"]),
+    )
+    .unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].description(), "Fix the parser");
+
+    let projects = cmd::list_projects(
+        &store,
+        &cmd::ListProjects::from_iter(["projects", "--regex", "^Project"]),
+    )
+    .unwrap();
+    assert_eq!(projects.len(), 1);
+    assert_eq!(projects[0].id(), "project-1");
+}
+
+#[test]
+fn audit_trail() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let log = store
+        .save_log(&Log::new("project-1").with_start(Timestamp::now().unwrap()))
+        .unwrap();
+    store
+        .save_state(&State::default().with_active_log("project-1", None, log.id().unwrap()))
+        .unwrap();
+
+    let before = Timestamp::now().unwrap();
+    let task = cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Some task"]),
+    )
+    .unwrap();
+    cmd::stop_log(&store, &cmd::StopLog::default()).unwrap();
+    let after = Timestamp::now().unwrap();
+
+    let entries = cmd::audit(&store, &cmd::Audit::from_iter(["audit"])).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].operation(), AuditOperation::AddTask);
+    assert_eq!(entries[0].project_id(), Some("project-1"));
+    assert_eq!(entries[0].task_id(), task.id());
+    assert_eq!(entries[0].log_id(), None);
+    assert_eq!(entries[1].operation(), AuditOperation::StopLog);
+    assert_eq!(entries[1].project_id(), Some("project-1"));
+    assert_eq!(entries[1].task_id(), None);
+    assert_eq!(entries[1].log_id(), log.id());
+    for entry in entries.iter() {
+        assert!(entry.timestamp() >= before && entry.timestamp() <= after);
+    }
+
+    let stops = cmd::audit(
+        &store,
+        &cmd::Audit::from_iter(["audit", "--operation", "stop-log"]),
+    )
+    .unwrap();
+    assert_eq!(stops, entries[1..]);
+    let last = cmd::audit(&store, &cmd::Audit::from_iter(["audit", "--limit", "1"])).unwrap();
+    assert_eq!(last, entries[1..]);
+}
+
+#[test]
+fn audit_log_rotation() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path())
+        .unwrap()
+        .with_audit_log_max_size(1);
+    for name in ["Project 1", "Project 2", "Project 3"] {
+        cmd::add_project(&store, &cmd::AddProject::from_iter(["project", name])).unwrap();
+    }
+    // Each entry exceeds the maximum size, so each ends up in its own file
+    assert!(temp_dir.path().join("audit.log.1").is_file());
+    assert!(temp_dir.path().join("audit.log.2").is_file());
+    assert!(!temp_dir.path().join("audit.log.3").is_file());
+
+    let project_ids = cmd::audit(&store, &cmd::Audit::from_iter(["audit"]))
+        .unwrap()
+        .iter()
+        .map(|entry| entry.project_id().unwrap().to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(project_ids, vec!["project-1", "project-2", "project-3"]);
+}
+
+#[test]
+fn create_log_does_not_overwrite() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let start = Timestamp::from_str("2021-11-29 08:00").unwrap();
+    let log = store
+        .create_log(
+            &Log::new("project-1")
+                .with_start(start)
+                .with_comment("Original"),
+        )
+        .unwrap();
+    assert_eq!(log.id(), Some(1));
+
+    let clobbering = Log::new("project-1")
+        .with_id(1)
+        .with_start(start)
+        .with_comment("Clobbered");
+    assert!(matches!(
+        store.create_log(&clobbering),
+        Err(loiter::Error::LogAlreadyExists(_, None, 1))
+    ));
+    assert_eq!(
+        store.log("project-1", None, 1).unwrap().comment(),
+        Some("Original")
+    );
+
+    let task = store
+        .save_task(&Task::new("project-1", "Some task"))
+        .unwrap();
+    store
+        .create_log(&Log::new("project-1").with_maybe_task_id(task.id()))
+        .unwrap();
+    assert_eq!(
+        store
+            .create_log(
+                &Log::new("project-1")
+                    .with_maybe_task_id(task.id())
+                    .with_id(1)
+            )
+            .unwrap_err()
+            .to_string(),
+        "log for project \"project-1\", task ID 1 with ID 1 already exists"
+    );
+
+    // An explicit, unused ID is fine
+    assert_eq!(
+        store
+            .create_log(&clobbering.clone().with_id(5))
+            .unwrap()
+            .id(),
+        Some(5)
+    );
+    // Overwriting is still possible when explicitly updating a log
+    store.save_log(&clobbering).unwrap();
+    assert_eq!(
+        store.log("project-1", None, 1).unwrap().comment(),
+        Some("Clobbered")
+    );
+}
+
+#[test]
+fn update_log_fields() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "--from",
+            "2021-11-29 09:00",
+            "--duration",
+            "1h",
+            "--comment",
+            "Typo",
+        ]),
+    )
+    .unwrap();
+
+    cmd::update_log(
+        &store,
+        &cmd::UpdateLog::from_iter([
+            "log",
+            "--project",
+            "project-1",
+            "--id",
+            "1",
+            "--from",
+            "2021-11-29 10:00",
+            "--comment",
+            "Fixed the parser",
+        ]),
+    )
+    .unwrap();
+    let log = store.log("project-1", None, 1).unwrap();
+    assert_eq!(
+        log.start(),
+        Some(Timestamp::from_str("2021-11-29 10:00").unwrap())
+    );
+    assert_eq!(log.duration(), Some(Duration::from_str("1h").unwrap()));
+    assert_eq!(log.comment(), Some("Fixed the parser"));
+
+    let result = cmd::update_log(
+        &store,
+        &cmd::UpdateLog::from_iter([
+            "log",
+            "--project",
+            "project-1",
+            "--id",
+            "1",
+            "--duration",
+            "2h",
+            "--stop",
+            "2021-11-29 12:00",
+        ]),
+    );
+    assert!(matches!(
+        result,
+        Err(loiter::Error::CannotAcceptDurationAndStop)
+    ));
+
+    // Giving the active log a stop time stops it.
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--from", "2021-11-29 13:00"]),
+    )
+    .unwrap();
+    let log = cmd::update_log(
+        &store,
+        &cmd::UpdateLog::from_iter([
+            "log",
+            "--project",
+            "project-1",
+            "--id",
+            "2",
+            "--stop",
+            "2021-11-29 14:30",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(log.duration(), Some(Duration::from_str("1h30m").unwrap()));
+    assert!(store.state().unwrap().active_log().is_none());
+}
+
+#[test]
+fn move_log_between_tasks() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 1")).unwrap();
+    store.save_task(&Task::new("project-1", "Task 2")).unwrap();
+    store.save_task(&Task::new("project-2", "Task 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "2",
+            "--from",
+            "07:00",
+            "--duration",
+            "1h",
+        ]),
+    )
+    .unwrap();
+    let log = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter([
+            "start",
+            "project-1",
+            "1",
+            "--from",
+            "08:00",
+            "--comment",
+            "Wrong task",
+            "--tags",
+            "oops",
+        ]),
+    )
+    .unwrap();
+
+    let move_log = |args: &[&str]| {
+        cmd::move_log(
+            &store,
+            &cmd::MoveLog::from_iter(["log"].iter().chain(args.iter())),
+        )
+    };
+    assert!(matches!(
+        move_log(&[
+            "--project",
+            "project-1",
+            "--task",
+            "1",
+            "--id",
+            "1",
+            "--to-task",
+            "3"
+        ]),
+        Err(loiter::Error::TaskNotFound(_, 3))
+    ));
+    assert!(matches!(
+        move_log(&[
+            "--project",
+            "project-1",
+            "--task",
+            "1",
+            "--id",
+            "1",
+            "--to-project",
+            "nope"
+        ]),
+        Err(loiter::Error::ProjectNotFound(_))
+    ));
+    // The store itself refuses to move logs to tasks that don't exist, even
+    // if the task's directory does
+    std::fs::create_dir_all(temp_dir.path().join("project-1/tasks/0003")).unwrap();
+    assert!(matches!(
+        store.move_log(("project-1", Some(1), 1), ("project-1", Some(3))),
+        Err(loiter::Error::TaskNotFound(_, 3))
+    ));
+    let moved = move_log(&[
+        "--project",
+        "project-1",
+        "--task",
+        "1",
+        "--id",
+        "1",
+        "--to-task",
+        "2",
+    ])
+    .unwrap();
+    assert_eq!(moved.project_id(), Some("project-1"));
+    assert_eq!(moved.task_id(), Some(2));
+    assert_eq!(moved.id(), Some(2));
+    assert_eq!(moved.start(), log.start());
+    assert_eq!(moved.comment(), Some("Wrong task"));
+    assert_eq!(moved.tags().collect::<Vec<&str>>(), vec!["oops"]);
+    assert!(store.log("project-1", Some(1), 1).is_err());
+    assert_eq!(store.log("project-1", Some(2), 2).unwrap(), moved);
+    // The moved log is still the active log
+    assert_eq!(
+        store.state().unwrap().active_log(),
+        Some(("project-1".to_string(), Some(2), 2))
+    );
+
+    // Move to another project's task
+    let moved = move_log(&[
+        "--project",
+        "project-1",
+        "--task",
+        "2",
+        "--id",
+        "2",
+        "--to-project",
+        "project-2",
+        "--to-task",
+        "1",
+    ])
+    .unwrap();
+    assert_eq!(moved.project_id(), Some("project-2"));
+    assert_eq!(moved.task_id(), Some(1));
+    assert_eq!(moved.id(), Some(1));
+    assert_eq!(
+        store.state().unwrap().active_log(),
+        Some(("project-2".to_string(), Some(1), 1))
+    );
+}
+
+#[test]
+fn fill_empty_workdays() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    // Monday to Friday, leaving Wednesday empty.
+    for from in [
+        "2021-11-29 10:00",
+        "2021-11-30 10:00",
+        "2021-12-02 10:00",
+        "2021-12-03 10:00",
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--from", from, "--duration", "1h"]),
+        )
+        .unwrap();
+    }
+    let args = [
+        "fill",
+        "project-1",
+        "--from",
+        "2021-11-29",
+        "--to",
+        "2021-12-05",
+    ];
+
+    let dry_run = cmd::fill_days(
+        &store,
+        &cmd::FillDays::from_iter(args.iter().chain(&["--dry-run"])),
+    )
+    .unwrap();
+    assert_eq!(dry_run.len(), 1);
+    assert!(store
+        .logs_on_day(Timestamp::from_str("2021-12-01").unwrap())
+        .unwrap()
+        .is_empty());
+
+    let logs = cmd::fill_days(&store, &cmd::FillDays::from_iter(args)).unwrap();
+    assert_eq!(logs.len(), 1);
+    let log = &logs[0];
+    assert_eq!(
+        log.start(),
+        Some(Timestamp::from_str("2021-12-01 09:00").unwrap())
+    );
+    assert_eq!(log.duration(), Some(Duration::from_str("8h").unwrap()));
+    assert_eq!(
+        store
+            .logs_on_day(Timestamp::from_str("2021-12-01").unwrap())
+            .unwrap(),
+        logs
+    );
+
+    // Nothing's left to fill, and skipped days are left blank.
+    assert!(cmd::fill_days(&store, &cmd::FillDays::from_iter(args))
+        .unwrap()
+        .is_empty());
+    let logs = cmd::fill_days(
+        &store,
+        &cmd::FillDays::from_iter([
+            "fill",
+            "project-1",
+            "--from",
+            "2021-11-29",
+            "--to",
+            "2021-12-08",
+            "--skip",
+            "2021-12-06,2021-12-07",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(
+        logs[0].start(),
+        Some(Timestamp::from_str("2021-12-08 09:00").unwrap())
+    );
+}
+
+#[test]
+fn states_in_use_include_unconfigured_states() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    for (project_id, description, state) in [
+        ("project-1", "First", "doing"),
+        ("project-1", "Second", "doing"),
+        ("project-1", "Third", "done"),
+        ("project-2", "Fourth", "done"),
+    ] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", project_id, description, "--state", state]),
+        )
+        .unwrap();
+    }
+    // Tasks keep their states when the configured states change.
+    cmd::config_states(
+        &store,
+        &cmd::ConfigStates::from_iter(["states", "--states", "todo,in-progress,done"]),
+        std::io::empty(),
+        std::io::sink(),
+    )
+    .unwrap();
+
+    let params = cmd::TaskStates::from_iter(["states", "--in-use"]);
+    assert!(!cmd::task_states(&store, &params)
+        .unwrap()
+        .contains(&"doing".to_string()));
+    let counts = cmd::states_in_use(&store, &params).unwrap();
+    assert_eq!(
+        counts.into_iter().collect::<Vec<(String, usize)>>(),
+        vec![("doing".to_string(), 2), ("done".to_string(), 2)]
+    );
+
+    let counts = cmd::states_in_use(
+        &store,
+        &cmd::TaskStates::from_iter(["states", "--in-use", "--project", "project-2"]),
+    )
+    .unwrap();
+    assert_eq!(
+        counts.into_iter().collect::<Vec<(String, usize)>>(),
+        vec![("done".to_string(), 1)]
+    );
+}
+
+#[test]
+fn weekly_reports_respect_week_start() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    // A Saturday, Sunday and Monday.
+    for from in ["2021-12-04 10:00", "2021-12-05 10:00", "2021-12-06 10:00"] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--from", from, "--duration", "1h"]),
+        )
+        .unwrap();
+    }
+    let params = cmd::Report::from_iter(["report", "--start", "from 2021-12-04", "--by", "week"]);
+    let weekly_totals = |store: &Store| {
+        cmd::report(store, &params)
+            .unwrap()
+            .iter()
+            .map(|period| (period.start.format_date(), period.total))
+            .collect::<Vec<(String, Duration)>>()
+    };
+    let hours = |h: i64| Duration::from(time::Duration::hours(h));
+
+    assert_eq!(
+        weekly_totals(&store),
+        vec![
+            ("2021-11-29".to_string(), hours(2)),
+            ("2021-12-06".to_string(), hours(1)),
+        ]
+    );
+
+    let config = cmd::config_week_start(
+        &store,
+        &cmd::ConfigWeekStart::from_iter(["week-start", "sunday"]),
+    )
+    .unwrap();
+    assert_eq!(config.week_start(), loiter::WeekStart::Sunday);
+    assert_eq!(
+        weekly_totals(&store),
+        vec![
+            ("2021-11-28".to_string(), hours(1)),
+            ("2021-12-05".to_string(), hours(2)),
+        ]
+    );
+}
+
+#[test]
+fn list_logs_page() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let task = store
+        .save_task(&Task::new("project-1", "Some task"))
+        .unwrap();
+    let first_start = Timestamp::from_str("2021-11-01 09:00").unwrap();
+    for i in 0..500 {
+        let log = Log::new("project-1")
+            .with_maybe_task_id(if i % 2 == 0 { task.id() } else { None })
+            .with_start(first_start + Duration::from(time::Duration::minutes(i)))
+            .with_duration(Duration::from_str("1m").unwrap());
+        store.create_log(&log).unwrap();
+    }
+
+    let params = cmd::ListLogs::from_iter([
+        "logs",
+        "project-1",
+        "--start",
+        "from 2021-11-01",
+        "--detailed",
+        "--offset",
+        "20",
+        "--limit",
+        "10",
+    ]);
+    let logs = cmd::list_logs(&store, &params).unwrap();
+    assert_eq!(
+        logs.iter().map(Log::start).collect::<Vec<_>>(),
+        (20..30)
+            .map(|i| Some(first_start + Duration::from(time::Duration::minutes(i))))
+            .collect::<Vec<_>>()
+    );
+    for log in logs.iter() {
+        assert_eq!(log.task().is_some(), log.task_id().is_some());
+    }
+    assert_eq!(cmd::count_logs(&store, &params).unwrap(), 500);
+
+    let past_the_end = cmd::ListLogs::from_iter([
+        "logs",
+        "project-1",
+        "--start",
+        "from 2021-11-01",
+        "--offset",
+        "495",
+        "--limit",
+        "10",
+    ]);
+    assert_eq!(cmd::list_logs(&store, &past_the_end).unwrap().len(), 5);
+
+    let all = FilterSpec::new(LogFilter::All);
+    assert_eq!(
+        store
+            .log_count(
+                &FilterSpec::new(ProjectFilter::All),
+                &FilterSpec::new(TaskFilter::All),
+                &all,
+            )
+            .unwrap(),
+        500
+    );
+}
+
+#[test]
+fn completing_blockers_unblocks_tasks() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Design"]),
+    )
+    .unwrap();
+    let task = cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Build", "--blocked-by", "1"]),
+    )
+    .unwrap();
+    assert_eq!(task.blocked_by().collect::<Vec<_>>(), vec![1]);
+    assert!(cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Ship", "--blocked-by", "3"]),
+    )
+    .is_err());
+    // Task 1 can't be blocked by task 2, since task 2 is blocked by task 1.
+    assert!(cmd::update_tasks(
+        &store,
+        &cmd::UpdateTask::from_iter(["task", "project-1", "1", "--blocked-by", "2"]),
+    )
+    .is_err());
+
+    let unblocked_ids = |store: &Store| {
+        cmd::list_tasks(
+            store,
+            &cmd::ListTasks::from_iter(["tasks", "--unblocked", "--state", "any"]),
+        )
+        .unwrap()
+        .iter()
+        .map(|task| task.id().unwrap())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(unblocked_ids(&store), vec![1]);
+
+    cmd::mark_tasks_done(
+        &store,
+        &cmd::MarkTasks::from_iter(["done", "project-1", "1"]),
+    )
+    .unwrap();
+    assert_eq!(unblocked_ids(&store), vec![1, 2]);
+    let tasks = store
+        .unblocked_tasks(
+            &FilterSpec::new(ProjectFilter::All),
+            &FilterSpec::new(TaskFilter::StateNot("done".to_string())),
+            false,
+        )
+        .unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].description(), "Build");
+
+    let tasks = cmd::update_tasks(
+        &store,
+        &cmd::UpdateTask::from_iter(["task", "project-1", "2", "--blocked-by", ""]),
+    )
+    .unwrap();
+    assert_eq!(tasks[0].blocked_by().count(), 0);
+}
+
+#[test]
+fn task_actual_duration_sums_logs() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let task = cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Task 1", "--estimate", "2h"]),
+    )
+    .unwrap();
+    for (start, duration) in [
+        ("2021-11-29 09:00", "1h"),
+        ("2021-11-29 13:00", "45m"),
+        ("2021-11-30 09:00", "30m"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "1", "--from", start, "-d", duration]),
+        )
+        .unwrap();
+    }
+    // Logs without a task don't count towards any task.
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "--from", "2021-11-30 10:00", "-d", "1h"]),
+    )
+    .unwrap();
+
+    let actual = store.task_actual_duration("project-1", 1).unwrap();
+    assert_eq!(actual, Duration::from_str("2h15m").unwrap());
+    assert!(actual > task.estimate().unwrap());
+    let task = store.task("project-1", 1, true).unwrap();
+    assert_eq!(task.stats().unwrap().time_logged, actual);
+    assert!(store.task_actual_duration("project-1", 2).is_err());
+}
+
+#[test]
+fn invoice_bills_logs_at_project_rate() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    cmd::add_project(
+        &store,
+        &cmd::AddProject::from_iter(["project", "Project 1", "--rate", "100", "--currency", "USD"]),
+    )
+    .unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    for (project_id, from, duration) in [
+        ("project-1", "2021-11-29 09:00", "1h30m"),
+        ("project-1", "2021-11-30 09:00", "1h"),
+        ("project-1", "2021-12-01 09:00", "4h"),
+        ("project-2", "2021-11-29 09:00", "1h"),
+    ] {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", project_id, "--from", from, "--duration", duration]),
+        )
+        .unwrap();
+    }
+
+    let invoice = cmd::invoice(
+        &store,
+        &cmd::Invoice::from_iter(["invoice", "project-1", "--start", "before 2021-12-01"]),
+    )
+    .unwrap();
+    assert_eq!(invoice.items.len(), 2);
+    assert_eq!(invoice.items[0].amount, 150.0);
+    assert_eq!(invoice.duration, Duration::from_str("2h30m").unwrap());
+    assert_eq!(invoice.total, 250.0);
+    assert_eq!(invoice.currency.as_deref(), Some("USD"));
+
+    assert!(cmd::invoice(
+        &store,
+        &cmd::Invoice::from_iter(["invoice", "project-2", "--start", "before 2021-12-01"]),
+    )
+    .is_err());
+    assert!(cmd::add_project(
+        &store,
+        &cmd::AddProject::from_iter(["project", "Project 3", "--rate=-5"]),
+    )
+    .is_err());
+}
+
+#[test]
+fn active_log_status_includes_task() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-1", "Write docs"]),
+    )
+    .unwrap();
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "1", "--tags", "docs"]),
+    )
+    .unwrap();
+
+    let status = cmd::active_log_status(&store).unwrap().unwrap();
+    assert_eq!(status.maybe_task_description.as_deref(), Some("Write docs"));
+    assert_eq!(status.log.task().unwrap().description(), "Write docs");
+    assert_eq!(status.log.tags().collect::<Vec<&str>>(), vec!["docs"]);
+
+    // The task's metadata disappears, but its log remains.
+    std::fs::remove_file(
+        temp_dir
+            .path()
+            .join("project-1")
+            .join("tasks")
+            .join("0001")
+            .join("task.json"),
+    )
+    .unwrap();
+    let err = cmd::active_log_status(&store).unwrap_err();
+    assert!(matches!(err, loiter::Error::ActiveLogTaskNotFound(_, 1, 1)));
+}
+
+#[test]
+fn stop_times_in_the_future_are_rejected() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let now = Timestamp::now().unwrap();
+    let local = |ts: Timestamp| format!("{} {}", ts.format_date(), ts.format_time());
+    let add_log = |stop: Timestamp| {
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--ago", "1h", "--to", &local(stop)]),
+        )
+    };
+
+    // A little clock skew is tolerated.
+    assert!(add_log(now + Duration::from_str("2m").unwrap()).is_ok());
+    assert!(matches!(
+        add_log(now + Duration::from_str("1d").unwrap()),
+        Err(loiter::Error::LogStopInFuture(_, _))
+    ));
+
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    let stop = local(now + Duration::from_str("1w").unwrap());
+    assert!(matches!(
+        cmd::stop_log(&store, &cmd::StopLog::from_iter(["stop", "--at", &stop])),
+        Err(loiter::Error::LogStopInFuture(_, _))
+    ));
+    assert!(store.state().unwrap().active_log().is_some());
+    assert!(matches!(
+        cmd::update_log(
+            &store,
+            &cmd::UpdateLog::from_iter([
+                "log",
+                "--project",
+                "project-1",
+                "--id",
+                "1",
+                "--stop",
+                &stop
+            ]),
+        ),
+        Err(loiter::Error::LogStopInFuture(_, _))
+    ));
+    // Stop times implied by durations are checked too.
+    assert!(matches!(
+        cmd::add_log(
+            &store,
+            &cmd::AddLog::from_iter(["log", "project-1", "--ago", "1h", "--duration", "1w"]),
+        ),
+        Err(loiter::Error::LogStopInFuture(_, _))
+    ));
+    assert!(matches!(
+        cmd::stop_log(
+            &store,
+            &cmd::StopLog::from_iter(["stop", "--duration", "1w"])
+        ),
+        Err(loiter::Error::LogStopInFuture(_, _))
+    ));
+    assert!(matches!(
+        cmd::update_log(
+            &store,
+            &cmd::UpdateLog::from_iter([
+                "log",
+                "--project",
+                "project-1",
+                "--id",
+                "1",
+                "--duration",
+                "1w"
+            ]),
+        ),
+        Err(loiter::Error::LogStopInFuture(_, _))
+    ));
+    assert!(store.state().unwrap().active_log().is_some());
+
+    cmd::config_stop_tolerance(
+        &store,
+        &cmd::ConfigStopTolerance::from_iter(["stop-tolerance", "2w"]),
+    )
+    .unwrap();
+    assert!(cmd::stop_log(&store, &cmd::StopLog::from_iter(["stop", "--at", &stop])).is_ok());
+}
+
+#[test]
+fn multiple_active_logs() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-2", "--keep-active"]),
+    )
+    .unwrap();
+
+    let statuses = cmd::active_log_statuses(&store).unwrap();
+    assert_eq!(
+        statuses
+            .iter()
+            .map(|status| status.log.project_id().unwrap())
+            .collect::<Vec<&str>>(),
+        vec!["project-1", "project-2"]
+    );
+    // It's ambiguous which log to stop.
+    assert!(matches!(
+        cmd::stop_log(&store, &cmd::StopLog::default()),
+        Err(loiter::Error::MultipleActiveLogs(2))
+    ));
+
+    let log = cmd::stop_log(
+        &store,
+        &cmd::StopLog::from_iter(["stop", "--project", "project-2", "--id", "1"]),
+    )
+    .unwrap();
+    assert_eq!(log.project_id(), Some("project-2"));
+    assert!(log.duration().is_some());
+    assert_eq!(
+        store.state().unwrap().active_logs(),
+        &[("project-1".to_string(), None, 1)]
+    );
+
+    // With just one log active, it can be stopped without specifying it.
+    let log = cmd::stop_log(&store, &cmd::StopLog::default()).unwrap();
+    assert_eq!(log.project_id(), Some("project-1"));
+    assert!(cmd::active_log_statuses(&store).unwrap().is_empty());
+
+    // Starting a log without --keep-active stops all active logs.
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-2", "--keep-active"]),
+    )
+    .unwrap();
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--restart"]),
+    )
+    .unwrap();
+    assert_eq!(
+        store.state().unwrap().active_logs(),
+        &[("project-1".to_string(), None, 3)]
+    );
+}
+
+#[test]
+fn starting_log_on_missing_project_keeps_active_log() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let active = cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+
+    assert!(matches!(
+        cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "projcet-1"])),
+        Err(loiter::Error::ProjectNotFoundDidYouMean(_, _))
+    ));
+    assert!(matches!(
+        cmd::start_log(
+            &store,
+            &cmd::StartLog::from_iter(["start", "project-1", "5"])
+        ),
+        Err(loiter::Error::TaskNotFound(_, 5))
+    ));
+    assert_eq!(
+        store.state().unwrap().active_logs(),
+        &[("project-1".to_string(), None, 1)]
+    );
+    assert_eq!(store.log("project-1", None, 1).unwrap(), active);
+}
+
+#[test]
+fn archived_projects_are_hidden_by_default() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    let project_ids = |args: &[&str]| {
+        cmd::list_projects(
+            &store,
+            &cmd::ListProjects::from_iter(["projects"].iter().chain(args)),
+        )
+        .unwrap()
+        .iter()
+        .map(|project| project.id().to_string())
+        .collect::<Vec<String>>()
+    };
+
+    let project = cmd::update_project(
+        &store,
+        &cmd::UpdateProject::from_iter(["project", "project-1", "--archive"]),
+    )
+    .unwrap();
+    assert!(project.is_archived());
+    assert!(store.project("project-1").unwrap().is_archived());
+    assert_eq!(project_ids(&[]), vec!["project-2"]);
+    assert_eq!(
+        project_ids(&["--include-archived"]),
+        vec!["project-1", "project-2"]
+    );
+
+    cmd::update_project(
+        &store,
+        &cmd::UpdateProject::from_iter(["project", "project-1", "--unarchive"]),
+    )
+    .unwrap();
+    assert!(!store.project("project-1").unwrap().is_archived());
+    assert_eq!(project_ids(&[]), vec!["project-1", "project-2"]);
+}
+
+#[test]
+fn continue_most_recently_stopped_log() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    store
+        .save_task(&Task::new("project-1", "Fix the parser"))
+        .unwrap();
+    assert!(matches!(
+        cmd::continue_log(&store, &cmd::ContinueLog::default()),
+        Err(loiter::Error::NoStoppedLog)
+    ));
+
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter([
+            "start",
+            "project-1",
+            "1",
+            "--ago",
+            "2h",
+            "--comment",
+            "Parsing",
+            "--tags",
+            "coding",
+        ]),
+    )
+    .unwrap();
+    cmd::stop_log(&store, &cmd::StopLog::default()).unwrap();
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-2"])).unwrap();
+
+    // The active log isn't stopped yet, so it can't be continued.
+    let log = cmd::continue_log(&store, &cmd::ContinueLog::default()).unwrap();
+    assert_eq!(log.project_id(), Some("project-1"));
+    assert_eq!(log.task_id(), Some(1));
+    assert_eq!(log.comment(), Some("Parsing"));
+    assert_eq!(log.tags().collect::<Vec<&str>>(), vec!["coding"]);
+    assert_eq!(log.id(), Some(2));
+    // The previously active log is stopped.
+    assert!(store
+        .log("project-2", None, 1)
+        .unwrap()
+        .duration()
+        .is_some());
+    assert_eq!(
+        store.state().unwrap().active_logs(),
+        &[("project-1".to_string(), Some(1), 2)]
+    );
+
+    cmd::stop_log(&store, &cmd::StopLog::default()).unwrap();
+    let log = cmd::continue_log(
+        &store,
+        &cmd::ContinueLog::from_iter(["continue", "--project", "project-2"]),
+    )
+    .unwrap();
+    assert_eq!(log.project_id(), Some("project-2"));
+    assert_eq!(log.task_id(), None);
+}
+
+#[test]
+fn mistyped_project_ids_get_suggestions() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store.save_project(&Project::new("Project 12")).unwrap();
+    store.save_project(&Project::new("Other")).unwrap();
+
+    match store.project("projetc-1") {
+        Err(e @ loiter::Error::ProjectNotFoundDidYouMean(_, _)) => {
+            assert_eq!(
+                e.to_string(),
+                "project \"projetc-1\" not found - did you mean: project-1?"
+            );
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+    match cmd::add_task(
+        &store,
+        &cmd::AddTask::from_iter(["task", "project-2", "Do things"]),
+    ) {
+        Err(loiter::Error::ProjectNotFoundDidYouMean(id, similar_ids)) => {
+            assert_eq!(id, "project-2");
+            assert_eq!(similar_ids, vec!["project-1", "project-12"]);
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert!(matches!(
+        store.project("unrelated"),
+        Err(loiter::Error::ProjectNotFound(_))
+    ));
+}
+
+#[test]
+fn verify_store() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for description in ["Task 1", "Task 2", "Task 3"] {
+        store
+            .save_task(&Task::new("project-1", description))
+            .unwrap();
+    }
+    for args in [
+        vec!["log", "project-1", "--from", "09:00", "--duration", "1h"],
+        vec!["log", "project-1", "--from", "10:00", "--duration", "1h"],
+        vec![
+            "log",
+            "project-1",
+            "2",
+            "--from",
+            "11:00",
+            "--duration",
+            "1h",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+    assert!(store.verify().unwrap().is_empty());
+
+    let project_path = temp_dir.path().join("project-1");
+    let logs_path = project_path.join("logs");
+    let tasks_path = project_path.join("tasks");
+    // A log whose project's metadata is missing
+    let orphan_logs_path = temp_dir.path().join("orphan").join("logs");
+    std::fs::create_dir_all(&orphan_logs_path).unwrap();
+    std::fs::copy(
+        logs_path.join("00001.json"),
+        orphan_logs_path.join("00001.json"),
+    )
+    .unwrap();
+    // A log whose task's metadata is missing
+    std::fs::remove_file(tasks_path.join("0002").join("task.json")).unwrap();
+    // A task whose state isn't in the task state configuration
+    let task_meta_path = tasks_path.join("0003").join("task.json");
+    let mut task_json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&task_meta_path).unwrap()).unwrap();
+    task_json["state"] = serde_json::json!("bogus");
+    std::fs::write(&task_meta_path, task_json.to_string()).unwrap();
+    // Two logs with the same ID
+    std::fs::copy(logs_path.join("00001.json"), logs_path.join("1.json")).unwrap();
+    // A log caught mid-write
+    std::fs::write(logs_path.join("00002.json"), "{\"start\": ").unwrap();
+
+    let inconsistencies = store.verify().unwrap();
+    assert_eq!(inconsistencies.len(), 5, "{:?}", inconsistencies);
+    assert!(
+        inconsistencies.contains(&loiter::Inconsistency::OrphanedLog(
+            orphan_logs_path.join("00001.json")
+        ))
+    );
+    assert!(
+        inconsistencies.contains(&loiter::Inconsistency::LogWithoutTask(
+            tasks_path.join("0002").join("00001.json")
+        ))
+    );
+    assert!(
+        inconsistencies.contains(&loiter::Inconsistency::InvalidTaskState(
+            "project-1".to_string(),
+            3,
+            "bogus".to_string()
+        ))
+    );
+    assert!(inconsistencies.iter().any(|inconsistency| matches!(
+        inconsistency,
+        loiter::Inconsistency::IdCollision(paths) if paths.len() == 2
+    )));
+    assert!(inconsistencies.iter().any(|inconsistency| matches!(
+        inconsistency,
+        loiter::Inconsistency::UnparseableFile(path, _) if path == &logs_path.join("00002.json")
+    )));
+}
+
+#[test]
+fn verify_fix_repairs_store() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for description in ["Task 1", "Task 2"] {
+        store
+            .save_task(&Task::new("project-1", description))
+            .unwrap();
+    }
+    for args in [
+        vec!["log", "project-1", "--from", "09:00", "--duration", "1h"],
+        vec![
+            "log",
+            "project-1",
+            "2",
+            "--from",
+            "10:00",
+            "--duration",
+            "1h",
+        ],
+        vec![
+            "log",
+            "project-1",
+            "2",
+            "--from",
+            "11:00",
+            "--duration",
+            "1h",
+        ],
+    ] {
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+    let project_path = temp_dir.path().join("project-1");
+    let logs_path = project_path.join("logs");
+    let tasks_path = project_path.join("tasks");
+    // A task whose state isn't in the task state configuration
+    let task_meta_path = tasks_path.join("0001").join("task.json");
+    let mut task_json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&task_meta_path).unwrap()).unwrap();
+    task_json["state"] = serde_json::json!("bogus");
+    std::fs::write(&task_meta_path, task_json.to_string()).unwrap();
+    // Logs whose task's metadata is missing
+    std::fs::remove_file(tasks_path.join("0002").join("task.json")).unwrap();
+    // Two logs with the same ID
+    std::fs::copy(logs_path.join("00001.json"), logs_path.join("1.json")).unwrap();
+    // A file that can't be repaired
+    let corrupt_path = temp_dir.path().join("state.json");
+    std::fs::write(&corrupt_path, "{").unwrap();
+    assert_eq!(store.verify().unwrap().len(), 5);
+
+    // Nothing is changed without --fix
+    let report = cmd::verify(&store, &cmd::Verify::default()).unwrap();
+    assert!(report.repairs.is_empty());
+    assert_eq!(report.inconsistencies.len(), 5);
+
+    let report = cmd::verify(&store, &cmd::Verify::from_iter(["verify", "--fix"])).unwrap();
+    assert_eq!(report.repairs.len(), 4, "{:?}", report.repairs);
+    assert!(report.repairs.contains(&loiter::Repair::TaskStateReset(
+        "project-1".to_string(),
+        1,
+        "inbox".to_string()
+    )));
+    assert_eq!(report.inconsistencies.len(), 1);
+    assert!(matches!(
+        &report.inconsistencies[0],
+        loiter::Inconsistency::UnparseableFile(path, _) if path == &corrupt_path
+    ));
+    assert!(!tasks_path.join("0002").exists());
+
+    std::fs::remove_file(&corrupt_path).unwrap();
+    assert!(store.verify().unwrap().is_empty());
+    assert_eq!(
+        store.task("project-1", 1, false).unwrap().state(),
+        Some("inbox")
+    );
+    // The task's logs are now project-level logs, after the renumbered one
+    let mut log_ids = store
+        .logs_for_project_or_task(
+            "project-1",
+            None,
+            &FilterSpec::new(LogFilter::All),
+            false,
+            None,
+        )
+        .unwrap()
+        .iter()
+        .map(|log| log.id().unwrap())
+        .collect::<Vec<u32>>();
+    log_ids.sort_unstable();
+    assert_eq!(log_ids, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn verify_fix_moves_active_colliding_log() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter(["log", "project-1", "--from", "09:00", "--duration", "1h"]),
+    )
+    .unwrap();
+    cmd::start_log(&store, &cmd::StartLog::from_iter(["start", "project-1"])).unwrap();
+    // The finished log now collides with the active one, and is the one that
+    // keeps the ID
+    let logs_path = temp_dir.path().join("project-1").join("logs");
+    std::fs::rename(logs_path.join("00001.json"), logs_path.join("2.json")).unwrap();
+    assert_eq!(
+        store.state().unwrap().active_log(),
+        Some(("project-1".to_string(), None, 2))
+    );
+
+    let report = cmd::verify(&store, &cmd::Verify::from_iter(["verify", "--fix"])).unwrap();
+    assert_eq!(
+        report.repairs,
+        vec![loiter::Repair::Moved(
+            logs_path.join("00002.json"),
+            logs_path.join("00003.json")
+        )]
+    );
+    assert!(store.verify().unwrap().is_empty());
+    assert_eq!(
+        store.checked_state().unwrap().active_logs(),
+        &[("project-1".to_string(), None, 3)]
+    );
+    assert!(store
+        .log("project-1", None, 3)
+        .unwrap()
+        .duration()
+        .is_none());
+}
+
+#[test]
+fn ids_are_not_reused_after_removal() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let start = Timestamp::from_str("2021-12-01 09:00").unwrap();
+    let new_log = |maybe_task_id: Option<u32>| {
+        store
+            .save_log(
+                &Log::new("project-1")
+                    .with_maybe_task_id(maybe_task_id)
+                    .with_start(start)
+                    .with_duration(Duration::from_str("1h").unwrap()),
+            )
+            .unwrap()
+            .id()
+            .unwrap()
+    };
+    for _ in 0..2 {
+        store.save_task(&Task::new("project-1", "Task")).unwrap();
+    }
+    assert_eq!(new_log(None), 1);
+    assert_eq!(new_log(None), 2);
+    assert_eq!(new_log(Some(1)), 1);
+
+    store.delete_log("project-1", None, 2).unwrap();
+    assert_eq!(store.peek_next_log_id("project-1", None).unwrap(), 3);
+    assert_eq!(new_log(None), 3);
+    store.remove_task("project-1", 2).unwrap();
+    let task = store.save_task(&Task::new("project-1", "Task")).unwrap();
+    assert_eq!(task.id(), Some(3));
+
+    // Stores without counters seed them from the highest IDs in use.
+    std::fs::remove_file(temp_dir.path().join("project-1").join("counters.json")).unwrap();
+    assert_eq!(store.peek_next_log_id("project-1", None).unwrap(), 4);
+    assert_eq!(store.peek_next_log_id("project-1", Some(1)).unwrap(), 2);
+    assert_eq!(store.peek_next_task_id("project-1").unwrap(), 4);
+    assert_eq!(new_log(Some(1)), 2);
+    assert_eq!(new_log(None), 4);
+
+    // Logs added by other means (e.g. by merging stores) are never overwritten.
+    let logs_path = temp_dir.path().join("project-1").join("logs");
+    std::fs::copy(logs_path.join("00001.json"), logs_path.join("00005.json")).unwrap();
+    std::fs::copy(logs_path.join("00001.json"), logs_path.join("00006.json")).unwrap();
+    assert_eq!(new_log(None), 7);
+}
+
+#[test]
+fn edit_comment_trims_and_ignores_blank_comments() {
+    // Replaces the file's contents, remembering what it contained before.
+    struct MockEditor {
+        text: &'static str,
+        initial: std::cell::RefCell<Option<String>>,
+    }
+
+    impl cmd::FileEditor for MockEditor {
+        fn edit(&self, path: &std::path::Path) -> Result<(), loiter::Error> {
+            self.initial
+                .replace(Some(std::fs::read_to_string(path).unwrap()));
+            std::fs::write(path, self.text).unwrap();
+            Ok(())
+        }
+    }
+
+    let editor = |text| MockEditor {
+        text,
+        initial: std::cell::RefCell::new(None),
+    };
+    let multi_line = editor("Fixed the parser.\n\nAlso added tests.\n\n");
+    assert_eq!(
+        cmd::edit_comment(&multi_line, Some("Parser")).unwrap(),
+        Some("Fixed the parser.\n\nAlso added tests.".to_string())
+    );
+    assert_eq!(multi_line.initial.borrow().as_deref(), Some("Parser"));
+    assert_eq!(cmd::edit_comment(&editor("  \n\n"), None).unwrap(), None);
+    assert_eq!(cmd::edit_comment(&editor(""), None).unwrap(), None);
+
+    // The comment file is removed even if the editor fails.
+    struct FailingEditor(std::cell::RefCell<Option<std::path::PathBuf>>);
+
+    impl cmd::FileEditor for FailingEditor {
+        fn edit(&self, path: &std::path::Path) -> Result<(), loiter::Error> {
+            self.0.replace(Some(path.to_path_buf()));
+            Err(loiter::Error::Io(std::io::Error::from(
+                std::io::ErrorKind::Other,
+            )))
+        }
+    }
+
+    let failing = FailingEditor(std::cell::RefCell::new(None));
+    assert!(cmd::edit_comment(&failing, None).is_err());
+    let path = failing.0.borrow().clone().unwrap();
+    assert!(path
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("loiter-comment-"));
+    assert!(!path.exists());
+}
+
+#[test]
+fn list_logs_for_single_task() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for description in ["Task 1", "Task 2"] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", "project-1", description]),
+        )
+        .unwrap();
+    }
+    for (maybe_task_id, from) in [
+        (None, "2021-12-01 09:00"),
+        (Some("1"), "2021-12-01 10:00"),
+        (Some("2"), "2021-12-01 11:00"),
+        (Some("1"), "2021-12-01 12:00"),
+    ] {
+        let mut args = vec!["log", "project-1"];
+        args.extend(maybe_task_id);
+        args.extend(["--from", from, "--duration", "30m"]);
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+
+    let logs = |args: &[&str]| {
+        let mut all_args = vec!["logs", "--start", "from 2021-12-01"];
+        all_args.extend_from_slice(args);
+        cmd::list_logs(&store, &cmd::ListLogs::from_iter(all_args))
+            .unwrap()
+            .iter()
+            .map(|log| (log.task_id(), log.id().unwrap()))
+            .collect::<Vec<(Option<u32>, u32)>>()
+    };
+    assert_eq!(logs(&["project-1", "1"]), vec![(Some(1), 1), (Some(1), 2)]);
+    assert_eq!(logs(&["project-1", "2"]), vec![(Some(2), 1)]);
+    assert_eq!(logs(&["project-1", "3"]), vec![]);
+    // Task filters still apply to the single task.
+    assert_eq!(logs(&["project-1", "1", "--task-state", "done"]), vec![]);
+    assert_eq!(logs(&["project-1", "1,2"]).len(), 3);
+    assert_eq!(logs(&["project-1"]).len(), 4);
+
+    let filter = FilterSpec::new(LogFilter::All);
+    let task_logs = store.logs_for_task("project-1", 1, &filter).unwrap();
+    assert_eq!(
+        task_logs.iter().map(Log::id).collect::<Vec<Option<u32>>>(),
+        vec![Some(1), Some(2)]
+    );
+}
+
+#[test]
+fn starting_identical_log_again_is_rejected() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    store
+        .save_task(&Task::new("project-1", "Fix the parser"))
+        .unwrap();
+    let start = |args: &[&str]| {
+        let mut all_args = vec!["start"];
+        all_args.extend_from_slice(args);
+        cmd::start_log(&store, &cmd::StartLog::from_iter(all_args))
+    };
+
+    start(&["project-1", "1", "--comment", "Parsing"]).unwrap();
+    assert!(matches!(
+        start(&["project-1", "1"]),
+        Err(loiter::Error::LogAlreadyActive(1))
+    ));
+    assert!(matches!(
+        start(&["project-1", "1", "--comment", " Parsing "]),
+        Err(loiter::Error::LogAlreadyActive(1))
+    ));
+    assert_eq!(
+        store.state().unwrap().active_logs(),
+        &[("project-1".to_string(), Some(1), 1)]
+    );
+    assert!(store
+        .log("project-1", Some(1), 1)
+        .unwrap()
+        .duration()
+        .is_none());
+
+    // A different comment, task or project makes for a different log.
+    let log = start(&["project-1", "1", "--comment", "Lexing"]).unwrap();
+    assert_eq!(log.id(), Some(2));
+    let log = start(&["project-1"]).unwrap();
+    assert_eq!((log.task_id(), log.id()), (None, Some(1)));
+}
+
+#[test]
+fn restarting_identical_log() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+
+    cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--ago", "1h"]),
+    )
+    .unwrap();
+    let log = cmd::start_log(
+        &store,
+        &cmd::StartLog::from_iter(["start", "project-1", "--restart"]),
+    )
+    .unwrap();
+    assert_eq!(log.id(), Some(2));
+    assert!(store
+        .log("project-1", None, 1)
+        .unwrap()
+        .duration()
+        .is_some());
+    assert_eq!(
+        store.state().unwrap().active_logs(),
+        &[("project-1".to_string(), None, 2)]
+    );
+}
+
+#[test]
+fn configured_default_task_priority() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let add_task = |args: &[&str]| {
+        let mut all_args = vec!["task", "project-1", "A task"];
+        all_args.extend_from_slice(args);
+        cmd::add_task(&store, &cmd::AddTask::from_iter(all_args)).unwrap()
+    };
+    assert_eq!(add_task(&[]).priority(), MAX_TASK_PRIORITY);
+
+    let config = store
+        .config()
+        .unwrap()
+        .with_default_task_priority(5)
+        .unwrap();
+    store.save_config(&config).unwrap();
+    assert_eq!(add_task(&[]).priority(), 5);
+    assert_eq!(store.task("project-1", 2, false).unwrap().priority(), 5);
+    assert_eq!(add_task(&["--priority", "2"]).priority(), 2);
+
+    assert!(store
+        .config()
+        .unwrap()
+        .with_default_task_priority(0)
+        .is_err());
+    assert!(store
+        .config()
+        .unwrap()
+        .with_default_task_priority(11)
+        .is_err());
+    // Out-of-range defaults in a hand-edited config are only rejected when
+    // they're needed to create a task.
+    let mut config = serde_json::to_value(store.config().unwrap()).unwrap();
+    config["default_task_priority"] = serde_json::json!(11);
+    std::fs::write(
+        temp_dir.path().join("config.json"),
+        serde_json::to_string(&config).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(store.config().unwrap().default_task_priority(), 11);
+    assert!(matches!(
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", "project-1", "Another task"])
+        ),
+        Err(loiter::Error::InvalidDefaultTaskPriority(11, _, _))
+    ));
+    assert_eq!(add_task(&["--priority", "2"]).priority(), 2);
+    assert_eq!(
+        cmd::list_tasks(&store, &cmd::ListTasks::from_iter(["tasks"]))
+            .unwrap()
+            .len(),
+        4
+    );
+}
+
+#[test]
+fn list_tasks_due_within() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let now = Timestamp::now().unwrap();
+    for (description, maybe_deadline) in [
+        ("Due tomorrow", Some(now.days_forward(1))),
+        ("Due next month", Some(now.days_forward(31))),
+        ("Overdue", Some(now.days_back(1))),
+        ("No deadline", None),
+    ] {
+        store
+            .save_task(&Task::new("project-1", description).with_maybe_deadline(maybe_deadline))
+            .unwrap();
+    }
+
+    let descriptions = |days: &str| {
+        cmd::list_tasks(
+            &store,
+            &cmd::ListTasks::from_iter(["tasks", "--due-within", days]),
+        )
+        .unwrap()
+        .iter()
+        .map(|task| task.description().to_string())
+        .collect::<Vec<String>>()
+    };
+    assert_eq!(descriptions("3"), vec!["Due tomorrow"]);
+    assert_eq!(descriptions("40"), vec!["Due tomorrow", "Due next month"]);
+    assert!(descriptions("0").is_empty());
+}
+
+#[test]
+fn list_overdue_tasks() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let task_state_config = store.config().unwrap().task_state_config().clone();
+    let now = Timestamp::now().unwrap();
+    for (description, deadline, state) in [
+        ("Overdue", now.days_back(2), "todo"),
+        (
+            "Overdue but done",
+            now.days_back(2),
+            task_state_config.done(),
+        ),
+        ("Due later", now.days_forward(2), "todo"),
+    ] {
+        store
+            .save_task(
+                &Task::new("project-1", description)
+                    .with_deadline(deadline)
+                    .with_maybe_state(Some(state.to_string())),
+            )
+            .unwrap();
+    }
+
+    let tasks = cmd::list_tasks(
+        &store,
+        &cmd::ListTasks::from_iter(["tasks", "--overdue", "--state", "any"]),
+    )
+    .unwrap();
+    assert_eq!(
+        tasks.iter().map(Task::description).collect::<Vec<&str>>(),
+        vec!["Overdue"]
+    );
+    assert!(tasks[0].is_overdue(now, &task_state_config));
+}
+
+#[test]
+fn list_overdue_tasks_with_project_task_states() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let task_state_config = TaskStateConfig::new(
+        vec![
+            "todo".to_string(),
+            "doing".to_string(),
+            "finished".to_string(),
+        ],
+        "todo".to_string(),
+        "doing".to_string(),
+        "finished".to_string(),
+    )
+    .unwrap();
+    store
+        .save_project(&Project::new("Project 1").with_task_state_config(&task_state_config))
+        .unwrap();
+    let now = Timestamp::now().unwrap();
+    for (description, state) in [("Overdue", "todo"), ("Overdue but finished", "finished")] {
+        store
+            .save_task(
+                &Task::new("project-1", description)
+                    .with_deadline(now.days_back(2))
+                    .with_maybe_state(Some(state.to_string())),
+            )
+            .unwrap();
+    }
+
+    let tasks = cmd::list_tasks(
+        &store,
+        &cmd::ListTasks::from_iter(["tasks", "--overdue", "--state", "any"]),
+    )
+    .unwrap();
+    assert_eq!(
+        tasks.iter().map(Task::description).collect::<Vec<&str>>(),
+        vec!["Overdue"]
+    );
+    let task_state_configs = store.task_state_configs().unwrap();
+    assert_eq!(
+        task_state_configs.get("project-1"),
+        Some(&task_state_config)
+    );
+}
+
+#[test]
+fn export_and_import_json() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path().join("store")).unwrap();
+    store
+        .save_project(&Project::new("Project 1").with_description("The first"))
+        .unwrap();
+    store.save_project(&Project::new("Project 2")).unwrap();
+    for description in ["Task 1", "Task 2"] {
+        cmd::add_task(
+            &store,
+            &cmd::AddTask::from_iter(["task", "project-1", description, "--tags", "dev"]),
+        )
+        .unwrap();
+    }
+    for (project_id, maybe_task_id, from) in [
+        ("project-1", None, "2021-12-01 09:00"),
+        ("project-1", Some("2"), "2021-12-01 10:00"),
+        ("project-2", None, "2021-12-01 11:00"),
+    ] {
+        let mut args = vec!["log", project_id];
+        args.extend(maybe_task_id);
+        args.extend(["--from", from, "--duration", "45m", "--comment", "Work"]);
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+    // A removed task leaves a gap in the task IDs, which must be preserved.
+    store.remove_task("project-1", 1).unwrap();
+
+    let export_path = temp_dir.path().join("export.json");
+    let summary = cmd::export_json(
+        &store,
+        &cmd::ExportJson::from_iter(["json", "--output", export_path.to_str().unwrap()]),
+        std::io::sink(),
+    )
+    .unwrap();
+    assert_eq!((summary.projects, summary.tasks, summary.logs), (2, 1, 3));
+
+    let all_projects = FilterSpec::new(ProjectFilter::All);
+    let all_tasks = FilterSpec::new(TaskFilter::All);
+    let all_logs = FilterSpec::new(LogFilter::All);
+    let contents = |store: &Store| {
+        (
+            store.projects(&all_projects).unwrap(),
+            store.tasks(&all_projects, &all_tasks, false).unwrap(),
+            store
+                .logs(&all_projects, &all_tasks, &all_logs, false)
+                .unwrap(),
+        )
+    };
+
+    let restored = Store::new(temp_dir.path().join("restored")).unwrap();
+    let import = cmd::Import::from_iter(["import", export_path.to_str().unwrap()]);
+    let summary = cmd::import(&restored, &import).unwrap();
+    assert_eq!((summary.projects, summary.tasks, summary.logs), (2, 1, 3));
+    assert!(summary.conflicts.is_empty());
+    assert_eq!(contents(&restored), contents(&store));
+    assert_eq!(
+        restored.task("project-1", 2, false).unwrap().description(),
+        "Task 2"
+    );
+
+    // Importing again conflicts with everything that was already imported.
+    let summary = cmd::import(&restored, &import).unwrap();
+    assert_eq!((summary.projects, summary.tasks, summary.logs), (0, 1, 3));
+    assert_eq!(
+        summary.conflicts,
+        vec![
+            cmd::ImportConflict::ProjectExists("project-1".to_string()),
+            cmd::ImportConflict::TaskRenumbered("project-1".to_string(), 2, 3),
+            cmd::ImportConflict::LogRenumbered("project-1".to_string(), None, 1, 2),
+            cmd::ImportConflict::ProjectExists("project-2".to_string()),
+            cmd::ImportConflict::LogRenumbered("project-2".to_string(), None, 1, 2),
+        ]
+    );
+    // The renumbered task's log moves along with it, keeping its ID.
+    assert!(restored.log("project-1", Some(3), 1).is_ok());
+}
+
+#[test]
+fn import_remaps_renumbered_blockers() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path().join("store")).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    let blocked = store.save_task(&Task::new("project-1", "Blocked")).unwrap();
+    store.save_task(&Task::new("project-1", "Blocker")).unwrap();
+    store.save_task(&blocked.with_blocked_by([2])).unwrap();
+    let export_path = temp_dir.path().join("export.json");
+    cmd::export_json(
+        &store,
+        &cmd::ExportJson::from_iter(["json", "--output", export_path.to_str().unwrap()]),
+        std::io::sink(),
+    )
+    .unwrap();
+
+    // Both imported tasks clash with existing ones, and the blocker is
+    // renumbered only after the task it blocks.
+    let other = Store::new(temp_dir.path().join("other")).unwrap();
+    other.save_project(&Project::new("Project 1")).unwrap();
+    for description in ["Existing 1", "Existing 2"] {
+        other
+            .save_task(&Task::new("project-1", description))
+            .unwrap();
+    }
+    let summary = cmd::import(
+        &other,
+        &cmd::Import::from_iter(["import", export_path.to_str().unwrap()]),
+    )
+    .unwrap();
+    assert_eq!(
+        summary.conflicts,
+        vec![
+            cmd::ImportConflict::ProjectExists("project-1".to_string()),
+            cmd::ImportConflict::TaskRenumbered("project-1".to_string(), 1, 3),
+            cmd::ImportConflict::TaskRenumbered("project-1".to_string(), 2, 4),
+        ]
+    );
+    let blocked = other.task("project-1", 3, false).unwrap();
+    assert_eq!(blocked.description(), "Blocked");
+    assert_eq!(
+        blocked.blocked_by().collect::<Vec<loiter::TaskId>>(),
+        vec![4]
+    );
+    assert_eq!(
+        other
+            .task("project-1", 2, false)
+            .unwrap()
+            .blocked_by()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn export_json_with_iso8601_durations() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    cmd::add_log(
+        &store,
+        &cmd::AddLog::from_iter([
+            "log",
+            "project-1",
+            "--from",
+            "2021-12-01 09:00",
+            "--duration",
+            "1h30m",
+        ]),
+    )
+    .unwrap();
+    let export = |store: &Store| {
+        let mut output = Vec::new();
+        cmd::export_json(store, &cmd::ExportJson::from_iter(["json"]), &mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        json[0]["logs"][0].clone()
+    };
+
+    let log = export(&store);
+    assert_eq!(log["duration_seconds"], 5400);
+    assert!(log.get("duration_iso8601").is_none());
+
+    let config = cmd::config_duration_format(
+        &store,
+        &cmd::ConfigDurationFormat::from_iter(["duration-format", "iso8601"]),
+    )
+    .unwrap();
+    assert_eq!(config.duration_format(), loiter::DurationFormat::Iso8601);
+    let log = export(&store);
+    assert_eq!(log["duration_seconds"], 5400);
+    assert_eq!(log["duration_iso8601"], "PT1H30M");
+}
+
+#[test]
+fn report_totals_by_tag() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    store.save_project(&Project::new("Project 1")).unwrap();
+    for (from, duration, maybe_tags) in [
+        ("2021-12-01 09:00", "1h", Some("coding")),
+        ("2021-12-01 10:00", "30m", Some("coding,meetings")),
+        ("2021-12-01 11:00", "2h", Some("meetings")),
+        ("2021-12-01 14:00", "15m", Some("email")),
+        ("2021-12-01 15:00", "45m", None),
+    ] {
+        let mut args = vec!["log", "project-1", "--from", from, "--duration", duration];
+        if let Some(tags) = maybe_tags {
+            args.extend(["--tags", tags]);
+        }
+        cmd::add_log(&store, &cmd::AddLog::from_iter(args)).unwrap();
+    }
+
+    let totals = cmd::report_by_tag(
+        &store,
+        &cmd::Report::from_iter(["report", "--start", "from 2021-12-01", "--by-tag"]),
+    )
+    .unwrap();
+    let duration = |s| Duration::from_str(s).unwrap();
+    assert_eq!(
+        totals,
+        vec![
+            ("meetings".to_string(), duration("2h30m")),
+            ("coding".to_string(), duration("1h30m")),
+            ("email".to_string(), duration("15m")),
+        ]
+    );
+    assert!(cmd::Report::from_iter_safe(["report", "--by-tag", "--by", "day"]).is_err());
+}
+
+#[test]
+fn colliding_project_ids() {
+    let temp_dir = tempdir().unwrap();
+    let store = Store::new(temp_dir.path()).unwrap();
+    let add_project = |args: &[&str]| {
+        let mut all_args = vec!["project"];
+        all_args.extend_from_slice(args);
+        cmd::add_project(&store, &cmd::AddProject::from_iter(all_args))
+    };
+
+    let first = add_project(&["A!", "--description", "The first"]).unwrap();
+    assert_eq!(first.id(), "a");
+    store.save_task(&Task::new("a", "A task")).unwrap();
+    assert!(matches!(
+        add_project(&["A?"]),
+        Err(loiter::Error::ProjectAlreadyExists(id)) if id == "a"
+    ));
+    // The existing project and its tasks are untouched.
+    assert_eq!(store.project("a").unwrap(), first);
+    assert_eq!(store.task("a", 1, false).unwrap().description(), "A task");
+
+    let second = add_project(&["A?", "--auto-suffix"]).unwrap();
+    assert_eq!((second.id(), second.name()), ("a-2", "A? 2"));
+    let third = add_project(&["A.", "--auto-suffix"]).unwrap();
+    assert_eq!(third.id(), "a-3");
+    assert_eq!(store.project("a").unwrap(), first);
+
+    // Renaming onto another project's ID fails without losing anything.
+    let rename = |old_id: &str, new_name: &str| {
+        cmd::rename_project(
+            &store,
+            &cmd::RenameProject::from_iter(["project", old_id, new_name]),
+        )
+    };
+    assert!(matches!(
+        rename("a-2", "A#"),
+        Err(loiter::Error::ProjectAlreadyExists(id)) if id == "a"
+    ));
+    assert_eq!(store.project("a").unwrap(), first);
+    assert_eq!(store.project("a-2").unwrap(), second);
+    assert_eq!(store.task("a", 1, false).unwrap().description(), "A task");
+
+    // A new name with the same ID just renames the project in place.
+    let renamed = rename("a", "A#").unwrap();
+    assert_eq!((renamed.id(), renamed.name()), ("a", "A#"));
+    assert_eq!(store.project("a").unwrap().description(), Some("The first"));
+    assert_eq!(store.task("a", 1, false).unwrap().description(), "A task");
+}